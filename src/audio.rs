@@ -0,0 +1,686 @@
+use std::fmt;
+
+use crate::bits::Bits;
+
+/// audio frame boundaries from sync headers
+#[derive(Clone, Copy, Debug)]
+pub struct Sync {
+    /// whole frame with header; bytes
+    pub size: usize,
+    pub samples: u32,
+    /// Hz; None if not signaled in header (LATM)
+    pub sample_rate: Option<u32>,
+}
+
+impl Sync {
+    /// frame duration; 90kHz
+    pub fn duration(&self) -> Option<u64> {
+        self.sample_rate
+            .filter(|rate| *rate > 0)
+            .map(|rate| u64::from(self.samples) * 90_000 / u64::from(rate))
+    }
+}
+
+/// header size needed by sync
+pub const SYNC_SZ: usize = 7;
+
+const ADTS_SAMPLE_RATES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+/// kbps by [version 1 or 2][layer I, II, III]
+const MPA_BITRATES: [[[u32; 15]; 3]; 2] = [
+    [
+        [
+            0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+        ],
+        [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+        ],
+        [
+            0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+        ],
+    ],
+    [
+        [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+        ],
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+    ],
+];
+
+const MPA_SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+
+/// 16-bit words by [fscod 48k, 44.1k, 32k][frmsizecod]
+const AC3_FRAME_SIZES: [[u16; 38]; 3] = [
+    [
+        64, 64, 80, 80, 96, 96, 112, 112, 128, 128, 160, 160, 192, 192, 224, 224, 256, 256, 320,
+        320, 384, 384, 448, 448, 512, 512, 640, 640, 768, 768, 896, 896, 1024, 1024, 1152, 1152,
+        1280, 1280,
+    ],
+    [
+        69, 70, 87, 88, 104, 105, 121, 122, 139, 140, 174, 175, 208, 209, 243, 244, 278, 279, 348,
+        349, 417, 418, 487, 488, 557, 558, 696, 697, 835, 836, 975, 976, 1114, 1115, 1253, 1254,
+        1393, 1394,
+    ],
+    [
+        96, 96, 120, 120, 144, 144, 168, 168, 192, 192, 240, 240, 288, 288, 336, 336, 384, 384,
+        480, 480, 576, 576, 672, 672, 768, 768, 960, 960, 1152, 1152, 1344, 1344, 1536, 1536, 1728,
+        1728, 1920, 1920,
+    ],
+];
+
+const AC3_SAMPLE_RATES: [u32; 3] = [48000, 44100, 32000];
+const EAC3_SAMPLE_RATES_REDUCED: [u32; 3] = [24000, 22050, 16000];
+const EAC3_BLOCKS: [u32; 4] = [1, 2, 3, 6];
+
+/// frame at start of buf by codec (Track::codec);
+/// None if buf does not start with a valid header
+pub fn sync(codec: &str, buf: &[u8]) -> Option<Sync> {
+    if buf.len() < SYNC_SZ {
+        return None;
+    }
+
+    match codec {
+        "aac" => adts(buf),
+        "aac-latm" => loas(buf),
+        "mp2" => mpa(buf),
+        "ac3" | "eac3" => ac3(buf),
+        _ => None,
+    }
+}
+
+/// ISO/IEC 13818-7 ADTS
+fn adts(buf: &[u8]) -> Option<Sync> {
+    // syncword and layer 00
+    if buf[0] != 0xFF || (buf[1] & 0xF6) != 0xF0 {
+        return None;
+    }
+
+    let sample_rate = *ADTS_SAMPLE_RATES.get(usize::from((buf[2] >> 2) & 0x0F))?;
+    let size = (usize::from(buf[3] & 0x03) << 11)
+        | (usize::from(buf[4]) << 3)
+        | (usize::from(buf[5]) >> 5);
+    let blocks = u32::from(buf[6] & 0x03) + 1;
+
+    if size < SYNC_SZ {
+        return None;
+    }
+
+    Some(Sync {
+        size,
+        samples: 1024 * blocks,
+        sample_rate: Some(sample_rate),
+    })
+}
+
+/// ISO/IEC 14496-3 LOAS AudioSyncStream
+fn loas(buf: &[u8]) -> Option<Sync> {
+    if buf[0] != 0x56 || (buf[1] & 0xE0) != 0xE0 {
+        return None;
+    }
+
+    let length = (usize::from(buf[1] & 0x1F) << 8) | usize::from(buf[2]);
+
+    Some(Sync {
+        size: 3 + length,
+        samples: 1024,
+        sample_rate: None,
+    })
+}
+
+/// ISO/IEC 11172-3 / 13818-3 MPEG audio
+fn mpa(buf: &[u8]) -> Option<Sync> {
+    if buf[0] != 0xFF || (buf[1] & 0xE0) != 0xE0 {
+        return None;
+    }
+
+    // 0: 2.5, 2: 2, 3: 1
+    let version = (buf[1] >> 3) & 0x03;
+    // 1: III, 2: II, 3: I
+    let layer = (buf[1] >> 1) & 0x03;
+    if version == 1 || layer == 0 {
+        return None;
+    }
+    let layer = usize::from(3 - layer);
+    let lsf = version != 3;
+
+    let bitrate = *MPA_BITRATES[usize::from(lsf)][layer].get(usize::from(buf[2] >> 4))? * 1000;
+    let sample_rate = *MPA_SAMPLE_RATES.get(usize::from((buf[2] >> 2) & 0x03))?
+        >> match version {
+            3 => 0,
+            2 => 1,
+            _ => 2,
+        };
+    let padding = u32::from((buf[2] >> 1) & 0x01);
+
+    // free format is not supported
+    if bitrate == 0 {
+        return None;
+    }
+
+    let (size, samples) = match layer {
+        0 => ((12 * bitrate / sample_rate + padding) * 4, 384),
+        1 => (144 * bitrate / sample_rate + padding, 1152),
+        _ if lsf => (72 * bitrate / sample_rate + padding, 576),
+        _ => (144 * bitrate / sample_rate + padding, 1152),
+    };
+
+    Some(Sync {
+        size: size as usize,
+        samples,
+        sample_rate: Some(sample_rate),
+    })
+}
+
+/// ATSC A/52 AC-3 and E-AC-3 syncframe
+fn ac3(buf: &[u8]) -> Option<Sync> {
+    if buf[0] != 0x0B || buf[1] != 0x77 {
+        return None;
+    }
+
+    let bsid = buf[5] >> 3;
+    let fscod = usize::from(buf[4] >> 6);
+
+    if bsid <= 10 {
+        let frmsizecod = usize::from(buf[4] & 0x3F);
+        let words = *AC3_FRAME_SIZES.get(fscod)?.get(frmsizecod)?;
+
+        Some(Sync {
+            size: usize::from(words) * 2,
+            samples: 1536,
+            sample_rate: Some(AC3_SAMPLE_RATES[fscod]),
+        })
+    } else if bsid <= 16 {
+        let frmsiz = (usize::from(buf[2] & 0x07) << 8) | usize::from(buf[3]);
+        let (sample_rate, blocks) = if fscod == 3 {
+            let fscod2 = usize::from((buf[4] >> 4) & 0x03);
+            (*EAC3_SAMPLE_RATES_REDUCED.get(fscod2)?, 6)
+        } else {
+            (
+                AC3_SAMPLE_RATES[fscod],
+                EAC3_BLOCKS[usize::from((buf[4] >> 4) & 0x03)],
+            )
+        };
+
+        Some(Sync {
+            size: (frmsiz + 1) * 2,
+            samples: blocks * 256,
+            sample_rate: Some(sample_rate),
+        })
+    } else {
+        None
+    }
+}
+
+/// channels by AAC channel_configuration 1..7
+const AAC_CHANNELS: [&str; 8] = ["pce", "1.0", "2.0", "3.0", "4.0", "5.0", "5.1", "7.1"];
+
+/// full bandwidth channels by AC-3 acmod
+const AC3_CHANNELS: [u8; 8] = [2, 1, 2, 3, 3, 4, 4, 5];
+
+/// kbps by frmsizecod / 2
+const AC3_BITRATES: [u32; 19] = [
+    32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384, 448, 512, 576, 640,
+];
+
+fn aac_object_type(object_type: u8) -> &'static str {
+    match object_type {
+        1 => "main",
+        2 => "lc",
+        3 => "ssr",
+        4 => "ltp",
+        5 => "he-aac",
+        6 => "scalable",
+        23 => "ld",
+        29 => "he-aacv2",
+        39 => "eld",
+        42 => "usac",
+        _ => "unknown",
+    }
+}
+
+/// AAC by ADTS header or LATM StreamMuxConfig
+#[derive(Clone, Debug, PartialEq)]
+pub struct Aac {
+    /// adts | latm
+    pub transport: &'static str,
+    /// MPEG-4 audio object type (AOT)
+    pub object_type: u8,
+    pub sample_rate: u32,
+    /// SBR/PS output rate
+    pub ext_sample_rate: Option<u32>,
+    pub channel_config: u8,
+    /// ADTS: MPEG-2 AAC (ID bit)
+    pub mpeg2: bool,
+    /// ADTS: CRC present
+    pub crc: bool,
+}
+
+impl Aac {
+    /// ADTS fixed header
+    pub fn adts(buf: &[u8]) -> Option<Aac> {
+        sync("aac", buf)?;
+
+        Some(Aac {
+            transport: "adts",
+            object_type: (buf[2] >> 6) + 1,
+            sample_rate: ADTS_SAMPLE_RATES[usize::from((buf[2] >> 2) & 0x0F)],
+            ext_sample_rate: None,
+            channel_config: ((buf[2] & 0x01) << 2) | (buf[3] >> 6),
+            mpeg2: buf[1] & 0x08 != 0,
+            crc: buf[1] & 0x01 == 0,
+        })
+    }
+
+    /// LOAS AudioMuxElement(1) with StreamMuxConfig;
+    /// None if config is not repeated in this frame
+    pub fn latm(buf: &[u8]) -> Option<Aac> {
+        sync("aac-latm", buf)?;
+        let mut r = Bits::new(&buf[3..]);
+
+        // useSameStreamMux
+        if r.flag()? {
+            return None;
+        }
+
+        let version = r.flag()?;
+        if version && r.flag()? {
+            // audioMuxVersionA
+            return None;
+        }
+        if version {
+            // taraBufferFullness
+            latm_value(&mut r)?;
+        }
+        // allStreamsSameTimeFraming, numSubFrames, numProgram, numLayer
+        r.skip(1 + 6 + 4 + 3)?;
+        if version {
+            // ascLen
+            latm_value(&mut r)?;
+        }
+
+        let mut aac = audio_specific_config(&mut r)?;
+        aac.transport = "latm";
+        Some(aac)
+    }
+
+    pub fn profile(&self) -> &'static str {
+        aac_object_type(self.object_type)
+    }
+
+    pub fn channels(&self) -> &'static str {
+        AAC_CHANNELS
+            .get(usize::from(self.channel_config))
+            .copied()
+            .unwrap_or("reserved")
+    }
+
+    pub fn json(&self) -> String {
+        format!(
+            r#"{{"transport":"{}","profile":"{}","sample_rate":{},"ext_sample_rate":{},"channels":"{}","mpeg2":{},"crc":{}}}"#,
+            self.transport,
+            self.profile(),
+            self.sample_rate,
+            self.ext_sample_rate
+                .map(|v| v.to_string())
+                .unwrap_or_else(|| "null".to_string()),
+            self.channels(),
+            self.mpeg2,
+            self.crc
+        )
+    }
+}
+
+impl fmt::Display for Aac {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            ":transport {} :profile {} :sample-rate {}",
+            self.transport,
+            self.profile(),
+            self.sample_rate
+        )?;
+        if let Some(ext_sample_rate) = self.ext_sample_rate {
+            write!(f, " :ext-sample-rate {}", ext_sample_rate)?;
+        }
+        write!(f, " :channels {}", self.channels())?;
+        if self.crc {
+            write!(f, " :crc true")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// LatmGetValue
+fn latm_value(r: &mut Bits) -> Option<u32> {
+    let bytes = r.u8(2)? + 1;
+    let mut v = 0;
+    for _ in 0..bytes {
+        v = (v << 8) | r.bits(8)?;
+    }
+    Some(v)
+}
+
+fn sampling_frequency(r: &mut Bits) -> Option<u32> {
+    match r.u8(4)? {
+        0x0F => r.bits(24),
+        index => ADTS_SAMPLE_RATES.get(usize::from(index)).copied(),
+    }
+}
+
+fn object_type(r: &mut Bits) -> Option<u8> {
+    match r.u8(5)? {
+        31 => Some(32 + r.u8(6)?),
+        object_type => Some(object_type),
+    }
+}
+
+/// ISO/IEC 14496-3 AudioSpecificConfig up to channelConfiguration
+/// and explicit SBR/PS signalling
+fn audio_specific_config(r: &mut Bits) -> Option<Aac> {
+    let object_type_ = object_type(r)?;
+    let sample_rate = sampling_frequency(r)?;
+    let channel_config = r.u8(4)?;
+
+    let mut ext_sample_rate = None;
+    if object_type_ == 5 || object_type_ == 29 {
+        ext_sample_rate = Some(sampling_frequency(r)?);
+        // core coder; profile is named by extension
+        object_type(r)?;
+    }
+
+    Some(Aac {
+        transport: "",
+        object_type: object_type_,
+        sample_rate,
+        ext_sample_rate,
+        channel_config,
+        mpeg2: false,
+        crc: false,
+    })
+}
+
+/// MPEG-1/2 audio header
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mpa {
+    /// 1, 2 or 2.5
+    pub version: &'static str,
+    /// 1..3
+    pub layer: u8,
+    /// bit/s
+    pub bitrate: u32,
+    pub sample_rate: u32,
+    /// channel mode
+    pub mode: u8,
+    pub crc: bool,
+}
+
+impl Mpa {
+    pub fn parse(buf: &[u8]) -> Option<Mpa> {
+        let sync = sync("mp2", buf)?;
+
+        let version = (buf[1] >> 3) & 0x03;
+        let layer = 4 - ((buf[1] >> 1) & 0x03);
+        let lsf = version != 3;
+        let bitrate =
+            MPA_BITRATES[usize::from(lsf)][usize::from(layer - 1)][usize::from(buf[2] >> 4)] * 1000;
+
+        Some(Mpa {
+            version: match version {
+                3 => "1",
+                2 => "2",
+                _ => "2.5",
+            },
+            layer,
+            bitrate,
+            sample_rate: sync.sample_rate?,
+            mode: buf[3] >> 6,
+            crc: buf[1] & 0x01 == 0,
+        })
+    }
+
+    pub fn channels(&self) -> &'static str {
+        match self.mode {
+            0 => "stereo",
+            1 => "joint-stereo",
+            2 => "dual-channel",
+            _ => "mono",
+        }
+    }
+
+    pub fn json(&self) -> String {
+        format!(
+            r#"{{"version":"{}","layer":{},"bitrate":{},"sample_rate":{},"channels":"{}","crc":{}}}"#,
+            self.version,
+            self.layer,
+            self.bitrate,
+            self.sample_rate,
+            self.channels(),
+            self.crc
+        )
+    }
+}
+
+impl fmt::Display for Mpa {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            ":version {} :layer {} :bitrate {} :sample-rate {} :channels {}",
+            self.version,
+            self.layer,
+            self.bitrate,
+            self.sample_rate,
+            self.channels()
+        )?;
+        if self.crc {
+            write!(f, " :crc true")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// AC-3 / E-AC-3 syncinfo and bit stream information
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ac3 {
+    pub bsid: u8,
+    /// bit/s
+    pub bitrate: u32,
+    pub sample_rate: u32,
+    /// audio coding mode (channel layout)
+    pub acmod: u8,
+    pub lfe: bool,
+    /// bit stream mode (main, commentary, ...)
+    pub bsmod: u8,
+    /// dialogue normalization; -dBFS
+    pub dialnorm: u8,
+}
+
+impl Ac3 {
+    /// independent (sub)stream; E-AC-3 dependent substreams are None
+    pub fn parse(buf: &[u8]) -> Option<Ac3> {
+        let sync = sync("ac3", buf)?;
+        let bsid = buf[5] >> 3;
+
+        let mut r = Bits::new(&buf[2..]);
+        let (acmod, lfe, bsmod, dialnorm, bitrate) = if bsid <= 10 {
+            // crc1, fscod, frmsizecod, bsid
+            r.skip(16 + 2 + 6 + 5)?;
+            let bsmod = r.u8(3)?;
+            let acmod = r.u8(3)?;
+            if acmod & 0x01 != 0 && acmod != 1 {
+                // cmixlev
+                r.skip(2)?;
+            }
+            if acmod & 0x04 != 0 {
+                // surmixlev
+                r.skip(2)?;
+            }
+            if acmod == 2 {
+                // dsurmod
+                r.skip(2)?;
+            }
+            let lfe = r.flag()?;
+            let dialnorm = r.u8(5)?;
+            let bitrate = *AC3_BITRATES.get(usize::from(buf[4] & 0x3F) / 2)? * 1000;
+
+            (acmod, lfe, bsmod, dialnorm, bitrate)
+        } else {
+            // strmtyp: 1 is dependent substream
+            if r.u8(2)? == 1 {
+                return None;
+            }
+            // substreamid, frmsiz, fscod, fscod2 / numblkscod
+            r.skip(3 + 11 + 2 + 2)?;
+            let acmod = r.u8(3)?;
+            let lfe = r.flag()?;
+            // bsid
+            r.skip(5)?;
+            let dialnorm = r.u8(5)?;
+            let sample_rate = sync.sample_rate?;
+            let bitrate =
+                (sync.size as u64 * 8 * u64::from(sample_rate) / u64::from(sync.samples)) as u32;
+
+            (acmod, lfe, 0, dialnorm, bitrate)
+        };
+
+        Some(Ac3 {
+            bsid,
+            bitrate,
+            sample_rate: sync.sample_rate?,
+            acmod,
+            lfe,
+            bsmod,
+            dialnorm: if dialnorm == 0 { 31 } else { dialnorm },
+        })
+    }
+
+    pub fn eac3(&self) -> bool {
+        self.bsid > 10
+    }
+
+    /// e.g. 5.1; 1+1 is dual mono
+    pub fn channels(&self) -> String {
+        if self.acmod == 0 {
+            return "1+1".to_string();
+        }
+        format!(
+            "{}.{}",
+            AC3_CHANNELS[usize::from(self.acmod & 0x07)],
+            u8::from(self.lfe)
+        )
+    }
+
+    pub fn service(&self) -> &'static str {
+        match self.bsmod {
+            0 => "main",
+            1 => "music-effects",
+            2 => "visually-impaired",
+            3 => "hearing-impaired",
+            4 => "dialogue",
+            5 => "commentary",
+            6 => "emergency",
+            7 if self.acmod == 1 => "voice-over",
+            _ => "karaoke",
+        }
+    }
+
+    pub fn json(&self) -> String {
+        format!(
+            r#"{{"bsid":{},"bitrate":{},"sample_rate":{},"channels":"{}","service":"{}","dialnorm":-{}}}"#,
+            self.bsid,
+            self.bitrate,
+            self.sample_rate,
+            self.channels(),
+            self.service(),
+            self.dialnorm
+        )
+    }
+}
+
+impl fmt::Display for Ac3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            ":bitrate {} :sample-rate {} :channels {}",
+            self.bitrate,
+            self.sample_rate,
+            self.channels()
+        )?;
+        if !self.eac3() {
+            write!(f, " :service {}", self.service())?;
+        }
+        write!(f, " :dialnorm -{}dB", self.dialnorm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// AAC LC 48kHz 2.0, 371 bytes
+    const ADTS: [u8; 7] = [0xFF, 0xF1, 0x4C, 0x80, 0x2E, 0x7F, 0xFC];
+    /// StreamMuxConfig with AAC LC 48kHz 2.0
+    const LOAS: [u8; 7] = [0x56, 0xE0, 0x04, 0x20, 0x00, 0x11, 0x90];
+    /// MPEG-1 layer II 192 kbit/s 48kHz stereo
+    const MPA: [u8; 7] = [0xFF, 0xFD, 0xA4, 0x00, 0x00, 0x00, 0x00];
+    /// AC-3 384 kbit/s 48kHz 3/2 with LFE, dialnorm -27
+    const AC3: [u8; 8] = [0x0B, 0x77, 0x00, 0x00, 0x1C, 0x40, 0xE1, 0xD8];
+
+    #[test]
+    fn valid() {
+        let frame = sync("aac", &ADTS).unwrap();
+        assert_eq!((frame.size, frame.samples), (371, 1024));
+        assert_eq!(frame.duration(), Some(1920));
+        let aac = Aac::adts(&ADTS).unwrap();
+        assert_eq!((aac.profile(), aac.sample_rate), ("lc", 48000));
+        assert_eq!(aac.channels(), "2.0");
+
+        assert_eq!(sync("aac-latm", &LOAS).unwrap().size, 7);
+        let aac = Aac::latm(&LOAS).unwrap();
+        assert_eq!((aac.transport, aac.sample_rate), ("latm", 48000));
+        assert_eq!(aac.channels(), "2.0");
+
+        assert_eq!(sync("mp2", &MPA).unwrap().size, 576);
+        let mpa = Mpa::parse(&MPA).unwrap();
+        assert_eq!((mpa.layer, mpa.bitrate), (2, 192_000));
+        assert_eq!(mpa.channels(), "stereo");
+
+        assert_eq!(sync("ac3", &AC3).unwrap().size, 1536);
+        let ac3 = Ac3::parse(&AC3).unwrap();
+        assert_eq!((ac3.bitrate, ac3.sample_rate), (384_000, 48000));
+        assert_eq!((ac3.channels().as_str(), ac3.dialnorm), ("5.1", 27));
+    }
+
+    #[test]
+    fn truncated() {
+        for len in 0..SYNC_SZ {
+            assert!(sync("aac", &ADTS[..len]).is_none());
+            assert!(Aac::adts(&ADTS[..len]).is_none());
+            assert!(Aac::latm(&LOAS[..len]).is_none());
+            assert!(Mpa::parse(&MPA[..len]).is_none());
+            assert!(Ac3::parse(&AC3[..len]).is_none());
+        }
+        // dialnorm cut
+        assert!(Ac3::parse(&AC3[..7]).is_none());
+    }
+
+    #[test]
+    fn length_overrun() {
+        // ADTS frame_length shorter than header
+        let mut adts = ADTS;
+        adts[3..6].copy_from_slice(&[0x80, 0x00, 0xDF]);
+        assert!(sync("aac", &adts).is_none());
+
+        // taraBufferFullness of 4 bytes past AudioMuxElement
+        let loas = [0x56, 0xE0, 0x04, 0x58, 0xFF, 0xFF, 0xFF];
+        assert!(Aac::latm(&loas).is_none());
+
+        // LOAS audioMuxLengthBytes past buffer is left to caller
+        let loas = [0x56, 0xFF, 0xFF, 0x20, 0x00, 0x11, 0x90];
+        assert_eq!(sync("aac-latm", &loas).unwrap().size, 3 + 0x1FFF);
+    }
+}
@@ -0,0 +1,263 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use log::trace;
+use url::Url;
+
+use crate::clock::{Clock, Throttle};
+use crate::filter::Consumer;
+use crate::metrics::Metrics;
+use crate::track::Track;
+
+/// window slot duration; instantaneous bitrate is measured over one slot
+const SLOT: Duration = Duration::from_millis(100);
+const SLOTS_1S: usize = 10;
+const SLOTS_10S: usize = 100;
+const SLOTS_60S: usize = 600;
+
+/// bytes per slot for the last 60s;
+/// back slot is the one being filled
+#[derive(Default)]
+struct Window {
+    slots: VecDeque<u64>,
+    /// index of back slot since clock origin
+    at: u64,
+}
+
+impl Window {
+    fn advance(&mut self, at: u64) {
+        if self.slots.is_empty() || at < self.at || at - self.at > SLOTS_60S as u64 {
+            self.slots.clear();
+            self.slots.push_back(0);
+            self.at = at;
+            return;
+        }
+
+        while self.at < at {
+            self.slots.push_back(0);
+            self.at += 1;
+        }
+        while self.slots.len() > SLOTS_60S + 1 {
+            self.slots.pop_front();
+        }
+    }
+
+    fn add(&mut self, bytes: u64) {
+        if let Some(back) = self.slots.back_mut() {
+            *back += bytes;
+        }
+    }
+
+    /// bits per second over the last n complete slots;
+    /// shorter history is averaged over what is available
+    fn rate(&self, n: usize) -> Option<u64> {
+        let complete = self.slots.len().saturating_sub(1);
+        let n = n.min(complete);
+        if n == 0 {
+            return None;
+        }
+
+        let bytes: u64 = self.slots.iter().rev().skip(1).take(n).sum();
+        let ns = n as u64 * SLOT.as_nanos() as u64;
+
+        Some(bytes * 8 * 1_000_000_000 / ns)
+    }
+}
+
+/// sliding averages with min/max of 1s average
+#[derive(Default)]
+struct Meter {
+    window: Window,
+    min: Option<u64>,
+    max: Option<u64>,
+}
+
+impl Meter {
+    fn advance(&mut self, at: u64) {
+        let before = self.window.at;
+        self.window.advance(at);
+
+        // min/max only over complete seconds
+        if at > before && self.window.slots.len() > SLOTS_1S {
+            if let Some(rate) = self.window.rate(SLOTS_1S) {
+                self.min = Some(self.min.map_or(rate, |min| min.min(rate)));
+                self.max = Some(self.max.map_or(rate, |max| max.max(rate)));
+            }
+        }
+    }
+}
+
+impl fmt::Display for Meter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let v = |v: Option<u64>| v.map(|v| v.to_string()).unwrap_or_else(|| "~".to_string());
+
+        write!(
+            f,
+            "(:now {} :1s {} :10s {} :60s {} :min {} :max {})",
+            v(self.window.rate(1)),
+            v(self.window.rate(SLOTS_1S)),
+            v(self.window.rate(SLOTS_10S)),
+            v(self.window.rate(SLOTS_60S)),
+            v(self.min),
+            v(self.max)
+        )
+    }
+}
+
+/// total, per-PID and per-program meters driven by one clock
+#[derive(Default)]
+struct Meters {
+    total: Meter,
+    pids: BTreeMap<u16, Meter>,
+    programs: BTreeMap<u16, Meter>,
+
+    at: Option<u64>,
+}
+
+impl Meters {
+    /// ns since clock origin
+    fn add(&mut self, ns: u64, pid: u16, program: Option<u16>, bytes: u64) {
+        let at = ns / SLOT.as_nanos() as u64;
+
+        if self.at != Some(at) {
+            self.total.advance(at);
+            for meter in self.pids.values_mut() {
+                meter.advance(at);
+            }
+            for meter in self.programs.values_mut() {
+                meter.advance(at);
+            }
+            self.at = Some(at);
+        }
+
+        self.total.window.add(bytes);
+
+        let meter = self.pids.entry(pid).or_default();
+        meter.window.advance(at);
+        meter.window.add(bytes);
+
+        if let Some(program) = program {
+            let meter = self.programs.entry(program).or_default();
+            meter.window.advance(at);
+            meter.window.add(bytes);
+        }
+    }
+
+    fn publish(&self, metrics: &Metrics, prefix: &str) {
+        let v = |v: Option<u64>| v.unwrap_or(0);
+        let total = &self.total;
+
+        metrics.set(prefix.to_string(), v(total.window.rate(1)));
+        metrics.set(format!("{}-1s", prefix), v(total.window.rate(SLOTS_1S)));
+        metrics.set(format!("{}-10s", prefix), v(total.window.rate(SLOTS_10S)));
+        metrics.set(format!("{}-60s", prefix), v(total.window.rate(SLOTS_60S)));
+        metrics.set(format!("{}-min", prefix), v(total.min));
+        metrics.set(format!("{}-max", prefix), v(total.max));
+
+        for (pid, meter) in self.pids.iter() {
+            metrics.set(format!("{}-pid-0x{:04X}", prefix, pid), meter.to_string());
+        }
+        for (program, meter) in self.programs.iter() {
+            metrics.set(format!("{}-program-{}", prefix, program), meter.to_string());
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// pid -> program
+    programs: BTreeMap<u16, u16>,
+
+    /// network bitrate by arrival time
+    net: Meters,
+    origin: Option<Instant>,
+
+    /// stream bitrate by PCR
+    ts: Meters,
+    clock: Clock,
+}
+
+/// total and per-PID/per-program bitrate;
+/// network (arrival time) and stream (PCR) clocks
+pub struct Bitrate {
+    url: Url,
+
+    state: RefCell<State>,
+
+    metrics: Metrics,
+    metrics_at: Throttle,
+}
+
+impl Bitrate {
+    pub fn new(url: Url) -> Bitrate {
+        Bitrate {
+            url,
+            state: Default::default(),
+            metrics: Default::default(),
+            metrics_at: Default::default(),
+        }
+    }
+
+    pub fn metrics(&mut self, metrics: Metrics) -> &Bitrate {
+        self.metrics = metrics;
+        self
+    }
+
+    fn metrics_publish(&self) {
+        if !self.metrics_at.ready() {
+            return;
+        }
+
+        let state = self.state.borrow();
+        state.net.publish(&self.metrics, "bitrate-net");
+        if state.clock.pcr_locked() {
+            state.ts.publish(&self.metrics, "bitrate-ts");
+        }
+    }
+}
+
+impl Consumer for Bitrate {
+    fn consume_trk(&self, trk: &Track) {
+        let mut state = self.state.borrow_mut();
+
+        state.programs.insert(trk.pid, trk.program_number);
+        state.programs.insert(trk.pmt_pid, trk.program_number);
+        // PCR may be carried on a PID without elementary stream
+        state.programs.insert(trk.pcr_pid, trk.program_number);
+    }
+
+    fn consume_pkt_raw(&self, pkt_raw: &[u8]) {
+        let pkt = match ts::Packet::new(pkt_raw) {
+            Ok(pkt) => pkt,
+            Err(err) => {
+                trace!("({}) [bitrate] skip packet (:reason {:?})", self.url, err);
+                return;
+            }
+        };
+        let pid = u16::from(pkt.pid());
+        let bytes = pkt_raw.len() as u64;
+
+        {
+            let mut state = self.state.borrow_mut();
+            let state = &mut *state;
+
+            let program = state.programs.get(&pid).cloned();
+
+            let now = Instant::now();
+            let ns = now
+                .duration_since(*state.origin.get_or_insert(now))
+                .as_nanos() as u64;
+            state.net.add(ns, pid, program, bytes);
+
+            // packets before first PCR have no stream time
+            let ns = state.clock.update(&pkt, now);
+            if state.clock.pcr_locked() {
+                state.ts.add(ns, pid, program, bytes);
+            }
+        }
+
+        self.metrics_publish();
+    }
+}
@@ -0,0 +1,79 @@
+/// MSB-first bit reader
+pub struct Bits<'a> {
+    buf: &'a [u8],
+    /// bits consumed
+    pos: usize,
+}
+
+impl<'a> Bits<'a> {
+    pub fn new(buf: &'a [u8]) -> Bits<'a> {
+        Bits { buf, pos: 0 }
+    }
+
+    pub fn left(&self) -> usize {
+        (self.buf.len() * 8).saturating_sub(self.pos)
+    }
+
+    /// n <= 32
+    pub fn bits(&mut self, n: usize) -> Option<u32> {
+        if n > 32 || self.left() < n {
+            return None;
+        }
+
+        let mut v = 0u32;
+        for _ in 0..n {
+            let bit = (self.buf[self.pos / 8] >> (7 - self.pos % 8)) & 1;
+            v = (v << 1) | u32::from(bit);
+            self.pos += 1;
+        }
+
+        Some(v)
+    }
+
+    pub fn u8(&mut self, n: usize) -> Option<u8> {
+        self.bits(n.min(8)).map(|v| v as u8)
+    }
+
+    pub fn flag(&mut self) -> Option<bool> {
+        self.bits(1).map(|v| v == 1)
+    }
+
+    pub fn skip(&mut self, n: usize) -> Option<()> {
+        if self.left() < n {
+            return None;
+        }
+        self.pos += n;
+        Some(())
+    }
+
+    /// Exp-Golomb ue(v)
+    pub fn ue(&mut self) -> Option<u32> {
+        let mut zeros = 0;
+        while !self.flag()? {
+            zeros += 1;
+            if zeros > 31 {
+                return None;
+            }
+        }
+
+        Some(((1u64 << zeros) - 1 + u64::from(self.bits(zeros)?)) as u32)
+    }
+
+    /// Exp-Golomb se(v)
+    pub fn se(&mut self) -> Option<i32> {
+        let k = i64::from(self.ue()?);
+        let v = if k % 2 == 1 { (k + 1) / 2 } else { -(k / 2) };
+        Some(v as i32)
+    }
+
+    /// data left before rbsp_trailing_bits
+    pub fn more_rbsp_data(&self) -> bool {
+        let last = match self.buf.iter().rposition(|b| *b != 0) {
+            Some(last) => last,
+            None => return false,
+        };
+        // position of rbsp_stop_one_bit
+        let stop = last * 8 + 7 - self.buf[last].trailing_zeros() as usize;
+        self.pos < stop
+    }
+}
@@ -0,0 +1,512 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
+use url::Url;
+
+use crate::clock::{Clock, Throttle};
+use crate::filter::Consumer;
+use crate::metrics::Metrics;
+use crate::psi::{self, CaDescriptor, Section};
+
+/// CA system by CA_system_ID (ETR 162 allocations)
+pub fn system_name(system_id: u16) -> &'static str {
+    match system_id {
+        0x2600 => "biss",
+        0x4AE0 | 0x4AE1 => "dre-crypt",
+        0x5601..=0x5604 => "verimatrix",
+        _ => match system_id >> 8 {
+            0x01 => "seca",
+            0x05 => "viaccess",
+            0x06 => "irdeto",
+            0x09 => "videoguard",
+            0x0B => "conax",
+            0x0D => "cryptoworks",
+            0x0E => "powervu",
+            0x10 => "ras",
+            0x17 => "betacrypt",
+            0x18 => "nagravision",
+            _ => "unknown",
+        },
+    }
+}
+
+/// CA descriptors of one PMT
+#[derive(Clone, Default, Eq, PartialEq)]
+struct Pmt {
+    program: u16,
+    /// program_info CA descriptors
+    ca: Vec<CaDescriptor>,
+    /// elementary PID -> ES_info CA descriptors
+    streams: BTreeMap<u16, Vec<CaDescriptor>>,
+}
+
+impl Pmt {
+    fn parse(section: &Section) -> Option<Pmt> {
+        let body = section.body();
+        if body.len() < 4 {
+            return None;
+        }
+
+        let program_info_length = (usize::from(body[2] & 0x0F) << 8) | usize::from(body[3]);
+        let program_info = body.get(4..4 + program_info_length)?;
+
+        let mut pmt = Pmt {
+            program: section.table_id_extension(),
+            ca: psi::ca_descriptors(program_info).collect(),
+            streams: BTreeMap::new(),
+        };
+
+        let mut buf = &body[4 + program_info_length..];
+        while buf.len() >= 5 {
+            let pid = (u16::from(buf[1] & 0x1F) << 8) | u16::from(buf[2]);
+            let es_info_length = (usize::from(buf[3] & 0x0F) << 8) | usize::from(buf[4]);
+            let es_info = buf.get(5..5 + es_info_length).unwrap_or_default();
+
+            pmt.streams
+                .insert(pid, psi::ca_descriptors(es_info).collect());
+            buf = buf.get(5 + es_info_length..).unwrap_or_default();
+        }
+
+        Some(pmt)
+    }
+}
+
+/// elementary stream; scrambling is judged per check interval
+/// by majority of packets with payload
+#[derive(Default)]
+struct Stream {
+    program: u16,
+    /// CA descriptor for program or stream
+    expected: bool,
+
+    /// packets by transport_scrambling_control
+    tsc: [u64; 4],
+    /// clear and scrambled packets since last check
+    window: (u64, u64),
+    /// last odd/even key; change starts crypto period
+    parity: Option<u8>,
+    key_changes: u64,
+
+    scrambled: Option<bool>,
+    mismatch: bool,
+}
+
+impl Stream {
+    fn expected(&self) -> &'static str {
+        if self.expected {
+            "scrambled"
+        } else {
+            "clear"
+        }
+    }
+}
+
+/// ECM PID signalled in PMT; sections are expected within timeout
+struct Ecm {
+    program: u16,
+    system_id: u16,
+
+    /// stream time since signalled
+    started_at: Duration,
+    /// section starts
+    sections: u64,
+    /// stream time of last section start
+    last_at: Option<Duration>,
+    /// longest gap between sections since last check
+    interval_max: Duration,
+
+    alive: bool,
+    missing: bool,
+}
+
+#[derive(Default)]
+struct State {
+    /// program_number -> PMT PID
+    programs: BTreeMap<u16, u16>,
+    /// PMT PID -> CA descriptors
+    pmts: BTreeMap<u16, Pmt>,
+    /// CAT CA descriptors
+    emms: Vec<CaDescriptor>,
+
+    streams: BTreeMap<u16, Stream>,
+    ecms: BTreeMap<u16, Ecm>,
+    /// EMM PID -> packets
+    emm_packets: BTreeMap<u16, u64>,
+
+    clock: Clock,
+    /// stream time of current packet
+    now: Duration,
+
+    packets: u64,
+}
+
+/// conditional access: CAT and PMT CA descriptors (CA systems,
+/// ECM/EMM PIDs), transport_scrambling_control per PID.
+///
+/// alarms when stream scrambling differs from what PMT signals
+/// and when ECM repetition stops
+pub struct Ca {
+    url: Url,
+    ecm_timeout: Duration,
+
+    state: RefCell<State>,
+
+    metrics: Metrics,
+    metrics_at: Throttle,
+}
+
+impl Ca {
+    pub fn new(url: Url) -> Ca {
+        Ca {
+            url,
+            ecm_timeout: Duration::from_secs(2),
+            state: Default::default(),
+            metrics: Default::default(),
+            metrics_at: Default::default(),
+        }
+    }
+
+    /// ECM repetition is lost when no section for
+    pub fn ecm_timeout(&mut self, ecm_timeout: Duration) -> &Ca {
+        self.ecm_timeout = ecm_timeout;
+        self
+    }
+
+    pub fn metrics(&mut self, metrics: Metrics) -> &Ca {
+        self.metrics = metrics;
+        self
+    }
+
+    fn pat(&self, state: &mut State, section: &Section) {
+        let programs: BTreeMap<u16, u16> = section
+            .body()
+            .chunks_exact(4)
+            .map(|b| {
+                (
+                    (u16::from(b[0]) << 8) | u16::from(b[1]),
+                    (u16::from(b[2] & 0x1F) << 8) | u16::from(b[3]),
+                )
+            })
+            // network PID
+            .filter(|(program, _)| *program != 0)
+            .collect();
+
+        if programs == state.programs {
+            return;
+        }
+
+        state
+            .pmts
+            .retain(|pid, _| programs.values().any(|p| p == pid));
+        state.programs = programs;
+        self.sync(state);
+    }
+
+    fn pmt(&self, state: &mut State, pid: u16, section: &Section) {
+        let pmt = match Pmt::parse(section) {
+            Some(pmt) => pmt,
+            None => return,
+        };
+        if state.pmts.get(&pid) == Some(&pmt) {
+            return;
+        }
+
+        for ca in pmt.ca.iter() {
+            info!(
+                "({}) [ca] ECM (:program {} :system 0x{:04X} :name {} :pid 0x{:04X} :scope program)",
+                self.url,
+                pmt.program,
+                ca.system_id,
+                system_name(ca.system_id),
+                ca.pid
+            );
+        }
+        for (es_pid, cas) in pmt.streams.iter() {
+            for ca in cas.iter() {
+                info!(
+                    "({}) [ca] ECM (:program {} :system 0x{:04X} :name {} :pid 0x{:04X} :scope 0x{:04X})",
+                    self.url,
+                    pmt.program,
+                    ca.system_id,
+                    system_name(ca.system_id),
+                    ca.pid,
+                    es_pid
+                );
+            }
+        }
+
+        state.pmts.insert(pid, pmt);
+        self.sync(state);
+    }
+
+    fn cat(&self, state: &mut State, section: &Section) {
+        let emms: Vec<CaDescriptor> = psi::ca_descriptors(section.body()).collect();
+        if emms == state.emms {
+            return;
+        }
+
+        for ca in emms.iter() {
+            info!(
+                "({}) [ca] EMM (:system 0x{:04X} :name {} :pid 0x{:04X})",
+                self.url,
+                ca.system_id,
+                system_name(ca.system_id),
+                ca.pid
+            );
+        }
+
+        state
+            .emm_packets
+            .retain(|pid, _| emms.iter().any(|ca| ca.pid == *pid));
+        for ca in emms.iter() {
+            state.emm_packets.entry(ca.pid).or_default();
+        }
+        state.emms = emms;
+    }
+
+    /// streams and ECM PIDs by current PMTs; counters are kept
+    fn sync(&self, state: &mut State) {
+        let mut streams = BTreeMap::new();
+        let mut ecms = BTreeMap::new();
+
+        for pmt in state.pmts.values() {
+            for (pid, cas) in pmt.streams.iter() {
+                let mut stream = state.streams.remove(pid).unwrap_or_default();
+                stream.program = pmt.program;
+                stream.expected = !pmt.ca.is_empty() || !cas.is_empty();
+                streams.insert(*pid, stream);
+            }
+
+            let cas = pmt.ca.iter().chain(pmt.streams.values().flatten());
+            for ca in cas {
+                // ECM PID may be shared by programs
+                if ecms.contains_key(&ca.pid) {
+                    continue;
+                }
+                let ecm = state.ecms.remove(&ca.pid).unwrap_or(Ecm {
+                    program: pmt.program,
+                    system_id: ca.system_id,
+                    started_at: state.now,
+                    sections: 0,
+                    last_at: None,
+                    interval_max: Duration::ZERO,
+                    alive: false,
+                    missing: false,
+                });
+                ecms.insert(ca.pid, ecm);
+            }
+        }
+
+        // ECM PIDs are not elementary streams
+        for pid in ecms.keys() {
+            streams.remove(pid);
+        }
+
+        state.streams = streams;
+        state.ecms = ecms;
+    }
+
+    fn packet(&self, state: &mut State, raw: &[u8]) {
+        let pid = (u16::from(raw[1] & 0x1F) << 8) | u16::from(raw[2]);
+        let tsc = raw[3] >> 6;
+        let payload = raw[3] & 0b0001_0000 != 0;
+        let pusi = raw[1] & 0b0100_0000 != 0;
+
+        if let Some(stream) = state.streams.get_mut(&pid) {
+            stream.tsc[usize::from(tsc)] += 1;
+            // adaptation field only packets are never scrambled
+            if !payload {
+                return;
+            }
+            if tsc == 0 {
+                stream.window.0 += 1;
+            } else {
+                stream.window.1 += 1;
+            }
+            if tsc >= 2 {
+                if stream.parity.is_some_and(|parity| parity != tsc) {
+                    stream.key_changes += 1;
+                }
+                stream.parity = Some(tsc);
+            }
+        } else if let Some(ecm) = state.ecms.get_mut(&pid) {
+            if !pusi {
+                return;
+            }
+
+            let now = state.now;
+            if let Some(last_at) = ecm.last_at {
+                ecm.interval_max = ecm.interval_max.max(now.saturating_sub(last_at));
+            }
+            ecm.sections += 1;
+            ecm.last_at = Some(now);
+            ecm.missing = false;
+
+            if !ecm.alive {
+                ecm.alive = true;
+                info!(
+                    "({}) [ca] ECM alive (:pid 0x{:04X} :program {} :system 0x{:04X})",
+                    self.url, pid, ecm.program, ecm.system_id
+                );
+            }
+        } else if let Some(packets) = state.emm_packets.get_mut(&pid) {
+            *packets += 1;
+        }
+    }
+
+    /// ECM repetition by stream time
+    fn ecm_check(&self, state: &mut State) {
+        let now = state.now;
+        for (pid, ecm) in state.ecms.iter_mut() {
+            match ecm.last_at {
+                Some(last_at) if ecm.alive && now.saturating_sub(last_at) > self.ecm_timeout => {
+                    ecm.alive = false;
+                    warn!(
+                        "({}) [ca] ECM repetition stopped (:pid 0x{:04X} :program {} :system 0x{:04X} :idle {:.3}s)",
+                        self.url,
+                        pid,
+                        ecm.program,
+                        ecm.system_id,
+                        now.saturating_sub(last_at).as_secs_f64()
+                    );
+                }
+                None if !ecm.missing && now.saturating_sub(ecm.started_at) > self.ecm_timeout => {
+                    ecm.missing = true;
+                    warn!(
+                        "({}) [ca] signalled ECM not present (:pid 0x{:04X} :program {} :system 0x{:04X})",
+                        self.url, pid, ecm.program, ecm.system_id
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check(&self, state: &mut State) {
+        self.ecm_check(state);
+
+        if !self.metrics_at.ready() {
+            return;
+        }
+
+        for (pid, stream) in state.streams.iter_mut() {
+            let (clear, scrambled) = std::mem::take(&mut stream.window);
+            if clear + scrambled != 0 {
+                let now = scrambled > clear;
+                if stream.scrambled.is_some_and(|last| last != now) {
+                    debug!(
+                        "({}) [ca] scrambling changed (:pid 0x{:04X} :program {} :scrambled {})",
+                        self.url, pid, stream.program, now
+                    );
+                }
+                stream.scrambled = Some(now);
+
+                let mismatch = now != stream.expected;
+                if mismatch && !stream.mismatch {
+                    warn!(
+                        "({}) [ca] scrambling mismatch (:pid 0x{:04X} :program {} :expected {} :clear {} :scrambled {})",
+                        self.url,
+                        pid,
+                        stream.program,
+                        stream.expected(),
+                        clear,
+                        scrambled
+                    );
+                } else if !mismatch && stream.mismatch {
+                    info!(
+                        "({}) [ca] scrambling OK (:pid 0x{:04X} :program {} :expected {})",
+                        self.url,
+                        pid,
+                        stream.program,
+                        stream.expected()
+                    );
+                }
+                stream.mismatch = mismatch;
+            }
+
+            self.metrics.set(
+                format!("ca-0x{:04X}", pid),
+                format!(
+                    "(:expected {} :scrambled {} :clear {} :even {} :odd {} :reserved {} :key-changes {})",
+                    stream.expected(),
+                    stream
+                        .scrambled
+                        .map(|scrambled| scrambled.to_string())
+                        .unwrap_or_else(|| "~".to_string()),
+                    stream.tsc[0],
+                    stream.tsc[2],
+                    stream.tsc[3],
+                    stream.tsc[1],
+                    stream.key_changes
+                ),
+            );
+        }
+
+        for (pid, ecm) in state.ecms.iter_mut() {
+            self.metrics.set(
+                format!("ca-ecm-0x{:04X}", pid),
+                format!(
+                    "(:program {} :system 0x{:04X} :alive {} :sections {} :interval-max {:.3}s)",
+                    ecm.program,
+                    ecm.system_id,
+                    ecm.alive,
+                    ecm.sections,
+                    std::mem::take(&mut ecm.interval_max).as_secs_f64()
+                ),
+            );
+        }
+
+        for ca in state.emms.iter() {
+            self.metrics.set(
+                format!("ca-emm-0x{:04X}", ca.pid),
+                format!(
+                    "(:system 0x{:04X} :packets {})",
+                    ca.system_id,
+                    state.emm_packets.get(&ca.pid).cloned().unwrap_or_default()
+                ),
+            );
+        }
+    }
+}
+
+impl Consumer for Ca {
+    fn consume_pkt_raw(&self, raw: &[u8]) {
+        let mut state = self.state.borrow_mut();
+        let state = &mut *state;
+
+        if let Ok(pkt) = ts::Packet::new(raw) {
+            state.now = Duration::from_nanos(state.clock.update(&pkt, Instant::now()));
+        }
+        self.packet(state, raw);
+
+        // cheap check; Instant::now is not free
+        state.packets += 1;
+        if state.packets.is_multiple_of(256) {
+            self.check(state);
+        }
+    }
+
+    fn consume_section(&self, pid: u16, buf: &[u8]) {
+        let section = match Section::try_new(buf) {
+            Some(section) => section,
+            None => return,
+        };
+        if !section.crc32_ok() || !section.current_next_indicator() {
+            return;
+        }
+
+        let mut state = self.state.borrow_mut();
+        let state = &mut *state;
+
+        match (pid, section.table_id()) {
+            (psi::PID_PAT, psi::TABLE_ID_PAT) => self.pat(state, &section),
+            (psi::PID_CAT, psi::TABLE_ID_CAT) => self.cat(state, &section),
+            (_, psi::TABLE_ID_PMT) if state.programs.values().any(|p| *p == pid) => {
+                self.pmt(state, pid, &section)
+            }
+            _ => {}
+        }
+    }
+}
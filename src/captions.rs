@@ -0,0 +1,628 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::mem;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
+use url::Url;
+
+use crate::cea608::Cea608;
+use crate::cea708::Cea708;
+use crate::clock::{wrapping_diff, Clock, Throttle, TS_MAX};
+use crate::filter::Consumer;
+use crate::frame::Frame;
+use crate::metrics::Metrics;
+use crate::mpeg2;
+use crate::nal;
+use crate::packet::Packet;
+use crate::sei;
+use crate::track::Track;
+
+/// cc_type of cc_data_pkt
+const CC_NTSC_FIELD_1: u8 = 0;
+const CC_NTSC_FIELD_2: u8 = 1;
+const CC_DTVCC_START: u8 = 3;
+
+/// access units held back to decode cc_data in presentation order
+const REORDER_DEPTH: usize = 8;
+
+/// caption on screen; 90kHz
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cue {
+    pub start: u64,
+    pub end: u64,
+    pub text: String,
+}
+
+/// displayed text changes to cues
+#[derive(Default)]
+pub struct Cues {
+    /// since and text on screen
+    shown: Option<(u64, String)>,
+    done: Vec<Cue>,
+}
+
+impl Cues {
+    /// text on screen from pts on; empty if cleared
+    pub fn show(&mut self, pts: u64, text: String) {
+        let shown = self.shown.as_ref().map(|(_, shown)| shown.as_str());
+        if shown.unwrap_or("") == text {
+            return;
+        }
+
+        if let Some((start, shown)) = self.shown.take() {
+            if start != pts {
+                self.done.push(Cue {
+                    start,
+                    end: pts,
+                    text: shown,
+                });
+            }
+        }
+        if !text.is_empty() {
+            self.shown = Some((pts, text));
+        }
+    }
+
+    /// cues finished since last call
+    pub fn take(&mut self) -> Vec<Cue> {
+        mem::take(&mut self.done)
+    }
+}
+
+/// caption export file format
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Srt,
+    WebVtt,
+}
+
+impl Format {
+    fn ext(self) -> &'static str {
+        match self {
+            Format::Srt => "srt",
+            Format::WebVtt => "vtt",
+        }
+    }
+
+    /// 90kHz
+    fn timestamp(self, ts: u64) -> String {
+        let ms = ts / 90;
+        let sep = match self {
+            Format::Srt => ',',
+            Format::WebVtt => '.',
+        };
+
+        format!(
+            "{:02}:{:02}:{:02}{}{:03}",
+            ms / 3_600_000,
+            ms / 60_000 % 60,
+            ms / 1000 % 60,
+            sep,
+            ms % 1000
+        )
+    }
+}
+
+impl FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "srt" => Ok(Format::Srt),
+            "vtt" | "webvtt" => Ok(Format::WebVtt),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Format::Srt => write!(f, "srt"),
+            Format::WebVtt => write!(f, "vtt"),
+        }
+    }
+}
+
+/// cc_data_pkt with cc_valid set: (cc_type, cc_data_1, cc_data_2)
+type CcPacket = (u8, u8, u8);
+
+/// A/53 cc_data(): flags, em_data, cc_data_pkt[cc_count]
+fn cc_data(data: &[u8], pkts: &mut Vec<CcPacket>) {
+    let (flags, data) = match data {
+        [flags, _, data @ ..] => (*flags, data),
+        _ => return,
+    };
+    // process_cc_data_flag
+    if flags & 0x40 == 0 {
+        return;
+    }
+
+    for pkt in data.chunks_exact(3).take(usize::from(flags & 0x1F)) {
+        if pkt[0] & 0x04 != 0 {
+            pkts.push((pkt[0] & 0x03, pkt[1], pkt[2]));
+        }
+    }
+}
+
+/// caption data of video access unit: MPEG-2 user_data or
+/// H.264/H.265 SEI user_data_registered_itu_t_t35 parsed by elementary
+/// stream analyzer
+fn extract(codec: &str, frm: &Frame) -> Vec<CcPacket> {
+    let mut pkts = Vec::new();
+
+    match codec {
+        "mpeg1video" | "mpeg2video" => {
+            for unit in nal::units(&frm.data) {
+                match unit.split_first() {
+                    Some((&mpeg2::USER_DATA_START, data)) => {
+                        if let Some(data) = sei::a53_cc_data(data) {
+                            cc_data(data, &mut pkts);
+                        }
+                    }
+                    Some((code, _))
+                        if (mpeg2::SLICE_START_MIN..=mpeg2::SLICE_START_MAX).contains(code) =>
+                    {
+                        break
+                    }
+                    _ => {}
+                }
+            }
+        }
+        "h264" | "h265" => {
+            for (payload_type, payload) in frm.sei.iter() {
+                if *payload_type != sei::SEI_USER_DATA_REGISTERED {
+                    continue;
+                }
+                if let Some(data) = sei::t35_cc_data(payload) {
+                    cc_data(data, &mut pkts);
+                }
+            }
+        }
+        _ => {}
+    }
+
+    pkts
+}
+
+/// CEA-608 data channel CC1..CC4 or CEA-708 service 1..63
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Channel {
+    Cc(u8),
+    Service(u8),
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Channel::Cc(n) => write!(f, "cc{}", n),
+            Channel::Service(n) => write!(f, "svc{}", n),
+        }
+    }
+}
+
+struct ChannelState {
+    /// stream time of last caption data
+    last_at: Duration,
+    alive: bool,
+    cues: u64,
+    /// None until first cue; Some(None) if export failed
+    file: Option<Option<BufWriter<File>>>,
+}
+
+/// per video PID
+struct Stream {
+    codec: &'static str,
+    /// first presentation time; exported cue times are relative to it
+    origin: Option<u64>,
+    /// (pts, cc_data) of access units in presentation order
+    pending: Vec<(u64, Vec<CcPacket>)>,
+    /// of last access unit; stands in for missing PTS and DTS
+    last_pts: Option<u64>,
+
+    fields: [Cea608; 2],
+    dtvcc: Cea708,
+
+    /// stream time caption data was carried at all (padding included)
+    data_at: Option<Duration>,
+    data_alive: bool,
+    channels: BTreeMap<Channel, ChannelState>,
+}
+
+impl Stream {
+    fn new(codec: &'static str) -> Stream {
+        Stream {
+            codec,
+            origin: None,
+            pending: Vec::new(),
+            last_pts: None,
+            fields: Default::default(),
+            dtvcc: Default::default(),
+            data_at: None,
+            data_alive: false,
+            channels: Default::default(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    clock: Clock,
+    /// stream time of current packet
+    now: Duration,
+
+    streams: BTreeMap<u16, Stream>,
+}
+
+/// ATSC A/53 closed captions of video streams: CEA-608 (CC1..CC4)
+/// and CEA-708 services decoded to cues.
+///
+/// channel is alive while it carries caption data at least once per
+/// timeout of stream time; cues are exported per channel to SRT or WebVTT files.
+///
+/// path template placeholders:
+///   $(pid) - e.g. 0x0100
+///   $(channel) - cc1..cc4, svc1..svc63
+///   $(ext) - srt | vtt
+pub struct Captions {
+    url: Url,
+    timeout: Duration,
+    out: Option<String>,
+    format: Format,
+
+    state: RefCell<State>,
+    paths: RefCell<HashSet<PathBuf>>,
+
+    metrics: Metrics,
+    metrics_at: Throttle,
+}
+
+impl Captions {
+    pub fn new(url: Url) -> Captions {
+        Captions {
+            url,
+            timeout: Duration::from_secs(30),
+            out: None,
+            format: Format::Srt,
+            state: Default::default(),
+            paths: Default::default(),
+            metrics: Default::default(),
+            metrics_at: Default::default(),
+        }
+    }
+
+    /// channel is lost when no caption data for
+    pub fn timeout(&mut self, timeout: Duration) -> &Captions {
+        self.timeout = timeout;
+        self
+    }
+
+    /// cue export path template
+    pub fn out(&mut self, out: Option<PathBuf>, format: Format) -> &Captions {
+        self.out = out.map(|out| out.to_string_lossy().to_string());
+        self.format = format;
+        self
+    }
+
+    pub fn metrics(&mut self, metrics: Metrics) -> &Captions {
+        self.metrics = metrics;
+        self
+    }
+
+    fn path(&self, pid: u16, channel: Channel) -> Option<PathBuf> {
+        let pid_s = format!("0x{:04X}", pid);
+        let channel_s = channel.to_string();
+        let path = PathBuf::from(
+            self.out
+                .as_ref()?
+                .replace("$(pid)", &pid_s)
+                .replace("$(channel)", &channel_s)
+                .replace("$(ext)", self.format.ext()),
+        );
+
+        let mut paths = self.paths.borrow_mut();
+        if !paths.contains(&path) {
+            paths.insert(path.clone());
+            return Some(path);
+        }
+
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let name = match path.extension() {
+            Some(ext) => format!("{}-{}-{}.{}", stem, pid_s, channel_s, ext.to_string_lossy()),
+            None => format!("{}-{}-{}", stem, pid_s, channel_s),
+        };
+        let path = path.with_file_name(name);
+        paths.insert(path.clone());
+        Some(path)
+    }
+
+    fn open(&self, pid: u16, channel: Channel) -> Option<BufWriter<File>> {
+        let path = self.path(pid, channel)?;
+
+        let file = File::create(&path).and_then(|file| {
+            let mut file = BufWriter::new(file);
+            if self.format == Format::WebVtt {
+                file.write_all(b"WEBVTT\n\n")?;
+            }
+            Ok(file)
+        });
+        match file {
+            Ok(file) => {
+                info!(
+                    "({}) [captions] export OK (:pid 0x{:04X} :channel {} :path {})",
+                    self.url,
+                    pid,
+                    channel,
+                    path.display()
+                );
+                Some(file)
+            }
+            Err(err) => {
+                warn!(
+                    "({}) [captions] export disabled (:pid 0x{:04X} :channel {} :path {} :error {})",
+                    self.url,
+                    pid,
+                    channel,
+                    path.display(),
+                    err
+                );
+                None
+            }
+        }
+    }
+
+    fn write(&self, file: &mut BufWriter<File>, index: u64, start: u64, end: u64, text: &str) {
+        let timing = format!(
+            "{} --> {}",
+            self.format.timestamp(start),
+            self.format.timestamp(end)
+        );
+        let result = match self.format {
+            Format::Srt => write!(file, "{}\n{}\n{}\n\n", index, timing, text),
+            Format::WebVtt => write!(file, "{}\n{}\n\n", timing, text),
+        };
+        // cues are rare; keep files complete for readers
+        if let Err(err) = result.and_then(|_| file.flush()) {
+            warn!("({}) [captions] export write (:error {})", self.url, err);
+        }
+    }
+
+    /// cc_data of access unit in presentation order
+    fn decode(&self, pid: u16, stream: &mut Stream, now: Duration, pts: u64, pkts: Vec<CcPacket>) {
+        let origin = *stream.origin.get_or_insert(pts);
+
+        let mut active = Vec::new();
+        for (kind, b1, b2) in pkts {
+            match kind {
+                CC_NTSC_FIELD_1 | CC_NTSC_FIELD_2 => {
+                    let field = usize::from(kind);
+                    if let Some(channel) = stream.fields[field].push(pts, b1, b2) {
+                        active.push(Channel::Cc((field * 2 + channel + 1) as u8));
+                    }
+                }
+                _ => {
+                    let start = kind == CC_DTVCC_START;
+                    for service in stream.dtvcc.push(pts, start, b1, b2) {
+                        active.push(Channel::Service(service));
+                    }
+                }
+            }
+        }
+
+        for channel in active {
+            let state = stream
+                .channels
+                .entry(channel)
+                .or_insert_with(|| ChannelState {
+                    last_at: now,
+                    alive: false,
+                    cues: 0,
+                    file: None,
+                });
+            state.last_at = now;
+
+            if !state.alive {
+                state.alive = true;
+                info!(
+                    "({}) [captions] channel alive (:pid 0x{:04X} :codec {} :channel {})",
+                    self.url, pid, stream.codec, channel
+                );
+            }
+        }
+
+        let mut cues = Vec::new();
+        for (field, cea608) in stream.fields.iter_mut().enumerate() {
+            for (channel, cue) in cea608.take_cues() {
+                cues.push((Channel::Cc((field * 2 + channel + 1) as u8), cue));
+            }
+        }
+        for (service, cue) in stream.dtvcc.take_cues() {
+            cues.push((Channel::Service(service), cue));
+        }
+
+        for (channel, cue) in cues {
+            let start = wrapping_diff(cue.start, origin, TS_MAX).max(0) as u64;
+            let end = wrapping_diff(cue.end, origin, TS_MAX).max(0) as u64;
+            debug!(
+                "({}) [captions] cue (:pid 0x{:04X} :channel {} :start {:.3}s :end {:.3}s :text {:?})",
+                self.url,
+                pid,
+                channel,
+                start as f64 / 90_000.0,
+                end as f64 / 90_000.0,
+                cue.text
+            );
+
+            let state = match stream.channels.get_mut(&channel) {
+                Some(state) => state,
+                None => continue,
+            };
+            state.cues += 1;
+
+            if self.out.is_none() {
+                continue;
+            }
+            if state.file.is_none() {
+                state.file = Some(self.open(pid, channel));
+            }
+            if let Some(Some(file)) = state.file.as_mut() {
+                self.write(file, state.cues, start, end, &cue.text);
+            }
+        }
+    }
+
+    /// timeouts by stream time; then metrics
+    fn check(&self) {
+        {
+            let mut state = self.state.borrow_mut();
+            let now = state.now;
+            for (pid, stream) in state.streams.iter_mut() {
+                self.timeouts(*pid, stream, now);
+            }
+        }
+
+        self.metrics_publish();
+    }
+
+    fn timeouts(&self, pid: u16, stream: &mut Stream, now: Duration) {
+        match stream.data_at {
+            Some(data_at) if stream.data_alive && now.saturating_sub(data_at) > self.timeout => {
+                stream.data_alive = false;
+                warn!(
+                    "({}) [captions] caption data lost (:pid 0x{:04X} :idle {:.3}s)",
+                    self.url,
+                    pid,
+                    now.saturating_sub(data_at).as_secs_f64()
+                );
+            }
+            _ => {}
+        }
+
+        for (channel, state) in stream.channels.iter_mut() {
+            if state.alive && now.saturating_sub(state.last_at) > self.timeout {
+                state.alive = false;
+                warn!(
+                    "({}) [captions] channel lost (:pid 0x{:04X} :channel {} :idle {:.3}s)",
+                    self.url,
+                    pid,
+                    channel,
+                    now.saturating_sub(state.last_at).as_secs_f64()
+                );
+            }
+        }
+    }
+
+    fn metrics_publish(&self) {
+        if !self.metrics_at.ready() {
+            return;
+        }
+
+        for (pid, stream) in self.state.borrow().streams.iter() {
+            let alive: Vec<String> = stream
+                .channels
+                .iter()
+                .filter(|(_, state)| state.alive)
+                .map(|(channel, _)| channel.to_string())
+                .collect();
+            self.metrics.set(
+                format!("captions-0x{:04X}", pid),
+                format!(
+                    "(:data {} :channels {} :cues {})",
+                    stream.data_alive,
+                    if alive.is_empty() {
+                        "~".to_string()
+                    } else {
+                        alive.join(",")
+                    },
+                    stream
+                        .channels
+                        .values()
+                        .map(|state| state.cues)
+                        .sum::<u64>()
+                ),
+            );
+        }
+    }
+}
+
+impl Consumer for Captions {
+    fn consume_trk(&self, trk: &Track) {
+        let streams = &mut self.state.borrow_mut().streams;
+
+        match trk.codec() {
+            "mpeg1video" | "mpeg2video" | "h264" | "h265" => {
+                let known = streams
+                    .get(&trk.pid)
+                    .is_some_and(|stream| stream.codec == trk.codec());
+                if !known {
+                    streams.insert(trk.pid, Stream::new(trk.codec()));
+                }
+            }
+            _ => {
+                streams.remove(&trk.pid);
+            }
+        }
+    }
+
+    fn consume_pkt_raw(&self, pkt_raw: &[u8]) {
+        if let Ok(pkt) = ts::Packet::new(pkt_raw) {
+            let mut state = self.state.borrow_mut();
+            state.now = Duration::from_nanos(state.clock.update(&pkt, Instant::now()));
+        }
+    }
+
+    fn consume_pkt(&self, _: &Packet) {
+        self.check();
+    }
+
+    fn consume_frm(&self, frm: &Frame) {
+        {
+            let mut state = self.state.borrow_mut();
+            let now = state.now;
+            let stream = match state.streams.get_mut(&frm.pid) {
+                Some(stream) => stream,
+                None => return,
+            };
+            let pts = match frm.pts.or(frm.dts).or(stream.last_pts) {
+                Some(pts) => pts,
+                None => return,
+            };
+            stream.last_pts = Some(pts);
+
+            let pkts = extract(stream.codec, frm);
+            if !pkts.is_empty() {
+                stream.data_at = Some(now);
+                if !stream.data_alive {
+                    stream.data_alive = true;
+                    info!(
+                        "({}) [captions] caption data present (:pid 0x{:04X} :codec {})",
+                        self.url, frm.pid, stream.codec
+                    );
+                }
+            }
+
+            let at = stream
+                .pending
+                .iter()
+                .position(|(at, _)| wrapping_diff(*at, pts, TS_MAX) > 0)
+                .unwrap_or(stream.pending.len());
+            stream.pending.insert(at, (pts, pkts));
+
+            while stream.pending.len() > REORDER_DEPTH {
+                let (pts, pkts) = stream.pending.remove(0);
+                self.decode(frm.pid, stream, now, pts, pkts);
+            }
+        }
+
+        self.check();
+    }
+}
@@ -0,0 +1,389 @@
+use std::mem;
+
+use crate::captions::{Cue, Cues};
+
+const ROWS: usize = 15;
+const COLS: usize = 32;
+
+/// basic North American character set differences to ASCII
+fn basic(b: u8) -> char {
+    match b {
+        0x2A => 'á',
+        0x5C => 'é',
+        0x5E => 'í',
+        0x5F => 'ó',
+        0x60 => 'ú',
+        0x7B => 'ç',
+        0x7C => '÷',
+        0x7D => 'Ñ',
+        0x7E => 'ñ',
+        0x7F => '■',
+        _ => char::from(b),
+    }
+}
+
+/// special characters 0x11 0x30..0x3F; transparent space as space
+const SPECIAL: [char; 16] = [
+    '®', '°', '½', '¿', '™', '¢', '£', '♪', 'à', ' ', 'è', 'â', 'ê', 'î', 'ô', 'û',
+];
+
+/// extended characters 0x12 0x20..0x3F (Spanish, French, misc)
+const EXTENDED_12: [char; 32] = [
+    'Á', 'É', 'Ó', 'Ú', 'Ü', 'ü', '‘', '¡', '*', '\'', '—', '©', '℠', '•', '“', '”', 'À', 'Â', 'Ç',
+    'È', 'Ê', 'Ë', 'ë', 'Î', 'Ï', 'ï', 'Ô', 'Ù', 'ù', 'Û', '«', '»',
+];
+
+/// extended characters 0x13 0x20..0x3F (Portuguese, German, Danish)
+const EXTENDED_13: [char; 32] = [
+    'Ã', 'ã', 'Í', 'Ì', 'ì', 'Ò', 'ò', 'Õ', 'õ', '{', '}', '\\', '^', '_', '|', '~', 'Ä', 'ä', 'Ö',
+    'ö', 'ß', '¥', '¤', '¦', 'Å', 'å', 'Ø', 'ø', '┌', '┐', '└', '┘',
+];
+
+/// preamble address code row (1-based) by ((b1 & 0x07) << 1) | b2 bit 5
+const PAC_ROWS: [usize; 16] = [11, 11, 1, 2, 3, 4, 12, 13, 14, 15, 5, 6, 7, 8, 9, 10];
+
+/// bytes carry odd parity
+fn parity(b: u8) -> Option<u8> {
+    if b.count_ones() % 2 == 1 {
+        Some(b & 0x7F)
+    } else {
+        None
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Mode {
+    PopOn,
+    /// rows of roll-up window
+    RollUp(usize),
+    PaintOn,
+    /// text service (T1..T4); not decoded
+    Text,
+}
+
+/// display or non-display memory
+#[derive(Clone, Copy)]
+struct Memory([[char; COLS]; ROWS]);
+
+impl Default for Memory {
+    fn default() -> Memory {
+        Memory([[' '; COLS]; ROWS])
+    }
+}
+
+impl Memory {
+    fn text(&self) -> String {
+        self.0
+            .iter()
+            .map(|row| row.iter().collect::<String>().trim().to_string())
+            .filter(|row| !row.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// caption channel (CC1..CC4) screen state
+struct Channel {
+    mode: Mode,
+    displayed: Memory,
+    non_displayed: Memory,
+    row: usize,
+    col: usize,
+
+    cues: Cues,
+}
+
+impl Default for Channel {
+    fn default() -> Channel {
+        Channel {
+            mode: Mode::PopOn,
+            displayed: Default::default(),
+            non_displayed: Default::default(),
+            row: ROWS - 1,
+            col: 0,
+            cues: Default::default(),
+        }
+    }
+}
+
+impl Channel {
+    fn memory(&mut self) -> Option<&mut Memory> {
+        match self.mode {
+            Mode::PopOn => Some(&mut self.non_displayed),
+            Mode::RollUp(_) | Mode::PaintOn => Some(&mut self.displayed),
+            Mode::Text => None,
+        }
+    }
+
+    fn put(&mut self, c: char) {
+        let (row, col) = (self.row, self.col);
+        if let Some(memory) = self.memory() {
+            memory.0[row][col] = c;
+            self.col = (col + 1).min(COLS - 1);
+        }
+    }
+
+    fn backspace(&mut self) {
+        self.col = self.col.saturating_sub(1);
+        let (row, col) = (self.row, self.col);
+        if let Some(memory) = self.memory() {
+            memory.0[row][col] = ' ';
+        }
+    }
+
+    fn show(&mut self, pts: u64) {
+        let text = self.displayed.text();
+        self.cues.show(pts, text);
+    }
+
+    /// miscellaneous control code
+    fn control(&mut self, pts: u64, code: u8) {
+        match code {
+            // RCL
+            0x20 => self.mode = Mode::PopOn,
+            // BS
+            0x21 => self.backspace(),
+            // DER
+            0x24 => {
+                let (row, col) = (self.row, self.col);
+                if let Some(memory) = self.memory() {
+                    memory.0[row][col..].iter_mut().for_each(|c| *c = ' ');
+                }
+            }
+            // RU2..RU4
+            0x25..=0x27 => {
+                if !matches!(self.mode, Mode::RollUp(_)) {
+                    self.displayed = Default::default();
+                    self.non_displayed = Default::default();
+                    self.row = ROWS - 1;
+                    self.show(pts);
+                }
+                self.mode = Mode::RollUp(usize::from(code - 0x23));
+                self.col = 0;
+            }
+            // RDC
+            0x29 => self.mode = Mode::PaintOn,
+            // TR, RTD
+            0x2A | 0x2B => self.mode = Mode::Text,
+            // EDM
+            0x2C => {
+                self.displayed = Default::default();
+                self.show(pts);
+            }
+            // CR: line complete, then roll window up
+            0x2D => {
+                if let Mode::RollUp(rows) = self.mode {
+                    self.show(pts);
+
+                    let top = (self.row + 1).saturating_sub(rows);
+                    for row in top..self.row {
+                        self.displayed.0[row] = self.displayed.0[row + 1];
+                    }
+                    for row in 0..top {
+                        self.displayed.0[row] = [' '; COLS];
+                    }
+                    self.displayed.0[self.row] = [' '; COLS];
+                    self.col = 0;
+                }
+            }
+            // ENM
+            0x2E => self.non_displayed = Default::default(),
+            // EOC
+            0x2F => {
+                mem::swap(&mut self.displayed, &mut self.non_displayed);
+                self.mode = Mode::PopOn;
+                self.show(pts);
+            }
+            _ => {}
+        }
+    }
+
+    /// preamble address code: cursor row and indent
+    fn pac(&mut self, b1: u8, b2: u8) {
+        let row = PAC_ROWS[usize::from(((b1 & 0x07) << 1) | ((b2 >> 5) & 0x01))] - 1;
+
+        // roll-up window moves with its base row
+        if let Mode::RollUp(rows) = self.mode {
+            if row != self.row {
+                let mut displayed = Memory::default();
+                for i in 0..rows.min(row + 1).min(self.row + 1) {
+                    displayed.0[row - i] = self.displayed.0[self.row - i];
+                }
+                self.displayed = displayed;
+            }
+        }
+
+        self.row = row;
+        self.col = if b2 & 0x10 != 0 {
+            usize::from((b2 & 0x0E) >> 1) * 4
+        } else {
+            0
+        };
+    }
+}
+
+/// EIA/CEA-608 decoder of one field (line 21 field 1: CC1/CC2,
+/// field 2: CC3/CC4); XDS on field 2 is skipped
+#[derive(Default)]
+pub struct Cea608 {
+    /// data channel selected by last control code
+    channel: usize,
+    channels: [Channel; 2],
+    /// control codes are sent twice; second one is ignored
+    last_control: Option<(u8, u8)>,
+    xds: bool,
+}
+
+impl Cea608 {
+    /// byte pair of cc_data; data channel (0, 1) if it carried captions
+    pub fn push(&mut self, pts: u64, b1: u8, b2: u8) -> Option<usize> {
+        let b1 = parity(b1)?;
+        match b1 {
+            // padding
+            0x00 => None,
+            // XDS start / continue, end
+            0x01..=0x0E => {
+                self.xds = true;
+                self.last_control = None;
+                None
+            }
+            0x0F => {
+                self.xds = false;
+                self.last_control = None;
+                None
+            }
+            0x10..=0x1F => {
+                let b2 = parity(b2)?;
+                if self.last_control == Some((b1, b2)) {
+                    self.last_control = None;
+                    return None;
+                }
+                self.last_control = Some((b1, b2));
+                self.xds = false;
+
+                self.channel = usize::from((b1 >> 3) & 0x01);
+                let channel = &mut self.channels[self.channel];
+                match (b1 & 0x17, b2) {
+                    // field 2 miscellaneous control codes use 0x15
+                    (0x14, 0x20..=0x2F) | (0x15, 0x20..=0x2F) => channel.control(pts, b2),
+                    // TO1..TO3
+                    (0x17, 0x21..=0x23) => {
+                        channel.col = (channel.col + usize::from(b2 - 0x20)).min(COLS - 1)
+                    }
+                    // mid-row code: attributes shown as space
+                    (0x11, 0x20..=0x2F) => channel.put(' '),
+                    (0x11, 0x30..=0x3F) => channel.put(SPECIAL[usize::from(b2 - 0x30)]),
+                    // extended characters replace fallback character
+                    (0x12, 0x20..=0x3F) => {
+                        channel.backspace();
+                        channel.put(EXTENDED_12[usize::from(b2 - 0x20)]);
+                    }
+                    (0x13, 0x20..=0x3F) => {
+                        channel.backspace();
+                        channel.put(EXTENDED_13[usize::from(b2 - 0x20)]);
+                    }
+                    (_, 0x40..=0x7F) => channel.pac(b1, b2),
+                    _ => {}
+                }
+
+                Some(self.channel)
+            }
+            _ if self.xds => None,
+            _ => {
+                self.last_control = None;
+
+                let channel = &mut self.channels[self.channel];
+                channel.put(basic(b1));
+                if let Some(b2) = parity(b2).filter(|b2| *b2 >= 0x20) {
+                    channel.put(basic(b2));
+                }
+
+                Some(self.channel)
+            }
+        }
+    }
+
+    /// cues finished since last call by data channel
+    pub fn take_cues(&mut self) -> Vec<(usize, Cue)> {
+        self.channels
+            .iter_mut()
+            .enumerate()
+            .flat_map(|(channel, state)| {
+                state.cues.take().into_iter().map(move |cue| (channel, cue))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// push CC1 pairs with odd parity added
+    fn push(cea608: &mut Cea608, pts: u64, pairs: &[(u8, u8)]) {
+        let odd = |b: u8| if parity(b).is_some() { b } else { b | 0x80 };
+        for (b1, b2) in pairs {
+            cea608.push(pts, odd(*b1), odd(*b2));
+        }
+    }
+
+    /// RCL, PAC row 15 with indent
+    fn pop_on(indent: u8) -> [(u8, u8); 2] {
+        [(0x14, 0x20), (0x14, 0x60 | indent)]
+    }
+
+    #[test]
+    fn valid() {
+        let mut cea608 = Cea608::default();
+        push(&mut cea608, 0, &pop_on(0));
+        push(&mut cea608, 0, &[(b'H', b'I'), (0x11, 0x37)]);
+        // EOC, EDM
+        push(&mut cea608, 1000, &[(0x14, 0x2F)]);
+        push(&mut cea608, 2000, &[(0x14, 0x2C)]);
+
+        let cues = cea608.take_cues();
+        assert_eq!(cues.len(), 1);
+        assert_eq!(cues[0].0, 0);
+        assert_eq!((cues[0].1.start, cues[0].1.end), (1000, 2000));
+        assert_eq!(cues[0].1.text, "HI♪");
+    }
+
+    #[test]
+    fn truncated() {
+        let mut cea608 = Cea608::default();
+        push(&mut cea608, 0, &pop_on(0));
+        push(&mut cea608, 0, &[(b'H', b'I')]);
+        // EOC with second byte lost (parity error)
+        assert!(cea608.push(1000, 0x94, 0xAF).is_none());
+        push(&mut cea608, 2000, &[(0x14, 0x2C)]);
+        assert!(cea608.take_cues().is_empty());
+
+        // character pair with second byte lost
+        assert_eq!(cea608.push(3000, 0xC1, 0x00), Some(0));
+    }
+
+    #[test]
+    fn length_overrun() {
+        let mut cea608 = Cea608::default();
+        // indent 28, tab offset 3 and 6 characters past last column
+        push(&mut cea608, 0, &pop_on(0x1E));
+        push(&mut cea608, 0, &[(b'A', b'B'), (0x17, 0x23), (b'C', b'D')]);
+        push(&mut cea608, 0, &[(b'E', b'F'), (b'G', b'H')]);
+        push(&mut cea608, 1000, &[(0x14, 0x2F)]);
+        push(&mut cea608, 2000, &[(0x14, 0x2C)]);
+
+        let cues = cea608.take_cues();
+        assert_eq!(cues[0].1.text, "AB H");
+
+        // roll-up of 4 rows moved to top row
+        push(
+            &mut cea608,
+            3000,
+            &[(0x14, 0x27), (0x11, 0x40), (b'X', b'Y')],
+        );
+        push(&mut cea608, 4000, &[(0x14, 0x2D)]);
+        push(&mut cea608, 5000, &[(0x14, 0x2C)]);
+        let cues = cea608.take_cues();
+        assert_eq!(cues[0].1.text, "XY");
+    }
+}
@@ -0,0 +1,472 @@
+use std::collections::BTreeMap;
+
+use crate::captions::{Cue, Cues};
+
+const WINDOWS: usize = 8;
+const ROWS_MAX: usize = 15;
+const COLS_MAX: usize = 42;
+
+/// packet_size_code 0 is 128 bytes
+const PACKET_SZ_MAX: usize = 128;
+
+/// G2 character set (after EXT1); None if not printable
+fn g2(b: u8) -> Option<char> {
+    let c = match b {
+        0x20 | 0x21 => ' ',
+        0x25 => '…',
+        0x2A => 'Š',
+        0x2C => 'Œ',
+        0x30 => '█',
+        0x31 => '‘',
+        0x32 => '’',
+        0x33 => '“',
+        0x34 => '”',
+        0x35 => '•',
+        0x39 => '™',
+        0x3A => 'š',
+        0x3C => 'œ',
+        0x3D => '℠',
+        0x3F => 'Ÿ',
+        0x76 => '⅛',
+        0x77 => '⅜',
+        0x78 => '⅝',
+        0x79 => '⅞',
+        0x7A => '│',
+        0x7B => '┐',
+        0x7C => '└',
+        0x7D => '─',
+        0x7E => '┘',
+        0x7F => '┌',
+        _ => return None,
+    };
+    Some(c)
+}
+
+/// caption window; attributes and position are not tracked
+#[derive(Clone)]
+struct Window {
+    visible: bool,
+    rows: Vec<Vec<char>>,
+    row: usize,
+    col: usize,
+}
+
+impl Window {
+    fn new(visible: bool, rows: usize, cols: usize) -> Window {
+        Window {
+            visible,
+            rows: vec![vec![' '; cols]; rows],
+            row: 0,
+            col: 0,
+        }
+    }
+
+    fn resize(&mut self, rows: usize, cols: usize) {
+        self.rows.resize(rows, vec![' '; cols]);
+        for row in self.rows.iter_mut() {
+            row.resize(cols, ' ');
+        }
+        self.row = self.row.min(rows - 1);
+        self.col = self.col.min(cols - 1);
+    }
+
+    fn clear(&mut self) {
+        for row in self.rows.iter_mut() {
+            row.iter_mut().for_each(|c| *c = ' ');
+        }
+        self.row = 0;
+        self.col = 0;
+    }
+
+    fn put(&mut self, c: char) {
+        let cols = self.rows[self.row].len();
+        self.rows[self.row][self.col] = c;
+        self.col = (self.col + 1).min(cols - 1);
+    }
+
+    /// CR: next row, scroll up at bottom
+    fn carriage_return(&mut self) {
+        if self.row + 1 < self.rows.len() {
+            self.row += 1;
+        } else {
+            let cols = self.rows[0].len();
+            self.rows.remove(0);
+            self.rows.push(vec![' '; cols]);
+        }
+        self.col = 0;
+    }
+
+    fn text(&self) -> String {
+        self.rows
+            .iter()
+            .map(|row| row.iter().collect::<String>().trim().to_string())
+            .filter(|row| !row.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// CEA-708 caption service: text of visible windows
+#[derive(Default)]
+struct Service {
+    windows: [Option<Window>; WINDOWS],
+    current: usize,
+
+    cues: Cues,
+}
+
+impl Service {
+    fn window(&mut self) -> Option<&mut Window> {
+        self.windows[self.current].as_mut()
+    }
+
+    fn show(&mut self, pts: u64) {
+        let text = self
+            .windows
+            .iter()
+            .flatten()
+            .filter(|window| window.visible)
+            .map(|window| window.text())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.cues.show(pts, text);
+    }
+
+    fn put(&mut self, c: char) {
+        if let Some(window) = self.window() {
+            window.put(c);
+        }
+    }
+
+    /// window command with window bitmap parameter
+    fn windows(&mut self, pts: u64, cmd: u8, bitmap: u8) {
+        for (id, slot) in self.windows.iter_mut().enumerate() {
+            if bitmap & (1 << id) == 0 {
+                continue;
+            }
+            match (cmd, slot.as_mut()) {
+                // CLW
+                (0x88, Some(window)) => window.clear(),
+                // DSW, HDW, TGW
+                (0x89, Some(window)) => window.visible = true,
+                (0x8A, Some(window)) => window.visible = false,
+                (0x8B, Some(window)) => window.visible = !window.visible,
+                // DLW
+                (0x8C, _) => *slot = None,
+                _ => {}
+            }
+        }
+        self.show(pts);
+    }
+
+    /// DF0..DF7: window_style, pen_style, row and column count
+    fn define(&mut self, pts: u64, id: usize, params: &[u8]) {
+        let visible = params[0] & 0x20 != 0;
+        let rows = usize::from((params[3] & 0x0F) + 1).min(ROWS_MAX);
+        let cols = usize::from((params[4] & 0x3F) + 1).min(COLS_MAX);
+
+        match self.windows[id].as_mut() {
+            Some(window) => {
+                window.visible = visible;
+                window.resize(rows, cols);
+            }
+            None => self.windows[id] = Some(Window::new(visible, rows, cols)),
+        }
+        self.current = id;
+        self.show(pts);
+    }
+
+    /// service_block_data: C0, C1, G0, G1 and EXT1 codes
+    fn push(&mut self, pts: u64, data: &[u8]) {
+        let mut pos = 0;
+
+        while pos < data.len() {
+            let code = data[pos];
+            pos += 1;
+
+            match code {
+                // ETX
+                0x03 => self.show(pts),
+                // BS
+                0x08 => {
+                    if let Some(window) = self.window() {
+                        window.col = window.col.saturating_sub(1);
+                        window.rows[window.row][window.col] = ' ';
+                    }
+                }
+                // FF
+                0x0C => {
+                    if let Some(window) = self.window() {
+                        window.clear();
+                    }
+                    self.show(pts);
+                }
+                // CR: row complete
+                0x0D => {
+                    self.show(pts);
+                    if let Some(window) = self.window() {
+                        window.carriage_return();
+                    }
+                }
+                // HCR
+                0x0E => {
+                    if let Some(window) = self.window() {
+                        let row = window.row;
+                        window.rows[row].iter_mut().for_each(|c| *c = ' ');
+                        window.col = 0;
+                    }
+                }
+                // EXT1: C2, C3, G2, G3
+                0x10 => {
+                    let ext = match data.get(pos) {
+                        Some(ext) => *ext,
+                        None => break,
+                    };
+                    pos += 1;
+                    match ext {
+                        0x00..=0x07 => {}
+                        0x08..=0x0F => pos += 1,
+                        0x10..=0x17 => pos += 2,
+                        0x18..=0x1F => pos += 3,
+                        0x20..=0x7F => {
+                            if let Some(c) = g2(ext) {
+                                self.put(c);
+                            }
+                        }
+                        0x80..=0x87 => pos += 4,
+                        0x88..=0x8F => pos += 5,
+                        // variable length: length in low bits of next byte
+                        0x90..=0x9F => {
+                            pos += 1 + data.get(pos).map(|b| usize::from(b & 0x1F)).unwrap_or(0)
+                        }
+                        // G3: [CC] icon
+                        0xA0..=0xFF => self.put(' '),
+                    }
+                }
+                0x11..=0x17 => pos += 1,
+                // P16
+                0x18 => {
+                    if let Some(p) = data.get(pos..pos + 2) {
+                        let c = char::from_u32(u32::from(u16::from_be_bytes([p[0], p[1]])));
+                        if let Some(c) = c {
+                            self.put(c);
+                        }
+                    }
+                    pos += 2;
+                }
+                0x19..=0x1F => pos += 2,
+                0x7F => self.put('♪'),
+                0x20..=0x7E | 0xA0..=0xFF => self.put(char::from(code)),
+                // CW0..CW7
+                0x80..=0x87 => self.current = usize::from(code - 0x80),
+                0x88..=0x8C => {
+                    if let Some(p) = data.get(pos..pos + 1) {
+                        self.windows(pts, code, p[0]);
+                    }
+                    pos += 1;
+                }
+                // DLY
+                0x8D => pos += 1,
+                // RST
+                0x8F => {
+                    self.windows = Default::default();
+                    self.show(pts);
+                }
+                // SPA, SPC
+                0x90 => pos += 2,
+                0x91 => pos += 3,
+                // SPL
+                0x92 => {
+                    if let (Some(p), Some(window)) =
+                        (data.get(pos..pos + 2), self.windows[self.current].as_mut())
+                    {
+                        window.row = usize::from(p[0] & 0x0F).min(window.rows.len() - 1);
+                        window.col = usize::from(p[1] & 0x3F).min(window.rows[0].len() - 1);
+                    }
+                    pos += 2;
+                }
+                // SWA
+                0x97 => pos += 4,
+                0x98..=0x9F => {
+                    if let Some(p) = data.get(pos..pos + 6) {
+                        self.define(pts, usize::from(code - 0x98), p);
+                    }
+                    pos += 6;
+                }
+                // NUL, DLC, reserved
+                _ => {}
+            }
+        }
+    }
+}
+
+/// CEA-708 (DTVCC) decoder: caption channel packets from cc_data
+/// are split into service blocks of services 1..63
+#[derive(Default)]
+pub struct Cea708 {
+    packet: Vec<u8>,
+    services: BTreeMap<u8, Service>,
+}
+
+impl Cea708 {
+    /// cc_data byte pair of DTVCC_PACKET_START (start) or DTVCC_PACKET_DATA;
+    /// services with data in packet completed by this pair
+    pub fn push(&mut self, pts: u64, start: bool, b1: u8, b2: u8) -> Vec<u8> {
+        if start {
+            // incomplete previous packet is dropped
+            self.packet.clear();
+        } else if self.packet.is_empty() {
+            return Vec::new();
+        }
+        self.packet.extend_from_slice(&[b1, b2]);
+
+        let size = match self.packet[0] & 0x3F {
+            0 => PACKET_SZ_MAX,
+            code => usize::from(code) * 2,
+        };
+        if self.packet.len() < size {
+            return Vec::new();
+        }
+
+        let packet = std::mem::take(&mut self.packet);
+        let mut active = Vec::new();
+        self.packet(pts, &packet[1..size], &mut active);
+
+        active
+    }
+
+    /// service blocks of caption channel packet
+    fn packet(&mut self, pts: u64, data: &[u8], active: &mut Vec<u8>) -> Option<()> {
+        let mut pos = 0;
+
+        while pos < data.len() {
+            let header = data[pos];
+            pos += 1;
+
+            let mut number = header >> 5;
+            let size = usize::from(header & 0x1F);
+            // null service block: rest is padding
+            if number == 0 {
+                break;
+            }
+            // extended_service_number
+            if number == 7 {
+                number = data.get(pos)? & 0x3F;
+                pos += 1;
+            }
+            let block = data.get(pos..pos + size)?;
+            pos += size;
+
+            if number != 0 && size != 0 {
+                self.services.entry(number).or_default().push(pts, block);
+                if !active.contains(&number) {
+                    active.push(number);
+                }
+            }
+        }
+
+        Some(())
+    }
+
+    /// cues finished since last call by service number
+    pub fn take_cues(&mut self) -> Vec<(u8, Cue)> {
+        self.services
+            .iter_mut()
+            .flat_map(|(number, service)| {
+                service
+                    .cues
+                    .take()
+                    .into_iter()
+                    .map(move |cue| (*number, cue))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// DF0: visible, 2 rows, 32 columns
+    const DEFINE: [u8; 7] = [0x98, 0x38, 0x00, 0x00, 0x01, 0x1F, 0x00];
+
+    /// caption channel packet of service 1 split into cc_data pairs;
+    /// services completed by last pair
+    fn push(cea708: &mut Cea708, pts: u64, packet: &[u8]) -> Vec<u8> {
+        let mut active = Vec::new();
+        for (i, pair) in packet.chunks(2).enumerate() {
+            active = cea708.push(pts, i == 0, pair[0], pair.get(1).copied().unwrap_or(0));
+        }
+        active
+    }
+
+    /// packet header and service 1 block header for block
+    fn packet(seq: u8, block: &[u8]) -> Vec<u8> {
+        let size = 2 + block.len() + block.len() % 2;
+        let mut packet = vec![(seq << 6) | (size / 2) as u8, 0x20 | block.len() as u8];
+        packet.extend_from_slice(block);
+        packet.resize(size, 0);
+        packet
+    }
+
+    #[test]
+    fn valid() {
+        let mut cea708 = Cea708::default();
+        let mut block = DEFINE.to_vec();
+        block.extend_from_slice(&[b'H', b'I', 0x10, 0x35, 0x03]);
+        assert_eq!(push(&mut cea708, 1000, &packet(0, &block)), vec![1]);
+        // CLW 0
+        assert_eq!(push(&mut cea708, 2000, &packet(1, &[0x88, 0x01])), vec![1]);
+
+        let cues = cea708.take_cues();
+        assert_eq!(cues.len(), 1);
+        assert_eq!(cues[0].0, 1);
+        assert_eq!((cues[0].1.start, cues[0].1.end), (1000, 2000));
+        assert_eq!(cues[0].1.text, "HI•");
+    }
+
+    #[test]
+    fn truncated() {
+        let mut cea708 = Cea708::default();
+        let mut block = DEFINE.to_vec();
+        block.extend_from_slice(&[b'H', b'I', 0x03]);
+        let buf = packet(0, &block);
+        // cc_data carries byte pairs
+        for len in (0..buf.len()).step_by(2) {
+            assert!(push(&mut cea708, 1000, &buf[..len]).is_empty());
+        }
+        // continuation without packet start
+        assert!(Cea708::default().push(1000, false, b'H', b'I').is_empty());
+
+        // DF0 parameters cut by block end
+        assert_eq!(push(&mut cea708, 1000, &packet(0, &DEFINE[..4])), vec![1]);
+        assert!(cea708.services[&1].windows.iter().all(Option::is_none));
+        assert!(cea708.take_cues().is_empty());
+    }
+
+    #[test]
+    fn length_overrun() {
+        let mut cea708 = Cea708::default();
+        // block_size past packet end
+        let mut buf = packet(0, b"HI");
+        buf[1] = 0x3F;
+        assert!(push(&mut cea708, 1000, &buf).is_empty());
+
+        // variable length EXT1 code past block end
+        let mut block = DEFINE.to_vec();
+        block.extend_from_slice(&[b'H', b'I', 0x03, 0x10, 0x90, 0x1F]);
+        assert_eq!(push(&mut cea708, 1000, &packet(1, &block)), vec![1]);
+
+        // SPL past window size, characters past last column
+        let mut block = vec![0x92, 0x0F, 0x3F];
+        block.extend_from_slice(&[b'X'; 20]);
+        block.push(b'Y');
+        block.push(0x03);
+        assert_eq!(push(&mut cea708, 2000, &packet(2, &block)), vec![1]);
+
+        let cues = cea708.take_cues();
+        assert_eq!(cues[0].1.text, "HI");
+        assert_eq!(cues.len(), 1);
+    }
+}
@@ -0,0 +1,155 @@
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+/// 27MHz ticks
+const PCR_CLOCK: u64 = 27_000_000;
+/// PCR wraps at 2^33 * 300 (27MHz)
+pub const PCR_MAX: u64 = (1 << 33) * 300;
+/// PTS/DTS wrap at 2^33 (90kHz)
+pub const TS_MAX: u64 = 1 << 33;
+/// larger PCR delta (or going backwards) is a discontinuity
+pub const PCR_GAP_MAX: u64 = PCR_CLOCK;
+
+/// stream time for analyzers.
+///
+/// driven by PCR of first seen PCR PID and interpolated between PCRs
+/// by previous PCR interval; arrival time is used until first PCR.
+/// never goes backwards and skips PCR discontinuities
+/// so files are analyzed with the same timing as live inputs
+#[derive(Default)]
+pub struct Clock {
+    origin: Option<Instant>,
+
+    pcr_pid: Option<u16>,
+    pcr_last: Option<u64>,
+    /// stream time of last PCR; ns
+    pcr_ns: u64,
+    /// packets since last PCR
+    pcr_packets: u64,
+    /// packet duration by last PCR interval; ns
+    pkt_ns: Option<u64>,
+
+    /// stream time of last packet; ns
+    ns: u64,
+}
+
+impl Clock {
+    /// stream time of packet; ns since first packet
+    pub fn update(&mut self, pkt: &ts::Packet, at: Instant) -> u64 {
+        let origin = *self.origin.get_or_insert(at);
+
+        if let Ok(Some(pcr)) = pkt.pcr() {
+            let pid = u16::from(pkt.pid());
+            if *self.pcr_pid.get_or_insert(pid) == pid {
+                self.pcr(pcr.value());
+            }
+        }
+
+        let ns = match self.pcr_last {
+            Some(_) => self.pcr_ns + self.pcr_packets * self.pkt_ns.unwrap_or(0),
+            None => at.saturating_duration_since(origin).as_nanos() as u64,
+        };
+        self.pcr_packets += 1;
+
+        self.ns = self.ns.max(ns);
+        self.ns
+    }
+
+    fn pcr(&mut self, pcr: u64) {
+        match self.pcr_last {
+            Some(pcr_last) if pcr > pcr_last && pcr - pcr_last <= PCR_GAP_MAX => {
+                let delta = (pcr - pcr_last) * 1000 / 27;
+                self.pcr_ns += delta;
+                self.pkt_ns = Some(delta / self.pcr_packets.max(1));
+            }
+            Some(_) => {
+                // continue from last packet time
+                self.pcr_ns = self.ns;
+            }
+            // continue from arrival time
+            None => self.pcr_ns = self.ns,
+        }
+
+        self.pcr_last = Some(pcr);
+        self.pcr_packets = 0;
+    }
+
+    /// stream time is driven by PCR
+    #[inline(always)]
+    pub fn pcr_locked(&self) -> bool {
+        self.pcr_last.is_some()
+    }
+}
+
+/// signed a - b on wrapping timeline
+pub fn wrapping_diff(a: u64, b: u64, max: u64) -> i64 {
+    let d = (a + max - b % max) % max;
+    if d > max / 2 {
+        d as i64 - max as i64
+    } else {
+        d as i64
+    }
+}
+
+/// discontinuity_indicator of adaptation field
+pub fn discontinuity(raw: &[u8]) -> bool {
+    let adaptation = (raw[3] & 0b0010_0000) != 0;
+    adaptation && raw[4] > 0 && (raw[5] & 0b1000_0000) != 0
+}
+
+/// min/max/sum over publish interval
+#[derive(Clone, Copy, Default)]
+pub struct Stat {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Stat {
+    pub fn add(&mut self, v: f64) {
+        self.min = Some(self.min.map_or(v, |min| min.min(v)));
+        self.max = Some(self.max.map_or(v, |max| max.max(v)));
+        self.sum += v;
+        self.count += 1;
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.sum / self.count as f64)
+        }
+    }
+}
+
+/// rate limit for metrics publishing; once per second by default
+pub struct Throttle {
+    interval: Duration,
+    at: Cell<Instant>,
+}
+
+impl Throttle {
+    pub fn new(interval: Duration) -> Throttle {
+        Throttle {
+            interval,
+            at: Cell::new(Instant::now()),
+        }
+    }
+
+    /// true (and restarts interval) when interval is elapsed
+    pub fn ready(&self) -> bool {
+        if self.at.get().elapsed() < self.interval {
+            return false;
+        }
+
+        self.at.set(Instant::now());
+        true
+    }
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Throttle::new(Duration::from_secs(1))
+    }
+}
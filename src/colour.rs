@@ -0,0 +1,243 @@
+use std::fmt;
+
+use crate::sei::Sei;
+
+pub const PRIMARIES_BT2020: u8 = 9;
+pub const TRANSFER_PQ: u8 = 16;
+pub const TRANSFER_HLG: u8 = 18;
+pub const MATRIX_BT2020_NCL: u8 = 9;
+pub const MATRIX_BT2020_CL: u8 = 10;
+
+/// ITU-T H.273 colour description from VUI / sequence display extension
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Colour {
+    pub primaries: u8,
+    pub transfer: u8,
+    pub matrix: u8,
+}
+
+impl Colour {
+    pub fn primaries_str(&self) -> &'static str {
+        match self.primaries {
+            1 => "bt709",
+            2 => "unspecified",
+            4 => "bt470m",
+            5 => "bt470bg",
+            6 => "smpte170m",
+            7 => "smpte240m",
+            8 => "film",
+            9 => "bt2020",
+            10 => "smpte428",
+            11 => "smpte431",
+            12 => "smpte432",
+            22 => "ebu3213",
+            _ => "reserved",
+        }
+    }
+
+    pub fn transfer_str(&self) -> &'static str {
+        match self.transfer {
+            1 => "bt709",
+            2 => "unspecified",
+            4 => "bt470m",
+            5 => "bt470bg",
+            6 => "smpte170m",
+            7 => "smpte240m",
+            8 => "linear",
+            9 => "log100",
+            10 => "log316",
+            11 => "iec61966-2-4",
+            12 => "bt1361e",
+            13 => "iec61966-2-1",
+            14 => "bt2020-10",
+            15 => "bt2020-12",
+            16 => "smpte2084",
+            17 => "smpte428",
+            18 => "arib-std-b67",
+            _ => "reserved",
+        }
+    }
+
+    pub fn matrix_str(&self) -> &'static str {
+        match self.matrix {
+            0 => "gbr",
+            1 => "bt709",
+            2 => "unspecified",
+            4 => "fcc",
+            5 => "bt470bg",
+            6 => "smpte170m",
+            7 => "smpte240m",
+            8 => "ycgco",
+            9 => "bt2020nc",
+            10 => "bt2020c",
+            11 => "smpte2085",
+            12 => "chroma-derived-nc",
+            13 => "chroma-derived-c",
+            14 => "ictcp",
+            _ => "reserved",
+        }
+    }
+
+    pub fn json(&self) -> String {
+        format!(
+            r#"{{"primaries":"{}","transfer":"{}","matrix":"{}"}}"#,
+            self.primaries_str(),
+            self.transfer_str(),
+            self.matrix_str()
+        )
+    }
+}
+
+impl fmt::Display for Colour {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            ":primaries {} :transfer {} :matrix {}",
+            self.primaries_str(),
+            self.transfer_str(),
+            self.matrix_str()
+        )
+    }
+}
+
+/// mastering_display_colour_volume SEI;
+/// chromaticity in 0.00002, luminance in 0.0001 cd/m2
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MasteringDisplay {
+    /// G, B, R
+    pub primaries: [(u16, u16); 3],
+    pub white_point: (u16, u16),
+    pub max_luminance: u32,
+    pub min_luminance: u32,
+}
+
+impl MasteringDisplay {
+    pub fn parse(payload: &[u8]) -> Option<MasteringDisplay> {
+        if payload.len() < 24 {
+            return None;
+        }
+
+        let u16_at = |i: usize| u16::from_be_bytes([payload[i], payload[i + 1]]);
+        let u32_at = |i: usize| {
+            u32::from_be_bytes([payload[i], payload[i + 1], payload[i + 2], payload[i + 3]])
+        };
+
+        Some(MasteringDisplay {
+            primaries: [
+                (u16_at(0), u16_at(2)),
+                (u16_at(4), u16_at(6)),
+                (u16_at(8), u16_at(10)),
+            ],
+            white_point: (u16_at(12), u16_at(14)),
+            max_luminance: u32_at(16),
+            min_luminance: u32_at(20),
+        })
+    }
+}
+
+/// x265 --master-display notation
+impl fmt::Display for MasteringDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [g, b, r] = self.primaries;
+        write!(
+            f,
+            "G({},{})B({},{})R({},{})WP({},{})L({},{})",
+            g.0,
+            g.1,
+            b.0,
+            b.1,
+            r.0,
+            r.1,
+            self.white_point.0,
+            self.white_point.1,
+            self.max_luminance,
+            self.min_luminance
+        )
+    }
+}
+
+/// content_light_level_info SEI; cd/m2
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ContentLight {
+    pub max_cll: u16,
+    pub max_fall: u16,
+}
+
+impl ContentLight {
+    pub fn parse(payload: &[u8]) -> Option<ContentLight> {
+        if payload.len() < 4 {
+            return None;
+        }
+
+        Some(ContentLight {
+            max_cll: u16::from_be_bytes([payload[0], payload[1]]),
+            max_fall: u16::from_be_bytes([payload[2], payload[3]]),
+        })
+    }
+}
+
+/// by transfer characteristics; HLG may be signalled
+/// as alternative transfer of SDR-compatible BT.2020
+pub fn dynamic_range(colour: Option<&Colour>, sei: &Sei) -> &'static str {
+    let transfer = colour.map(|colour| colour.transfer);
+    let primaries = colour.map(|colour| colour.primaries);
+
+    match transfer {
+        Some(TRANSFER_PQ) if sei.hdr10plus => "hdr10+",
+        Some(TRANSFER_PQ) if primaries == Some(PRIMARIES_BT2020) => "hdr10",
+        Some(TRANSFER_PQ) => "pq",
+        Some(TRANSFER_HLG) => "hlg",
+        _ if sei.alternative_transfer == Some(TRANSFER_HLG) => "hlg",
+        _ => "sdr",
+    }
+}
+
+/// HDR signalling inconsistencies
+pub fn hdr_issues(
+    colour: Option<&Colour>,
+    bit_depth: u8,
+    width: u32,
+    sei: &Sei,
+) -> Vec<&'static str> {
+    let mut issues = Vec::new();
+
+    let colour = match colour {
+        Some(colour) => colour,
+        None => {
+            if width >= 3840 {
+                issues.push("uhd-without-colour-description");
+            }
+            if sei.mastering_display.is_some() || sei.content_light.is_some() {
+                issues.push("hdr-metadata-without-colour-description");
+            }
+            return issues;
+        }
+    };
+
+    let hdr = dynamic_range(Some(colour), sei) != "sdr";
+    let pq = colour.transfer == TRANSFER_PQ;
+
+    if hdr && bit_depth < 10 {
+        issues.push("hdr-8bit");
+    }
+    if hdr && colour.primaries != PRIMARIES_BT2020 {
+        issues.push("hdr-without-bt2020-primaries");
+    }
+    if colour.primaries == PRIMARIES_BT2020
+        && colour.matrix != MATRIX_BT2020_NCL
+        && colour.matrix != MATRIX_BT2020_CL
+    {
+        issues.push("bt2020-primaries-matrix-mismatch");
+    }
+    if pq && sei.mastering_display.is_none() {
+        issues.push("pq-without-mastering-display");
+    }
+    if pq && sei.content_light.is_none() {
+        issues.push("pq-without-content-light-level");
+    }
+    if !hdr && (sei.mastering_display.is_some() || sei.content_light.is_some()) {
+        issues.push("hdr-metadata-on-sdr");
+    }
+
+    issues
+}
@@ -1,14 +1,110 @@
-#[derive(Debug, Eq, PartialEq)]
+use std::fmt;
+
+use crate::audio::{Aac, Ac3, Mpa};
+use crate::dvbsub::DvbSub;
+use crate::h264::H264;
+use crate::h265::H265;
+use crate::mpeg2::Mpeg2;
+use crate::teletext::Teletext;
+
+/// video parameters by bitstream headers
+#[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Video {
-    Mpeg2,
+    Mpeg2(Mpeg2),
     Mpeg4,
-    H264,
-    H265,
+    H264(H264),
+    H265(H265),
     Vp8,
     Vp9,
     AV1,
 }
 
+impl Video {
+    /// HDR signalling inconsistencies
+    pub fn hdr_issues(&self) -> Vec<&'static str> {
+        match self {
+            Video::H264(h264) => h264.hdr_issues(),
+            Video::H265(h265) => h265.hdr_issues(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn json(&self) -> String {
+        match self {
+            Video::Mpeg2(mpeg2) => mpeg2.json(),
+            Video::H264(h264) => h264.json(),
+            Video::H265(h265) => h265.json(),
+            _ => "null".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Video {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Video::Mpeg2(mpeg2) => write!(f, "{}", mpeg2),
+            Video::H264(h264) => write!(f, "{}", h264),
+            Video::H265(h265) => write!(f, "{}", h265),
+            _ => write!(f, "~"),
+        }
+    }
+}
+
+/// audio parameters by frame headers
+#[derive(Clone, Debug, PartialEq)]
+pub enum Audio {
+    Aac(Aac),
+    Mpa(Mpa),
+    Ac3(Ac3),
+}
+
+impl Audio {
+    pub fn json(&self) -> String {
+        match self {
+            Audio::Aac(aac) => aac.json(),
+            Audio::Mpa(mpa) => mpa.json(),
+            Audio::Ac3(ac3) => ac3.json(),
+        }
+    }
+}
+
+impl fmt::Display for Audio {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Audio::Aac(aac) => write!(f, "{}", aac),
+            Audio::Mpa(mpa) => write!(f, "{}", mpa),
+            Audio::Ac3(ac3) => write!(f, "{}", ac3),
+        }
+    }
+}
+
+/// subtitle pages found in elementary stream
+#[derive(Clone, Debug, PartialEq)]
+pub enum Subtitle {
+    Teletext(Teletext),
+    Dvb(DvbSub),
+}
+
+impl Subtitle {
+    pub fn json(&self) -> String {
+        match self {
+            Subtitle::Teletext(teletext) => teletext.json(),
+            Subtitle::Dvb(dvb) => dvb.json(),
+        }
+    }
+}
+
+impl fmt::Display for Subtitle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Subtitle::Teletext(teletext) => write!(f, "{}", teletext),
+            Subtitle::Dvb(dvb) => write!(f, "{}", dvb),
+        }
+    }
+}
+
+#[allow(dead_code)]
 #[derive(Debug, Eq, PartialEq)]
 pub enum CompressionStandard {
     Video,
@@ -1,66 +1,178 @@
 use std::env;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
 
 use regex::Regex;
 use url::Url;
 
+use crate::captions::Format as CaptionsFormat;
+use crate::eit::Format as EpgFormat;
 use crate::error::{Error, Result};
+use crate::fec::Mode as FecMode;
+use crate::gen::{self, Params as GenParams};
+use crate::iat;
+use crate::mdi::Opts as MdiOpts;
 use crate::opt::{Match as OptMatch, Matcher as OptMatcher, Opt, OptKind, Opts};
+use crate::probe::Format as ProbeFormat;
+use crate::tr101290::{self, Opts as Tr101290Opts};
+use crate::udp::SockOpts;
 
 #[rustfmt::skip]
 const OPTS: Opts = &[
-    &Opt(&"vv", &["verbose"], OptKind::NoArg),
-    &Opt(&"vvv", &["very-verbose"], OptKind::NoArg),
-    &Opt(&"help", &["h"], OptKind::NoArg),
-    &Opt(&"version", &["v"], OptKind::NoArg),
-    &Opt(&"print-config", &[], OptKind::NoArg),
+    &Opt("vv", &["verbose"], OptKind::NoArg),
+    &Opt("vvv", &["very-verbose"], OptKind::NoArg),
+    &Opt("help", &["h"], OptKind::NoArg),
+    &Opt("version", &["v"], OptKind::NoArg),
+    &Opt("print-config", &[], OptKind::NoArg),
 
-    &Opt(&"config", &["c", "cfg"], OptKind::Arg),
+    &Opt("config", &["c", "cfg"], OptKind::Arg),
+    &Opt("metrics-interval", &["stats-interval"], OptKind::Arg),
 
-    &Opt(&"input", &["i"], OptKind::Arg),
-        &Opt(&"fifo-sz", &["udp-fifo-sz", "udp-fifo-size","fifo-size"], OptKind::Arg),
-        &Opt(&"out", &["o", "output"], OptKind::Arg),
+    // probe
+    &Opt("duration", &["probe-duration"], OptKind::Arg),
+    &Opt("packets", &["probe-packets"], OptKind::Arg),
+    &Opt("format", &["probe-format", "of"], OptKind::Arg),
+
+    &Opt("input", &["i"], OptKind::Arg),
+        &Opt("fifo-sz", &["udp-fifo-sz", "udp-fifo-size","fifo-size"], OptKind::Arg),
+        &Opt("fec", &["rtp-fec"], OptKind::Arg),
+        &Opt("rcvbuf", &["udp-rcvbuf", "so-rcvbuf"], OptKind::Arg),
+        &Opt("reuse", &["udp-reuse", "so-reuse"], OptKind::Arg),
+        &Opt("ttl", &["udp-ttl"], OptKind::Arg),
+        &Opt("iat-buckets", &["iat-histogram"], OptKind::Arg),
+        &Opt("mdi-rate", &[], OptKind::Arg),
+        &Opt("mdi-df-max", &[], OptKind::Arg),
+        &Opt("mdi-mlr-max", &[], OptKind::Arg),
+        &Opt("pid-interval-max", &[], OptKind::Arg),
+        &Opt("pcr-interval-max", &["pcr-repetition-max"], OptKind::Arg),
+        &Opt("pcr-discontinuity-max", &[], OptKind::Arg),
+        &Opt("pcr-accuracy-max", &[], OptKind::Arg),
+        &Opt("pts-interval-max", &["pts-repetition-max"], OptKind::Arg),
+        &Opt("si-profile", &[], OptKind::Arg),
+        &Opt("si-repetition", &[], OptKind::Arg),
+        &Opt("pcr-csv", &[], OptKind::Arg),
+        &Opt("av-offset-max", &["lip-sync-max"], OptKind::Arg),
+        &Opt("av-offset-abs-max", &[], OptKind::Arg),
+        &Opt("keyframe-interval-max", &["gop-duration-max"], OptKind::Arg),
+        &Opt("subtitle-timeout", &[], OptKind::Arg),
+        &Opt("scte35-log", &["cue-log"], OptKind::Arg),
+        &Opt("scte35-format", &["cue-log-format"], OptKind::Arg),
+        &Opt("captions-timeout", &["cc-timeout"], OptKind::Arg),
+        &Opt("captions-out", &["cc-out"], OptKind::Arg),
+        &Opt("captions-format", &["cc-format"], OptKind::Arg),
+        &Opt("epg-out", &["eit-out"], OptKind::Arg),
+        &Opt("epg-format", &["eit-format"], OptKind::Arg),
+        &Opt("ecm-timeout", &[], OptKind::Arg),
+        &Opt("out", &["o", "output"], OptKind::Arg),
 ];
 
-#[allow(dead_code)]
 pub struct ConfigOutput {
-    url: Url,
+    pub url: Url,
 }
 
 pub struct ConfigInput {
     id: u64,
     pub url: Url,
     pub udp_fifo_sz: usize,
+    pub rtp_fec: FecMode,
+    pub udp_sock_opts: SockOpts,
+    /// datagram inter-arrival time histogram upper bounds
+    pub iat_buckets: Vec<Duration>,
+    pub mdi_opts: MdiOpts,
+    pub tr101290_opts: Tr101290Opts,
+    /// PCR time series output
+    pub pcr_csv: Option<PathBuf>,
+    /// A/V offset drift alarm threshold
+    pub av_offset_max: Duration,
+    /// absolute A/V offset alarm threshold
+    pub av_offset_abs_max: Duration,
+    /// intra picture interval alarm threshold
+    pub keyframe_interval_max: Duration,
+    /// subtitle service is lost when not seen for
+    pub subtitle_timeout: Duration,
+    /// SCTE-35 cue event log
+    pub scte35_log: Option<PathBuf>,
+    pub scte35_format: ProbeFormat,
+    /// caption channel is lost when no data for
+    pub captions_timeout: Duration,
+    /// caption cue export path template
+    pub captions_out: Option<PathBuf>,
+    pub captions_format: CaptionsFormat,
+
+    /// EIT p/f and schedule export
+    pub epg_out: Option<PathBuf>,
+    pub epg_format: EpgFormat,
+
+    /// ECM repetition lost alarm
+    pub ecm_timeout: Duration,
+    pub outputs: Vec<ConfigOutput>,
+}
+
+/// first positional argument
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Action {
+    /// run forever and log metrics
+    Analyze,
+    /// read for a while, print stream inventory and exit
+    Probe,
 }
 
 pub struct Config {
-    // pub _action: analyze | report | plot
+    // report | plot
+    pub action: Action,
+
     pub print_help: bool,
     pub print_version: bool,
     pub print_config: bool,
 
     pub log_level: log::Level,
 
+    /// how often to log collected metrics
+    pub metrics_interval: Duration,
+
     pub inputs: Vec<ConfigInput>,
+
+    /// probe stops after duration or packets whichever comes first
+    pub probe_duration: Duration,
+    pub probe_packets: Option<u64>,
+    pub probe_format: ProbeFormat,
 }
 
 impl Config {
     pub(crate) fn parse() -> Result<Config> {
         let mut c = Config {
+            action: Action::Analyze,
+
             print_help: false,
             print_version: false,
             print_config: false,
             log_level: log::Level::Info,
 
+            metrics_interval: Duration::from_secs(10),
+
             inputs: Default::default(),
+
+            probe_duration: Duration::from_secs(5),
+            probe_packets: None,
+            probe_format: ProbeFormat::Text,
         };
 
+        let mut verbose = false;
+
         let opt_matcher = OptMatcher::new(env::args().skip(1).collect(), OPTS);
 
         for (i, mtch) in opt_matcher.into_iter().enumerate() {
             match mtch {
                 OptMatch::Key(key, _) => match key {
-                    "vv" => c.log_level = log::Level::Debug,
-                    "vvv" => c.log_level = log::Level::Trace,
+                    "vv" => {
+                        c.log_level = log::Level::Debug;
+                        verbose = true;
+                    }
+                    "vvv" => {
+                        c.log_level = log::Level::Trace;
+                        verbose = true;
+                    }
                     "help" => c.print_help = true,
                     "version" => c.print_version = true,
                     "print-config" => c.print_config = true,
@@ -69,12 +181,240 @@ impl Config {
 
                 OptMatch::KeyValue(key, value) => match key {
                     "input" => c.push_input(value)?,
+                    "metrics-interval" => {
+                        let secs = value
+                            .parse::<u64>()
+                            .ok()
+                            .filter(|secs| *secs > 0)
+                            .ok_or_else(|| Error::config_value(key, &value))?;
+                        c.metrics_interval = Duration::from_secs(secs);
+                    }
+                    "duration" => {
+                        c.probe_duration = value
+                            .parse::<f64>()
+                            .ok()
+                            .filter(|secs| *secs > 0.0)
+                            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                            .ok_or_else(|| Error::config_value(key, &value))?;
+                    }
+                    "packets" => {
+                        let packets = value
+                            .parse::<u64>()
+                            .map_err(|_| Error::config_value(key, &value))?;
+                        c.probe_packets = Some(packets);
+                    }
+                    "format" => {
+                        c.probe_format = value
+                            .parse::<ProbeFormat>()
+                            .map_err(|_| Error::config_value(key, &value))?;
+                    }
                     "fifo-sz" => {
                         let udp_fifo_sz = value.parse::<usize>().unwrap();
-                        c.inputs.last_mut().and_then(|input| {
+                        if let Some(input) = c.inputs.last_mut() {
                             input.udp_fifo_sz = udp_fifo_sz;
-                            Some(input)
-                        });
+                        }
+                    }
+                    "fec" => {
+                        let rtp_fec = value
+                            .parse::<FecMode>()
+                            .map_err(|_| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.rtp_fec = rtp_fec;
+                        }
+                    }
+                    "rcvbuf" => {
+                        let rcvbuf =
+                            size_parse(&value).ok_or_else(|| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.udp_sock_opts.rcvbuf = Some(rcvbuf);
+                        }
+                    }
+                    "reuse" => {
+                        let reuse =
+                            bool_parse(&value).ok_or_else(|| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.udp_sock_opts.reuse = Some(reuse);
+                        }
+                    }
+                    "iat-buckets" => {
+                        let iat_buckets = iat::buckets_parse(&value)
+                            .ok_or_else(|| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.iat_buckets = iat_buckets;
+                        }
+                    }
+                    "mdi-rate" => {
+                        let rate = gen::bitrate_parse(&value)
+                            .ok_or_else(|| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.mdi_opts.rate = Some(rate);
+                        }
+                    }
+                    "mdi-df-max" => {
+                        let df_max =
+                            ms_parse(&value).ok_or_else(|| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.mdi_opts.df_max = df_max;
+                        }
+                    }
+                    "mdi-mlr-max" => {
+                        let mlr_max = value
+                            .parse::<f64>()
+                            .ok()
+                            .filter(|mlr| *mlr >= 0.0 && mlr.is_finite())
+                            .ok_or_else(|| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.mdi_opts.mlr_max = mlr_max;
+                        }
+                    }
+                    "pid-interval-max" => {
+                        let v = ms_parse(&value).ok_or_else(|| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.tr101290_opts.pid_interval_max = v;
+                        }
+                    }
+                    "pcr-interval-max" => {
+                        let v = ms_parse(&value).ok_or_else(|| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.tr101290_opts.pcr_interval_max = v;
+                        }
+                    }
+                    "pcr-discontinuity-max" => {
+                        let v = ms_parse(&value).ok_or_else(|| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.tr101290_opts.pcr_discontinuity_max = v;
+                        }
+                    }
+                    "pcr-accuracy-max" => {
+                        let ns = value
+                            .parse::<u64>()
+                            .ok()
+                            .filter(|ns| *ns > 0)
+                            .ok_or_else(|| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.tr101290_opts.pcr_accuracy_max = Duration::from_nanos(ns);
+                        }
+                    }
+                    "pts-interval-max" => {
+                        let v = ms_parse(&value).ok_or_else(|| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.tr101290_opts.pts_interval_max = v;
+                        }
+                    }
+                    "si-profile" => {
+                        let si_profile = value
+                            .parse()
+                            .map_err(|_| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.tr101290_opts.si_profile = si_profile;
+                        }
+                    }
+                    "si-repetition" => {
+                        let si_repetition = tr101290::si_repetition_parse(&value)
+                            .ok_or_else(|| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.tr101290_opts.si_repetition.extend(si_repetition);
+                        }
+                    }
+                    "pcr-csv" => {
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.pcr_csv = Some(PathBuf::from(&value));
+                        }
+                    }
+                    "av-offset-max" => {
+                        let av_offset_max =
+                            ms_parse(&value).ok_or_else(|| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.av_offset_max = av_offset_max;
+                        }
+                    }
+                    "av-offset-abs-max" => {
+                        let av_offset_abs_max =
+                            ms_parse(&value).ok_or_else(|| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.av_offset_abs_max = av_offset_abs_max;
+                        }
+                    }
+                    "keyframe-interval-max" => {
+                        let keyframe_interval_max =
+                            ms_parse(&value).ok_or_else(|| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.keyframe_interval_max = keyframe_interval_max;
+                        }
+                    }
+                    "subtitle-timeout" => {
+                        let subtitle_timeout =
+                            ms_parse(&value).ok_or_else(|| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.subtitle_timeout = subtitle_timeout;
+                        }
+                    }
+                    "scte35-log" => {
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.scte35_log = Some(PathBuf::from(&value));
+                        }
+                    }
+                    "scte35-format" => {
+                        let scte35_format = value
+                            .parse::<ProbeFormat>()
+                            .map_err(|_| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.scte35_format = scte35_format;
+                        }
+                    }
+                    "captions-timeout" => {
+                        let captions_timeout =
+                            ms_parse(&value).ok_or_else(|| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.captions_timeout = captions_timeout;
+                        }
+                    }
+                    "captions-out" => {
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.captions_out = Some(PathBuf::from(&value));
+                        }
+                    }
+                    "captions-format" => {
+                        let captions_format = value
+                            .parse::<CaptionsFormat>()
+                            .map_err(|_| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.captions_format = captions_format;
+                        }
+                    }
+                    "epg-out" => {
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.epg_out = Some(PathBuf::from(&value));
+                        }
+                    }
+                    "epg-format" => {
+                        let epg_format = value
+                            .parse::<EpgFormat>()
+                            .map_err(|_| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.epg_format = epg_format;
+                        }
+                    }
+                    "ecm-timeout" => {
+                        let ecm_timeout =
+                            ms_parse(&value).ok_or_else(|| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.ecm_timeout = ecm_timeout;
+                        }
+                    }
+                    "out" => {
+                        let url = url_parse(&value)?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.outputs.push(ConfigOutput { url });
+                        }
+                    }
+                    "ttl" => {
+                        let ttl = value
+                            .parse::<u32>()
+                            .map_err(|_| Error::config_value(key, &value))?;
+                        if let Some(input) = c.inputs.last_mut() {
+                            input.udp_sock_opts.ttl = Some(ttl);
+                        }
                     }
 
                     _ => {}
@@ -82,11 +422,17 @@ impl Config {
 
                 OptMatch::Positional(value) | OptMatch::ExtraPositional(value) => {
                     if i == 0 && value == "analyze" {
+                    } else if i == 0 && value == "probe" {
+                        c.action = Action::Probe;
                     } else {
                         c.push_input(value)?
                     }
                 }
 
+                OptMatch::NoArg(key) => {
+                    log::warn!(r#"missing argument for option "{}""#, key);
+                }
+
                 OptMatch::UnknownKey(key) => {
                     log::warn!(r#"unrecognized option "{}""#, key);
                 }
@@ -96,11 +442,14 @@ impl Config {
                 OptMatch::No(key) => {
                     log::warn!(r#"unknown argument "{}""#, key);
                 }
-
-                _ => {}
             }
         }
 
+        // keep stdout report readable; track logs go to stderr
+        if c.action == Action::Probe && !verbose {
+            c.log_level = log::Level::Warn;
+        }
+
         Ok(c)
     }
 
@@ -109,15 +458,109 @@ impl Config {
         println!();
         println!("Usage:");
         println!(r#"  va-tool [...] [-arg ...] [--arg[="..."]] [--] [...]"#);
+        println!(r#"  va-tool probe [-arg ...] [--arg[="..."]] [--] [...]"#);
+        println!();
+        println!("Actions:");
+        println!("  analyze                        | default; run and log metrics");
+        println!("  probe                          | list programs, streams and bitrates; exit");
         println!();
         println!("Flags:");
         println!("  -vv, --verbose                 | <bool>    | ... ");
         println!("  -vvv, --very-verbose           | <bool>    | ... ");
         println!("  -i, --intput                   | <str/url> | Where to read from");
+        println!(
+            "                                             . gen://spts|mpts?bitrate=4M&programs=1"
+        );
+        println!("                                             .   &pcr-interval=35&realtime=1&packets=N");
+        println!(
+            "                                             .   &cc-error=N&pcr-jump=N&no-pat=1"
+        );
+        println!("                                             .   synthetic stream with injected faults");
+        println!(
+            "                                             . ?format=ts|mp4|fmp4|webm|hls overrides"
+        );
+        println!("                                             .   container detection");
         println!("    --fifo-sz                    | <size>    | circular buffer size; result allocaed size");
         println!("                                             . is $(mpeg-ts-packer-size) * $(fifo-size)");
         println!("                                             . mpeg-ts-packer-size is 188");
+        println!(
+            "    --fec, --rtp-fec             | <str>     | SMPTE 2022-1 FEC for rtp:// input"
+        );
+        println!("                                             . off | col | row | 2d");
+        println!("                                             . column FEC on port+2, row FEC on port+4");
+        println!("    --rcvbuf, --udp-rcvbuf       | <size>    | udp socket receive buffer (SO_RCVBUF) e.g. 8M");
+        println!("                                             . kernel doubles it and caps by net.core.rmem_max");
+        println!("    --reuse, --udp-reuse         | <bool>    | SO_REUSEADDR + SO_REUSEPORT");
+        println!("                                             . default: on for multicast groups");
+        println!("    --ttl, --udp-ttl             | <int>     | IP_TTL + IP_MULTICAST_TTL");
+        println!(
+            "    --iat-buckets                | <ms,...>  | datagram inter-arrival time histogram"
+        );
+        println!(
+            "                                             . bucket upper bounds e.g. 0.5,1,2,5,10"
+        );
+        println!(
+            "    --pid-interval-max           | <ms>      | TR 101 290 PID_error; default 5000"
+        );
+        println!("    --pcr-interval-max           | <ms>      | TR 101 290 PCR_repetition_error; default 40");
+        println!("    --pcr-discontinuity-max      | <ms>      | TR 101 290 PCR_discontinuity_indicator_error");
+        println!("                                             . default 100");
+        println!("    --pcr-accuracy-max           | <ns>      | TR 101 290 PCR_accuracy_error; default 500");
+        println!(
+            "    --pts-interval-max           | <ms>      | TR 101 290 PTS_error; default 700"
+        );
+        println!("    --si-profile                 | <str>     | TR 101 290 SI repetition limits");
+        println!("                                             . dvb | atsc | custom; default dvb");
+        println!(
+            "    --si-repetition              | <str>     | per table max interval (ms) overrides"
+        );
+        println!(
+            "                                             . e.g. --si-repetition nit=5000,bat=off"
+        );
+        println!(
+            "                                             . nit nit-other sdt sdt-other bat eit-pf"
+        );
+        println!(
+            "                                             . eit-pf-other eit-sched eit-sched-other"
+        );
+        println!("                                             . tdt tot mgt tvct cvct stt");
+        println!("    --pcr-csv                    | <path>    | PCR jitter/accuracy time series for plotting");
+        println!("    --av-offset-max              | <ms>      | A/V offset drift alarm threshold; default 40");
+        println!("    --av-offset-abs-max          | <ms>      | absolute A/V offset alarm threshold; default 500");
+        println!("    --keyframe-interval-max      | <ms>      | max interval between intra pictures; default 2000");
+        println!("    --subtitle-timeout           | <ms>      | subtitle service lost alarm; default 30000");
+        println!(
+            "    --scte35-log, --cue-log      | <path>    | SCTE-35 cue and splice point event log"
+        );
+        println!("    --scte35-format              | <str>     | event log: text | json (one object per line)");
+        println!("                                             . default text");
+        println!("    --captions-timeout           | <ms>      | closed caption channel lost alarm; default 30000");
+        println!(
+            "    --captions-out, --cc-out     | <path>    | CEA-608/708 cue export per channel"
+        );
+        println!("                                             . $(pid) e.g. 0x0100; $(channel) cc1..cc4");
+        println!("                                             .   svc1..svc63; $(ext) srt | vtt");
+        println!(
+            "    --captions-format            | <str>     | cue export: srt | vtt; default srt"
+        );
+        println!("    --epg-out, --eit-out         | <path>    | EIT present/following and schedule export");
+        println!("                                             . rewritten every 10s when changed");
+        println!(
+            "    --epg-format                 | <str>     | EPG export: json | xmltv; default json"
+        );
+        println!("    --ecm-timeout                | <ms>      | ECM repetition stopped alarm; default 2000");
         println!("  -o, --output, --out            | <str/url> | Where to write to");
+        println!("                                             . file:///tmp/dump.$(ext) elementary streams");
+        println!("                                             .   $(ext) by stream type: h264 h265 m2v aac");
+        println!("                                             .   latm mp2 ac3 eac3; $(pid) e.g. 0x0100");
+        println!(
+            "  --metrics-interval             | <sec>     | how often to log metrics; default 10"
+        );
+        println!(
+            "  --duration, --probe-duration   | <sec>     | probe: how long to read; default 5"
+        );
+        println!("  --packets, --probe-packets     | <int>     | probe: stop after N packets");
+        println!("  --format, --of                 | <str>     | probe: text | json; default text");
         println!();
     }
 
@@ -126,18 +569,120 @@ impl Config {
     }
 
     pub(crate) fn print_config(&self) {
+        println!("action: {}", self.action);
         println!("log-level: {}", self.log_level.to_string().to_lowercase());
+        println!("metrics-interval: {}s", self.metrics_interval.as_secs());
+        if self.action == Action::Probe {
+            println!("probe-duration: {}s", self.probe_duration.as_secs_f64());
+            match self.probe_packets {
+                Some(packets) => println!("probe-packets: {}", packets),
+                None => println!("probe-packets: ~"),
+            }
+            println!("probe-format: {}", self.probe_format);
+        }
         println!("inputs:");
         for input in self.inputs.iter() {
             println!("  - id: {}", input.id);
             println!("    url: {}", input.url);
-            if input.url.scheme() == "udp" {
+            if input.url.scheme() == "udp" || input.url.scheme() == "rtp" {
+                let opts = &input.udp_sock_opts;
+
                 println!("    udp-fifo-sz: {}", input.udp_fifo_sz);
+                match opts.rcvbuf_effective() {
+                    Ok(effective) => println!(
+                        "    udp-rcvbuf: {} # effective: {}",
+                        opts.rcvbuf
+                            .map(|v| v.to_string())
+                            .unwrap_or_else(|| "default".to_string()),
+                        effective
+                    ),
+                    Err(err) => println!("    udp-rcvbuf: ~ # {}", err),
+                }
+                match opts.reuse {
+                    Some(reuse) => println!("    udp-reuse: {}", reuse),
+                    None => println!("    udp-reuse: auto # on for multicast"),
+                }
+                if let Some(ttl) = opts.ttl {
+                    println!("    udp-ttl: {}", ttl);
+                }
+                let iat_buckets: Vec<String> = input
+                    .iat_buckets
+                    .iter()
+                    .map(|bucket| (bucket.as_secs_f64() * 1000.0).to_string())
+                    .collect();
+                println!("    iat-buckets: [{}] # ms", iat_buckets.join(", "));
+                println!("    mdi: {}", input.mdi_opts);
+            }
+            println!("    tr101290: {}", input.tr101290_opts);
+            println!(
+                "    av-offset-max: {}ms",
+                input.av_offset_max.as_secs_f64() * 1000.0
+            );
+            println!(
+                "    av-offset-abs-max: {}ms",
+                input.av_offset_abs_max.as_secs_f64() * 1000.0
+            );
+            println!(
+                "    keyframe-interval-max: {}ms",
+                input.keyframe_interval_max.as_secs_f64() * 1000.0
+            );
+            if let Some(pcr_csv) = input.pcr_csv.as_ref() {
+                println!("    pcr-csv: {}", pcr_csv.display());
+            }
+            println!(
+                "    subtitle-timeout: {}ms",
+                input.subtitle_timeout.as_secs_f64() * 1000.0
+            );
+            if let Some(scte35_log) = input.scte35_log.as_ref() {
+                println!(
+                    "    scte35-log: {} # {}",
+                    scte35_log.display(),
+                    input.scte35_format
+                );
+            }
+            println!(
+                "    captions-timeout: {}ms",
+                input.captions_timeout.as_secs_f64() * 1000.0
+            );
+            if let Some(captions_out) = input.captions_out.as_ref() {
+                println!(
+                    "    captions-out: {} # {}",
+                    captions_out.display(),
+                    input.captions_format
+                );
+            }
+            if let Some(epg_out) = input.epg_out.as_ref() {
+                println!("    epg-out: {} # {}", epg_out.display(), input.epg_format);
+            }
+            println!(
+                "    ecm-timeout: {}ms",
+                input.ecm_timeout.as_secs_f64() * 1000.0
+            );
+            if !input.outputs.is_empty() {
+                println!("    outputs:");
+                for output in input.outputs.iter() {
+                    println!("      - {}", output.url);
+                }
+            }
+            if input.url.scheme() == "rtp" {
+                println!("    rtp-fec: {}", input.rtp_fec);
+            }
+            if input.url.scheme() == "gen" {
+                match GenParams::from_url(&input.url) {
+                    Ok(params) => println!("    gen: {}", params),
+                    Err(err) => println!("    gen: ~ # {}", err),
+                }
             }
         }
     }
 
     pub(crate) fn validate(&self) -> Result<()> {
+        for input in self.inputs.iter() {
+            if input.url.scheme() == "gen" {
+                GenParams::from_url(&input.url)?;
+            }
+        }
+
         Ok(())
     }
 
@@ -146,6 +691,25 @@ impl Config {
             id: 0,
             url: url_parse(&url_raw)?,
             udp_fifo_sz: 5 * 1000,
+            rtp_fec: FecMode::Off,
+            udp_sock_opts: Default::default(),
+            iat_buckets: iat::BUCKETS_DEFAULT.to_vec(),
+            mdi_opts: Default::default(),
+            tr101290_opts: Default::default(),
+            pcr_csv: None,
+            av_offset_max: Duration::from_millis(40),
+            av_offset_abs_max: Duration::from_millis(500),
+            keyframe_interval_max: Duration::from_secs(2),
+            subtitle_timeout: Duration::from_secs(30),
+            scte35_log: None,
+            scte35_format: ProbeFormat::Text,
+            captions_timeout: Duration::from_secs(30),
+            captions_out: None,
+            captions_format: CaptionsFormat::Srt,
+            epg_out: None,
+            epg_format: EpgFormat::Json,
+            ecm_timeout: Duration::from_secs(2),
+            outputs: Default::default(),
         };
 
         self.inputs.push(cfg_input);
@@ -154,6 +718,15 @@ impl Config {
     }
 }
 
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::Analyze => write!(f, "analyze"),
+            Action::Probe => write!(f, "probe"),
+        }
+    }
+}
+
 /// patched version of url-parse
 /// add udp:// to udp-like host
 /// add file:// to file-like paths
@@ -191,3 +764,34 @@ fn url_parse<UR: AsRef<str>>(url_raw: UR) -> Result<url::Url> {
 
     Url::parse(&url_raw).map_err(|err| Error::url_parse(err, url_raw))
 }
+
+/// parse size with optional binary suffix: 512, 64K, 8M, 1G
+fn size_parse<S: AsRef<str>>(s: S) -> Option<usize> {
+    let s = s.as_ref();
+
+    let (digits, mul) = match s.chars().last()?.to_ascii_uppercase() {
+        'K' => (&s[..s.len() - 1], 1 << 10),
+        'M' => (&s[..s.len() - 1], 1 << 20),
+        'G' => (&s[..s.len() - 1], 1 << 30),
+        _ => (s, 1),
+    };
+
+    digits.parse::<usize>().ok()?.checked_mul(mul)
+}
+
+/// parse positive milliseconds e.g. "40", "0.5"
+fn ms_parse<S: AsRef<str>>(s: S) -> Option<Duration> {
+    s.as_ref()
+        .parse::<f64>()
+        .ok()
+        .filter(|ms| *ms > 0.0)
+        .and_then(|ms| Duration::try_from_secs_f64(ms / 1000.0).ok())
+}
+
+pub(crate) fn bool_parse<S: AsRef<str>>(s: S) -> Option<bool> {
+    match s.as_ref() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
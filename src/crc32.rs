@@ -0,0 +1,37 @@
+/// CRC-32/MPEG-2 (ISO/IEC 13818-1 Annex A)
+///
+/// poly 0x04C11DB7, init 0xFFFFFFFF, no reflection, no final xor;
+/// CRC over whole section including CRC_32 field is zero
+const POLY: u32 = 0x04C1_1DB7;
+
+const TABLE: [u32; 256] = table();
+
+const fn table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ POLY
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+}
+
+pub fn mpeg2(buf: &[u8]) -> u32 {
+    buf.iter().fold(0xFFFF_FFFF, |crc, b| {
+        (crc << 8) ^ TABLE[(((crc >> 24) as u8) ^ *b) as usize]
+    })
+}
@@ -0,0 +1,639 @@
+use log::{debug, info, trace};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use url::Url;
+
+use crate::clock::{discontinuity, Throttle};
+use crate::filter::{Consumer, Consumers, Producer};
+use crate::metrics::Metrics;
+use crate::packet::Packet;
+use crate::psi::{self, Section};
+use crate::track::Track;
+
+/// PSI/SI section reassembly for one PID
+#[derive(Default)]
+struct SectionBuf {
+    buf: Vec<u8>,
+}
+
+impl SectionBuf {
+    fn push<F>(&mut self, payload: &[u8], pusi: bool, mut emit: F)
+    where
+        F: FnMut(&[u8]),
+    {
+        let mut data = payload;
+
+        if pusi {
+            let pointer_field = match data.first() {
+                Some(v) => usize::from(*v),
+                None => return,
+            };
+            data = &data[1..];
+
+            if pointer_field > data.len() {
+                self.buf.clear();
+                return;
+            }
+
+            // tail of previous section
+            if !self.buf.is_empty() {
+                self.buf.extend_from_slice(&data[..pointer_field]);
+                self.drain(&mut emit);
+            }
+
+            self.buf.clear();
+            data = &data[pointer_field..];
+        } else if self.buf.is_empty() {
+            // not synced; wait for section start
+            return;
+        }
+
+        self.buf.extend_from_slice(data);
+        self.drain(&mut emit);
+    }
+
+    /// emit complete sections; drop stuffing
+    fn drain<F>(&mut self, emit: &mut F)
+    where
+        F: FnMut(&[u8]),
+    {
+        let mut pos = 0;
+
+        loop {
+            let buf = &self.buf[pos..];
+
+            if buf.is_empty() || buf[0] == 0xFF {
+                pos = self.buf.len();
+                break;
+            }
+            if buf.len() < Section::HEADER_SZ {
+                break;
+            }
+
+            let sz = Section::HEADER_SZ + ((usize::from(buf[1] & 0x0F) << 8) | usize::from(buf[2]));
+            if sz > Section::SZ_MAX {
+                pos = self.buf.len();
+                break;
+            }
+            if buf.len() < sz {
+                break;
+            }
+
+            emit(&buf[..sz]);
+            pos += sz;
+        }
+
+        self.buf.drain(..pos);
+    }
+}
+
+/// PES reassembly for one PID
+#[derive(Default)]
+struct PesBuf {
+    pkt: Option<Packet>,
+    /// bytes left till PES_packet_length; None if unbounded
+    left: Option<usize>,
+}
+
+impl PesBuf {
+    /// PES header without optional fields
+    /// (ISO/IEC 13818-1 Table 2-21)
+    fn stream_id_no_header(stream_id: u8) -> bool {
+        matches!(
+            stream_id,
+            0xBC | 0xBE | 0xBF | 0xF0 | 0xF1 | 0xF2 | 0xF8 | 0xFF
+        )
+    }
+
+    fn push<F>(&mut self, pid: u16, n: u64, payload: &[u8], pusi: bool, mut emit: F)
+    where
+        F: FnMut(Packet),
+    {
+        if pusi {
+            // unbounded PES ends with the next one
+            if let Some(pkt) = self.pkt.take() {
+                emit(pkt);
+            }
+
+            // start code, stream_id, PES_packet_length and flags
+            // must fit into the first packet
+            let no_header = payload.len() >= 6 && Self::stream_id_no_header(payload[3]);
+            let header_sz = match payload.len() {
+                _ if no_header => 6,
+                9.. => 9 + usize::from(payload[8]),
+                _ => usize::MAX,
+            };
+            if payload.len() < header_sz {
+                trace!(
+                    "skip PES (:pid 0x{:04X} :reason truncated header :sz {})",
+                    pid,
+                    payload.len()
+                );
+                return;
+            }
+
+            let pes = match ts::PES::try_new(payload) {
+                Ok(pes) => pes,
+                Err(err) => {
+                    trace!("skip PES (:pid 0x{:04X} :reason {:?})", pid, err);
+                    return;
+                }
+            };
+
+            let stream_id = payload[3];
+            let data = &payload[header_sz..];
+
+            // PTS_DTS_flags; PTS needs 5 bytes, DTS 5 more
+            let pts_dts_flags = if no_header { 0 } else { payload[7] >> 6 };
+            let pts = match pts_dts_flags {
+                0b10 | 0b11 if header_sz >= 14 => pes.pts().map(|ts| ts.value()),
+                _ => None,
+            };
+            let dts = match pts_dts_flags {
+                0b11 if header_sz >= 19 => pes.dts().map(|ts| ts.value()),
+                _ => None,
+            };
+
+            let length = (usize::from(payload[4]) << 8) | usize::from(payload[5]);
+            self.left = if length == 0 {
+                None
+            } else {
+                Some((6 + length).saturating_sub(header_sz))
+            };
+
+            self.pkt = Some(Packet {
+                pid,
+                stream_id,
+                n,
+                pts,
+                dts,
+                data: Vec::with_capacity(self.left.unwrap_or(data.len())),
+            });
+
+            self.append(data, &mut emit);
+        } else if self.pkt.is_some() {
+            self.append(payload, &mut emit);
+        }
+    }
+
+    fn append<F>(&mut self, data: &[u8], emit: &mut F)
+    where
+        F: FnMut(Packet),
+    {
+        let pkt = match self.pkt.as_mut() {
+            Some(pkt) => pkt,
+            None => return,
+        };
+
+        match self.left.as_mut() {
+            Some(left) => {
+                let n = std::cmp::min(*left, data.len());
+                pkt.data.extend_from_slice(&data[..n]);
+                *left -= n;
+
+                // bounded PES is complete - no need to wait for the next one
+                if *left == 0 {
+                    if let Some(pkt) = self.pkt.take() {
+                        emit(pkt);
+                    }
+                }
+            }
+            None => pkt.data.extend_from_slice(data),
+        }
+    }
+
+    /// continuity lost: drop incomplete packet
+    fn reset(&mut self) {
+        self.pkt = None;
+        self.left = None;
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// program_number -> PMT PID
+    programs: BTreeMap<u16, u16>,
+    /// version_number of PAT sections merged into programs
+    pat_version: Option<u8>,
+
+    /// pid -> track
+    tracks: BTreeMap<u16, Track>,
+
+    /// service_id -> (provider, service) names
+    services: HashMap<u16, (String, String)>,
+
+    /// last continuity counter per PID with payload
+    cc: HashMap<u16, u8>,
+
+    sections: HashMap<u16, SectionBuf>,
+    pes: HashMap<u16, PesBuf>,
+
+    /// (pid, table_id, table_id_extension, section_number) -> CRC_32
+    /// of last processed section; repetitions are not parsed again
+    seen: HashMap<(u16, u8, u16, u8), u32>,
+
+    stats: Stats,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Stats {
+    packets: u64,
+    sections: u64,
+    sections_crc_errors: u64,
+    pes: u64,
+}
+
+impl State {
+    fn is_section_pid(&self, pid: u16) -> bool {
+        psi::is_si_pid(pid)
+            || self.programs.values().any(|pmt_pid| *pmt_pid == pid)
+            || self.tracks.get(&pid).is_some_and(|trk| trk.is_sectioned())
+    }
+
+    /// false on duplicate packet;
+    /// CC may restart where discontinuity_indicator is set
+    fn cc_check(&mut self, pid: u16, cc: u8, discontinuity: bool) -> (bool, bool) {
+        match self.cc.insert(pid, cc) {
+            _ if discontinuity => (true, false),
+            Some(last) if last == cc => (false, false),
+            Some(last) => (true, (last + 1) & 0x0F != cc),
+            None => (true, false),
+        }
+    }
+}
+
+/// MPEG-TS demuxer
+///
+/// consumes raw TS packets; tracks PAT/PMT/SDT,
+/// reassembles sections and PES and produces them
+/// (together with raw packets) to own consumers
+pub struct Demuxer {
+    url: Url,
+
+    consumers: Consumers,
+
+    state: RefCell<State>,
+
+    metrics: Metrics,
+    metrics_at: Throttle,
+}
+
+impl Demuxer {
+    pub fn new(url: Url) -> Demuxer {
+        Demuxer {
+            url,
+            consumers: Default::default(),
+            state: Default::default(),
+            metrics: Default::default(),
+            metrics_at: Default::default(),
+        }
+    }
+
+    pub fn metrics(&mut self, metrics: Metrics) -> &Demuxer {
+        self.metrics = metrics;
+        self
+    }
+
+    fn demux(&self, raw: &[u8]) {
+        let pkt = match ts::Packet::new(raw) {
+            Ok(pkt) => pkt,
+            Err(err) => {
+                trace!("({}) [ts] skip packet (:reason {:?})", self.url, err);
+                return;
+            }
+        };
+
+        let pid = u16::from(pkt.pid());
+        if pid == psi::PID_NULL {
+            return;
+        }
+
+        // adaptation field only
+        let payload = match pkt.buf_payload_pes() {
+            Ok(payload) => payload,
+            Err(_) => return,
+        };
+
+        let mut sections = Vec::new();
+        // unbounded PES may end with the next one in the same packet
+        let mut pes = Vec::new();
+        {
+            let mut state = self.state.borrow_mut();
+            let n = state.stats.packets;
+
+            let (fresh, gap) = state.cc_check(pid, pkt.cc(), discontinuity(raw));
+            if !fresh {
+                return;
+            }
+
+            if state.is_section_pid(pid) {
+                let buf = state.sections.entry(pid).or_default();
+                if gap {
+                    buf.buf.clear();
+                }
+                buf.push(payload, pkt.pusi(), |section| {
+                    sections.push(section.to_vec())
+                });
+            } else if state.tracks.contains_key(&pid) {
+                let buf = state.pes.entry(pid).or_default();
+                if gap {
+                    buf.reset();
+                }
+                buf.push(pid, n, payload, pkt.pusi(), |pkt| pes.push(pkt));
+            }
+        }
+
+        for section in sections.iter() {
+            self.produce_section(pid, section);
+            self.section(pid, section);
+        }
+
+        for pes in pes {
+            trace!(
+                "({}) [ts] PES (:pid 0x{:04X} :stream-id 0x{:02X} :pts {:?} :dts {:?} :sz {})",
+                self.url,
+                pes.pid,
+                pes.stream_id,
+                pes.pts,
+                pes.dts,
+                pes.data.len()
+            );
+
+            self.state.borrow_mut().stats.pes += 1;
+            self.produce_pkt(&pes);
+        }
+    }
+
+    fn section(&self, pid: u16, raw: &[u8]) {
+        let section = match Section::try_new(raw) {
+            Some(section) => section,
+            None => return,
+        };
+
+        {
+            let mut state = self.state.borrow_mut();
+            state.stats.sections += 1;
+
+            if !section.crc32_ok() {
+                state.stats.sections_crc_errors += 1;
+                trace!(
+                    "({}) [ts] section CRC mismatch (:pid 0x{:04X} :table-id 0x{:02X})",
+                    self.url,
+                    pid,
+                    section.table_id()
+                );
+                return;
+            }
+
+            if !section.current_next_indicator() {
+                return;
+            }
+
+            let key = (
+                pid,
+                section.table_id(),
+                section.table_id_extension(),
+                section.section_number(),
+            );
+            let crc = section.crc32().unwrap_or(0);
+            if section.syntax() && state.seen.insert(key, crc) == Some(crc) {
+                return;
+            }
+        }
+
+        let is_pmt_pid = self.state.borrow().programs.values().any(|p| *p == pid);
+
+        match (pid, section.table_id()) {
+            (psi::PID_PAT, psi::TABLE_ID_PAT) => self.pat(&section),
+            (_, psi::TABLE_ID_PMT) if is_pmt_pid => self.pmt(pid, &section),
+            (psi::PID_SDT, psi::TABLE_ID_SDT) => self.sdt(&section),
+            _ => {}
+        }
+    }
+
+    fn pat(&self, section: &Section) {
+        let pat = ts::PAT::new(section.buf());
+
+        let mut programs = BTreeMap::new();
+        for program in pat.programs().filter_map(Result::ok) {
+            // network PID
+            if program.number() != 0 {
+                programs.insert(program.number(), program.pid_raw());
+            }
+        }
+
+        debug!(
+            "({}) [ts] PAT (:transport-stream-id {} :version {} :programs {})",
+            self.url,
+            section.table_id_extension(),
+            section.version_number(),
+            programs.len()
+        );
+
+        let removed: Vec<Track> = {
+            let mut state = self.state.borrow_mut();
+
+            // multi-section PAT is merged within one version
+            let version = section.version_number();
+            if section.last_section_number() == 0 || state.pat_version != Some(version) {
+                state.programs = programs;
+            } else {
+                state.programs.extend(programs);
+            }
+            state.pat_version = Some(version);
+
+            let state = &mut *state;
+            let programs = &state.programs;
+            let (keep, removed) = std::mem::take(&mut state.tracks)
+                .into_iter()
+                .partition(|(_, trk)| programs.get(&trk.program_number) == Some(&trk.pmt_pid));
+            state.tracks = keep;
+
+            // new PMT versions must be parsed again
+            state
+                .seen
+                .retain(|(_, table_id, _, _), _| *table_id != psi::TABLE_ID_PMT);
+
+            removed.into_values().collect()
+        };
+
+        for trk in removed.iter() {
+            info!("({}) [-] track {}", self.url, trk);
+        }
+    }
+
+    fn pmt(&self, pmt_pid: u16, section: &Section) {
+        let program_number = section.table_id_extension();
+        let body = section.body();
+        if body.len() < 4 {
+            return;
+        }
+
+        let pcr_pid = (u16::from(body[0] & 0x1F) << 8) | u16::from(body[1]);
+        let program_info_length = (usize::from(body[2] & 0x0F) << 8) | usize::from(body[3]);
+
+        let mut tracks = Vec::new();
+        let mut pos = 4 + program_info_length;
+        while pos + 5 <= body.len() {
+            let es_info_length =
+                (usize::from(body[pos + 3] & 0x0F) << 8) | usize::from(body[pos + 4]);
+            let end = std::cmp::min(pos + 5 + es_info_length, body.len());
+
+            tracks.push(Track {
+                pid: (u16::from(body[pos + 1] & 0x1F) << 8) | u16::from(body[pos + 2]),
+                program_number,
+                pmt_pid,
+                pcr_pid,
+                stream_type: body[pos],
+                descriptors: body[pos + 5..end].to_vec(),
+                provider_name: None,
+                service_name: None,
+                video: None,
+                audio: None,
+                subtitle: None,
+            });
+
+            pos += 5 + es_info_length;
+        }
+
+        debug!(
+            "({}) [ts] PMT (:program {} :pid 0x{:04X} :version {} :pcr-pid 0x{:04X} :streams {})",
+            self.url,
+            program_number,
+            pmt_pid,
+            section.version_number(),
+            pcr_pid,
+            tracks.len()
+        );
+
+        let (added, removed) = {
+            let mut state = self.state.borrow_mut();
+
+            if let Some((provider, service)) = state.services.get(&program_number).cloned() {
+                for trk in tracks.iter_mut() {
+                    trk.provider_name = Some(provider.clone());
+                    trk.service_name = Some(service.clone());
+                }
+            }
+
+            let removed: Vec<Track> = state
+                .tracks
+                .values()
+                .filter(|trk| {
+                    trk.program_number == program_number
+                        && !tracks.iter().any(|new| new.pid == trk.pid)
+                })
+                .cloned()
+                .collect();
+            for trk in removed.iter() {
+                state.tracks.remove(&trk.pid);
+                state.pes.remove(&trk.pid);
+            }
+
+            let mut added = Vec::new();
+            for trk in tracks.into_iter() {
+                if state.tracks.get(&trk.pid) != Some(&trk) {
+                    state.tracks.insert(trk.pid, trk.clone());
+                    added.push(trk);
+                }
+            }
+
+            (added, removed)
+        };
+
+        for trk in removed.iter() {
+            info!("({}) [-] track {}", self.url, trk);
+        }
+        for trk in added.iter() {
+            info!("({}) [+] track {}", self.url, trk);
+            self.produce_trk(trk);
+        }
+    }
+
+    /// service names for tracks
+    fn sdt(&self, section: &Section) {
+        let services = match psi::sdt_services(section.body()) {
+            Some((_, services)) => services,
+            None => return,
+        };
+
+        let updated: Vec<Track> = {
+            let mut state = self.state.borrow_mut();
+            let mut updated = Vec::new();
+
+            for service in services.into_iter() {
+                debug!(
+                    r#"({}) [ts] SDT (:service-id {} :provider "{}" :service "{}")"#,
+                    self.url, service.service_id, service.provider, service.name
+                );
+
+                for trk in state.tracks.values_mut() {
+                    if trk.program_number == service.service_id
+                        && (trk.provider_name.as_ref() != Some(&service.provider)
+                            || trk.service_name.as_ref() != Some(&service.name))
+                    {
+                        trk.provider_name = Some(service.provider.clone());
+                        trk.service_name = Some(service.name.clone());
+                        updated.push(trk.clone());
+                    }
+                }
+
+                state
+                    .services
+                    .insert(service.service_id, (service.provider, service.name));
+            }
+
+            updated
+        };
+
+        for trk in updated.iter() {
+            debug!("({}) [*] track {}", self.url, trk);
+            self.produce_trk(trk);
+        }
+    }
+
+    fn metrics_publish(&self) {
+        if !self.metrics_at.ready() {
+            return;
+        }
+
+        let state = self.state.borrow();
+        let stats = state.stats;
+
+        self.metrics.set("ts-packets", stats.packets);
+        self.metrics.set("ts-sections", stats.sections);
+        self.metrics
+            .set("ts-sections-crc-errors", stats.sections_crc_errors);
+        self.metrics.set("ts-pes", stats.pes);
+        self.metrics.set("ts-programs", state.programs.len());
+        self.metrics.set("ts-tracks", state.tracks.len());
+    }
+}
+
+impl Producer for Demuxer {
+    fn consumers(&self) -> &Consumers {
+        &self.consumers
+    }
+
+    fn consumers_mut(&mut self) -> &mut Consumers {
+        &mut self.consumers
+    }
+}
+
+impl Consumer for Demuxer {
+    fn consume_pkt_raw(&self, pkt_raw: &[u8]) {
+        let packets = {
+            let mut state = self.state.borrow_mut();
+            state.stats.packets += 1;
+            state.stats.packets
+        };
+
+        self.produce_pkt_raw(pkt_raw);
+        self.demux(pkt_raw);
+
+        // cheap check; Instant::now is not free
+        if packets % 256 == 0 {
+            self.metrics_publish();
+        }
+    }
+}
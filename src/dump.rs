@@ -0,0 +1,153 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use url::Url;
+
+use crate::filter::Consumer;
+use crate::frame::Frame;
+
+/// raw elementary stream file extension by codec (Track::codec)
+pub fn ext(codec: &str) -> Option<&'static str> {
+    match codec {
+        "h264" => Some("h264"),
+        "h265" => Some("h265"),
+        "mpeg2video" => Some("m2v"),
+        "mpeg1video" => Some("m1v"),
+        "aac" => Some("aac"),
+        "aac-latm" => Some("latm"),
+        "mp2" => Some("mp2"),
+        "ac3" => Some("ac3"),
+        "eac3" => Some("eac3"),
+        "dts" => Some("dts"),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// None if not dumped (unknown codec or write error)
+    files: HashMap<u16, Option<BufWriter<File>>>,
+    paths: HashSet<PathBuf>,
+}
+
+/// writes access units of every audio/video PID to its own file.
+///
+/// path template placeholders:
+///   $(ext) - by stream type e.g. h264, aac, ac3
+///   $(pid) - e.g. 0x0100
+/// PID is appended to file name if template gives the same path
+/// for several PIDs
+pub struct EsDump {
+    url: Url,
+    template: String,
+
+    state: RefCell<State>,
+    flushed_at: RefCell<Instant>,
+}
+
+impl EsDump {
+    const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+    pub fn new(url: Url, template: PathBuf) -> EsDump {
+        EsDump {
+            url,
+            template: template.to_string_lossy().to_string(),
+            state: Default::default(),
+            flushed_at: RefCell::new(Instant::now()),
+        }
+    }
+
+    fn path(&self, state: &State, pid: u16, ext: &str) -> PathBuf {
+        let pid_s = format!("0x{:04X}", pid);
+        let path = PathBuf::from(
+            self.template
+                .replace("$(ext)", ext)
+                .replace("$(pid)", &pid_s),
+        );
+
+        if !state.paths.contains(&path) {
+            return path;
+        }
+
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let name = match path.extension() {
+            Some(ext) => format!("{}-{}.{}", stem, pid_s, ext.to_string_lossy()),
+            None => format!("{}-{}", stem, pid_s),
+        };
+        path.with_file_name(name)
+    }
+
+    fn open(&self, state: &mut State, frm: &Frame) -> Option<BufWriter<File>> {
+        let ext = ext(frm.codec)?;
+        let path = self.path(state, frm.pid, ext);
+
+        match File::create(&path) {
+            Ok(file) => {
+                info!(
+                    "({}) [dump] OK (:pid 0x{:04X} :codec {} :path {})",
+                    self.url,
+                    frm.pid,
+                    frm.codec,
+                    path.display()
+                );
+                state.paths.insert(path);
+                Some(BufWriter::new(file))
+            }
+            Err(err) => {
+                warn!(
+                    "({}) [dump] disabled (:pid 0x{:04X} :path {} :error {})",
+                    self.url,
+                    frm.pid,
+                    path.display(),
+                    err
+                );
+                None
+            }
+        }
+    }
+
+    fn flush(&self, state: &mut State) {
+        let mut flushed_at = self.flushed_at.borrow_mut();
+        if flushed_at.elapsed() < Self::FLUSH_INTERVAL {
+            return;
+        }
+
+        for file in state.files.values_mut().flatten() {
+            let _ = file.flush();
+        }
+
+        *flushed_at = Instant::now();
+    }
+}
+
+impl Consumer for EsDump {
+    fn consume_frm(&self, frm: &Frame) {
+        let mut state = self.state.borrow_mut();
+        let state = &mut *state;
+
+        if !state.files.contains_key(&frm.pid) {
+            let file = self.open(state, frm);
+            state.files.insert(frm.pid, file);
+        }
+
+        if let Some(file) = state.files.get_mut(&frm.pid) {
+            if let Some(Err(err)) = file.as_mut().map(|file| file.write_all(&frm.data)) {
+                warn!(
+                    "({}) [dump] disabled (:pid 0x{:04X} :error {})",
+                    self.url, frm.pid, err
+                );
+                *file = None;
+            }
+        }
+
+        self.flush(state);
+    }
+}
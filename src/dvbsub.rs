@@ -0,0 +1,184 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::track::Track;
+
+/// subtitling_descriptor
+const TAG_SUBTITLING: u8 = 0x59;
+
+/// PES_data_field data_identifier and subtitle_stream_id
+const DATA_IDENTIFIER: u8 = 0x20;
+const STREAM_ID: u8 = 0x00;
+
+const SYNC_BYTE: u8 = 0x0F;
+
+pub const SEGMENT_PAGE_COMPOSITION: u8 = 0x10;
+pub const SEGMENT_DISPLAY_DEFINITION: u8 = 0x14;
+
+/// subtitling_descriptor entry
+#[derive(Clone, Debug, PartialEq)]
+pub struct Service {
+    pub lang: String,
+    pub kind: u8,
+    pub composition_page: u16,
+    pub ancillary_page: u16,
+}
+
+impl Service {
+    /// signalled in PMT
+    pub fn from_track(trk: &Track) -> Vec<Service> {
+        let mut services = Vec::new();
+
+        for (_, data) in trk.descriptors().filter(|(tag, _)| *tag == TAG_SUBTITLING) {
+            for entry in data.chunks_exact(8) {
+                services.push(Service {
+                    lang: String::from_utf8_lossy(&entry[..3]).into_owned(),
+                    kind: entry[3],
+                    composition_page: u16::from_be_bytes([entry[4], entry[5]]),
+                    ancillary_page: u16::from_be_bytes([entry[6], entry[7]]),
+                });
+            }
+        }
+
+        services
+    }
+
+    /// EN 300 468 table 2 subtitling_type
+    pub fn kind_name(&self) -> &'static str {
+        match self.kind {
+            0x01 => "ebu-teletext",
+            0x02 => "associated-ebu-teletext",
+            0x03 => "vbi",
+            0x10..=0x14 => "normal",
+            0x15 => "normal-3d",
+            0x20..=0x24 => "hard-of-hearing",
+            0x25 => "hard-of-hearing-3d",
+            0x30 => "sign-language",
+            0x31 => "sign-language-3d",
+            _ => "reserved",
+        }
+    }
+}
+
+/// subtitling_segment
+#[derive(Clone, Copy, Debug)]
+pub struct Segment<'buf> {
+    pub kind: u8,
+    pub page_id: u16,
+    pub data: &'buf [u8],
+}
+
+/// EN 300 743 segments in PES_data_field;
+/// None if not a DVB subtitle stream
+pub fn segments(buf: &[u8]) -> Option<Vec<Segment<'_>>> {
+    if buf.len() < 2 || buf[0] != DATA_IDENTIFIER || buf[1] != STREAM_ID {
+        return None;
+    }
+
+    let mut segments = Vec::new();
+    let mut pos = 2;
+    // end_of_PES_data_field_marker or stuffing ends the loop
+    while pos + 6 <= buf.len() && buf[pos] == SYNC_BYTE {
+        let len = usize::from(u16::from_be_bytes([buf[pos + 4], buf[pos + 5]]));
+        let data = match buf.get(pos + 6..pos + 6 + len) {
+            Some(data) => data,
+            None => break,
+        };
+
+        segments.push(Segment {
+            kind: buf[pos + 1],
+            page_id: u16::from_be_bytes([buf[pos + 2], buf[pos + 3]]),
+            data,
+        });
+        pos += 6 + len;
+    }
+
+    Some(segments)
+}
+
+/// page_state of page composition segment
+pub fn page_state(state: u8) -> &'static str {
+    match state {
+        0 => "normal",
+        1 => "acquisition-point",
+        2 => "mode-change",
+        _ => "reserved",
+    }
+}
+
+/// pages and regions seen in elementary stream
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DvbSub {
+    pub pages: BTreeSet<u16>,
+    /// (page_id, region_id)
+    pub regions: BTreeSet<(u16, u8)>,
+    /// display definition segment; 720x576 if absent
+    pub display: Option<(u16, u16)>,
+}
+
+impl DvbSub {
+    /// true if new page, region or display size found
+    pub fn push(&mut self, buf: &[u8]) -> bool {
+        let segments = match segments(buf) {
+            Some(segments) => segments,
+            None => return false,
+        };
+
+        let mut changed = false;
+        for segment in segments {
+            changed |= self.pages.insert(segment.page_id);
+
+            match segment.kind {
+                // page_time_out, version/state, then 6 bytes per region
+                SEGMENT_PAGE_COMPOSITION if segment.data.len() >= 2 => {
+                    for region in segment.data[2..].chunks_exact(6) {
+                        changed |= self.regions.insert((segment.page_id, region[0]));
+                    }
+                }
+                SEGMENT_DISPLAY_DEFINITION if segment.data.len() >= 5 => {
+                    let d = segment.data;
+                    let display = Some((
+                        u16::from_be_bytes([d[1], d[2]]).saturating_add(1),
+                        u16::from_be_bytes([d[3], d[4]]).saturating_add(1),
+                    ));
+                    changed |= self.display != display;
+                    self.display = display;
+                }
+                _ => {}
+            }
+        }
+
+        changed
+    }
+
+    pub fn json(&self) -> String {
+        let pages: Vec<String> = self.pages.iter().map(|page| page.to_string()).collect();
+
+        format!(
+            r#"{{"pages":[{}],"regions":{},"display":{}}}"#,
+            pages.join(","),
+            self.regions.len(),
+            self.display
+                .map(|(width, height)| format!(r#""{}x{}""#, width, height))
+                .unwrap_or_else(|| "null".to_string())
+        )
+    }
+}
+
+impl fmt::Display for DvbSub {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pages: Vec<String> = self.pages.iter().map(|page| page.to_string()).collect();
+
+        write!(
+            f,
+            ":pages {} :regions {}",
+            pages.join(","),
+            self.regions.len()
+        )?;
+        if let Some((width, height)) = self.display {
+            write!(f, " :display {}x{}", width, height)?;
+        }
+
+        Ok(())
+    }
+}
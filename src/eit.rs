@@ -0,0 +1,1027 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write};
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::{debug, info, trace, warn};
+use url::Url;
+
+use crate::clock::Throttle;
+use crate::filter::Consumer;
+use crate::metrics::Metrics;
+use crate::probe::{json_opt_str, json_str};
+use crate::psi::{self, Section};
+
+/// short_event_descriptor
+const TAG_SHORT_EVENT: u8 = 0x4D;
+/// extended_event_descriptor
+const TAG_EXTENDED_EVENT: u8 = 0x4E;
+/// content_descriptor
+const TAG_CONTENT: u8 = 0x54;
+/// parental_rating_descriptor
+const TAG_PARENTAL_RATING: u8 = 0x55;
+
+/// MJD of 1970-01-01
+const MJD_UNIX_EPOCH: i64 = 40_587;
+
+/// present/following may be off the current time by
+const PF_TOLERANCE: i64 = 60;
+
+/// schedule events ended earlier than this are dropped
+const SCHEDULE_KEEP: i64 = 3600;
+
+/// content_nibble_level_1 (EN 300 468 table 28)
+#[rustfmt::skip]
+const GENRES: [Option<&str>; 16] = [
+    None,
+    Some("movie/drama"),
+    Some("news/current affairs"),
+    Some("show/game show"),
+    Some("sports"),
+    Some("children's/youth"),
+    Some("music/ballet/dance"),
+    Some("arts/culture"),
+    Some("social/political/economics"),
+    Some("education/science/factual"),
+    Some("leisure hobbies"),
+    Some("special characteristics"),
+    Some("adult"),
+    None, None, None,
+];
+
+fn bcd(b: u8) -> Option<u32> {
+    let (hi, lo) = (u32::from(b >> 4), u32::from(b & 0x0F));
+    if hi > 9 || lo > 9 {
+        None
+    } else {
+        Some(hi * 10 + lo)
+    }
+}
+
+/// 6 BCD digits hhmmss in seconds
+fn bcd_hms(buf: &[u8]) -> Option<u32> {
+    Some(bcd(buf[0])? * 3600 + bcd(buf[1])? * 60 + bcd(buf[2])?)
+}
+
+/// UTC_time (16 bit MJD, 24 bit BCD) as unix time;
+/// None if undefined (all bits set) or malformed
+pub fn utc(buf: &[u8]) -> Option<i64> {
+    let buf = buf.get(..5)?;
+    if buf.iter().all(|b| *b == 0xFF) {
+        return None;
+    }
+
+    let mjd = i64::from(u16::from_be_bytes([buf[0], buf[1]]));
+    Some((mjd - MJD_UNIX_EPOCH) * 86_400 + i64::from(bcd_hms(&buf[2..5])?))
+}
+
+/// unix time by system clock
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// (year, month, day, hour, minute, second) of unix time
+fn civil(t: i64) -> (i64, u32, u32, u32, u32, u32) {
+    let (days, secs) = (t.div_euclid(86_400), t.rem_euclid(86_400) as u32);
+
+    // days to civil date; era of 400 years starting at 0000-03-01
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day, secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// 2026-10-18T20:15:00Z
+pub fn iso8601(t: i64) -> String {
+    let (y, mo, d, h, mi, s) = civil(t);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", y, mo, d, h, mi, s)
+}
+
+/// 20261018201500 +0000
+fn xmltv_time(t: i64) -> String {
+    let (y, mo, d, h, mi, s) = civil(t);
+    format!("{:04}{:02}{:02}{:02}{:02}{:02} +0000", y, mo, d, h, mi, s)
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            // not allowed in XML 1.0
+            c if (c as u32) < 0x20 && c != '\n' && c != '\t' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// ISO 639 language code
+fn lang(buf: &[u8]) -> Option<String> {
+    std::str::from_utf8(buf)
+        .ok()
+        .filter(|s| s.bytes().all(|b| b.is_ascii_alphabetic()))
+        .map(|s| s.to_ascii_lowercase())
+}
+
+/// short_event_descriptor: (event_name, text)
+fn short_event(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let name_end = 4 + usize::from(*data.get(3)?);
+    let text_len = usize::from(*data.get(name_end)?);
+    Some((
+        &data[4..name_end],
+        data.get(name_end + 1..name_end + 1 + text_len)?,
+    ))
+}
+
+/// output format of EPG file
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Json,
+    Xmltv,
+}
+
+impl FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Format::Json),
+            "xmltv" | "xml" => Ok(Format::Xmltv),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Format::Json => write!(f, "json"),
+            Format::Xmltv => write!(f, "xmltv"),
+        }
+    }
+}
+
+/// EIT event with short/extended event, content and parental rating
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Event {
+    pub event_id: u16,
+    /// unix time; None if undefined
+    pub start: Option<i64>,
+    /// seconds; None if undefined
+    pub duration: Option<u32>,
+    pub running_status: u8,
+    pub free_ca: bool,
+
+    pub lang: Option<String>,
+    pub title: String,
+    pub text: String,
+    /// extended_event_descriptor items ("description: item") and text
+    pub extended: String,
+    /// content_nibble_level_1 << 4 | content_nibble_level_2
+    pub content: Option<u8>,
+    /// minimum age
+    pub rating: Option<u8>,
+}
+
+impl Event {
+    /// event and its size in event loop
+    fn parse(buf: &[u8]) -> Option<(Event, usize)> {
+        if buf.len() < 12 {
+            return None;
+        }
+
+        // descriptors_loop_length overrunning the section ends the loop
+        let loop_length = (usize::from(buf[10] & 0x0F) << 8) | usize::from(buf[11]);
+        let end = std::cmp::min(12 + loop_length, buf.len());
+
+        let mut event = Event {
+            event_id: u16::from_be_bytes([buf[0], buf[1]]),
+            start: utc(&buf[2..7]),
+            duration: Some(&buf[7..10])
+                .filter(|d| d.iter().any(|b| *b != 0xFF))
+                .and_then(bcd_hms),
+            running_status: buf[10] >> 5,
+            free_ca: buf[10] & 0x10 != 0,
+            ..Default::default()
+        };
+
+        let mut short = false;
+        let mut extended = Vec::new();
+        for (tag, data) in psi::descriptors(&buf[12..end]) {
+            match tag {
+                // first language only
+                TAG_SHORT_EVENT if !short => {
+                    if let Some((name, text)) = short_event(data) {
+                        event.lang = lang(&data[0..3]);
+                        event.title = psi::dvb_str(name);
+                        event.text = psi::dvb_str(text);
+                        short = true;
+                    }
+                }
+                TAG_EXTENDED_EVENT if data.len() >= 6 => {
+                    extended.push((data[0] >> 4, data));
+                }
+                TAG_CONTENT if event.content.is_none() && data.len() >= 2 => {
+                    event.content = Some(data[0]);
+                }
+                // first country; 0x01..0x0F is minimum age - 3
+                TAG_PARENTAL_RATING if event.rating.is_none() && data.len() >= 4 => {
+                    event.rating = Some(data[3])
+                        .filter(|r| (0x01..=0x0F).contains(r))
+                        .map(|r| r + 3);
+                }
+                _ => {}
+            }
+        }
+
+        // text may be split across descriptors in descriptor_number order
+        extended.sort_by_key(|(number, _)| *number);
+        let mut items = Vec::new();
+        let mut text = Vec::new();
+        for (_, data) in extended {
+            let items_end = std::cmp::min(5 + usize::from(data[4]), data.len());
+            let mut pos = 5;
+            while pos < items_end {
+                let desc_end = pos + 1 + usize::from(data[pos]);
+                let item_len = match data.get(desc_end) {
+                    Some(len) if desc_end + 1 + usize::from(*len) <= items_end => usize::from(*len),
+                    _ => break,
+                };
+                items.push(format!(
+                    "{}: {}",
+                    psi::dvb_str(&data[pos + 1..desc_end]),
+                    psi::dvb_str(&data[desc_end + 1..desc_end + 1 + item_len])
+                ));
+                pos = desc_end + 1 + item_len;
+            }
+
+            if let Some(len) = data.get(items_end) {
+                let text_end = std::cmp::min(items_end + 1 + usize::from(*len), data.len());
+                // character table is only signalled at the start of the text
+                text.extend_from_slice(&data[items_end + 1..text_end]);
+            }
+        }
+        if !text.is_empty() {
+            items.push(psi::dvb_str(&text));
+        }
+        event.extended = items.join("\n");
+
+        Some((event, end))
+    }
+
+    pub fn end(&self) -> Option<i64> {
+        Some(self.start? + i64::from(self.duration?))
+    }
+
+    pub fn genre(&self) -> Option<&'static str> {
+        GENRES[usize::from(self.content? >> 4)]
+    }
+
+    pub fn running_status_name(&self) -> &'static str {
+        match self.running_status {
+            1 => "not-running",
+            2 => "starts-soon",
+            3 => "pausing",
+            4 => "running",
+            5 => "off-air",
+            _ => "undefined",
+        }
+    }
+
+    pub fn json(&self) -> String {
+        let opt = |v: Option<String>| v.unwrap_or_else(|| "null".to_string());
+
+        format!(
+            r#"{{"event_id":{},"start":{},"end":{},"duration":{},"running_status":"{}","free_ca":{},"lang":{},"title":{},"text":{},"extended":{},"genre":{},"rating":{}}}"#,
+            self.event_id,
+            opt(self.start.map(|t| json_str(&iso8601(t)))),
+            opt(self.end().map(|t| json_str(&iso8601(t)))),
+            opt(self.duration.map(|d| d.to_string())),
+            self.running_status_name(),
+            self.free_ca,
+            json_opt_str(self.lang.as_deref()),
+            json_str(&self.title),
+            json_str(&self.text),
+            json_str(&self.extended),
+            json_opt_str(self.genre()),
+            opt(self.rating.map(|r| r.to_string()))
+        )
+    }
+
+    fn xmltv(&self, channel: &str, out: &mut String) {
+        let (start, end) = match (self.start, self.end()) {
+            (Some(start), Some(end)) => (start, end),
+            // programme without start time can't be placed
+            _ => return,
+        };
+
+        let lang = self
+            .lang
+            .as_deref()
+            .map(|lang| format!(r#" lang="{}""#, xml_escape(lang)))
+            .unwrap_or_default();
+
+        let _ = writeln!(
+            out,
+            r#"  <programme start="{}" stop="{}" channel="{}">"#,
+            xmltv_time(start),
+            xmltv_time(end),
+            xml_escape(channel)
+        );
+        let _ = writeln!(
+            out,
+            "    <title{}>{}</title>",
+            lang,
+            xml_escape(&self.title)
+        );
+        if !self.text.is_empty() {
+            let _ = writeln!(
+                out,
+                "    <sub-title{}>{}</sub-title>",
+                lang,
+                xml_escape(&self.text)
+            );
+        }
+        if !self.extended.is_empty() {
+            let _ = writeln!(
+                out,
+                "    <desc{}>{}</desc>",
+                lang,
+                xml_escape(&self.extended)
+            );
+        }
+        if let Some(genre) = self.genre() {
+            let _ = writeln!(
+                out,
+                r#"    <category lang="en">{}</category>"#,
+                xml_escape(genre)
+            );
+        }
+        if let Some(rating) = self.rating {
+            let _ = writeln!(
+                out,
+                "    <rating system=\"dvb\">\n      <value>{}</value>\n    </rating>",
+                rating
+            );
+        }
+        let _ = writeln!(out, "  </programme>");
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            r#"(:event-id {} :start {} :duration {} :running-status {} :title "{}")"#,
+            self.event_id,
+            self.start.map(iso8601).unwrap_or_else(|| "~".to_string()),
+            self.duration
+                .map(|d| format!("{}s", d))
+                .unwrap_or_else(|| "~".to_string()),
+            self.running_status_name(),
+            self.title
+        )
+    }
+}
+
+/// event_information_section
+#[derive(Clone, Debug)]
+pub struct Eit {
+    pub table_id: u8,
+    pub service_id: u16,
+    pub transport_stream_id: u16,
+    pub original_network_id: u16,
+    pub section_number: u8,
+    pub events: Vec<Event>,
+}
+
+impl Eit {
+    pub fn parse(section: &Section) -> Option<Eit> {
+        let body = section.body();
+        if body.len() < 6 {
+            return None;
+        }
+
+        let mut eit = Eit {
+            table_id: section.table_id(),
+            service_id: section.table_id_extension(),
+            transport_stream_id: u16::from_be_bytes([body[0], body[1]]),
+            original_network_id: u16::from_be_bytes([body[2], body[3]]),
+            section_number: section.section_number(),
+            events: Vec::new(),
+        };
+
+        // segment_last_section_number, last_table_id
+        let mut pos = 6;
+        while let Some((event, sz)) = body.get(pos..).and_then(Event::parse) {
+            eit.events.push(event);
+            pos += sz;
+        }
+
+        Some(eit)
+    }
+
+    /// present/following (not schedule)
+    pub fn is_pf(&self) -> bool {
+        self.table_id == psi::TABLE_ID_EIT_PF || self.table_id == psi::TABLE_ID_EIT_PF_OTHER
+    }
+
+    /// actual transport stream (not other)
+    pub fn is_actual(&self) -> bool {
+        self.table_id == psi::TABLE_ID_EIT_PF
+            || (psi::TABLE_ID_EIT_SCHED..=psi::TABLE_ID_EIT_SCHED_LAST).contains(&self.table_id)
+    }
+
+    pub fn key(&self) -> ServiceKey {
+        (
+            self.original_network_id,
+            self.transport_stream_id,
+            self.service_id,
+        )
+    }
+}
+
+/// (original_network_id, transport_stream_id, service_id)
+pub type ServiceKey = (u16, u16, u16);
+
+/// events of one service
+#[derive(Default)]
+pub struct Service {
+    pub actual: bool,
+    pub name: Option<String>,
+
+    pub present: Option<Event>,
+    pub following: Option<Event>,
+    /// event_id -> event
+    pub schedule: BTreeMap<u16, Event>,
+
+    /// present/following issues last reported
+    issues: Vec<&'static str>,
+}
+
+impl Service {
+    /// p/f and schedule merged; p/f is more recent for same event
+    fn events(&self) -> Vec<&Event> {
+        let mut events: BTreeMap<u16, &Event> = self
+            .schedule
+            .iter()
+            .map(|(id, event)| (*id, event))
+            .collect();
+        for event in self.present.iter().chain(self.following.iter()) {
+            events.insert(event.event_id, event);
+        }
+
+        let mut events: Vec<&Event> = events.into_values().collect();
+        events.sort_by_key(|event| (event.start, event.event_id));
+        events
+    }
+
+    /// present/following against time now
+    fn check(&self, now: i64) -> Vec<&'static str> {
+        let mut issues = Vec::new();
+
+        if let Some(present) = self.present.as_ref() {
+            if present
+                .start
+                .is_some_and(|start| start > now + PF_TOLERANCE)
+            {
+                issues.push("present-not-started");
+            }
+            if present.end().is_some_and(|end| end + PF_TOLERANCE < now) {
+                issues.push("present-ended");
+            }
+            if present.running_status == 1 {
+                issues.push("present-not-running");
+            }
+        }
+
+        if let Some(following) = self.following.as_ref() {
+            if following
+                .start
+                .is_some_and(|start| start + PF_TOLERANCE < now)
+            {
+                issues.push("following-started");
+            }
+            let present_end = self.present.as_ref().and_then(Event::end);
+            if let (Some(start), Some(end)) = (following.start, present_end) {
+                if start + PF_TOLERANCE < end {
+                    issues.push("following-overlaps-present");
+                }
+            }
+        }
+
+        issues
+    }
+
+    fn json(&self, key: ServiceKey) -> String {
+        let opt =
+            |event: Option<&Event>| event.map(Event::json).unwrap_or_else(|| "null".to_string());
+
+        format!(
+            r#"{{"original_network_id":{},"transport_stream_id":{},"service_id":{},"actual":{},"name":{},"present":{},"following":{},"schedule":[{}]}}"#,
+            key.0,
+            key.1,
+            key.2,
+            self.actual,
+            json_opt_str(self.name.as_deref()),
+            opt(self.present.as_ref()),
+            opt(self.following.as_ref()),
+            self.schedule
+                .values()
+                .map(Event::json)
+                .collect::<Vec<_>>()
+                .join(",")
+        )
+    }
+}
+
+/// electronic program guide collected from EIT p/f and schedule
+/// of actual and other transport streams
+#[derive(Default)]
+pub struct Guide {
+    pub services: BTreeMap<ServiceKey, Service>,
+
+    /// UTC by TDT/TOT and when it was received
+    time: Option<(i64, Instant)>,
+
+    /// changed since last take_changed
+    changed: bool,
+}
+
+impl Guide {
+    /// true if any event changed
+    pub fn push(&mut self, eit: Eit) -> bool {
+        let key = eit.key();
+        let service = self.services.entry(key).or_default();
+        service.actual = eit.is_actual();
+
+        let mut changed = false;
+        if eit.is_pf() {
+            // section 0: present, section 1: following; empty section clears
+            let slot = match eit.section_number {
+                0 => &mut service.present,
+                1 => &mut service.following,
+                _ => return false,
+            };
+            let event = eit.events.into_iter().next();
+            if *slot != event {
+                *slot = event;
+                changed = true;
+            }
+        } else {
+            for event in eit.events.into_iter() {
+                if service.schedule.get(&event.event_id) != Some(&event) {
+                    service.schedule.insert(event.event_id, event);
+                    changed = true;
+                }
+            }
+        }
+
+        self.changed |= changed;
+        changed
+    }
+
+    /// service name (e.g. from SDT)
+    pub fn name(&mut self, key: ServiceKey, name: &str) {
+        let service = self.services.entry(key).or_default();
+        if service.name.as_deref() != Some(name) {
+            service.name = Some(name.to_string());
+            self.changed = true;
+        }
+    }
+
+    /// UTC of TDT/TOT
+    pub fn time(&mut self, utc: i64) {
+        self.time = Some((utc, Instant::now()));
+    }
+
+    /// current time: last TDT/TOT advanced by wall clock, else system clock
+    pub fn now(&self) -> i64 {
+        match self.time {
+            Some((utc, at)) => utc + at.elapsed().as_secs() as i64,
+            None => now(),
+        }
+    }
+
+    pub fn events(&self) -> usize {
+        self.services
+            .values()
+            .map(|service| service.events().len())
+            .sum()
+    }
+
+    /// present/following issues changed since last call: (service, issues);
+    /// ended schedule events are dropped
+    pub fn check(&mut self) -> Vec<(ServiceKey, Vec<&'static str>)> {
+        let now = self.now();
+        let mut changed = Vec::new();
+
+        for (key, service) in self.services.iter_mut() {
+            let len = service.schedule.len();
+            service
+                .schedule
+                .retain(|_, event| event.end().is_none_or(|end| end + SCHEDULE_KEEP >= now));
+            self.changed |= service.schedule.len() != len;
+
+            let issues = service.check(now);
+            if issues != service.issues {
+                service.issues = issues.clone();
+                changed.push((*key, issues));
+            }
+        }
+
+        changed
+    }
+
+    pub fn issues(&self) -> usize {
+        self.services
+            .values()
+            .map(|service| service.issues.len())
+            .sum()
+    }
+
+    /// true once after any change
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Json => self.json(),
+            Format::Xmltv => self.xmltv(),
+        }
+    }
+
+    pub fn json(&self) -> String {
+        format!(
+            r#"{{"time":{},"services":[{}]}}"#,
+            json_str(&iso8601(self.now())),
+            self.services
+                .iter()
+                .map(|(key, service)| service.json(*key))
+                .collect::<Vec<_>>()
+                .join(",")
+        )
+    }
+
+    /// channel id is "onid.tsid.sid"
+    pub fn xmltv(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<!DOCTYPE tv SYSTEM \"xmltv.dtd\">\n");
+        out.push_str("<tv generator-info-name=\"va-tool\">\n");
+
+        let channel = |key: &ServiceKey| format!("{}.{}.{}", key.0, key.1, key.2);
+
+        for (key, service) in self.services.iter() {
+            let _ = writeln!(out, r#"  <channel id="{}">"#, channel(key));
+            let name = service.name.clone().unwrap_or_else(|| key.2.to_string());
+            let _ = writeln!(
+                out,
+                "    <display-name>{}</display-name>",
+                xml_escape(&name)
+            );
+            let _ = writeln!(out, "  </channel>");
+        }
+        for (key, service) in self.services.iter() {
+            let channel = channel(key);
+            for event in service.events() {
+                event.xmltv(&channel, &mut out);
+            }
+        }
+
+        out.push_str("</tv>\n");
+        out
+    }
+}
+
+#[derive(Default)]
+struct State {
+    guide: Guide,
+
+    /// (pid, table_id, table_id_extension, section_number) -> CRC_32
+    /// of last processed section; repetitions are not parsed again
+    seen: HashMap<(u16, u8, u16, u8), u32>,
+
+    /// result of last EPG file write
+    ok: Option<bool>,
+}
+
+/// EPG from EIT p/f and schedule sections, named by SDT and timed by
+/// TDT/TOT; present/following is checked against current time.
+///
+/// EPG is written to file when changed, at most once per EPG_INTERVAL
+pub struct Epg {
+    url: Url,
+    out: Option<PathBuf>,
+    format: Format,
+
+    state: RefCell<State>,
+
+    metrics: Metrics,
+    metrics_at: Throttle,
+    written_at: Cell<Instant>,
+}
+
+impl Epg {
+    /// how often to rewrite changed EPG file
+    const EPG_INTERVAL: Duration = Duration::from_secs(10);
+
+    pub fn new(url: Url) -> Epg {
+        Epg {
+            url,
+            out: None,
+            format: Format::Json,
+            state: Default::default(),
+            metrics: Default::default(),
+            metrics_at: Default::default(),
+            written_at: Cell::new(Instant::now()),
+        }
+    }
+
+    /// EIT p/f and schedule are written to file when changed
+    pub fn out(&mut self, out: Option<PathBuf>, format: Format) -> &Epg {
+        self.out = out;
+        self.format = format;
+        self
+    }
+
+    pub fn metrics(&mut self, metrics: Metrics) -> &Epg {
+        self.metrics = metrics;
+        self
+    }
+
+    fn section(&self, state: &mut State, pid: u16, section: &Section) {
+        if !section.crc32_ok() || !section.current_next_indicator() {
+            return;
+        }
+
+        let key = (
+            pid,
+            section.table_id(),
+            section.table_id_extension(),
+            section.section_number(),
+        );
+        let crc = section.crc32().unwrap_or(0);
+        if section.syntax() && state.seen.insert(key, crc) == Some(crc) {
+            return;
+        }
+
+        match (pid, section.table_id()) {
+            (psi::PID_SDT, psi::TABLE_ID_SDT) => self.sdt(state, section),
+            (psi::PID_EIT, psi::TABLE_ID_EIT_PF..=psi::TABLE_ID_EIT_SCHED_OTHER_LAST) => {
+                self.eit(state, section)
+            }
+            (psi::PID_TDT, psi::TABLE_ID_TDT) | (psi::PID_TDT, psi::TABLE_ID_TOT) => {
+                self.tdt(state, section)
+            }
+            _ => {}
+        }
+    }
+
+    /// service names
+    fn sdt(&self, state: &mut State, section: &Section) {
+        if let Some((original_network_id, services)) = psi::sdt_services(section.body()) {
+            let transport_stream_id = section.table_id_extension();
+            for service in services.iter() {
+                state.guide.name(
+                    (original_network_id, transport_stream_id, service.service_id),
+                    &service.name,
+                );
+            }
+        }
+    }
+
+    /// present/following and schedule events
+    fn eit(&self, state: &mut State, section: &Section) {
+        let eit = match Eit::parse(section) {
+            Some(eit) => eit,
+            None => return,
+        };
+
+        for event in eit.events.iter() {
+            debug!(
+                "({}) [epg] EIT (:table-id 0x{:02X} :service-id {} :section {} {})",
+                self.url, eit.table_id, eit.service_id, eit.section_number, event
+            );
+        }
+
+        state.guide.push(eit);
+    }
+
+    /// stream time for present/following check
+    fn tdt(&self, state: &mut State, section: &Section) {
+        if let Some(utc) = utc(section.body()) {
+            trace!(
+                "({}) [epg] {} (:utc {})",
+                self.url,
+                if section.table_id() == psi::TABLE_ID_TDT {
+                    "TDT"
+                } else {
+                    "TOT"
+                },
+                iso8601(utc)
+            );
+            state.guide.time(utc);
+        }
+    }
+
+    /// present/following must match current time
+    fn check(&self, state: &mut State) {
+        for ((onid, tsid, sid), issues) in state.guide.check() {
+            let service = state.guide.services.get(&(onid, tsid, sid));
+            let present = service
+                .and_then(|service| service.present.as_ref())
+                .map(|event| event.to_string())
+                .unwrap_or_else(|| "~".to_string());
+
+            if issues.is_empty() {
+                info!(
+                    "({}) [epg] present/following OK (:onid {} :tsid {} :service-id {})",
+                    self.url, onid, tsid, sid
+                );
+            } else {
+                warn!(
+                    "({}) [epg] present/following mismatch (:onid {} :tsid {} :service-id {} :issues {} :now {} :present {})",
+                    self.url,
+                    onid,
+                    tsid,
+                    sid,
+                    issues.join(","),
+                    iso8601(state.guide.now()),
+                    present
+                );
+            }
+        }
+    }
+
+    /// replace file as a whole; readers never see partial EPG
+    fn write(&self, state: &mut State) {
+        let path = match self.out.as_ref() {
+            Some(path) => path,
+            None => return,
+        };
+
+        if self.written_at.get().elapsed() < Self::EPG_INTERVAL || !state.guide.take_changed() {
+            return;
+        }
+        self.written_at.set(Instant::now());
+
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        let result =
+            fs::write(&tmp, state.guide.render(self.format)).and_then(|_| fs::rename(&tmp, path));
+
+        let ok = result.is_ok();
+        if state.ok != Some(ok) {
+            match result {
+                Ok(_) => info!(
+                    "({}) [epg] export OK (:path {} :format {})",
+                    self.url,
+                    path.display(),
+                    self.format
+                ),
+                Err(err) => warn!(
+                    "({}) [epg] export failed (:path {} :error {})",
+                    self.url,
+                    path.display(),
+                    err
+                ),
+            }
+        }
+        state.ok = Some(ok);
+    }
+
+    fn metrics_publish(&self) {
+        if !self.metrics_at.ready() {
+            return;
+        }
+
+        let mut state = self.state.borrow_mut();
+        self.check(&mut state);
+        self.write(&mut state);
+
+        self.metrics.set("epg-services", state.guide.services.len());
+        self.metrics.set("epg-events", state.guide.events());
+        self.metrics.set("epg-pf-issues", state.guide.issues());
+    }
+}
+
+impl Consumer for Epg {
+    fn consume_section(&self, pid: u16, raw: &[u8]) {
+        if pid == psi::PID_SDT || pid == psi::PID_EIT || pid == psi::PID_TDT {
+            if let Some(section) = Section::try_new(raw) {
+                self.section(&mut self.state.borrow_mut(), pid, &section);
+            }
+        }
+
+        // sections of any PID keep checks going
+        self.metrics_publish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// EIT p/f actual section of service 1 with CRC placeholder
+    fn section(events: &[u8]) -> Vec<u8> {
+        let len = 5 + 6 + events.len() + 4;
+        let mut buf = vec![
+            psi::TABLE_ID_EIT_PF,
+            0xF0 | (len >> 8) as u8,
+            len as u8,
+            0x00,
+            0x01,
+            0xC1,
+            0x00,
+            0x01,
+        ];
+        // transport_stream_id, original_network_id, segment_last_section_number, last_table_id
+        buf.extend_from_slice(&[0x00, 0x02, 0x00, 0x03, 0x01, psi::TABLE_ID_EIT_PF]);
+        buf.extend_from_slice(events);
+        buf.extend_from_slice(&[0; 4]);
+        buf
+    }
+
+    /// event 0x0102 on 2020-01-01 12:00:00 for 00:30:00 with descriptors
+    fn event(loop_length: u16, descriptors: &[u8]) -> Vec<u8> {
+        let mut buf = vec![0x01, 0x02, 0xE5, 0xE1, 0x12, 0x00, 0x00, 0x00, 0x30, 0x00];
+        buf.push(0x80 | (loop_length >> 8) as u8);
+        buf.push(loop_length as u8);
+        buf.extend_from_slice(descriptors);
+        buf
+    }
+
+    #[test]
+    fn valid() {
+        let short = [
+            TAG_SHORT_EVENT,
+            9,
+            b'e',
+            b'n',
+            b'g',
+            3,
+            b'N',
+            b'e',
+            b'w',
+            1,
+            b'!',
+        ];
+        let buf = section(&event(short.len() as u16, &short));
+        let eit = Eit::parse(&Section::try_new(&buf).unwrap()).unwrap();
+
+        assert_eq!(eit.key(), (3, 2, 1));
+        assert_eq!(eit.events.len(), 1);
+        let event = &eit.events[0];
+        assert_eq!(event.event_id, 0x0102);
+        assert_eq!(event.start, Some(1_577_880_000));
+        assert_eq!(event.duration, Some(1800));
+        assert_eq!(event.title, "New");
+        assert_eq!(event.text, "!");
+    }
+
+    #[test]
+    fn truncated() {
+        let buf = section(&event(0, &[])[..8]);
+        let eit = Eit::parse(&Section::try_new(&buf).unwrap()).unwrap();
+        assert!(eit.events.is_empty());
+
+        // short_event_descriptor cut in event name
+        let short = [TAG_SHORT_EVENT, 5, b'e', b'n', b'g', 9, b'N'];
+        let buf = section(&event(short.len() as u16, &short));
+        let eit = Eit::parse(&Section::try_new(&buf).unwrap()).unwrap();
+        assert_eq!(eit.events.len(), 1);
+        assert!(eit.events[0].title.is_empty());
+    }
+
+    #[test]
+    fn loop_length_overrun() {
+        let mut events = event(0xFFF, &[TAG_CONTENT, 2, 0x10, 0x00]);
+        events.extend_from_slice(&event(0, &[]));
+        let buf = section(&events);
+        let eit = Eit::parse(&Section::try_new(&buf).unwrap()).unwrap();
+
+        // first event takes the rest of the section
+        assert_eq!(eit.events.len(), 1);
+        assert_eq!(eit.events[0].content, Some(0x10));
+    }
+}
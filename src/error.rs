@@ -8,6 +8,7 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub enum ErrorKind {
     Logger,
     Config,
+    ConfigValue(String, String),
     URLParse(String),
     Signal,
 
@@ -18,13 +19,30 @@ pub enum ErrorKind {
 
     UdpUrlMissingHost,
     UdpSocketBind(String, u16),
+    UdpSocketOpt(String),
     UdpJoinMulticastV4(String, u16, String),
     UdpJoinMulticastV6(String, u16, u32),
     UdpDomainToIpV4(String),
     UdpFifoNotInitialized,
     UdpFifoLock(String),
     UdpFifoCvarWait(String),
-    UdpFifoPopEmpty,
+
+    MetricsLock(String),
+    MetricsSpawn,
+
+    RtpBuf(usize, usize),
+    RtpVersion(u8),
+    RtpFecPort(u16, u16),
+
+    FileOpen(String),
+    FileRead(String),
+    FileNotInitialized,
+
+    GenParam(String, String),
+    GenNotInitialized,
+
+    MediacontainerFormat(String),
+    MediacontainerUnknown(String),
 }
 
 #[derive(Debug)]
@@ -37,6 +55,13 @@ impl Error {
         Error::from(err.context(ErrorKind::Config))
     }
 
+    pub(crate) fn config_value<S: AsRef<str>>(key: S, value: S) -> Error {
+        Error::from(ErrorKind::ConfigValue(
+            key.as_ref().to_string(),
+            value.as_ref().to_string(),
+        ))
+    }
+
     pub(crate) fn url_parse<E: Fail, S: AsRef<str>>(err: E, url_raw: S) -> Error {
         Error::from(err.context(ErrorKind::URLParse(url_raw.as_ref().to_string())))
     }
@@ -73,6 +98,10 @@ impl Error {
         Error::from(err.context(ErrorKind::UdpSocketBind(host.as_ref().to_string(), port)))
     }
 
+    pub(crate) fn udp_socket_opt<E: Fail, S: AsRef<str>>(err: E, opt: S) -> Error {
+        Error::from(err.context(ErrorKind::UdpSocketOpt(opt.as_ref().to_string())))
+    }
+
     pub(crate) fn udp_join_multicast_v4<E: Fail, S: AsRef<str>>(
         err: E,
         host: S,
@@ -115,8 +144,55 @@ impl Error {
         Error::from(ErrorKind::UdpFifoCvarWait(reason.as_ref().to_string()))
     }
 
-    pub(crate) fn udp_fifo_pop_empty() -> Error {
-        Error::from(ErrorKind::UdpFifoPopEmpty)
+    pub(crate) fn metrics_lock<S: AsRef<str>>(reason: S) -> Error {
+        Error::from(ErrorKind::MetricsLock(reason.as_ref().to_string()))
+    }
+
+    pub(crate) fn metrics_spawn<E: Fail>(err: E) -> Error {
+        Error::from(err.context(ErrorKind::MetricsSpawn))
+    }
+
+    pub(crate) fn rtp_buf(actual: usize, expected: usize) -> Error {
+        Error::from(ErrorKind::RtpBuf(actual, expected))
+    }
+
+    pub(crate) fn rtp_version(version: u8) -> Error {
+        Error::from(ErrorKind::RtpVersion(version))
+    }
+
+    pub(crate) fn rtp_fec_port(port: u16, offset: u16) -> Error {
+        Error::from(ErrorKind::RtpFecPort(port, offset))
+    }
+
+    pub(crate) fn file_open<E: Fail, S: AsRef<str>>(err: E, path: S) -> Error {
+        Error::from(err.context(ErrorKind::FileOpen(path.as_ref().to_string())))
+    }
+
+    pub(crate) fn file_read<E: Fail, S: AsRef<str>>(err: E, path: S) -> Error {
+        Error::from(err.context(ErrorKind::FileRead(path.as_ref().to_string())))
+    }
+
+    pub(crate) fn file_not_initialized() -> Error {
+        Error::from(ErrorKind::FileNotInitialized)
+    }
+
+    pub(crate) fn gen_param<S: AsRef<str>>(key: S, value: S) -> Error {
+        Error::from(ErrorKind::GenParam(
+            key.as_ref().to_string(),
+            value.as_ref().to_string(),
+        ))
+    }
+
+    pub(crate) fn gen_not_initialized() -> Error {
+        Error::from(ErrorKind::GenNotInitialized)
+    }
+
+    pub(crate) fn mediacontainer_format<S: AsRef<str>>(format: S) -> Error {
+        Error::from(ErrorKind::MediacontainerFormat(format.as_ref().to_string()))
+    }
+
+    pub(crate) fn mediacontainer_unknown<S: AsRef<str>>(url: S) -> Error {
+        Error::from(ErrorKind::MediacontainerUnknown(url.as_ref().to_string()))
     }
 }
 
@@ -142,9 +218,14 @@ impl fmt::Display for Error {
 
 impl fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match &*self {
+        match self {
             ErrorKind::Logger => write!(f, "logger error"),
             ErrorKind::Config => write!(f, "config parse error"),
+            ErrorKind::ConfigValue(key, value) => write!(
+                f,
+                "config - invalid option value (:key {} :value {})",
+                key, value
+            ),
             ErrorKind::URLParse(url_raw) => write!(f, "url-parse error (:url-raw {})", url_raw),
             ErrorKind::Signal => write!(f, "subscription to signals failed"),
 
@@ -161,6 +242,9 @@ impl fmt::Display for ErrorKind {
             ErrorKind::UdpSocketBind(h, p) => {
                 write!(f, "source-udp - bind error (:host {} :port {})", h, p)
             }
+            ErrorKind::UdpSocketOpt(opt) => {
+                write!(f, "source-udp - set socket option error (:option {})", opt)
+            }
             ErrorKind::UdpJoinMulticastV4(h, p, g) => write!(
                 f,
                 "source-udp - join multicast v4 error (:host {} :port {} :group {})",
@@ -185,7 +269,50 @@ impl fmt::Display for ErrorKind {
             ErrorKind::UdpFifoCvarWait(reason) => {
                 write!(f, "source-udp - condvar wait error (:reason {})", reason)
             }
-            ErrorKind::UdpFifoPopEmpty => write!(f, "source-udp - no data after fifo pop"),
+
+            ErrorKind::MetricsLock(reason) => {
+                write!(f, "metrics lock error (:reason {})", reason)
+            }
+            ErrorKind::MetricsSpawn => write!(f, "metrics-spawn thread error"),
+
+            ErrorKind::RtpBuf(actual, expected) => write!(
+                f,
+                "source-rtp - buffer is too small (:actual {} :expected {})",
+                actual, expected
+            ),
+            ErrorKind::RtpVersion(version) => {
+                write!(f, "source-rtp - unsupported version (:version {})", version)
+            }
+            ErrorKind::RtpFecPort(port, offset) => write!(
+                f,
+                "source-rtp - FEC port is out of range (:port {} :offset {})",
+                port, offset
+            ),
+
+            ErrorKind::FileOpen(path) => write!(f, "source-file - open error (:path {})", path),
+            ErrorKind::FileRead(path) => write!(f, "source-file - read error (:path {})", path),
+            ErrorKind::FileNotInitialized => {
+                write!(f, "source-file - file is not opened. call open first")
+            }
+
+            ErrorKind::GenParam(key, value) => write!(
+                f,
+                "source-gen - invalid parameter (:key {} :value {})",
+                key, value
+            ),
+            ErrorKind::GenNotInitialized => write!(
+                f,
+                "source-gen - generator is not initialized. call open first"
+            ),
+
+            ErrorKind::MediacontainerFormat(format) => write!(
+                f,
+                "mediacontainer - unsupported format override (:format {})",
+                format
+            ),
+            ErrorKind::MediacontainerUnknown(url) => {
+                write!(f, "mediacontainer - unable to detect (:url {})", url)
+            }
         }
     }
 }
@@ -0,0 +1,370 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use log::{debug, info, warn};
+use url::Url;
+
+use crate::audio::{Aac, Ac3, Mpa};
+use crate::clock::Throttle;
+use crate::compression_standard::{Audio, Subtitle, Video};
+use crate::dvbsub::DvbSub;
+use crate::filter::{Consumer, Consumers, Producer};
+use crate::frame::{Frame, Picture};
+use crate::h264;
+use crate::h265;
+use crate::metrics::Metrics;
+use crate::mpeg2;
+use crate::packet::Packet;
+use crate::teletext::Teletext;
+use crate::track::Track;
+
+/// set field if parsed value differs; true if changed
+fn update<T: PartialEq>(field: &mut Option<T>, value: Option<T>) -> bool {
+    if value.is_some() && value != *field {
+        *field = value;
+        true
+    } else {
+        false
+    }
+}
+
+/// bitstream header parser by codec
+enum Headers {
+    Mpeg2(mpeg2::Headers),
+    H264(h264::Headers),
+    H265(h265::Headers),
+    Adts,
+    Latm,
+    Mpa,
+    Ac3,
+    /// pages seen so far
+    Teletext(Teletext),
+    DvbSub(DvbSub),
+}
+
+impl Headers {
+    fn new(codec: &str) -> Option<Headers> {
+        match codec {
+            "mpeg1video" | "mpeg2video" => Some(Headers::Mpeg2(Default::default())),
+            "h264" => Some(Headers::H264(Default::default())),
+            "h265" => Some(Headers::H265(Default::default())),
+            "aac" => Some(Headers::Adts),
+            "aac-latm" => Some(Headers::Latm),
+            "mp2" => Some(Headers::Mpa),
+            "ac3" | "eac3" => Some(Headers::Ac3),
+            "teletext" => Some(Headers::Teletext(Default::default())),
+            "dvb-subtitle" => Some(Headers::DvbSub(Default::default())),
+            _ => None,
+        }
+    }
+
+    /// access unit; true if track parameters changed
+    fn push(&mut self, data: &[u8], trk: &mut Track) -> bool {
+        match self {
+            // parameters are cloned only when headers changed
+            Headers::Mpeg2(headers) => {
+                headers.push(data) && update(&mut trk.video, headers.params().map(Video::Mpeg2))
+            }
+            Headers::H264(headers) => {
+                headers.push(data) && update(&mut trk.video, headers.params().map(Video::H264))
+            }
+            Headers::H265(headers) => {
+                headers.push(data) && update(&mut trk.video, headers.params().map(Video::H265))
+            }
+            // LATM config and E-AC-3 dependent substreams are not in every frame
+            Headers::Adts => update(&mut trk.audio, Aac::adts(data).map(Audio::Aac)),
+            Headers::Latm => update(&mut trk.audio, Aac::latm(data).map(Audio::Aac)),
+            Headers::Mpa => update(&mut trk.audio, Mpa::parse(data).map(Audio::Mpa)),
+            Headers::Ac3 => update(&mut trk.audio, Ac3::parse(data).map(Audio::Ac3)),
+            Headers::Teletext(teletext) => {
+                teletext.push(data)
+                    && update(
+                        &mut trk.subtitle,
+                        Some(Subtitle::Teletext(teletext.clone())),
+                    )
+            }
+            Headers::DvbSub(dvb) => {
+                dvb.push(data) && update(&mut trk.subtitle, Some(Subtitle::Dvb(dvb.clone())))
+            }
+        }
+    }
+
+    /// coding type of last pushed access unit
+    fn picture(&self) -> Option<Picture> {
+        match self {
+            Headers::Mpeg2(headers) => headers.picture(),
+            Headers::H264(headers) => headers.picture(),
+            Headers::H265(headers) => headers.picture(),
+            _ => None,
+        }
+    }
+
+    /// SEI messages found by last push
+    fn messages(&mut self) -> Vec<(u32, Vec<u8>)> {
+        match self {
+            Headers::H264(headers) => headers.take_messages(),
+            Headers::H265(headers) => headers.take_messages(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// GOP header found by last push
+    fn gop(&mut self) -> Option<mpeg2::Gop> {
+        match self {
+            Headers::Mpeg2(headers) => headers.gop(),
+            _ => None,
+        }
+    }
+}
+
+struct Stream {
+    track: Track,
+    headers: Option<Headers>,
+    /// HDR signalling issues already reported
+    issues: Vec<&'static str>,
+
+    /// audio: frames, frames after sync loss and bytes dropped
+    frames: u64,
+    sync_errors: u64,
+    skipped: u64,
+}
+
+impl Stream {
+    fn new(trk: &Track) -> Stream {
+        Stream {
+            track: trk.clone(),
+            headers: Headers::new(trk.codec()),
+            issues: Vec::new(),
+            frames: 0,
+            sync_errors: 0,
+            skipped: 0,
+        }
+    }
+}
+
+/// stream parameters from elementary stream headers (SPS/PPS/SEI,
+/// audio frame headers, ...); re-emits track with parameters attached
+/// on every change, everything else is passed through
+pub struct Es {
+    url: Url,
+
+    consumers: Consumers,
+
+    streams: RefCell<HashMap<u16, Stream>>,
+
+    metrics: Metrics,
+    metrics_at: Throttle,
+}
+
+impl Es {
+    pub fn new(url: Url) -> Es {
+        Es {
+            url,
+            consumers: Default::default(),
+            streams: Default::default(),
+            metrics: Default::default(),
+            metrics_at: Default::default(),
+        }
+    }
+
+    pub fn metrics(&mut self, metrics: Metrics) -> &Es {
+        self.metrics = metrics;
+        self
+    }
+
+    fn metrics_publish(&self) {
+        if !self.metrics_at.ready() {
+            return;
+        }
+
+        for (pid, stream) in self.streams.borrow().iter() {
+            if stream.frames == 0 || stream.track.audio.is_none() {
+                continue;
+            }
+
+            self.metrics.set(
+                format!("audio-0x{:04X}", pid),
+                format!(
+                    "(:frames {} :sync-errors {} :skipped {})",
+                    stream.frames, stream.sync_errors, stream.skipped
+                ),
+            );
+        }
+    }
+}
+
+impl Producer for Es {
+    fn consumers(&self) -> &Consumers {
+        &self.consumers
+    }
+
+    fn consumers_mut(&mut self) -> &mut Consumers {
+        &mut self.consumers
+    }
+}
+
+impl Consumer for Es {
+    fn consume_trk(&self, trk: &Track) {
+        let trk = {
+            let mut streams = self.streams.borrow_mut();
+
+            let mut trk = trk.clone();
+            match streams.get_mut(&trk.pid) {
+                // e.g. service name update; keep parameters
+                Some(stream) if stream.track.stream_type == trk.stream_type => {
+                    trk.video = stream.track.video.clone();
+                    trk.audio = stream.track.audio.clone();
+                    trk.subtitle = stream.track.subtitle.clone();
+                    stream.track = trk.clone();
+                }
+                _ => {
+                    streams.insert(trk.pid, Stream::new(&trk));
+                }
+            }
+
+            trk
+        };
+
+        self.produce_trk(&trk)
+    }
+
+    fn consume_pkt_raw(&self, pkt_raw: &[u8]) {
+        self.produce_pkt_raw(pkt_raw)
+    }
+
+    fn consume_section(&self, pid: u16, section: &[u8]) {
+        self.produce_section(pid, section)
+    }
+
+    fn consume_pkt(&self, pkt: &Packet) {
+        self.produce_pkt(pkt)
+    }
+
+    fn consume_frm(&self, frm: &Frame) {
+        let (updated, issues, gop, picture, messages, sync_error) = {
+            let mut streams = self.streams.borrow_mut();
+
+            let stream = match streams.get_mut(&frm.pid) {
+                Some(stream) => stream,
+                None => return self.produce_frm(frm),
+            };
+            let headers = match stream.headers.as_mut() {
+                Some(headers) => headers,
+                None => return self.produce_frm(frm),
+            };
+
+            let changed = headers.push(&frm.data, &mut stream.track);
+            let gop = headers.gop();
+            let picture = headers.picture();
+            let messages = headers.messages();
+
+            stream.frames += 1;
+            let sync_error = if frm.skipped > 0 {
+                stream.sync_errors += 1;
+                stream.skipped += frm.skipped as u64;
+                Some(stream.sync_errors)
+            } else {
+                None
+            };
+
+            if changed {
+                // report each issue once while it persists
+                let issues = stream
+                    .track
+                    .video
+                    .as_ref()
+                    .map(|video| video.hdr_issues())
+                    .unwrap_or_default();
+                let new = issues
+                    .iter()
+                    .filter(|issue| !stream.issues.contains(issue))
+                    .copied()
+                    .collect::<Vec<_>>();
+                stream.issues = issues;
+
+                (
+                    Some(stream.track.clone()),
+                    new,
+                    gop,
+                    picture,
+                    messages,
+                    sync_error,
+                )
+            } else {
+                (None, Vec::new(), gop, picture, messages, sync_error)
+            }
+        };
+
+        if let Some(gop) = gop {
+            if gop.broken_link {
+                warn!(
+                    "({}) [es] GOP broken link (:pid 0x{:04X} {})",
+                    self.url, frm.pid, gop
+                );
+            } else {
+                debug!("({}) [es] GOP (:pid 0x{:04X} {})", self.url, frm.pid, gop);
+            }
+            self.metrics
+                .set(format!("es-0x{:04X}-timecode", frm.pid), gop.timecode());
+        }
+
+        if let Some(sync_errors) = sync_error {
+            if sync_errors == 1 {
+                warn!(
+                    "({}) [es] audio sync lost (:pid 0x{:04X} :codec {} :skipped {})",
+                    self.url, frm.pid, frm.codec, frm.skipped
+                );
+            } else {
+                debug!(
+                    "({}) [es] audio sync lost (:pid 0x{:04X} :codec {} :skipped {} :count {})",
+                    self.url, frm.pid, frm.codec, frm.skipped, sync_errors
+                );
+            }
+        }
+
+        for issue in issues {
+            warn!(
+                "({}) [es] HDR signalling (:pid 0x{:04X} :issue {})",
+                self.url, frm.pid, issue
+            );
+        }
+
+        if let Some(trk) = updated {
+            let params = match (
+                trk.video.as_ref(),
+                trk.audio.as_ref(),
+                trk.subtitle.as_ref(),
+            ) {
+                (Some(video), _, _) => Some(video.to_string()),
+                (None, Some(audio), _) => Some(audio.to_string()),
+                (None, None, Some(subtitle)) => Some(subtitle.to_string()),
+                (None, None, None) => None,
+            };
+            if let Some(params) = params {
+                info!(
+                    "({}) [es] params (:pid 0x{:04X} :codec {} {})",
+                    self.url,
+                    trk.pid,
+                    trk.codec(),
+                    params
+                );
+                self.metrics
+                    .set(format!("es-0x{:04X}", trk.pid), format!("({})", params));
+            }
+            self.produce_trk(&trk);
+        }
+
+        self.metrics_publish();
+
+        if picture.is_none() && messages.is_empty() {
+            return self.produce_frm(frm);
+        }
+
+        // annotated copy; data is shared
+        self.produce_frm(&Frame {
+            picture,
+            sei: messages,
+            ..frm.clone()
+        });
+    }
+}
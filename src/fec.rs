@@ -0,0 +1,505 @@
+use std::cmp;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::rtp::FecHeader;
+
+/// which SMPTE 2022-1 / Pro-MPEG COP3 FEC streams to receive
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mode {
+    Off,
+    /// column FEC only; received on port+2
+    Col,
+    /// row FEC only; received on port+4
+    Row,
+    /// column + row (2D) FEC
+    Both,
+}
+
+impl Mode {
+    #[inline(always)]
+    pub fn col(self) -> bool {
+        self == Mode::Col || self == Mode::Both
+    }
+
+    #[inline(always)]
+    pub fn row(self) -> bool {
+        self == Mode::Row || self == Mode::Both
+    }
+}
+
+impl FromStr for Mode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" | "none" | "0" => Ok(Mode::Off),
+            "col" | "column" | "1d" => Ok(Mode::Col),
+            "row" => Ok(Mode::Row),
+            "both" | "2d" => Ok(Mode::Both),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mode::Off => write!(f, "off"),
+            Mode::Col => write!(f, "col"),
+            Mode::Row => write!(f, "row"),
+            Mode::Both => write!(f, "2d"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Stats {
+    /// media packets received from network
+    pub received: u64,
+    /// media packets received after they were declared lost
+    pub late: u64,
+    /// media packets reconstructed from FEC
+    pub recovered: u64,
+    /// media packets lost even after FEC
+    pub unrecoverable: u64,
+
+    pub fec_col: u64,
+    pub fec_row: u64,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "(:received {} :recovered {} :unrecoverable {} :late {} :fec-col {} :fec-row {})",
+            self.received,
+            self.recovered,
+            self.unrecoverable,
+            self.late,
+            self.fec_col,
+            self.fec_row,
+        )
+    }
+}
+
+/// pending FEC packet
+struct Parity {
+    /// extended sequence number of first protected packet
+    base: u64,
+    /// distance between protected packets
+    step: u64,
+    /// number of protected packets
+    count: u64,
+
+    length_recovery: u16,
+    payload: Vec<u8>,
+}
+
+impl Parity {
+    #[inline(always)]
+    fn last(&self) -> u64 {
+        self.base + self.step * (self.count - 1)
+    }
+
+    #[inline(always)]
+    fn seqs(&self) -> impl Iterator<Item = u64> + '_ {
+        (0..self.count).map(move |i| self.base + i * self.step)
+    }
+}
+
+/// SMPTE 2022-1 FEC decoder with reorder buffer
+///
+/// media packets are kept in sequence number order and released
+/// either when they are contiguous or when the reorder depth is
+/// exceeded; missing packets are reconstructed from column/row
+/// parity (XOR) while they are still inside the buffer.
+pub struct Fec {
+    /// extended sequence number of next packet to release
+    next: Option<u64>,
+    /// highest extended sequence number seen
+    highest: u64,
+
+    /// received/recovered payloads; released ones are kept
+    /// for `depth` packets to serve as recovery history
+    pkts: BTreeMap<u64, Vec<u8>>,
+    parity: Vec<Parity>,
+
+    /// reorder depth in packets
+    depth: u64,
+    depth_min: u64,
+
+    /// column FEC is expected but matrix size is not known yet
+    col_pending: bool,
+    /// largest seen FEC matrix (L x D for column, L for row)
+    matrix: u64,
+
+    stats: Stats,
+}
+
+impl Fec {
+    /// L * D <= 100 for SMPTE 2022-1; column parity for a matrix
+    /// arrives during the next matrix, so two matrices are buffered
+    const DEPTH_MAX: u64 = 2 * 100;
+
+    pub fn new(mode: Mode, depth_min: usize) -> Fec {
+        let depth_min = depth_min as u64;
+        let col_pending = mode.col();
+
+        Fec {
+            next: None,
+            highest: 0,
+
+            pkts: BTreeMap::new(),
+            parity: Vec::new(),
+
+            // wait long enough for the first column parity;
+            // nothing to wait for without FEC - pass through
+            depth: if col_pending {
+                Self::DEPTH_MAX
+            } else if mode == Mode::Off {
+                0
+            } else {
+                depth_min
+            },
+            depth_min,
+
+            col_pending,
+            matrix: 0,
+
+            stats: Default::default(),
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// 16-bit sequence number to wrap-around free one,
+    /// nearest to reference
+    #[inline(always)]
+    fn seq_unwrap(reference: u64, seq: u16) -> u64 {
+        let diff = i64::from(seq.wrapping_sub(reference as u16) as i16);
+        cmp::max(reference as i64 + diff, 0) as u64
+    }
+
+    pub fn push_media<F>(&mut self, seq: u16, payload: &[u8], mut emit: F)
+    where
+        F: FnMut(&[u8]),
+    {
+        self.stats.received += 1;
+
+        // sender restarted with lower sequence number
+        if self.next.is_some()
+            && self
+                .highest
+                .saturating_sub(Self::seq_unwrap(self.highest, seq))
+                > cmp::max(self.depth, self.depth_min)
+        {
+            self.restart(&mut emit);
+        }
+
+        let seq = match self.next {
+            Some(_) => Self::seq_unwrap(self.highest, seq),
+            None => {
+                // shift first packet to keep unwrap above zero
+                let seq = (1 << 16) | u64::from(seq);
+                self.next = Some(seq);
+                self.highest = seq;
+                seq
+            }
+        };
+
+        if seq < self.next.unwrap_or(seq) {
+            if !self.pkts.contains_key(&seq) {
+                self.stats.late += 1;
+            }
+            return;
+        }
+
+        self.highest = cmp::max(self.highest, seq);
+        self.pkts.entry(seq).or_insert_with(|| payload.to_vec());
+
+        self.recover();
+        self.release(&mut emit);
+    }
+
+    /// flush buffered packets and start over as on first packet
+    fn restart<F>(&mut self, emit: &mut F)
+    where
+        F: FnMut(&[u8]),
+    {
+        if let Some(next) = self.next {
+            self.pkts
+                .range(next..)
+                .for_each(|(_, payload)| emit(payload));
+        }
+
+        self.next = None;
+        self.highest = 0;
+        self.pkts.clear();
+        self.parity.clear();
+    }
+
+    pub fn push_fec<F>(&mut self, header: &FecHeader, mut emit: F)
+    where
+        F: FnMut(&[u8]),
+    {
+        if header.d() {
+            self.stats.fec_row += 1;
+        } else {
+            self.stats.fec_col += 1;
+        }
+
+        // no media yet - nothing to protect
+        let next = match self.next {
+            Some(next) => next,
+            None => return,
+        };
+
+        let (step, count) = (u64::from(header.offset()), u64::from(header.na()));
+        if step == 0 || count == 0 {
+            return;
+        }
+
+        // row: L x 1 ; column: L x D
+        if header.d() {
+            self.matrix = cmp::max(self.matrix, count);
+        } else {
+            self.matrix = cmp::max(self.matrix, step * count);
+            self.col_pending = false;
+        }
+
+        if !self.col_pending {
+            self.depth = cmp::min(cmp::max(self.depth_min, 2 * self.matrix), Self::DEPTH_MAX);
+        }
+
+        let parity = Parity {
+            base: Self::seq_unwrap(self.highest, header.sn_base()),
+            step,
+            count,
+            length_recovery: header.length_recovery(),
+            payload: header.payload().to_vec(),
+        };
+
+        // protects already released packets only
+        if parity.last() < next {
+            return;
+        }
+
+        self.parity.push(parity);
+
+        self.recover();
+        self.release(&mut emit);
+    }
+
+    /// try to reconstruct single missing packet per parity packet;
+    /// repeat while progress is made - every recovered packet can
+    /// complete another row/column
+    fn recover(&mut self) {
+        let next = match self.next {
+            Some(next) => next,
+            None => return,
+        };
+
+        loop {
+            let mut progress = false;
+            let pkts = &mut self.pkts;
+            let stats = &mut self.stats;
+
+            self.parity.retain(|parity| {
+                let mut missing = parity.seqs().filter(|seq| !pkts.contains_key(seq));
+
+                let seq = match (missing.next(), missing.next()) {
+                    // nothing to recover
+                    (None, _) => return false,
+                    // 2+ missing - wait for more data
+                    (Some(_), Some(_)) => return true,
+                    (Some(seq), None) => seq,
+                };
+
+                // already declared lost
+                if seq < next {
+                    return false;
+                }
+
+                let mut length = parity.length_recovery;
+                let mut payload = parity.payload.clone();
+
+                for other in parity.seqs().filter(|s| *s != seq) {
+                    let other = &pkts[&other];
+
+                    length ^= other.len() as u16;
+
+                    if payload.len() < other.len() {
+                        payload.resize(other.len(), 0);
+                    }
+                    for (dst, src) in payload.iter_mut().zip(other.iter()) {
+                        *dst ^= *src;
+                    }
+                }
+
+                // corrupt parity; recovered packet would be padded
+                if usize::from(length) > payload.len() {
+                    return false;
+                }
+
+                payload.truncate(usize::from(length));
+                pkts.insert(seq, payload);
+                stats.recovered += 1;
+                progress = true;
+
+                false
+            });
+
+            if !progress {
+                break;
+            }
+        }
+    }
+
+    fn release<F>(&mut self, emit: &mut F)
+    where
+        F: FnMut(&[u8]),
+    {
+        let mut next = match self.next {
+            Some(next) => next,
+            None => return,
+        };
+
+        loop {
+            if let Some(payload) = self.pkts.get(&next) {
+                emit(payload);
+                next += 1;
+            } else if next <= self.highest && self.highest - next >= self.depth {
+                // give up; jump to first present packet
+                let present = self
+                    .pkts
+                    .range(next..)
+                    .next()
+                    .map(|(seq, _)| *seq)
+                    .unwrap_or(self.highest + 1);
+
+                self.stats.unrecoverable += present - next;
+                next = present;
+            } else {
+                break;
+            }
+        }
+
+        self.next = Some(next);
+
+        // keep released packets as recovery history
+        let keep = next.saturating_sub(self.depth);
+        while self
+            .pkts
+            .first_key_value()
+            .is_some_and(|(seq, _)| *seq < keep)
+        {
+            self.pkts.pop_first();
+        }
+        self.parity.retain(|parity| parity.last() >= next);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// XOR of lengths and payloads [1], [2, 2], [3, 3, 3], [4]
+    const LENGTH_RECOVERY: u16 = 1;
+    const PARITY: [u8; 3] = [4, 1, 3];
+
+    /// push media packets by sequence number; emitted first payload bytes
+    fn push(fec: &mut Fec, seqs: &[u16]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for seq in seqs {
+            fec.push_media(*seq, &[*seq as u8], |payload| out.push(payload[0]));
+        }
+        out
+    }
+
+    /// row FEC header with parity payload
+    fn row(sn_base: u16, na: u8, length_recovery: u16, payload: &[u8]) -> Vec<u8> {
+        let mut buf = vec![0; FecHeader::SZ];
+        buf[0..2].copy_from_slice(&sn_base.to_be_bytes());
+        buf[2..4].copy_from_slice(&length_recovery.to_be_bytes());
+        buf[12] = 0x40;
+        buf[13] = 1;
+        buf[14] = na;
+        buf.extend_from_slice(payload);
+        buf
+    }
+
+    /// row of 4 with 3 lost
+    fn recover_row(parity: &[u8]) -> (Vec<u8>, Stats) {
+        let mut fec = Fec::new(Mode::Row, 32);
+        let mut out = Vec::new();
+        for seq in [1u16, 2, 4] {
+            let payload = vec![seq as u8; if seq == 4 { 1 } else { seq as usize }];
+            fec.push_media(seq, &payload, |payload| out.extend_from_slice(payload));
+        }
+        if let Ok(header) = FecHeader::try_new(parity) {
+            fec.push_fec(&header, |payload| out.extend_from_slice(payload));
+        }
+        (out, fec.stats())
+    }
+
+    #[test]
+    fn recover_from_row_parity() {
+        let (out, stats) = recover_row(&row(1, 4, LENGTH_RECOVERY, &PARITY));
+        assert_eq!(out, vec![1, 2, 2, 3, 3, 3, 4]);
+        assert_eq!(stats.recovered, 1);
+    }
+
+    #[test]
+    fn truncated_parity() {
+        let parity = row(1, 4, LENGTH_RECOVERY, &PARITY);
+        for len in 0..FecHeader::SZ {
+            assert!(FecHeader::try_new(&parity[..len]).is_err());
+        }
+
+        // lost packet is longer than parity payload
+        let (out, stats) = recover_row(&parity[..parity.len() - 1]);
+        assert_eq!(out, vec![1, 2, 2]);
+        assert_eq!(stats.recovered, 0);
+    }
+
+    #[test]
+    fn length_recovery_overrun() {
+        let (out, stats) = recover_row(&row(1, 4, 0xFFFF, &PARITY));
+        assert_eq!(out, vec![1, 2, 2]);
+        assert_eq!(stats.recovered, 0);
+    }
+
+    #[test]
+    fn off_passes_through_loss() {
+        let mut fec = Fec::new(Mode::Off, 32);
+        assert_eq!(push(&mut fec, &[1, 2, 4, 5]), vec![1, 2, 4, 5]);
+        assert_eq!(push(&mut fec, &[3]), vec![]);
+
+        let stats = fec.stats();
+        assert_eq!(stats.unrecoverable, 1);
+        assert_eq!(stats.late, 1);
+    }
+
+    #[test]
+    fn reorder_within_depth() {
+        let mut fec = Fec::new(Mode::Row, 32);
+        assert_eq!(push(&mut fec, &[1, 3, 2, 4]), vec![1, 2, 3, 4]);
+        assert_eq!(fec.stats().unrecoverable, 0);
+    }
+
+    #[test]
+    fn restart_with_lower_seq() {
+        let mut fec = Fec::new(Mode::Off, 32);
+        assert_eq!(push(&mut fec, &[1000, 1001, 1002]), vec![232, 233, 234]);
+        assert_eq!(push(&mut fec, &[10, 11, 12]), vec![10, 11, 12]);
+
+        let mut fec = Fec::new(Mode::Row, 32);
+        assert_eq!(push(&mut fec, &[1000, 1001, 1003]), vec![232, 233]);
+        assert_eq!(push(&mut fec, &[10, 11, 12]), vec![235, 10, 11, 12]);
+        assert_eq!(fec.stats().late, 0);
+    }
+}
@@ -1,20 +1,23 @@
+use crate::frame::Frame;
+use crate::packet::Packet;
+use crate::track::Track;
+
 // trait EntryPoint: Producer {}
 // trait Filter: Producer + Consumer {}
 // trait EndPoint: Consumer {}
 
+#[derive(Default)]
 pub struct Consumers(Vec<Box<dyn Consumer>>);
 
-impl Default for Consumers {
-    fn default() -> Self {
-        Consumers(Vec::new())
-    }
-}
-
-pub trait Consumer {
-    fn consume_trk(&self) {}
+/// consumers live inside source thread
+pub trait Consumer: Send {
+    fn consume_trk(&self, _: &Track) {}
     fn consume_pkt_raw(&self, _: &[u8]) {}
-    fn consume_pkt(&self) {}
-    fn consume_frm(&self) {}
+    /// complete PSI/SI section (CRC is not checked)
+    fn consume_section(&self, _pid: u16, _: &[u8]) {}
+    fn consume_pkt(&self, _: &Packet) {}
+    /// complete access unit (see Reassembler)
+    fn consume_frm(&self, _: &Frame) {}
 }
 
 pub trait Producer {
@@ -25,9 +28,9 @@ pub trait Producer {
         self.consumers_mut().0.push(consumer)
     }
 
-    fn produce_trk(&self) {
+    fn produce_trk(&self, trk: &Track) {
         for consumer in self.consumers().0.iter() {
-            consumer.consume_trk()
+            consumer.consume_trk(trk)
         }
     }
 
@@ -37,31 +40,31 @@ pub trait Producer {
         }
     }
 
-    fn produce_pkt(&self) {
+    fn produce_section(&self, pid: u16, section: &[u8]) {
+        for consumer in self.consumers().0.iter() {
+            consumer.consume_section(pid, section)
+        }
+    }
+
+    fn produce_pkt(&self, pkt: &Packet) {
         for consumer in self.consumers().0.iter() {
-            consumer.consume_pkt()
+            consumer.consume_pkt(pkt)
         }
     }
 
-    fn consume_frm(&self) {
+    fn produce_frm(&self, frm: &Frame) {
         for consumer in self.consumers().0.iter() {
-            consumer.consume_frm()
+            consumer.consume_frm(frm)
         }
     }
 }
 
+/// fan-out: pass everything to all consumers
+#[derive(Default)]
 pub struct Filter {
     consumers: Consumers,
 }
 
-impl Default for Filter {
-    fn default() -> Self {
-        Filter {
-            consumers: Default::default(),
-        }
-    }
-}
-
 impl Producer for Filter {
     fn consumers(&self) -> &Consumers {
         &self.consumers
@@ -73,7 +76,23 @@ impl Producer for Filter {
 }
 
 impl Consumer for Filter {
-    fn consume_trk(&self) {
-        self.produce_trk()
+    fn consume_trk(&self, trk: &Track) {
+        self.produce_trk(trk)
+    }
+
+    fn consume_pkt_raw(&self, pkt_raw: &[u8]) {
+        self.produce_pkt_raw(pkt_raw)
+    }
+
+    fn consume_section(&self, pid: u16, section: &[u8]) {
+        self.produce_section(pid, section)
+    }
+
+    fn consume_pkt(&self, pkt: &Packet) {
+        self.produce_pkt(pkt)
+    }
+
+    fn consume_frm(&self, frm: &Frame) {
+        self.produce_frm(frm)
     }
 }
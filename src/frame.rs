@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+/// complete access unit: video picture or audio frame
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub pid: u16,
+
+    /// Track::codec
+    pub codec: &'static str,
+
+    /// presentation time stamp; 90kHz
+    /// (signaled or extrapolated from previous audio frame)
+    pub pts: Option<u64>,
+    /// decode time stamp; 90kHz
+    pub dts: Option<u64>,
+
+    /// elementary stream data; shared by annotated copies
+    pub data: Arc<Vec<u8>>,
+
+    /// video: coding type if known (see Es)
+    pub picture: Option<Picture>,
+    /// H.264 / H.265: (payloadType, payload) SEI messages (see Es)
+    pub sei: Vec<(u32, Vec<u8>)>,
+    /// audio: bytes dropped before this frame to regain sync
+    pub skipped: usize,
+}
+
+/// picture coding type by slice / picture header
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PictureType {
+    Idr,
+    I,
+    P,
+    B,
+}
+
+impl PictureType {
+    /// GOP pattern letter
+    pub fn letter(self) -> char {
+        match self {
+            PictureType::Idr | PictureType::I => 'I',
+            PictureType::P => 'P',
+            PictureType::B => 'B',
+        }
+    }
+}
+
+/// video access unit properties found by elementary stream analyzer
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Picture {
+    pub kind: PictureType,
+    /// intra picture starting GOP without references to previous one
+    pub closed: bool,
+    /// second field of field pair; belongs to previous frame
+    pub second_field: bool,
+}
@@ -0,0 +1,989 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+use url::Url;
+
+use crate::clock::PCR_MAX;
+use crate::config::bool_parse;
+use crate::crc32;
+use crate::error::{Error, Result};
+
+const PKT_SZ: usize = ts::Packet::SZ;
+const PAYLOAD_SZ: usize = PKT_SZ - 4;
+
+/// 27MHz system clock
+const CLOCK: u64 = 27_000_000;
+const SYNC_BYTE: u8 = 0x47;
+
+const PID_PAT: u16 = 0x0000;
+const PID_SDT: u16 = 0x0011;
+const PID_NULL: u16 = 0x1FFF;
+
+const TABLE_ID_PAT: u8 = 0x00;
+const TABLE_ID_PMT: u8 = 0x02;
+const TABLE_ID_SDT: u8 = 0x42;
+
+const STREAM_TYPE_H264: u8 = 0x1B;
+const STREAM_TYPE_AAC_ADTS: u8 = 0x0F;
+
+const TRANSPORT_STREAM_ID: u16 = 1;
+const ORIGINAL_NETWORK_ID: u16 = 1;
+const PROVIDER: &str = "va-tool";
+
+/// first PCR; far enough from zero for PTS to stay positive
+const PCR_START: u64 = 10 * CLOCK;
+/// PTS - PCR for every access unit (90kHz)
+const PTS_DELAY: u64 = 63_000;
+/// PCR discontinuity injected by `pcr-jump`
+const PCR_JUMP: u64 = CLOCK;
+
+const PAT_INTERVAL: u64 = CLOCK / 10;
+const PMT_INTERVAL: u64 = CLOCK / 10;
+const SDT_INTERVAL: u64 = CLOCK / 2;
+
+/// 25fps
+const VIDEO_FRAME_DUR: u64 = CLOCK / 25;
+/// AAC: 1024 samples @ 48kHz
+const AUDIO_FRAME_DUR: u64 = 1024 * CLOCK / 48_000;
+/// 128kbps
+const AUDIO_FRAME_SZ: usize = 341;
+
+/// start code + stream id + length + flags + PTS
+const PES_HEADER_SZ: usize = 14;
+/// adaptation field length + flags + PCR
+const PCR_AF_SZ: usize = 8;
+
+/// gen:// input parameters
+///
+/// gen://spts?bitrate=4M
+/// gen://mpts?programs=3&bitrate=20M&cc-error=1000&pcr-jump=50&no-pat=1
+#[derive(Clone, Debug)]
+pub struct Params {
+    pub programs: u16,
+
+    /// transport stream bitrate, bits per second
+    pub bitrate: u64,
+
+    pub pcr_interval: Duration,
+
+    /// pace output to bitrate;
+    /// otherwise generate as fast as possible
+    pub realtime: bool,
+
+    /// stop after this number of packets
+    pub packets: Option<u64>,
+
+    /// skip continuity counter of first video PID every N packets
+    pub cc_error: Option<u64>,
+
+    /// jump first program PCR forward every N PCRs
+    pub pcr_jump: Option<u64>,
+
+    /// never send PAT
+    pub no_pat: bool,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            programs: 1,
+            bitrate: 4_000_000,
+            pcr_interval: Duration::from_millis(35),
+            realtime: true,
+            packets: None,
+            cc_error: None,
+            pcr_jump: None,
+            no_pat: false,
+        }
+    }
+}
+
+impl Params {
+    const PROGRAMS_MAX: u16 = 16;
+    const MPTS_PROGRAMS: u16 = 3;
+
+    /// host selects profile (spts, mpts), query overrides defaults
+    pub fn from_url(url: &Url) -> Result<Params> {
+        let mut params = match url.host_str() {
+            None | Some("") | Some("spts") => Params::default(),
+            Some("mpts") => Params {
+                programs: Self::MPTS_PROGRAMS,
+                ..Default::default()
+            },
+            Some(profile) => return Err(Error::gen_param("profile", profile)),
+        };
+
+        for (key, value) in url.query_pairs() {
+            let err = || Error::gen_param(&key, &value);
+
+            match key.as_ref() {
+                "programs" => {
+                    params.programs = value
+                        .parse()
+                        .ok()
+                        .filter(|v| (1..=Self::PROGRAMS_MAX).contains(v))
+                        .ok_or_else(err)?
+                }
+                "bitrate" => params.bitrate = bitrate_parse(&value).ok_or_else(err)?,
+                "pcr-interval" => {
+                    params.pcr_interval = value
+                        .parse()
+                        .ok()
+                        .filter(|v| (1..=1000).contains(v))
+                        .map(Duration::from_millis)
+                        .ok_or_else(err)?
+                }
+                "realtime" => params.realtime = bool_parse(&value).ok_or_else(err)?,
+                "packets" => params.packets = Some(value.parse().map_err(|_| err())?),
+                "cc-error" => {
+                    params.cc_error = Some(value.parse().ok().filter(|v| *v > 0).ok_or_else(err)?)
+                }
+                "pcr-jump" => {
+                    params.pcr_jump = Some(value.parse().ok().filter(|v| *v > 0).ok_or_else(err)?)
+                }
+                "no-pat" => params.no_pat = bool_parse(&value).ok_or_else(err)?,
+                // container override (see Mediacontainer::detect)
+                "format" => match value.to_lowercase().as_str() {
+                    "ts" | "mpegts" => {}
+                    _ => return Err(err()),
+                },
+                _ => return Err(err()),
+            }
+        }
+
+        if params.video_frame_sz().is_none() {
+            return Err(Error::gen_param("bitrate", &params.bitrate.to_string()));
+        }
+
+        Ok(params)
+    }
+
+    /// split bitrate between PSI, PCR, audio and video;
+    /// video gets 90% of what is left - the rest is null packets.
+    /// None if bitrate is too low
+    fn video_frame_sz(&self) -> Option<usize> {
+        let programs = u64::from(self.programs);
+
+        let slots = self.bitrate / (8 * PKT_SZ as u64);
+        let psi = CLOCK / PAT_INTERVAL
+            + programs * CLOCK / PMT_INTERVAL
+            + CLOCK / SDT_INTERVAL * (sdt(self.programs).len() / PAYLOAD_SZ + 1) as u64;
+        let pcr = 1000 / self.pcr_interval.as_millis() as u64;
+        let audio = ((AUDIO_FRAME_SZ + PES_HEADER_SZ) / PAYLOAD_SZ + 1) as u64 * CLOCK
+            / AUDIO_FRAME_DUR
+            + 1;
+
+        let available = slots.checked_sub(psi + programs * (audio + pcr))?;
+        let pkts = available * 9 / 10 / programs / (CLOCK / VIDEO_FRAME_DUR);
+
+        if pkts == 0 {
+            None
+        } else {
+            Some(pkts as usize * PAYLOAD_SZ - PES_HEADER_SZ - PCR_AF_SZ)
+        }
+    }
+}
+
+impl fmt::Display for Params {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "(:programs {} :bitrate {} :pcr-interval {}ms :realtime {}",
+            self.programs,
+            self.bitrate,
+            self.pcr_interval.as_millis(),
+            self.realtime
+        )?;
+        if let Some(packets) = self.packets {
+            write!(f, " :packets {}", packets)?;
+        }
+        if let Some(cc_error) = self.cc_error {
+            write!(f, " :cc-error {}", cc_error)?;
+        }
+        if let Some(pcr_jump) = self.pcr_jump {
+            write!(f, " :pcr-jump {}", pcr_jump)?;
+        }
+        if self.no_pat {
+            write!(f, " :no-pat true")?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// parse bitrate with optional decimal suffix: 512000, 512k, 2.5M, 1G;
+/// whole token must be a number
+pub(crate) fn bitrate_parse<S: AsRef<str>>(s: S) -> Option<u64> {
+    let s = s.as_ref();
+
+    let (digits, mul) = match s.chars().last()?.to_ascii_uppercase() {
+        'K' => (&s[..s.len() - 1], 1_000.0),
+        'M' => (&s[..s.len() - 1], 1_000_000.0),
+        'G' => (&s[..s.len() - 1], 1_000_000_000.0),
+        _ => (s, 1.0),
+    };
+
+    if !digits.bytes().all(|c| c.is_ascii_digit() || c == b'.') {
+        return None;
+    }
+
+    let bitrate = (digits.parse::<f64>().ok()? * mul).round();
+    (bitrate >= 1.0 && bitrate < u64::MAX as f64).then_some(bitrate as u64)
+}
+
+/// generated packets and injected faults
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    pub packets: u64,
+    pub null: u64,
+    pub cc_errors: u64,
+    pub pcr_jumps: u64,
+}
+
+/// elementary stream: frames are produced at fixed rate
+/// and packetized lazily when multiplexer gives a slot
+struct Es {
+    pid: u16,
+    cc: u8,
+
+    stream_type: u8,
+    stream_id: u8,
+    /// PES_packet_length is set (audio) or zero (video)
+    bounded: bool,
+
+    frame_dur: u64,
+    frame_sz: usize,
+    /// frames produced so far
+    frames: u64,
+
+    /// PES packets waiting for slots; offset in front one
+    queue: VecDeque<Vec<u8>>,
+    pos: usize,
+}
+
+impl Es {
+    fn new(pid: u16, stream_type: u8, stream_id: u8, frame_dur: u64, frame_sz: usize) -> Es {
+        Es {
+            pid,
+            cc: 0,
+
+            stream_type,
+            stream_id,
+            bounded: stream_type != STREAM_TYPE_H264,
+
+            frame_dur,
+            frame_sz,
+            frames: 0,
+
+            queue: VecDeque::new(),
+            pos: 0,
+        }
+    }
+
+    /// produce frames up to stream time
+    fn produce(&mut self, t: u64) {
+        while self.frames * self.frame_dur <= t {
+            let n = self.frames;
+            let pts = (PCR_START + n * self.frame_dur) / 300 + PTS_DELAY;
+
+            let es = if self.stream_type == STREAM_TYPE_H264 {
+                frame_video(n, self.frame_sz)
+            } else {
+                frame_audio(self.frame_sz)
+            };
+
+            self.queue
+                .push_back(pes(self.stream_id, pts, &es, self.bounded));
+            self.frames += 1;
+        }
+    }
+}
+
+struct Program {
+    pmt_pid: u16,
+    pmt_cc: u8,
+    pmt: Vec<u8>,
+
+    video: Es,
+    audio: Es,
+
+    /// stream time of next PCR
+    pcr_next: u64,
+}
+
+impl Program {
+    fn new(number: u16, video_frame_sz: usize) -> Program {
+        let pmt_pid = 0x0100 * number;
+        let video = Es::new(
+            pmt_pid + 1,
+            STREAM_TYPE_H264,
+            0xE0,
+            VIDEO_FRAME_DUR,
+            video_frame_sz,
+        );
+        let audio = Es::new(
+            pmt_pid + 2,
+            STREAM_TYPE_AAC_ADTS,
+            0xC0,
+            AUDIO_FRAME_DUR,
+            AUDIO_FRAME_SZ,
+        );
+
+        Program {
+            pmt_pid,
+            pmt_cc: 0,
+            pmt: pmt(number, &video, &audio),
+
+            video,
+            audio,
+
+            pcr_next: 0,
+        }
+    }
+}
+
+/// deterministic MPEG-TS generator: SPTS/MPTS with PAT, PMT, SDT,
+/// PCR on video PID and dummy H.264/AAC PES
+///
+/// packet N always has the same content for the same parameters;
+/// stream time is derived from packet index and bitrate
+pub struct Gen {
+    params: Params,
+
+    /// packets generated so far
+    n: u64,
+
+    pat: Vec<u8>,
+    pat_cc: u8,
+    pat_next: u64,
+
+    pmt_next: u64,
+
+    sdt: Vec<u8>,
+    sdt_cc: u8,
+    sdt_next: u64,
+
+    /// PSI packets waiting for slots
+    psi: VecDeque<[u8; PKT_SZ]>,
+
+    programs: Vec<Program>,
+    /// round-robin position over elementary streams
+    rr: usize,
+
+    /// accumulated PCR jumps
+    pcr_offset: u64,
+    /// PCRs of first program
+    pcrs: u64,
+    /// payload packets of first video PID
+    video_pkts: u64,
+
+    stats: Stats,
+}
+
+impl Gen {
+    pub fn new(params: Params) -> Gen {
+        let video_frame_sz = params.video_frame_sz().unwrap_or(0);
+
+        Gen {
+            n: 0,
+
+            pat: pat(params.programs),
+            pat_cc: 0,
+            pat_next: 0,
+
+            pmt_next: 0,
+
+            sdt: sdt(params.programs),
+            sdt_cc: 0,
+            sdt_next: 0,
+
+            psi: VecDeque::new(),
+
+            programs: (1..=params.programs)
+                .map(|number| Program::new(number, video_frame_sz))
+                .collect(),
+            rr: 0,
+
+            pcr_offset: 0,
+            pcrs: 0,
+            video_pkts: 0,
+
+            stats: Default::default(),
+
+            params,
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// stream time of packet (27MHz)
+    #[inline(always)]
+    fn time(&self, n: u64) -> u64 {
+        (u128::from(n) * (8 * PKT_SZ as u128) * u128::from(CLOCK) / u128::from(self.params.bitrate))
+            as u64
+    }
+
+    /// when next packet is due relative to stream start
+    pub fn at(&self) -> Duration {
+        let t = self.time(self.n);
+        Duration::new(t / CLOCK, (t % CLOCK * 1000 / 27) as u32)
+    }
+
+    /// None when `packets` limit is reached
+    pub fn next_packet(&mut self) -> Option<[u8; PKT_SZ]> {
+        if self.params.packets.is_some_and(|max| self.n >= max) {
+            return None;
+        }
+
+        let t = self.time(self.n);
+
+        self.psi_produce(t);
+        for program in self.programs.iter_mut() {
+            program.video.produce(t);
+            program.audio.produce(t);
+        }
+
+        let pkt = if let Some(pkt) = self.psi.pop_front() {
+            pkt
+        } else if let Some(i) = self.programs.iter().position(|p| t >= p.pcr_next) {
+            self.pcr_packet(i)
+        } else if let Some(k) = self.es_next() {
+            self.es_packet(k, None)
+        } else {
+            self.stats.null += 1;
+            packet(PID_NULL, false, 0, None, &[0xFF; PAYLOAD_SZ])
+        };
+
+        self.n += 1;
+        self.stats.packets += 1;
+
+        Some(pkt)
+    }
+
+    fn psi_produce(&mut self, t: u64) {
+        if t >= self.pat_next {
+            if !self.params.no_pat {
+                psi_packets(PID_PAT, &mut self.pat_cc, &self.pat, &mut self.psi);
+            }
+            self.pat_next += PAT_INTERVAL;
+        }
+
+        if t >= self.pmt_next {
+            for program in self.programs.iter_mut() {
+                psi_packets(
+                    program.pmt_pid,
+                    &mut program.pmt_cc,
+                    &program.pmt,
+                    &mut self.psi,
+                );
+            }
+            self.pmt_next += PMT_INTERVAL;
+        }
+
+        if t >= self.sdt_next {
+            psi_packets(PID_SDT, &mut self.sdt_cc, &self.sdt, &mut self.psi);
+            self.sdt_next += SDT_INTERVAL;
+        }
+    }
+
+    fn pcr_packet(&mut self, i: usize) -> [u8; PKT_SZ] {
+        let interval = self.params.pcr_interval.as_nanos() as u64 * 27 / 1000;
+        self.programs[i].pcr_next += interval;
+
+        if i == 0 {
+            self.pcrs += 1;
+            if self
+                .params
+                .pcr_jump
+                .is_some_and(|n| self.pcrs.is_multiple_of(n))
+            {
+                self.pcr_offset += PCR_JUMP;
+                self.stats.pcr_jumps += 1;
+            }
+        }
+
+        // PCR refers to the byte containing its last bit
+        let at = self.time(self.n) + (11 * 8 * CLOCK) / self.params.bitrate;
+        let offset = if i == 0 { self.pcr_offset } else { 0 };
+        let pcr = (PCR_START + at + offset) % PCR_MAX;
+
+        self.es_packet(2 * i, Some(pcr))
+    }
+
+    /// next elementary stream with pending data
+    fn es_next(&mut self) -> Option<usize> {
+        let count = 2 * self.programs.len();
+
+        let rr = self.rr;
+        let k = (0..count)
+            .map(|i| (rr + i) % count)
+            .find(|k| !self.es(*k).queue.is_empty())?;
+        self.rr = (k + 1) % count;
+
+        Some(k)
+    }
+
+    /// even - video, odd - audio
+    fn es(&self, k: usize) -> &Es {
+        let program = &self.programs[k / 2];
+        if k.is_multiple_of(2) {
+            &program.video
+        } else {
+            &program.audio
+        }
+    }
+
+    fn es_packet(&mut self, k: usize, pcr: Option<u64>) -> [u8; PKT_SZ] {
+        let cap = if pcr.is_some() {
+            PAYLOAD_SZ - PCR_AF_SZ
+        } else {
+            PAYLOAD_SZ
+        };
+
+        let program = &mut self.programs[k / 2];
+        let es = if k.is_multiple_of(2) {
+            &mut program.video
+        } else {
+            &mut program.audio
+        };
+
+        let pes = match es.queue.front() {
+            Some(pes) => pes,
+            // PCR only; continuity counter is not incremented
+            None => return packet(es.pid, false, es.cc.wrapping_sub(1), pcr, &[]),
+        };
+
+        if k == 0 {
+            self.video_pkts += 1;
+
+            let video_pkts = self.video_pkts;
+            if self
+                .params
+                .cc_error
+                .is_some_and(|n| video_pkts.is_multiple_of(n))
+            {
+                es.cc = es.cc.wrapping_add(1);
+                self.stats.cc_errors += 1;
+            }
+        }
+
+        let pusi = es.pos == 0;
+        let end = std::cmp::min(es.pos + cap, pes.len());
+
+        let pkt = packet(es.pid, pusi, es.cc, pcr, &pes[es.pos..end]);
+        es.cc = es.cc.wrapping_add(1);
+
+        if end == pes.len() {
+            es.queue.pop_front();
+            es.pos = 0;
+        } else {
+            es.pos = end;
+        }
+
+        pkt
+    }
+}
+
+/// TS packet; payload shorter than 184 bytes
+/// (or carried with PCR) is padded with adaptation field stuffing
+fn packet(pid: u16, pusi: bool, cc: u8, pcr: Option<u64>, payload: &[u8]) -> [u8; PKT_SZ] {
+    let mut pkt = [0xFF; PKT_SZ];
+
+    let af = pcr.is_some() || payload.len() < PAYLOAD_SZ;
+    let afc = match (af, payload.is_empty()) {
+        (false, _) => 0b01,
+        (true, false) => 0b11,
+        (true, true) => 0b10,
+    };
+    header(&mut pkt, pid, pusi, afc, cc);
+
+    let mut pos = 4;
+    if af {
+        // bytes following adaptation_field_length
+        let af_sz = PKT_SZ - 5 - payload.len();
+        pkt[4] = af_sz as u8;
+
+        if af_sz > 0 {
+            pkt[5] = 0x00;
+        }
+
+        if let Some(pcr) = pcr {
+            let (base, ext) = (pcr / 300, pcr % 300);
+
+            pkt[5] = 0x10;
+            pkt[6] = (base >> 25) as u8;
+            pkt[7] = (base >> 17) as u8;
+            pkt[8] = (base >> 9) as u8;
+            pkt[9] = (base >> 1) as u8;
+            pkt[10] = ((base & 1) << 7) as u8 | 0x7E | (ext >> 8) as u8;
+            pkt[11] = ext as u8;
+        }
+
+        pos = 5 + af_sz;
+    }
+
+    pkt[pos..].copy_from_slice(payload);
+    pkt
+}
+
+#[inline(always)]
+fn header(pkt: &mut [u8; PKT_SZ], pid: u16, pusi: bool, afc: u8, cc: u8) {
+    pkt[0] = SYNC_BYTE;
+    pkt[1] = (u8::from(pusi) << 6) | ((pid >> 8) as u8 & 0x1F);
+    pkt[2] = pid as u8;
+    pkt[3] = (afc << 4) | (cc & 0x0F);
+}
+
+/// pointer_field + section split into packets;
+/// tail is stuffed with 0xFF
+fn psi_packets(pid: u16, cc: &mut u8, section: &[u8], out: &mut VecDeque<[u8; PKT_SZ]>) {
+    let mut data = Vec::with_capacity(1 + section.len());
+    data.push(0);
+    data.extend_from_slice(section);
+
+    for (i, chunk) in data.chunks(PAYLOAD_SZ).enumerate() {
+        let mut pkt = [0xFF; PKT_SZ];
+        header(&mut pkt, pid, i == 0, 0b01, *cc);
+        pkt[4..4 + chunk.len()].copy_from_slice(chunk);
+
+        *cc = cc.wrapping_add(1) & 0x0F;
+        out.push_back(pkt);
+    }
+}
+
+/// long-form section with version 0, single section, CRC_32
+fn section(table_id: u8, reserved_future_use: bool, ext: u16, body: &[u8]) -> Vec<u8> {
+    // table id extension .. last_section_number + body + CRC_32
+    let len = 5 + body.len() + 4;
+
+    let mut s = Vec::with_capacity(3 + len);
+    s.push(table_id);
+    s.push(0xB0 | (u8::from(reserved_future_use) << 6) | ((len >> 8) as u8 & 0x0F));
+    s.push(len as u8);
+    s.extend_from_slice(&ext.to_be_bytes());
+    // reserved, version 0, current_next_indicator
+    s.push(0xC1);
+    s.push(0);
+    s.push(0);
+    s.extend_from_slice(body);
+
+    let crc = crc32::mpeg2(&s);
+    s.extend_from_slice(&crc.to_be_bytes());
+
+    s
+}
+
+fn pat(programs: u16) -> Vec<u8> {
+    let mut body = Vec::new();
+    for number in 1..=programs {
+        let pmt_pid = 0x0100 * number;
+
+        body.extend_from_slice(&number.to_be_bytes());
+        body.extend_from_slice(&(0xE000 | pmt_pid).to_be_bytes());
+    }
+
+    section(TABLE_ID_PAT, false, TRANSPORT_STREAM_ID, &body)
+}
+
+fn pmt(number: u16, video: &Es, audio: &Es) -> Vec<u8> {
+    let mut body = Vec::new();
+
+    // PCR_PID, program_info_length
+    body.extend_from_slice(&(0xE000 | video.pid).to_be_bytes());
+    body.extend_from_slice(&0xF000u16.to_be_bytes());
+
+    body.push(video.stream_type);
+    body.extend_from_slice(&(0xE000 | video.pid).to_be_bytes());
+    body.extend_from_slice(&0xF000u16.to_be_bytes());
+
+    // ISO_639_language_descriptor
+    let lang = [0x0A, 4, b'e', b'n', b'g', 0];
+
+    body.push(audio.stream_type);
+    body.extend_from_slice(&(0xE000 | audio.pid).to_be_bytes());
+    body.extend_from_slice(&(0xF000 | lang.len() as u16).to_be_bytes());
+    body.extend_from_slice(&lang);
+
+    section(TABLE_ID_PMT, false, number, &body)
+}
+
+fn sdt(programs: u16) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&ORIGINAL_NETWORK_ID.to_be_bytes());
+    body.push(0xFF);
+
+    for number in 1..=programs {
+        let name = format!("{} {}", PROVIDER, number);
+
+        // service_descriptor; digital television service
+        let mut desc = vec![0x48, 0, 0x01, PROVIDER.len() as u8];
+        desc.extend_from_slice(PROVIDER.as_bytes());
+        desc.push(name.len() as u8);
+        desc.extend_from_slice(name.as_bytes());
+        desc[1] = (desc.len() - 2) as u8;
+
+        body.extend_from_slice(&number.to_be_bytes());
+        // no EIT
+        body.push(0xFC);
+        // running, not scrambled
+        body.extend_from_slice(&(0x8000 | desc.len() as u16).to_be_bytes());
+        body.extend_from_slice(&desc);
+    }
+
+    section(TABLE_ID_SDT, true, TRANSPORT_STREAM_ID, &body)
+}
+
+/// PES with PTS only
+fn pes(stream_id: u8, pts: u64, es: &[u8], bounded: bool) -> Vec<u8> {
+    let pts = pts & ((1 << 33) - 1);
+
+    // flags + header_data_length + PTS + payload
+    let len = 3 + 5 + es.len();
+    let len = if bounded && len <= 0xFFFF { len } else { 0 };
+
+    let mut pes = Vec::with_capacity(PES_HEADER_SZ + es.len());
+    pes.extend_from_slice(&[0x00, 0x00, 0x01, stream_id]);
+    pes.extend_from_slice(&(len as u16).to_be_bytes());
+    pes.extend_from_slice(&[0x80, 0x80, 5]);
+    pes.extend_from_slice(&[
+        0x21 | ((pts >> 29) as u8 & 0x0E),
+        (pts >> 22) as u8,
+        ((pts >> 14) as u8 & 0xFE) | 1,
+        (pts >> 7) as u8,
+        ((pts << 1) as u8 & 0xFE) | 1,
+    ]);
+    pes.extend_from_slice(es);
+
+    pes
+}
+
+/// High@4.0 1920x1080 25fps BT.709
+const SPS: [u8; 27] = [
+    0x67, 0x64, 0x00, 0x28, 0xAC, 0xD9, 0x40, 0x78, 0x02, 0x27, 0xE5, 0xC0, 0x5A, 0x80, 0x80, 0x80,
+    0xA0, 0x00, 0x00, 0x03, 0x00, 0x20, 0x00, 0x00, 0x06, 0x50, 0x80,
+];
+/// CABAC, 8x8 transform
+const PPS: [u8; 5] = [0x68, 0xEB, 0xCC, 0xB2, 0x2C];
+
+/// IDR slice header only (frame_num 0)
+const SLICE_IDR: [u8; 4] = [0x65, 0x88, 0x84, 0x08];
+/// P slice header only (frame_num 0)
+const SLICE_P: [u8; 4] = [0x41, 0x9A, 0x00, 0x20];
+
+/// access unit delimiter + slice header + filler data NAL;
+/// every 25th frame is IDR and carries SPS/PPS, others are P
+fn frame_video(n: u64, sz: usize) -> Vec<u8> {
+    let primary_pic_type = if n.is_multiple_of(25) { 0 } else { 1 };
+
+    let mut es = vec![0x00, 0x00, 0x00, 0x01, 0x09, (primary_pic_type << 5) | 0x10];
+    if primary_pic_type == 0 {
+        for nal in [&SPS[..], &PPS[..]].iter() {
+            es.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+            es.extend_from_slice(nal);
+        }
+    }
+    es.extend_from_slice(&[0x00, 0x00, 0x01]);
+    es.extend_from_slice(if primary_pic_type == 0 {
+        &SLICE_IDR
+    } else {
+        &SLICE_P
+    });
+    es.extend_from_slice(&[0x00, 0x00, 0x01, 0x0C]);
+    es.resize(sz - 1, 0xFF);
+    // rbsp_trailing_bits
+    es.push(0x80);
+
+    es
+}
+
+/// ADTS header (AAC LC, 48kHz, stereo) + zeroed raw data block
+fn frame_audio(sz: usize) -> Vec<u8> {
+    let mut es = vec![
+        0xFF,
+        0xF1,
+        0x4C,
+        0x80 | ((sz >> 11) as u8 & 0x03),
+        (sz >> 3) as u8,
+        ((sz as u8 & 0x07) << 5) | 0x1F,
+        0xFC,
+    ];
+    es.resize(sz, 0x00);
+
+    es
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::thread;
+
+    use super::*;
+    use crate::demuxer::Demuxer;
+    use crate::filter::{Consumer, Producer};
+    use crate::metrics::{Metrics, Value};
+    use crate::pcr::Pcr;
+    use crate::pts::Pts;
+    use crate::tr101290::Tr101290;
+
+    #[test]
+    fn bitrate() {
+        assert_eq!(bitrate_parse("512000"), Some(512_000));
+        assert_eq!(bitrate_parse("512k"), Some(512_000));
+        assert_eq!(bitrate_parse("2.5M"), Some(2_500_000));
+        assert_eq!(bitrate_parse("1G"), Some(1_000_000_000));
+
+        assert_eq!(bitrate_parse("2.5Mb"), None);
+        assert_eq!(bitrate_parse("2x"), None);
+        assert_eq!(bitrate_parse("M"), None);
+        assert_eq!(bitrate_parse("0"), None);
+        assert_eq!(bitrate_parse("-1M"), None);
+    }
+
+    /// run generator through demuxer and analyzers;
+    /// last packet is held back until metrics are due.
+    /// demuxer publishes every 256 packets
+    fn analyze(url: &str) -> (Stats, BTreeMap<String, Value>) {
+        let url = Url::parse(url).unwrap();
+        let mut gen = Gen::new(Params::from_url(&url).unwrap());
+
+        let metrics = Metrics::default();
+        let mut demuxer = Demuxer::new(url.clone());
+        demuxer.metrics(metrics.clone());
+
+        let mut tr101290 = Tr101290::new(url.clone());
+        tr101290.metrics(metrics.clone());
+        let mut pcr = Pcr::new(url.clone());
+        pcr.metrics(metrics.clone());
+        let mut pts = Pts::new(url.clone());
+        pts.metrics(metrics.clone());
+
+        demuxer.add_consumer(Box::new(tr101290));
+        demuxer.add_consumer(Box::new(pcr));
+        demuxer.add_consumer(Box::new(pts));
+
+        let mut last = gen.next_packet().unwrap();
+        while let Some(pkt) = gen.next_packet() {
+            demuxer.consume_pkt_raw(&last);
+            last = pkt;
+        }
+
+        thread::sleep(Duration::from_millis(1100));
+        demuxer.consume_pkt_raw(&last);
+
+        (gen.stats(), metrics.snapshot().unwrap())
+    }
+
+    /// ":key value" of formatted metric
+    fn field(metrics: &BTreeMap<String, Value>, metric: &str, key: &str) -> u64 {
+        let text = metrics.get(metric).unwrap().to_string();
+        let needle = format!(":{} ", key);
+        let pos = text.find(&needle).unwrap() + needle.len();
+
+        text[pos..]
+            .split(|c: char| !c.is_ascii_digit())
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap()
+    }
+
+    fn counter(metrics: &BTreeMap<String, Value>, priority: u8, check: &str) -> u64 {
+        field(
+            metrics,
+            &format!("tr101290-p{}-{}", priority, check),
+            "count",
+        )
+    }
+
+    #[test]
+    fn params() {
+        let url = Url::parse("gen://mpts?bitrate=20M&format=ts").unwrap();
+        let params = Params::from_url(&url).unwrap();
+        assert_eq!(params.programs, 3);
+        assert_eq!(params.bitrate, 20_000_000);
+
+        for url in [
+            "gen://spts?format=mp4",
+            "gen://spts?bitrate=20000000000000000G",
+            "gen://spts?bitrate=0",
+            "gen://spts?programs=17",
+            "gen://hls",
+        ] {
+            assert!(
+                Params::from_url(&Url::parse(url).unwrap()).is_err(),
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn at() {
+        let mut gen = Gen::new(Default::default());
+        // stream time close to u64::MAX
+        gen.n = u64::MAX / (8 * PKT_SZ as u64 * CLOCK / 4_000_000);
+
+        assert_eq!(gen.at().as_secs(), gen.time(gen.n) / CLOCK);
+    }
+
+    #[test]
+    fn spts() {
+        let (stats, metrics) = analyze("gen://spts?realtime=0&packets=20480");
+        assert_eq!(stats.packets, 20480);
+
+        assert_eq!(metrics["ts-packets"], Value::Counter(20480));
+        assert_eq!(metrics["ts-programs"], Value::Counter(1));
+        assert_eq!(metrics["ts-tracks"], Value::Counter(2));
+
+        assert_eq!(counter(&metrics, 1, "pat-error-2"), 0);
+        assert_eq!(counter(&metrics, 1, "pmt-error-2"), 0);
+        assert_eq!(counter(&metrics, 1, "continuity-count-error"), 0);
+        assert_eq!(counter(&metrics, 2, "pcr-repetition-error"), 0);
+        assert_eq!(counter(&metrics, 2, "pts-error"), 0);
+
+        assert_eq!(field(&metrics, "pcr-0x0101", "discontinuities"), 0);
+        for pid in ["0x0101", "0x0102"] {
+            let metric = format!("pts-{}", pid);
+            assert!(field(&metrics, &metric, "pes") > 0);
+            assert_eq!(field(&metrics, &metric, "no-pts"), 0);
+            assert_eq!(field(&metrics, &metric, "backwards"), 0);
+        }
+    }
+
+    #[test]
+    fn cc_error() {
+        let (stats, metrics) = analyze("gen://spts?realtime=0&packets=20480&cc-error=1000");
+        assert!(stats.cc_errors > 0);
+
+        assert_eq!(
+            counter(&metrics, 1, "continuity-count-error"),
+            stats.cc_errors
+        );
+    }
+
+    #[test]
+    fn pcr_jump() {
+        let (stats, metrics) = analyze("gen://spts?realtime=0&packets=20480&pcr-jump=20");
+        assert!(stats.pcr_jumps > 0);
+
+        assert_eq!(
+            field(&metrics, "pcr-0x0101", "discontinuities"),
+            stats.pcr_jumps
+        );
+        assert_eq!(
+            counter(&metrics, 2, "pcr-discontinuity-indicator-error"),
+            stats.pcr_jumps
+        );
+    }
+
+    #[test]
+    fn mpts_no_pat() {
+        let (_, metrics) = analyze("gen://mpts?realtime=0&packets=20480&no-pat=1");
+
+        assert!(counter(&metrics, 1, "pat-error-2") > 0);
+        assert_eq!(metrics["ts-programs"], Value::Counter(0));
+        assert_eq!(counter(&metrics, 1, "continuity-count-error"), 0);
+    }
+}
@@ -0,0 +1,332 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
+use url::Url;
+
+use crate::clock::{wrapping_diff, Clock, Stat, Throttle, TS_MAX};
+use crate::filter::Consumer;
+use crate::frame::{Frame, Picture, PictureType};
+use crate::metrics::Metrics;
+use crate::track::Track;
+
+/// longer GOP pattern is cut
+const PATTERN_MAX: usize = 64;
+
+/// min/mean/max
+fn range(stat: &Stat) -> String {
+    match (stat.min, stat.mean(), stat.max) {
+        (Some(min), Some(mean), Some(max)) => format!("{:.0}/{:.0}/{:.0}", min, mean, max),
+        _ => "~".to_string(),
+    }
+}
+
+fn type_index(kind: PictureType) -> usize {
+    match kind {
+        PictureType::Idr => 0,
+        PictureType::I => 1,
+        PictureType::P => 2,
+        PictureType::B => 3,
+    }
+}
+
+/// per video elementary stream GOP structure
+#[derive(Default)]
+struct Stream {
+    codec: &'static str,
+
+    /// picture and size of last frame waiting for its second field
+    pending: Option<(PictureType, usize)>,
+
+    /// current GOP (from intra picture): coding types in decode order
+    pattern: String,
+    frames: u64,
+    closed: bool,
+    /// decode time of GOP intra picture; 90kHz
+    key_at: Option<u64>,
+    /// stream time of GOP intra picture (first picture until then)
+    key_clock_at: Option<Duration>,
+
+    /// frames and decode time since last IDR
+    idr_frames: Option<u64>,
+    idr_at: Option<u64>,
+
+    gops: u64,
+    gops_closed: u64,
+    gops_open: u64,
+    lengths: Stat,
+    /// last complete GOP
+    length: Option<u64>,
+    last_pattern: Option<String>,
+    /// frames, ms
+    idr_interval: Option<(u64, Option<f64>)>,
+    /// ms
+    keyframe_interval: Option<f64>,
+
+    /// by IDR, I, P, B
+    counts: [u64; 4],
+    /// bytes by IDR, I, P, B
+    sizes: [Stat; 4],
+
+    alarm: bool,
+    alarms: u64,
+}
+
+#[derive(Default)]
+struct State {
+    clock: Clock,
+    /// stream time of current packet
+    now: Duration,
+
+    streams: BTreeMap<u16, Stream>,
+}
+
+/// frame types, GOP length/pattern, IDR interval and frame sizes per
+/// video PID by slice / picture headers (no decoding).
+///
+/// GOP starts at every intra picture; alarm is raised when intra
+/// pictures are further apart than keyframe-interval-max, or no intra
+/// picture follows for that long by stream time
+pub struct Gop {
+    url: Url,
+    keyframe_interval_max: Duration,
+
+    state: RefCell<State>,
+
+    metrics: Metrics,
+    metrics_at: Throttle,
+}
+
+impl Gop {
+    pub fn new(url: Url) -> Gop {
+        Gop {
+            url,
+            keyframe_interval_max: Duration::from_secs(2),
+            state: Default::default(),
+            metrics: Default::default(),
+            metrics_at: Default::default(),
+        }
+    }
+
+    /// keyframe interval alarm threshold
+    pub fn keyframe_interval_max(&mut self, keyframe_interval_max: Duration) -> &Gop {
+        self.keyframe_interval_max = keyframe_interval_max;
+        self
+    }
+
+    pub fn metrics(&mut self, metrics: Metrics) -> &Gop {
+        self.metrics = metrics;
+        self
+    }
+
+    fn alarm(&self, stream: &mut Stream, pid: u16, alarm: bool, interval: f64) {
+        let max = self.keyframe_interval_max.as_secs_f64() * 1000.0;
+
+        if alarm && !stream.alarm {
+            stream.alarms += 1;
+            warn!(
+                "({}) [gop] keyframe interval alarm raised (:pid 0x{:04X} :interval {:.3}ms :threshold {}ms)",
+                self.url, pid, interval, max
+            );
+        } else if !alarm && stream.alarm {
+            info!(
+                "({}) [gop] keyframe interval alarm cleared (:pid 0x{:04X} :interval {:.3}ms)",
+                self.url, pid, interval
+            );
+        }
+        stream.alarm = alarm;
+    }
+
+    fn gop_end(&self, stream: &mut Stream, pid: u16) {
+        if stream.closed {
+            stream.gops_closed += 1;
+        } else {
+            stream.gops_open += 1;
+        }
+        stream.gops += 1;
+        stream.lengths.add(stream.frames as f64);
+        stream.length = Some(stream.frames);
+
+        debug!(
+            "({}) [gop] GOP (:pid 0x{:04X} :length {} :pattern {} :closed {})",
+            self.url, pid, stream.frames, stream.pattern, stream.closed
+        );
+        stream.last_pattern = Some(stream.pattern.clone());
+    }
+
+    fn picture(&self, stream: &mut Stream, frm: &Frame, picture: Picture, now: Duration) {
+        let size = frm.data.len();
+        if picture.second_field {
+            if let Some((_, pending)) = stream.pending.as_mut() {
+                *pending += size;
+            }
+            return;
+        }
+        if let Some((kind, size)) = stream.pending.replace((picture.kind, size)) {
+            stream.counts[type_index(kind)] += 1;
+            stream.sizes[type_index(kind)].add(size as f64);
+        }
+
+        let max = self.keyframe_interval_max.as_secs_f64() * 1000.0;
+        let at = frm.dts.or(frm.pts);
+        let since = |from: Option<u64>| match (at, from) {
+            (Some(at), Some(from)) => Some(wrapping_diff(at, from, TS_MAX) as f64 / 90.0),
+            _ => None,
+        };
+
+        let key_clock_at = *stream.key_clock_at.get_or_insert(now);
+
+        match picture.kind {
+            PictureType::Idr | PictureType::I => {
+                let first = stream.key_at.is_none() && stream.frames == 0;
+                if !first {
+                    self.gop_end(stream, frm.pid);
+                }
+
+                // stream time when intra picture has no timestamps
+                let interval = since(stream.key_at).or_else(|| {
+                    (!first).then(|| now.saturating_sub(key_clock_at).as_secs_f64() * 1000.0)
+                });
+                if let Some(interval) = interval {
+                    stream.keyframe_interval = Some(interval);
+                    self.alarm(stream, frm.pid, interval > max, interval);
+                }
+                stream.key_at = at;
+                stream.key_clock_at = Some(now);
+                stream.pattern.clear();
+                stream.frames = 0;
+                stream.closed = picture.closed;
+
+                if picture.kind == PictureType::Idr {
+                    if let Some(frames) = stream.idr_frames {
+                        stream.idr_interval = Some((frames, since(stream.idr_at)));
+                    }
+                    stream.idr_frames = Some(0);
+                    stream.idr_at = at;
+                }
+            }
+            // before first intra picture
+            _ if stream.key_at.is_none() && stream.frames == 0 => return,
+            _ => {}
+        }
+
+        stream.frames += 1;
+        if stream.pattern.len() < PATTERN_MAX {
+            stream.pattern.push(picture.kind.letter());
+        }
+        if let Some(frames) = stream.idr_frames.as_mut() {
+            *frames += 1;
+        }
+    }
+
+    /// no intra picture for too long; also for stalled PIDs
+    fn timeouts(&self, state: &mut State) {
+        let now = state.now;
+
+        for (pid, stream) in state.streams.iter_mut() {
+            let since = match stream.key_clock_at {
+                Some(key_clock_at) if !stream.alarm => now.saturating_sub(key_clock_at),
+                _ => continue,
+            };
+            if since > self.keyframe_interval_max {
+                self.alarm(stream, *pid, true, since.as_secs_f64() * 1000.0);
+            }
+        }
+    }
+
+    fn metrics_publish(&self) {
+        if !self.metrics_at.ready() {
+            return;
+        }
+
+        let state = self.state.borrow();
+        let opt = |v: Option<u64>| v.map(|v| v.to_string()).unwrap_or_else(|| "~".to_string());
+
+        for (pid, stream) in state.streams.iter() {
+            if stream.counts.iter().sum::<u64>() == 0 {
+                continue;
+            }
+
+            let idr_interval = match stream.idr_interval {
+                Some((frames, Some(ms))) => format!("{}/{:.3}ms", frames, ms),
+                Some((frames, None)) => frames.to_string(),
+                None => "~".to_string(),
+            };
+
+            self.metrics.set(
+                format!("gop-0x{:04X}", pid),
+                format!(
+                    "(:gops {} :length {} :length-min {} :length-max {} :pattern {} :closed {} :open {} :idr-interval {} :keyframe-interval {} :alarms {} :frames-idr {} :frames-i {} :frames-p {} :frames-b {} :size-idr {} :size-i {} :size-p {} :size-b {})",
+                    stream.gops,
+                    opt(stream.length),
+                    opt(stream.lengths.min.map(|v| v as u64)),
+                    opt(stream.lengths.max.map(|v| v as u64)),
+                    stream.last_pattern.as_deref().unwrap_or("~"),
+                    stream.gops_closed,
+                    stream.gops_open,
+                    idr_interval,
+                    stream
+                        .keyframe_interval
+                        .map(|ms| format!("{:.3}ms", ms))
+                        .unwrap_or_else(|| "~".to_string()),
+                    stream.alarms,
+                    stream.counts[0],
+                    stream.counts[1],
+                    stream.counts[2],
+                    stream.counts[3],
+                    range(&stream.sizes[0]),
+                    range(&stream.sizes[1]),
+                    range(&stream.sizes[2]),
+                    range(&stream.sizes[3])
+                ),
+            );
+        }
+    }
+}
+
+impl Consumer for Gop {
+    fn consume_trk(&self, trk: &Track) {
+        let mut state = self.state.borrow_mut();
+
+        let codec = trk.codec();
+        if state
+            .streams
+            .get(&trk.pid)
+            .is_none_or(|stream| stream.codec != codec)
+        {
+            state.streams.insert(
+                trk.pid,
+                Stream {
+                    codec,
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    fn consume_pkt_raw(&self, pkt_raw: &[u8]) {
+        if let Ok(pkt) = ts::Packet::new(pkt_raw) {
+            let mut state = self.state.borrow_mut();
+            state.now = Duration::from_nanos(state.clock.update(&pkt, Instant::now()));
+            self.timeouts(&mut state);
+        }
+    }
+
+    fn consume_frm(&self, frm: &Frame) {
+        let picture = match frm.picture {
+            Some(picture) => picture,
+            None => return,
+        };
+
+        {
+            let mut state = self.state.borrow_mut();
+            let now = state.now;
+            if let Some(stream) = state.streams.get_mut(&frm.pid) {
+                self.picture(stream, frm, picture, now);
+            }
+        }
+
+        self.metrics_publish();
+    }
+}
@@ -0,0 +1,781 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::bits::Bits;
+use crate::colour::{self, Colour};
+use crate::frame::{Picture, PictureType};
+use crate::nal;
+use crate::probe::{json_opt_str, json_str};
+use crate::sei::{self, Sei};
+
+pub const NAL_SLICE: u8 = 1;
+pub const NAL_IDR: u8 = 5;
+pub const NAL_SEI: u8 = 6;
+pub const NAL_SPS: u8 = 7;
+pub const NAL_PPS: u8 = 8;
+
+/// seq_parameter_set_id and pic_parameter_set_id ranges (7.4.2.1.1, 7.4.2.2)
+const SPS_ID_MAX: u32 = 31;
+const PPS_ID_MAX: u32 = 255;
+
+/// Table E-1
+const SAR: [(u16, u16); 17] = [
+    (0, 0),
+    (1, 1),
+    (12, 11),
+    (10, 11),
+    (16, 11),
+    (40, 33),
+    (24, 11),
+    (20, 11),
+    (32, 11),
+    (80, 33),
+    (18, 11),
+    (15, 11),
+    (64, 33),
+    (160, 99),
+    (4, 3),
+    (3, 2),
+    (2, 1),
+];
+
+/// 25.000 -> 25, 29.970 -> 29.97
+pub fn fps_str(fps: f64) -> String {
+    let s = format!("{:.3}", fps);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+pub fn chroma_str(chroma_format_idc: u8) -> &'static str {
+    match chroma_format_idc {
+        0 => "4:0:0",
+        1 => "4:2:0",
+        2 => "4:2:2",
+        _ => "4:4:4",
+    }
+}
+
+/// parameter set by id; true if new or differs from stored one
+pub fn insert<K: Ord, T: PartialEq>(sets: &mut BTreeMap<K, T>, id: K, set: T) -> bool {
+    if sets.get(&id) == Some(&set) {
+        return false;
+    }
+    sets.insert(id, set);
+    true
+}
+
+/// video_signal_type and timing from VUI
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Vui {
+    /// sample aspect ratio
+    pub sar: Option<(u16, u16)>,
+
+    pub full_range: bool,
+    pub colour: Option<Colour>,
+
+    pub num_units_in_tick: Option<u32>,
+    pub time_scale: Option<u32>,
+    pub fixed_frame_rate: bool,
+
+    /// first NAL HRD SchedSel; bits per second / bits
+    pub hrd_bitrate: Option<u64>,
+    pub hrd_cpb_size: Option<u64>,
+}
+
+/// sequence parameter set (7.3.2.1.1)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sps {
+    pub id: u32,
+
+    pub profile_idc: u8,
+    /// constraint_set0..5_flag
+    pub constraint_flags: u8,
+    pub level_idc: u8,
+
+    pub chroma_format_idc: u8,
+    pub bit_depth_luma: u8,
+    pub bit_depth_chroma: u8,
+
+    /// cropped
+    pub width: u32,
+    pub height: u32,
+
+    pub separate_colour_plane: bool,
+    /// frame_num bits in slice header
+    pub log2_max_frame_num: u8,
+
+    pub frame_mbs_only: bool,
+    pub mb_adaptive_frame_field: bool,
+
+    pub max_num_ref_frames: u32,
+
+    pub vui: Option<Vui>,
+}
+
+impl Sps {
+    pub fn parse(rbsp: &[u8]) -> Option<Sps> {
+        let mut r = Bits::new(rbsp);
+
+        let profile_idc = r.u8(8)?;
+        let constraint_flags = r.u8(8)?;
+        let level_idc = r.u8(8)?;
+        let id = r.ue().filter(|id| *id <= SPS_ID_MAX)?;
+
+        let (mut chroma_format_idc, mut bit_depth_luma, mut bit_depth_chroma) = (1, 8, 8);
+        let mut separate_colour_plane = false;
+        if matches!(
+            profile_idc,
+            100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135
+        ) {
+            chroma_format_idc = r.ue()?.min(3) as u8;
+            if chroma_format_idc == 3 {
+                separate_colour_plane = r.flag()?;
+            }
+            bit_depth_luma = 8 + r.ue()?.min(8) as u8;
+            bit_depth_chroma = 8 + r.ue()?.min(8) as u8;
+            // qpprime_y_zero_transform_bypass_flag
+            r.skip(1)?;
+            if r.flag()? {
+                let lists = if chroma_format_idc == 3 { 12 } else { 8 };
+                for i in 0..lists {
+                    if r.flag()? {
+                        scaling_list(&mut r, if i < 6 { 16 } else { 64 })?;
+                    }
+                }
+            }
+        }
+
+        let log2_max_frame_num = 4 + r.ue()?.min(12) as u8;
+        match r.ue()? {
+            0 => {
+                // log2_max_pic_order_cnt_lsb_minus4
+                r.ue()?;
+            }
+            1 => {
+                // delta_pic_order_always_zero_flag
+                r.skip(1)?;
+                // offset_for_non_ref_pic, offset_for_top_to_bottom_field
+                r.se()?;
+                r.se()?;
+                for _ in 0..r.ue()? {
+                    r.se()?;
+                }
+            }
+            _ => {}
+        }
+
+        let max_num_ref_frames = r.ue()?;
+        // gaps_in_frame_num_value_allowed_flag
+        r.skip(1)?;
+        let width_mbs = r.ue()?.checked_add(1)?;
+        let height_map_units = r.ue()?.checked_add(1)?;
+        let frame_mbs_only = r.flag()?;
+        let mb_adaptive_frame_field = if frame_mbs_only { false } else { r.flag()? };
+        // direct_8x8_inference_flag
+        r.skip(1)?;
+
+        let mut crop = (0, 0, 0, 0);
+        if r.flag()? {
+            crop = (r.ue()?, r.ue()?, r.ue()?, r.ue()?);
+        }
+
+        let vui = if r.flag()? { vui(&mut r) } else { None };
+
+        // 7-19, 7-20
+        let field = 2 - u32::from(frame_mbs_only);
+        let (crop_x, crop_y): (u32, u32) = if separate_colour_plane || chroma_format_idc == 0 {
+            (1, field)
+        } else {
+            let sub_width = if chroma_format_idc == 3 { 1 } else { 2 };
+            let sub_height = if chroma_format_idc == 1 { 2 } else { 1 };
+            (sub_width, sub_height * field)
+        };
+
+        // None on overflow or cropping beyond picture
+        let width = width_mbs
+            .checked_mul(16)?
+            .checked_sub(crop_x.checked_mul(crop.0.checked_add(crop.1)?)?)?;
+        let height = height_map_units
+            .checked_mul(16 * field)?
+            .checked_sub(crop_y.checked_mul(crop.2.checked_add(crop.3)?)?)?;
+
+        Some(Sps {
+            id,
+            profile_idc,
+            constraint_flags,
+            level_idc,
+            chroma_format_idc,
+            bit_depth_luma,
+            bit_depth_chroma,
+            width,
+            height,
+            separate_colour_plane,
+            log2_max_frame_num,
+            frame_mbs_only,
+            mb_adaptive_frame_field,
+            max_num_ref_frames,
+            vui,
+        })
+    }
+
+    pub fn profile(&self) -> &'static str {
+        let cs = |n: u8| self.constraint_flags & (0x80 >> n) != 0;
+
+        match self.profile_idc {
+            66 if cs(1) => "constrained-baseline",
+            66 => "baseline",
+            77 => "main",
+            88 => "extended",
+            100 if cs(4) && cs(5) => "constrained-high",
+            100 if cs(4) => "progressive-high",
+            100 => "high",
+            110 if cs(3) => "high10-intra",
+            110 => "high10",
+            122 if cs(3) => "high422-intra",
+            122 => "high422",
+            244 if cs(3) => "high444-intra",
+            244 => "high444",
+            44 => "cavlc444-intra",
+            83 => "scalable-baseline",
+            86 => "scalable-high",
+            118 => "multiview-high",
+            128 => "stereo-high",
+            _ => "unknown",
+        }
+    }
+
+    pub fn level(&self) -> String {
+        // level 1b
+        if self.level_idc == 11 && (self.constraint_flags & 0x10) != 0 && self.profile_idc <= 88 {
+            return "1b".to_string();
+        }
+        if self.level_idc == 9 {
+            return "1b".to_string();
+        }
+
+        format!("{}.{}", self.level_idc / 10, self.level_idc % 10)
+    }
+
+    /// frames per second by VUI timing (two ticks per frame)
+    pub fn fps(&self) -> Option<f64> {
+        let vui = self.vui.as_ref()?;
+        let num_units_in_tick = vui.num_units_in_tick.filter(|v| *v > 0)?;
+        let time_scale = vui.time_scale.filter(|v| *v > 0)?;
+
+        Some(f64::from(time_scale) / (2.0 * f64::from(num_units_in_tick)))
+    }
+
+    pub fn scan(&self) -> &'static str {
+        if self.frame_mbs_only {
+            "progressive"
+        } else if self.mb_adaptive_frame_field {
+            "mbaff"
+        } else {
+            "interlaced"
+        }
+    }
+}
+
+/// skip scaling_list (7.3.2.1.1.1)
+fn scaling_list(r: &mut Bits, size: usize) -> Option<()> {
+    let (mut last, mut next) = (8i32, 8i32);
+    for _ in 0..size {
+        if next != 0 {
+            // delta_scale is -128..127
+            next = (last + r.se()?.clamp(-128, 127) + 256) % 256;
+        }
+        if next != 0 {
+            last = next;
+        }
+    }
+    Some(())
+}
+
+/// E.1.1; None on truncated VUI
+fn vui(r: &mut Bits) -> Option<Vui> {
+    let mut vui = Vui::default();
+
+    if r.flag()? {
+        let aspect_ratio_idc = r.u8(8)?;
+        vui.sar = if aspect_ratio_idc == 255 {
+            Some((r.bits(16)? as u16, r.bits(16)? as u16))
+        } else {
+            SAR.get(usize::from(aspect_ratio_idc))
+                .copied()
+                .filter(|(w, _)| *w > 0)
+        };
+    }
+
+    // overscan_info_present_flag, overscan_appropriate_flag
+    if r.flag()? {
+        r.skip(1)?;
+    }
+
+    // video_signal_type_present_flag
+    if r.flag()? {
+        // video_format
+        r.skip(3)?;
+        vui.full_range = r.flag()?;
+        if r.flag()? {
+            vui.colour = Some(Colour {
+                primaries: r.u8(8)?,
+                transfer: r.u8(8)?,
+                matrix: r.u8(8)?,
+            });
+        }
+    }
+
+    // chroma_loc_info_present_flag
+    if r.flag()? {
+        r.ue()?;
+        r.ue()?;
+    }
+
+    if r.flag()? {
+        vui.num_units_in_tick = Some(r.bits(32)?);
+        vui.time_scale = Some(r.bits(32)?);
+        vui.fixed_frame_rate = r.flag()?;
+    }
+
+    let nal_hrd = r.flag()?;
+    if nal_hrd {
+        let (bitrate, cpb_size) = hrd(r)?;
+        vui.hrd_bitrate = Some(bitrate);
+        vui.hrd_cpb_size = Some(cpb_size);
+    }
+    if r.flag()? {
+        hrd(r)?;
+    }
+
+    Some(vui)
+}
+
+/// hrd_parameters (E.1.2); first SchedSel bitrate and CPB size
+fn hrd(r: &mut Bits) -> Option<(u64, u64)> {
+    let cpb_cnt = r.ue()? + 1;
+    let bit_rate_scale = r.bits(4)?;
+    let cpb_size_scale = r.bits(4)?;
+
+    let mut first = None;
+    for _ in 0..cpb_cnt.min(32) {
+        let bit_rate = u64::from(r.ue()?) + 1;
+        let cpb_size = u64::from(r.ue()?) + 1;
+        // cbr_flag
+        r.skip(1)?;
+
+        first.get_or_insert((
+            bit_rate << (6 + bit_rate_scale),
+            cpb_size << (4 + cpb_size_scale),
+        ));
+    }
+
+    // initial_cpb_removal_delay_length_minus1, cpb_removal_delay_length_minus1,
+    // dpb_output_delay_length_minus1, time_offset_length
+    r.skip(20)?;
+
+    first
+}
+
+/// picture parameter set (7.3.2.2)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pps {
+    pub id: u32,
+    pub sps_id: u32,
+
+    /// CABAC
+    pub entropy_coding_mode: bool,
+    pub weighted_pred: bool,
+    pub weighted_bipred_idc: u8,
+    pub transform_8x8_mode: bool,
+}
+
+impl Pps {
+    pub fn parse(rbsp: &[u8]) -> Option<Pps> {
+        let mut r = Bits::new(rbsp);
+
+        let id = r.ue().filter(|id| *id <= PPS_ID_MAX)?;
+        let sps_id = r.ue().filter(|id| *id <= SPS_ID_MAX)?;
+        let entropy_coding_mode = r.flag()?;
+        // bottom_field_pic_order_in_frame_present_flag
+        r.skip(1)?;
+
+        let mut pps = Pps {
+            id,
+            sps_id,
+            entropy_coding_mode,
+            weighted_pred: false,
+            weighted_bipred_idc: 0,
+            transform_8x8_mode: false,
+        };
+
+        // slice groups (baseline only) are not parsed further
+        if r.ue()? > 0 {
+            return Some(pps);
+        }
+
+        // num_ref_idx_l0/l1_default_active_minus1
+        r.ue()?;
+        r.ue()?;
+        pps.weighted_pred = r.flag()?;
+        pps.weighted_bipred_idc = r.u8(2)?;
+        // pic_init_qp_minus26, pic_init_qs_minus26, chroma_qp_index_offset
+        r.se()?;
+        r.se()?;
+        r.se()?;
+        // deblocking_filter_control_present_flag, constrained_intra_pred_flag,
+        // redundant_pic_cnt_present_flag
+        r.skip(3)?;
+
+        if r.more_rbsp_data() {
+            pps.transform_8x8_mode = r.flag().unwrap_or(false);
+        }
+
+        Some(pps)
+    }
+}
+
+/// stream parameters attached to track
+#[derive(Clone, Debug, PartialEq)]
+pub struct H264 {
+    pub sps: Sps,
+    pub pps: Option<Pps>,
+    pub sei: Sei,
+}
+
+impl H264 {
+    pub fn colour(&self) -> Option<&Colour> {
+        self.sps.vui.as_ref().and_then(|vui| vui.colour.as_ref())
+    }
+
+    pub fn dynamic_range(&self) -> &'static str {
+        colour::dynamic_range(self.colour(), &self.sei)
+    }
+
+    pub fn hdr_issues(&self) -> Vec<&'static str> {
+        colour::hdr_issues(
+            self.colour(),
+            self.sps.bit_depth_luma,
+            self.sps.width,
+            &self.sei,
+        )
+    }
+
+    pub fn json(&self) -> String {
+        let sps = &self.sps;
+        let vui = sps.vui.clone().unwrap_or_default();
+        let opt = |v: Option<String>| v.unwrap_or_else(|| "null".to_string());
+
+        format!(
+            r#"{{"profile":{},"level":{},"width":{},"height":{},"chroma_format":"{}","bit_depth_luma":{},"bit_depth_chroma":{},"scan":"{}","fps":{},"sar":{},"colour":{},"full_range":{},"dynamic_range":"{}","hdr_issues":[{}],"hrd_bitrate":{},"refs":{},"entropy":{},{}}}"#,
+            json_str(sps.profile()),
+            json_str(&sps.level()),
+            sps.width,
+            sps.height,
+            chroma_str(sps.chroma_format_idc),
+            sps.bit_depth_luma,
+            sps.bit_depth_chroma,
+            sps.scan(),
+            opt(sps.fps().map(fps_str)),
+            opt(vui.sar.map(|(w, h)| format!(r#""{}:{}""#, w, h))),
+            opt(vui.colour.map(|colour| colour.json())),
+            vui.full_range,
+            self.dynamic_range(),
+            self.hdr_issues()
+                .iter()
+                .map(|issue| format!(r#""{}""#, issue))
+                .collect::<Vec<_>>()
+                .join(","),
+            opt(vui.hrd_bitrate.map(|v| v.to_string())),
+            sps.max_num_ref_frames,
+            json_opt_str(self.pps.as_ref().map(|pps| {
+                if pps.entropy_coding_mode {
+                    "cabac"
+                } else {
+                    "cavlc"
+                }
+            })),
+            self.sei.json_fields()
+        )
+    }
+}
+
+impl fmt::Display for H264 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sps = &self.sps;
+
+        write!(
+            f,
+            ":profile {} :level {} :size {}x{} :chroma {} :bit-depth {} :scan {}",
+            sps.profile(),
+            sps.level(),
+            sps.width,
+            sps.height,
+            chroma_str(sps.chroma_format_idc),
+            sps.bit_depth_luma,
+            sps.scan()
+        )?;
+        if let Some(fps) = sps.fps() {
+            write!(f, " :fps {}", fps_str(fps))?;
+        }
+        if let Some(vui) = sps.vui.as_ref() {
+            if let Some((w, h)) = vui.sar {
+                write!(f, " :sar {}:{}", w, h)?;
+            }
+            if let Some(colour) = vui.colour.as_ref() {
+                write!(f, " {}", colour)?;
+            }
+            if vui.full_range {
+                write!(f, " :range full")?;
+            }
+            if let Some(bitrate) = vui.hrd_bitrate {
+                write!(f, " :hrd-bitrate {}", bitrate)?;
+            }
+        }
+        write!(f, " :refs {}", sps.max_num_ref_frames)?;
+        if let Some(pps) = self.pps.as_ref() {
+            write!(
+                f,
+                " :entropy {}",
+                if pps.entropy_coding_mode {
+                    "cabac"
+                } else {
+                    "cavlc"
+                }
+            )?;
+        }
+        if self.dynamic_range() != "sdr" {
+            write!(f, " :dynamic-range {}", self.dynamic_range())?;
+        }
+
+        write!(f, "{}", self.sei)
+    }
+}
+
+/// parameter sets and SEI of one stream
+#[derive(Default)]
+pub struct Headers {
+    sps: BTreeMap<u32, Sps>,
+    pps: BTreeMap<u32, Pps>,
+    sei: Sei,
+    /// (payloadType, payload) of last pushed access unit
+    messages: Vec<(u32, Vec<u8>)>,
+
+    /// referenced by last slice
+    pps_id: Option<u32>,
+
+    /// first slice of last pushed access unit
+    picture: Option<Picture>,
+    /// (frame_num, bottom_field_flag) of previous field without its pair
+    field: Option<(u32, bool)>,
+}
+
+impl Headers {
+    /// non-VCL NAL units of access unit up to first slice;
+    /// true if parameter sets, SEI or active PPS changed
+    pub fn push(&mut self, au: &[u8]) -> bool {
+        let pps_id = self.pps_id;
+        let mut changed = false;
+        self.picture = None;
+        self.messages.clear();
+
+        for unit in nal::units(au) {
+            if unit.is_empty() {
+                continue;
+            }
+
+            let nal_type = unit[0] & 0x1F;
+            match nal_type {
+                NAL_SPS => {
+                    if let Some(sps) = Sps::parse(&nal::rbsp(&unit[1..])) {
+                        changed |= insert(&mut self.sps, sps.id, sps);
+                    }
+                }
+                NAL_PPS => {
+                    if let Some(pps) = Pps::parse(&nal::rbsp(&unit[1..])) {
+                        changed |= insert(&mut self.pps, pps.id, pps);
+                    }
+                }
+                NAL_SEI => {
+                    let rbsp = nal::rbsp(&unit[1..]);
+                    let messages = sei::messages(&rbsp);
+                    changed |= self.sei.parse("h264", &messages);
+                    self.messages.extend(
+                        messages
+                            .into_iter()
+                            .map(|(t, payload)| (t, payload.to_vec())),
+                    );
+                }
+                NAL_SLICE | NAL_IDR => {
+                    let rbsp = nal::rbsp(&unit[1..unit.len().min(24)]);
+                    self.slice(nal_type == NAL_IDR, &rbsp);
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        changed || self.pps_id != pps_id
+    }
+
+    /// slice header (7.3.3) up to field flags
+    fn slice(&mut self, idr: bool, rbsp: &[u8]) -> Option<()> {
+        let mut r = Bits::new(rbsp);
+
+        // first_mb_in_slice
+        r.ue()?;
+        let kind = match r.ue()? % 5 {
+            0 | 3 => PictureType::P,
+            1 => PictureType::B,
+            _ if idr => PictureType::Idr,
+            _ => PictureType::I,
+        };
+        let pps_id = r.ue().filter(|id| *id <= PPS_ID_MAX)?;
+        self.pps_id = Some(pps_id);
+
+        let mut second_field = false;
+        let sps = self
+            .pps
+            .get(&pps_id)
+            .and_then(|pps| self.sps.get(&pps.sps_id));
+        if let Some(sps) = sps {
+            if sps.separate_colour_plane {
+                r.skip(2)?;
+            }
+            let frame_num = r.bits(usize::from(sps.log2_max_frame_num))?;
+            let field = if !sps.frame_mbs_only && r.flag()? {
+                Some(r.flag()?)
+            } else {
+                None
+            };
+
+            // field pair: same frame_num, opposite parity
+            second_field = match (self.field, field) {
+                (Some((num, bottom)), Some(b)) if num == frame_num && bottom != b => {
+                    self.field = None;
+                    true
+                }
+                _ => {
+                    self.field = field.map(|bottom| (frame_num, bottom));
+                    false
+                }
+            };
+        }
+
+        self.picture = Some(Picture {
+            kind,
+            closed: idr,
+            second_field,
+        });
+
+        Some(())
+    }
+
+    /// by active PPS; falls back to last SPS
+    pub fn params(&self) -> Option<H264> {
+        let pps = self
+            .pps_id
+            .and_then(|pps_id| self.pps.get(&pps_id))
+            .or_else(|| self.pps.values().next());
+        let sps = pps
+            .and_then(|pps| self.sps.get(&pps.sps_id))
+            .or_else(|| self.sps.values().next())?;
+
+        Some(H264 {
+            sps: sps.clone(),
+            pps: pps.cloned(),
+            sei: self.sei.clone(),
+        })
+    }
+
+    /// first slice of last pushed access unit
+    pub fn picture(&self) -> Option<Picture> {
+        self.picture
+    }
+
+    /// SEI messages of last pushed access unit
+    pub fn take_messages(&mut self) -> Vec<(u32, Vec<u8>)> {
+        std::mem::take(&mut self.messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// High@4.0 1920x1080 25fps BT.709
+    const SPS: [u8; 27] = [
+        0x67, 0x64, 0x00, 0x28, 0xAC, 0xD9, 0x40, 0x78, 0x02, 0x27, 0xE5, 0xC0, 0x5A, 0x80, 0x80,
+        0x80, 0xA0, 0x00, 0x00, 0x03, 0x00, 0x20, 0x00, 0x00, 0x06, 0x50, 0x80,
+    ];
+    /// CABAC, 8x8 transform
+    const PPS: [u8; 5] = [0x68, 0xEB, 0xCC, 0xB2, 0x2C];
+    /// IDR slice header only (frame_num 0)
+    const SLICE_IDR: [u8; 4] = [0x65, 0x88, 0x84, 0x08];
+
+    /// user_data_unregistered SEI NAL of payload_size
+    fn sei(payload_size: u8) -> Vec<u8> {
+        let mut nal = vec![0x06, 0x05, payload_size];
+        nal.extend_from_slice(&[0xAA; 16]);
+        nal.extend_from_slice(b"x264 - H.264/MPEG-4 AVC codec");
+        nal.push(0x80);
+        nal
+    }
+
+    fn au(nals: &[&[u8]]) -> Vec<u8> {
+        let mut au = Vec::new();
+        for nal in nals {
+            au.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+            au.extend_from_slice(nal);
+        }
+        au
+    }
+
+    #[test]
+    fn valid() {
+        let sps = Sps::parse(&nal::rbsp(&SPS[1..])).unwrap();
+        assert_eq!(sps.profile(), "high");
+        assert_eq!(sps.level(), "4.0");
+        assert_eq!((sps.width, sps.height), (1920, 1080));
+        assert_eq!(sps.fps(), Some(25.0));
+
+        let mut headers = Headers::default();
+        assert!(headers.push(&au(&[&SPS, &PPS, &sei(45), &SLICE_IDR])));
+
+        let params = headers.params().unwrap();
+        assert!(params.pps.unwrap().entropy_coding_mode);
+        assert_eq!(params.sei.encoder.as_deref(), Some("x264"));
+        assert_eq!(headers.picture().unwrap().kind, PictureType::Idr);
+        assert_eq!(headers.take_messages().len(), 1);
+    }
+
+    #[test]
+    fn truncated() {
+        let rbsp = nal::rbsp(&SPS[1..]);
+        for len in 0..rbsp.len() {
+            if let Some(sps) = Sps::parse(&rbsp[..len]) {
+                // VUI is optional
+                assert_eq!((sps.width, sps.height), (1920, 1080));
+            }
+        }
+        assert!(Sps::parse(&rbsp[..8]).is_none());
+
+        for len in 0..PPS.len() {
+            Pps::parse(&PPS[1..len.max(1)]);
+        }
+
+        let au = au(&[&SPS, &PPS, &sei(45), &SLICE_IDR]);
+        for len in 0..au.len() {
+            Headers::default().push(&au[..len]);
+        }
+    }
+
+    #[test]
+    fn length_overrun() {
+        assert!(sei::messages(&nal::rbsp(&sei(0xFE)[1..])).is_empty());
+
+        let mut headers = Headers::default();
+        headers.push(&au(&[&SPS, &PPS, &sei(0xFE), &SLICE_IDR]));
+        assert!(headers.params().unwrap().sei.encoder.is_none());
+        assert!(headers.take_messages().is_empty());
+        assert_eq!(headers.picture().unwrap().kind, PictureType::Idr);
+    }
+}
@@ -0,0 +1,819 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::bits::Bits;
+use crate::colour::{self, Colour};
+use crate::frame::{Picture, PictureType};
+use crate::h264::{chroma_str, fps_str, insert};
+use crate::nal;
+use crate::probe::json_str;
+use crate::sei::{self, Sei};
+
+pub const NAL_BLA_W_LP: u8 = 16;
+pub const NAL_IDR_W_RADL: u8 = 19;
+pub const NAL_IDR_N_LP: u8 = 20;
+pub const NAL_CRA: u8 = 21;
+pub const NAL_RSV_IRAP_23: u8 = 23;
+pub const NAL_VPS: u8 = 32;
+pub const NAL_SPS: u8 = 33;
+pub const NAL_PPS: u8 = 34;
+pub const NAL_PREFIX_SEI: u8 = 39;
+pub const NAL_SUFFIX_SEI: u8 = 40;
+/// Dolby Vision RPU (unspecified NAL type)
+pub const NAL_DOVI_RPU: u8 = 62;
+
+/// Table E-1
+const SAR: [(u16, u16); 17] = [
+    (0, 0),
+    (1, 1),
+    (12, 11),
+    (10, 11),
+    (16, 11),
+    (40, 33),
+    (24, 11),
+    (20, 11),
+    (32, 11),
+    (80, 33),
+    (18, 11),
+    (15, 11),
+    (64, 33),
+    (160, 99),
+    (4, 3),
+    (3, 2),
+    (2, 1),
+];
+
+/// nal_unit_type of 2-byte NAL unit header
+pub fn nal_type(unit: &[u8]) -> Option<u8> {
+    unit.first().map(|b| (b >> 1) & 0x3F)
+}
+
+/// general profile_tier_level (7.3.3)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ptl {
+    pub profile_idc: u8,
+    pub tier: bool,
+    pub level_idc: u8,
+    pub progressive_source: bool,
+    pub interlaced_source: bool,
+}
+
+impl Ptl {
+    fn parse(r: &mut Bits, max_sub_layers_minus1: usize) -> Option<Ptl> {
+        // general_profile_space
+        r.skip(2)?;
+        let tier = r.flag()?;
+        let profile_idc = r.u8(5)?;
+        // general_profile_compatibility_flag[32]
+        r.skip(32)?;
+        let progressive_source = r.flag()?;
+        let interlaced_source = r.flag()?;
+        // non_packed_constraint, frame_only_constraint, 43 reserved bits, inbld
+        r.skip(2 + 43 + 1)?;
+        let level_idc = r.u8(8)?;
+
+        let mut sub_layers = Vec::with_capacity(max_sub_layers_minus1);
+        for _ in 0..max_sub_layers_minus1 {
+            sub_layers.push((r.flag()?, r.flag()?));
+        }
+        if max_sub_layers_minus1 > 0 {
+            r.skip(2 * (8 - max_sub_layers_minus1))?;
+        }
+        for (profile_present, level_present) in sub_layers {
+            if profile_present {
+                r.skip(88)?;
+            }
+            if level_present {
+                r.skip(8)?;
+            }
+        }
+
+        Some(Ptl {
+            profile_idc,
+            tier,
+            level_idc,
+            progressive_source,
+            interlaced_source,
+        })
+    }
+
+    pub fn profile(&self) -> &'static str {
+        match self.profile_idc {
+            1 => "main",
+            2 => "main10",
+            3 => "main-still-picture",
+            4 => "rext",
+            5 => "high-throughput",
+            6 => "multiview-main",
+            7 => "scalable-main",
+            8 => "3d-main",
+            9 => "screen-extended",
+            10 => "scalable-rext",
+            11 => "high-throughput-screen-extended",
+            _ => "unknown",
+        }
+    }
+
+    pub fn tier(&self) -> &'static str {
+        if self.tier {
+            "high"
+        } else {
+            "main"
+        }
+    }
+
+    /// level_idc is 30 * level
+    pub fn level(&self) -> String {
+        let level = self.level_idc / 3;
+        format!("{}.{}", level / 10, level % 10)
+    }
+}
+
+/// video parameter set (7.3.2.1)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vps {
+    pub id: u8,
+    pub max_sub_layers: u8,
+    pub ptl: Ptl,
+}
+
+impl Vps {
+    pub fn parse(rbsp: &[u8]) -> Option<Vps> {
+        let mut r = Bits::new(rbsp);
+
+        let id = r.u8(4)?;
+        // base_layer_internal, base_layer_available, max_layers_minus1
+        r.skip(2 + 6)?;
+        let max_sub_layers_minus1 = r.u8(3)?;
+        // temporal_id_nesting, reserved 0xffff
+        r.skip(1 + 16)?;
+        let ptl = Ptl::parse(&mut r, usize::from(max_sub_layers_minus1))?;
+
+        Some(Vps {
+            id,
+            max_sub_layers: max_sub_layers_minus1 + 1,
+            ptl,
+        })
+    }
+}
+
+/// video_signal_type and timing from VUI (E.2.1)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Vui {
+    pub sar: Option<(u16, u16)>,
+
+    pub full_range: bool,
+    pub colour: Option<Colour>,
+
+    /// pictures are fields
+    pub field_seq: bool,
+
+    pub num_units_in_tick: Option<u32>,
+    pub time_scale: Option<u32>,
+}
+
+impl Vui {
+    /// up to timing info; HRD is not parsed
+    fn parse(r: &mut Bits) -> Option<Vui> {
+        let mut vui = Vui::default();
+
+        if r.flag()? {
+            let aspect_ratio_idc = r.u8(8)?;
+            vui.sar = if aspect_ratio_idc == 255 {
+                Some((r.bits(16)? as u16, r.bits(16)? as u16))
+            } else {
+                SAR.get(usize::from(aspect_ratio_idc))
+                    .copied()
+                    .filter(|(w, _)| *w > 0)
+            };
+        }
+
+        // overscan_info_present_flag, overscan_appropriate_flag
+        if r.flag()? {
+            r.skip(1)?;
+        }
+
+        // video_signal_type_present_flag
+        if r.flag()? {
+            // video_format
+            r.skip(3)?;
+            vui.full_range = r.flag()?;
+            if r.flag()? {
+                vui.colour = Some(Colour {
+                    primaries: r.u8(8)?,
+                    transfer: r.u8(8)?,
+                    matrix: r.u8(8)?,
+                });
+            }
+        }
+
+        // chroma_loc_info_present_flag
+        if r.flag()? {
+            r.ue()?;
+            r.ue()?;
+        }
+
+        // neutral_chroma_indication_flag
+        r.skip(1)?;
+        vui.field_seq = r.flag()?;
+        // frame_field_info_present_flag
+        r.skip(1)?;
+
+        // default_display_window_flag
+        if r.flag()? {
+            for _ in 0..4 {
+                r.ue()?;
+            }
+        }
+
+        if r.flag()? {
+            vui.num_units_in_tick = Some(r.bits(32)?);
+            vui.time_scale = Some(r.bits(32)?);
+        }
+
+        Some(vui)
+    }
+}
+
+/// sequence parameter set (7.3.2.2)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sps {
+    pub id: u32,
+    pub vps_id: u8,
+    pub max_sub_layers: u8,
+
+    pub ptl: Ptl,
+
+    pub chroma_format_idc: u8,
+    pub bit_depth_luma: u8,
+    pub bit_depth_chroma: u8,
+
+    /// conformance window applied
+    pub width: u32,
+    pub height: u32,
+
+    pub log2_max_poc_lsb: u8,
+
+    pub vui: Option<Vui>,
+}
+
+impl Sps {
+    pub fn parse(rbsp: &[u8]) -> Option<Sps> {
+        let mut r = Bits::new(rbsp);
+
+        let vps_id = r.u8(4)?;
+        let max_sub_layers_minus1 = r.u8(3)?;
+        // temporal_id_nesting_flag
+        r.skip(1)?;
+        let ptl = Ptl::parse(&mut r, usize::from(max_sub_layers_minus1))?;
+
+        let id = r.ue()?;
+        let chroma_format_idc = r.ue()?.min(3) as u8;
+        if chroma_format_idc == 3 {
+            // separate_colour_plane_flag
+            r.skip(1)?;
+        }
+        let mut width = r.ue()?;
+        let mut height = r.ue()?;
+        if r.flag()? {
+            let sub_width: u32 = if chroma_format_idc == 1 || chroma_format_idc == 2 {
+                2
+            } else {
+                1
+            };
+            let sub_height: u32 = if chroma_format_idc == 1 { 2 } else { 1 };
+            let (left, right, top, bottom) = (r.ue()?, r.ue()?, r.ue()?, r.ue()?);
+            // None on overflow or conformance window beyond picture
+            width = width.checked_sub(sub_width.checked_mul(left.checked_add(right)?)?)?;
+            height = height.checked_sub(sub_height.checked_mul(top.checked_add(bottom)?)?)?;
+        }
+        let bit_depth_luma = 8 + r.ue()?.min(8) as u8;
+        let bit_depth_chroma = 8 + r.ue()?.min(8) as u8;
+        let log2_max_poc_lsb = 4 + r.ue()?.min(12) as u8;
+
+        let mut sps = Sps {
+            id,
+            vps_id,
+            max_sub_layers: max_sub_layers_minus1 + 1,
+            ptl,
+            chroma_format_idc,
+            bit_depth_luma,
+            bit_depth_chroma,
+            width,
+            height,
+            log2_max_poc_lsb,
+            vui: None,
+        };
+
+        // VUI follows coding tools; stream parameters above are kept
+        // even if the rest is malformed
+        sps.vui = Self::coding_tools_vui(&mut r, max_sub_layers_minus1, log2_max_poc_lsb);
+
+        Some(sps)
+    }
+
+    /// skip the rest of SPS (sub-layer ordering .. coding tools)
+    /// and parse VUI if present
+    fn coding_tools_vui(
+        r: &mut Bits,
+        max_sub_layers_minus1: u8,
+        log2_max_poc_lsb: u8,
+    ) -> Option<Vui> {
+        // sps_sub_layer_ordering_info_present_flag
+        let layers = if r.flag()? {
+            usize::from(max_sub_layers_minus1) + 1
+        } else {
+            1
+        };
+        for _ in 0..layers {
+            r.ue()?;
+            r.ue()?;
+            r.ue()?;
+        }
+
+        // log2_min_luma_coding_block_size_minus3 .. max_transform_hierarchy_depth_intra
+        for _ in 0..6 {
+            r.ue()?;
+        }
+
+        // scaling_list_enabled_flag, sps_scaling_list_data_present_flag
+        if r.flag()? && r.flag()? {
+            scaling_list_data(r)?;
+        }
+
+        // amp_enabled_flag, sample_adaptive_offset_enabled_flag
+        r.skip(2)?;
+        // pcm_enabled_flag
+        if r.flag()? {
+            r.skip(4 + 4)?;
+            r.ue()?;
+            r.ue()?;
+            r.skip(1)?;
+        }
+
+        let num_short_term_ref_pic_sets = r.ue()?.min(64) as usize;
+        let mut num_delta_pocs = Vec::with_capacity(num_short_term_ref_pic_sets);
+        for i in 0..num_short_term_ref_pic_sets {
+            let n = st_ref_pic_set(r, i, &num_delta_pocs)?;
+            num_delta_pocs.push(n);
+        }
+
+        // long_term_ref_pics_present_flag
+        if r.flag()? {
+            for _ in 0..r.ue()? {
+                r.skip(usize::from(log2_max_poc_lsb) + 1)?;
+            }
+        }
+
+        // sps_temporal_mvp_enabled_flag, strong_intra_smoothing_enabled_flag
+        r.skip(2)?;
+
+        if r.flag()? {
+            Vui::parse(r)
+        } else {
+            None
+        }
+    }
+
+    /// frames (or fields) per second by VUI timing
+    pub fn fps(&self) -> Option<f64> {
+        let vui = self.vui.as_ref()?;
+        let num_units_in_tick = vui.num_units_in_tick.filter(|v| *v > 0)?;
+        let time_scale = vui.time_scale.filter(|v| *v > 0)?;
+
+        Some(f64::from(time_scale) / f64::from(num_units_in_tick))
+    }
+
+    pub fn scan(&self) -> &'static str {
+        if self.vui.as_ref().map(|vui| vui.field_seq).unwrap_or(false)
+            || (self.ptl.interlaced_source && !self.ptl.progressive_source)
+        {
+            "interlaced"
+        } else {
+            "progressive"
+        }
+    }
+}
+
+/// skip scaling_list_data (7.3.4)
+fn scaling_list_data(r: &mut Bits) -> Option<()> {
+    for size_id in 0..4 {
+        let step = if size_id == 3 { 3 } else { 1 };
+        for _ in (0..6).step_by(step) {
+            if !r.flag()? {
+                // scaling_list_pred_matrix_id_delta
+                r.ue()?;
+                continue;
+            }
+
+            let coefs = std::cmp::min(64, 1 << (4 + (size_id << 1)));
+            if size_id > 1 {
+                r.se()?;
+            }
+            for _ in 0..coefs {
+                r.se()?;
+            }
+        }
+    }
+
+    Some(())
+}
+
+/// skip st_ref_pic_set (7.3.7); NumDeltaPocs
+fn st_ref_pic_set(r: &mut Bits, idx: usize, num_delta_pocs: &[u32]) -> Option<u32> {
+    // inter_ref_pic_set_prediction_flag
+    if idx != 0 && r.flag()? {
+        // delta_rps_sign, abs_delta_rps_minus1
+        r.skip(1)?;
+        r.ue()?;
+
+        let mut n = 0;
+        for _ in 0..=num_delta_pocs[idx - 1] {
+            // used_by_curr_pic_flag, use_delta_flag
+            if r.flag()? || r.flag()? {
+                n += 1;
+            }
+        }
+        return Some(n);
+    }
+
+    let negative = r.ue()?.min(16);
+    let positive = r.ue()?.min(16);
+    for _ in 0..negative + positive {
+        // delta_poc_minus1, used_by_curr_pic_flag
+        r.ue()?;
+        r.skip(1)?;
+    }
+
+    Some(negative + positive)
+}
+
+/// picture parameter set (7.3.2.3); fields needed by slice header
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pps {
+    pub id: u32,
+    pub sps_id: u32,
+
+    pub dependent_slice_segments_enabled: bool,
+    pub output_flag_present: bool,
+    pub num_extra_slice_header_bits: u8,
+}
+
+impl Pps {
+    pub fn parse(rbsp: &[u8]) -> Option<Pps> {
+        let mut r = Bits::new(rbsp);
+
+        Some(Pps {
+            id: r.ue()?,
+            sps_id: r.ue()?,
+            dependent_slice_segments_enabled: r.flag()?,
+            output_flag_present: r.flag()?,
+            num_extra_slice_header_bits: r.u8(3)?,
+        })
+    }
+}
+
+/// stream parameters attached to track
+#[derive(Clone, Debug, PartialEq)]
+pub struct H265 {
+    pub vps: Option<Vps>,
+    pub sps: Sps,
+    pub sei: Sei,
+    /// RPU NAL units present
+    pub dolby_vision: bool,
+}
+
+impl H265 {
+    pub fn colour(&self) -> Option<&Colour> {
+        self.sps.vui.as_ref().and_then(|vui| vui.colour.as_ref())
+    }
+
+    pub fn dynamic_range(&self) -> &'static str {
+        colour::dynamic_range(self.colour(), &self.sei)
+    }
+
+    pub fn hdr_issues(&self) -> Vec<&'static str> {
+        colour::hdr_issues(
+            self.colour(),
+            self.sps.bit_depth_luma,
+            self.sps.width,
+            &self.sei,
+        )
+    }
+
+    pub fn json(&self) -> String {
+        let sps = &self.sps;
+        let vui = sps.vui.clone().unwrap_or_default();
+        let opt = |v: Option<String>| v.unwrap_or_else(|| "null".to_string());
+
+        format!(
+            r#"{{"profile":{},"tier":"{}","level":{},"width":{},"height":{},"chroma_format":"{}","bit_depth_luma":{},"bit_depth_chroma":{},"scan":"{}","fps":{},"sar":{},"colour":{},"full_range":{},"dynamic_range":"{}","hdr_issues":[{}],"dolby_vision":{},"sub_layers":{},{}}}"#,
+            json_str(sps.ptl.profile()),
+            sps.ptl.tier(),
+            json_str(&sps.ptl.level()),
+            sps.width,
+            sps.height,
+            chroma_str(sps.chroma_format_idc),
+            sps.bit_depth_luma,
+            sps.bit_depth_chroma,
+            sps.scan(),
+            opt(sps.fps().map(fps_str)),
+            opt(vui.sar.map(|(w, h)| format!(r#""{}:{}""#, w, h))),
+            opt(vui.colour.map(|colour| colour.json())),
+            vui.full_range,
+            self.dynamic_range(),
+            self.hdr_issues()
+                .iter()
+                .map(|issue| format!(r#""{}""#, issue))
+                .collect::<Vec<_>>()
+                .join(","),
+            self.dolby_vision,
+            sps.max_sub_layers,
+            self.sei.json_fields()
+        )
+    }
+}
+
+impl fmt::Display for H265 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sps = &self.sps;
+
+        write!(
+            f,
+            ":profile {} :tier {} :level {} :size {}x{} :chroma {} :bit-depth {} :scan {}",
+            sps.ptl.profile(),
+            sps.ptl.tier(),
+            sps.ptl.level(),
+            sps.width,
+            sps.height,
+            chroma_str(sps.chroma_format_idc),
+            sps.bit_depth_luma,
+            sps.scan()
+        )?;
+        if let Some(fps) = sps.fps() {
+            write!(f, " :fps {}", fps_str(fps))?;
+        }
+        if let Some(vui) = sps.vui.as_ref() {
+            if let Some((w, h)) = vui.sar {
+                write!(f, " :sar {}:{}", w, h)?;
+            }
+            if let Some(colour) = vui.colour.as_ref() {
+                write!(f, " {}", colour)?;
+            }
+            if vui.full_range {
+                write!(f, " :range full")?;
+            }
+        }
+        write!(f, " :dynamic-range {}", self.dynamic_range())?;
+        if self.dolby_vision {
+            write!(f, " :dolby-vision true")?;
+        }
+
+        write!(f, "{}", self.sei)
+    }
+}
+
+/// parameter sets and SEI of one stream
+#[derive(Default)]
+pub struct Headers {
+    vps: BTreeMap<u8, Vps>,
+    sps: BTreeMap<u32, Sps>,
+    pps: BTreeMap<u32, Pps>,
+    sei: Sei,
+    /// (payloadType, payload) of last pushed access unit
+    messages: Vec<(u32, Vec<u8>)>,
+    dolby_vision: bool,
+
+    /// referenced by last slice
+    pps_id: Option<u32>,
+
+    /// first slice segment of last pushed access unit
+    picture: Option<Picture>,
+}
+
+impl Headers {
+    /// non-VCL NAL units of access unit up to first slice
+    /// (suffix SEI and RPU follow slices);
+    /// true if parameter sets, SEI, RPU presence or active PPS changed
+    pub fn push(&mut self, au: &[u8]) -> bool {
+        let pps_id = self.pps_id;
+        let mut changed = false;
+        let mut slice = false;
+        self.picture = None;
+        self.messages.clear();
+
+        for unit in nal::units(au) {
+            if unit.len() < 2 {
+                continue;
+            }
+            let rbsp = || nal::rbsp(&unit[2..]);
+
+            match nal_type(unit) {
+                Some(NAL_VPS) => {
+                    if let Some(vps) = Vps::parse(&rbsp()) {
+                        changed |= insert(&mut self.vps, vps.id, vps);
+                    }
+                }
+                Some(NAL_SPS) => {
+                    if let Some(sps) = Sps::parse(&rbsp()) {
+                        changed |= insert(&mut self.sps, sps.id, sps);
+                    }
+                }
+                Some(NAL_PPS) => {
+                    if let Some(pps) = Pps::parse(&rbsp()) {
+                        changed |= insert(&mut self.pps, pps.id, pps);
+                    }
+                }
+                Some(NAL_PREFIX_SEI) | Some(NAL_SUFFIX_SEI) => {
+                    let rbsp = rbsp();
+                    let messages = sei::messages(&rbsp);
+                    changed |= self.sei.parse("h265", &messages);
+                    self.messages.extend(
+                        messages
+                            .into_iter()
+                            .map(|(t, payload)| (t, payload.to_vec())),
+                    );
+                }
+                Some(NAL_DOVI_RPU) if !self.dolby_vision => {
+                    self.dolby_vision = true;
+                    changed = true;
+                }
+                Some(t) if t < NAL_VPS && !slice => {
+                    slice = true;
+
+                    let rbsp = nal::rbsp(&unit[2..unit.len().min(16)]);
+                    self.slice(t, &rbsp);
+                }
+                _ => {}
+            }
+        }
+
+        changed || self.pps_id != pps_id
+    }
+
+    /// slice segment header (7.3.6.1) up to slice_type
+    fn slice(&mut self, nal_type: u8, rbsp: &[u8]) -> Option<()> {
+        let mut r = Bits::new(rbsp);
+
+        let first_slice_segment_in_pic = r.flag()?;
+        let irap = (NAL_BLA_W_LP..=NAL_RSV_IRAP_23).contains(&nal_type);
+        if irap {
+            // no_output_of_prior_pics_flag
+            r.skip(1)?;
+        }
+        let pps_id = r.ue()?;
+        self.pps_id = Some(pps_id);
+
+        // slice_segment_address needs picture size in CTBs
+        if !first_slice_segment_in_pic {
+            return None;
+        }
+        let pps = self.pps.get(&pps_id)?;
+        r.skip(usize::from(pps.num_extra_slice_header_bits))?;
+
+        let kind = match r.ue()? {
+            0 => PictureType::B,
+            1 => PictureType::P,
+            _ if nal_type == NAL_IDR_W_RADL || nal_type == NAL_IDR_N_LP => PictureType::Idr,
+            _ => PictureType::I,
+        };
+
+        self.picture = Some(Picture {
+            kind,
+            // CRA may have leading pictures referencing previous GOP
+            closed: irap && nal_type != NAL_CRA,
+            second_field: false,
+        });
+
+        Some(())
+    }
+
+    /// by active PPS; falls back to last SPS
+    pub fn params(&self) -> Option<H265> {
+        let pps = self
+            .pps_id
+            .and_then(|pps_id| self.pps.get(&pps_id))
+            .or_else(|| self.pps.values().next());
+        let sps = pps
+            .and_then(|pps| self.sps.get(&pps.sps_id))
+            .or_else(|| self.sps.values().next())?;
+
+        Some(H265 {
+            vps: self.vps.get(&sps.vps_id).cloned(),
+            sps: sps.clone(),
+            sei: self.sei.clone(),
+            dolby_vision: self.dolby_vision,
+        })
+    }
+
+    /// first slice segment of last pushed access unit
+    pub fn picture(&self) -> Option<Picture> {
+        self.picture
+    }
+
+    /// prefix and suffix SEI messages of last pushed access unit
+    pub fn take_messages(&mut self) -> Vec<(u32, Vec<u8>)> {
+        std::mem::take(&mut self.messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VPS: [u8; 23] = [
+        0x40, 0x01, 0x0C, 0x01, 0xFF, 0xFF, 0x02, 0x20, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00,
+        0x03, 0x00, 0x00, 0x03, 0x00, 0x99, 0x70, 0x12,
+    ];
+    /// Main 10@5.1 3840x2160 4:2:0 50fps BT.2020 PQ
+    const SPS: [u8; 49] = [
+        0x42, 0x01, 0x01, 0x02, 0x20, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00, 0x00,
+        0x03, 0x00, 0x99, 0xA0, 0x01, 0xE0, 0x20, 0x02, 0x1C, 0x4D, 0x94, 0x5E, 0x49, 0x1B, 0x66,
+        0xBD, 0xAF, 0x01, 0x6A, 0x12, 0x20, 0x12, 0x08, 0x00, 0x00, 0x03, 0x00, 0x08, 0x00, 0x00,
+        0x03, 0x01, 0x90, 0x40,
+    ];
+    const PPS: [u8; 4] = [0x44, 0x01, 0xC0, 0x40];
+    /// IDR_W_RADL first slice segment, I slice
+    const SLICE_IDR: [u8; 5] = [0x26, 0x01, 0xAE, 0x11, 0x22];
+
+    /// prefix SEI NAL: content_light_level_info and user_data_unregistered
+    /// of payload_size
+    fn sei(payload_size: u8) -> Vec<u8> {
+        let mut nal = vec![0x4E, 0x01, 144, 4, 0x03, 0xE8, 0x01, 0x90, 5, payload_size];
+        nal.extend_from_slice(&[0xAA; 16]);
+        nal.extend_from_slice(b"x265 - H.265/HEVC codec");
+        nal.push(0x80);
+        nal
+    }
+
+    fn au(nals: &[&[u8]]) -> Vec<u8> {
+        let mut au = Vec::new();
+        for nal in nals {
+            au.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+            au.extend_from_slice(nal);
+        }
+        au
+    }
+
+    #[test]
+    fn valid() {
+        let vps = Vps::parse(&nal::rbsp(&VPS[2..])).unwrap();
+        assert_eq!(vps.ptl.profile(), "main10");
+
+        let sps = Sps::parse(&nal::rbsp(&SPS[2..])).unwrap();
+        assert_eq!(sps.ptl.level(), "5.1");
+        assert_eq!((sps.width, sps.height), (3840, 2160));
+        assert_eq!((sps.bit_depth_luma, sps.bit_depth_chroma), (10, 10));
+        assert_eq!(sps.fps(), Some(50.0));
+
+        let mut headers = Headers::default();
+        assert!(headers.push(&au(&[&VPS, &SPS, &PPS, &sei(39), &SLICE_IDR])));
+
+        let params = headers.params().unwrap();
+        assert!(params.vps.is_some());
+        assert_eq!(params.sei.encoder.as_deref(), Some("x265"));
+        assert_eq!(params.sei.content_light.unwrap().max_cll, 1000);
+        assert_eq!(headers.picture().unwrap().kind, PictureType::Idr);
+        assert!(headers.picture().unwrap().closed);
+        assert_eq!(headers.take_messages().len(), 2);
+    }
+
+    #[test]
+    fn truncated() {
+        let rbsp = nal::rbsp(&VPS[2..]);
+        for len in 0..rbsp.len() {
+            Vps::parse(&rbsp[..len]);
+        }
+        assert!(Vps::parse(&rbsp[..8]).is_none());
+
+        let rbsp = nal::rbsp(&SPS[2..]);
+        for len in 0..rbsp.len() {
+            if let Some(sps) = Sps::parse(&rbsp[..len]) {
+                // VUI is optional
+                assert_eq!((sps.width, sps.height), (3840, 2160));
+            }
+        }
+        assert!(Sps::parse(&rbsp[..16]).is_none());
+
+        let au = au(&[&VPS, &SPS, &PPS, &sei(39), &SLICE_IDR]);
+        for len in 0..au.len() {
+            Headers::default().push(&au[..len]);
+        }
+    }
+
+    #[test]
+    fn length_overrun() {
+        // content light level still parsed ahead of overrunning message
+        let messages = sei::messages(&nal::rbsp(&sei(0xFE)[2..]))
+            .into_iter()
+            .map(|(t, _)| t)
+            .collect::<Vec<_>>();
+        assert_eq!(messages, [144]);
+
+        let mut headers = Headers::default();
+        headers.push(&au(&[&VPS, &SPS, &PPS, &sei(0xFE), &SLICE_IDR]));
+        assert!(headers.params().unwrap().sei.encoder.is_none());
+        assert_eq!(headers.take_messages().len(), 1);
+        assert_eq!(headers.picture().unwrap().kind, PictureType::Idr);
+    }
+}
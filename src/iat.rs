@@ -0,0 +1,144 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::metrics::Metrics;
+
+/// histogram upper bounds if not configured
+pub const BUCKETS_DEFAULT: &[Duration] = &[
+    Duration::from_micros(100),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_millis(2),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(20),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+];
+
+/// datagram inter-arrival time statistics.
+///
+/// min/max/mean/stddev and percentiles are per publish interval;
+/// histogram is cumulative since start
+pub struct Iat {
+    /// ascending upper bounds; last bucket (overflow) is unbounded
+    buckets: Vec<Duration>,
+    counts: Vec<u64>,
+
+    last_at: Option<Instant>,
+
+    /// current interval samples; ns
+    samples: Vec<u64>,
+}
+
+impl Iat {
+    const PERCENTILES: &'static [(&'static str, f64)] =
+        &[("p50", 0.5), ("p90", 0.9), ("p99", 0.99), ("p999", 0.999)];
+
+    pub fn new(buckets: &[Duration]) -> Iat {
+        let mut buckets = buckets.to_vec();
+        buckets.sort();
+        buckets.dedup();
+
+        Iat {
+            counts: vec![0; buckets.len() + 1],
+            buckets,
+            last_at: None,
+            samples: Vec::new(),
+        }
+    }
+
+    /// datagram received at
+    #[inline(always)]
+    pub fn update(&mut self, at: Instant) {
+        if let Some(last_at) = self.last_at {
+            let iat = at.saturating_duration_since(last_at);
+
+            let i = self
+                .buckets
+                .iter()
+                .position(|bucket| iat <= *bucket)
+                .unwrap_or(self.buckets.len());
+            self.counts[i] += 1;
+
+            self.samples.push(iat.as_nanos() as u64);
+        }
+
+        self.last_at = Some(at);
+    }
+
+    /// publish interval statistics (microseconds) and histogram;
+    /// starts new interval
+    pub fn publish(&mut self, metrics: &Metrics, prefix: &str) {
+        metrics.set(format!("{}-histogram", prefix), self.to_string());
+
+        if self.samples.is_empty() {
+            return;
+        }
+
+        self.samples.sort_unstable();
+
+        let n = self.samples.len() as f64;
+        let mean = self.samples.iter().sum::<u64>() as f64 / n;
+        let variance = self
+            .samples
+            .iter()
+            .map(|v| (*v as f64 - mean).powi(2))
+            .sum::<f64>()
+            / n;
+
+        let us = |ns: u64| ns / 1000;
+
+        metrics.set(format!("{}-min-us", prefix), us(self.samples[0]));
+        metrics.set(
+            format!("{}-max-us", prefix),
+            us(self.samples[self.samples.len() - 1]),
+        );
+        metrics.set(format!("{}-mean-us", prefix), mean / 1000.0);
+        metrics.set(format!("{}-stddev-us", prefix), variance.sqrt() / 1000.0);
+
+        for (name, q) in Self::PERCENTILES {
+            let i = ((n - 1.0) * q).round() as usize;
+            metrics.set(format!("{}-{}-us", prefix, name), us(self.samples[i]));
+        }
+
+        self.samples.clear();
+    }
+}
+
+impl fmt::Display for Iat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(")?;
+        for (i, count) in self.counts.iter().enumerate() {
+            if i != 0 {
+                write!(f, " ")?;
+            }
+            match self.buckets.get(i) {
+                Some(bucket) => write!(f, ":{}ms {}", bucket.as_secs_f64() * 1000.0, count)?,
+                None => write!(f, ":inf {}", count)?,
+            }
+        }
+        write!(f, ")")
+    }
+}
+
+/// parse comma separated milliseconds e.g. "0.5,1,2,5"
+pub fn buckets_parse<S: AsRef<str>>(s: S) -> Option<Vec<Duration>> {
+    let buckets = s
+        .as_ref()
+        .split(',')
+        .map(|v| {
+            v.trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|ms| *ms > 0.0)
+                .and_then(|ms| Duration::try_from_secs_f64(ms / 1000.0).ok())
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    if buckets.is_empty() {
+        None
+    } else {
+        Some(buckets)
+    }
+}
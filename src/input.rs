@@ -1,18 +1,31 @@
 use std::collections::VecDeque;
-use std::net::Ipv4Addr;
-use std::net::UdpSocket;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Condvar, Mutex};
-use std::thread;
-use std::time::Duration;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
-use log::{debug, error, info, trace};
-use url::{Host, Url};
+use crossbeam_channel::{bounded, Sender, TrySendError};
+use log::{debug, error, info, trace, warn};
+use url::Url;
 
 use crate::error::{Error, Result};
+use crate::fec::{Fec, Mode as FecMode};
+use crate::filter::Consumer;
+use crate::gen::{Gen, Params as GenParams};
+use crate::iat::{self, Iat};
+use crate::mdi::{Mdi, Opts as MdiOpts};
+use crate::mediacontainer::Mediacontainer;
+use crate::metrics::Metrics;
+use crate::rtp::{self, FecHeader};
+use crate::udp::{SockOpts, Socket as UdpSocket, Stats as UdpStats};
 
 pub trait Input {
     fn open(&mut self) -> Result<()>;
-    fn read(&self) -> Result<()>;
+    /// pass read TS packets to consumer
+    fn read(&mut self, consumer: &dyn Consumer) -> Result<()>;
+    #[allow(dead_code)]
     fn close(&self) -> Result<()>;
 }
 
@@ -24,9 +37,20 @@ pub struct InputUdp {
     /// a.k.a. circular buffer size
     fifo_sz: usize,
 
+    sock_opts: SockOpts,
+
+    iat_buckets: Vec<Duration>,
+    mdi_opts: MdiOpts,
+
     /// circullar-buffer / fifo
     /// use two threads and buffer to read from udp
     fifo: Option<UDPFifo>,
+
+    /// receiver of the current open; stopped and joined on reopen
+    threads: Vec<JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
+
+    metrics: Metrics,
 }
 
 impl InputUdp {
@@ -34,7 +58,13 @@ impl InputUdp {
         InputUdp {
             url,
             fifo_sz: 1000,
+            sock_opts: Default::default(),
+            iat_buckets: iat::BUCKETS_DEFAULT.to_vec(),
+            mdi_opts: Default::default(),
             fifo: None,
+            threads: Vec::new(),
+            stop: Arc::new(AtomicBool::new(false)),
+            metrics: Default::default(),
         }
     }
 
@@ -42,146 +72,608 @@ impl InputUdp {
         self.fifo_sz = fifo_sz;
         self
     }
+
+    pub fn sock_opts(&mut self, sock_opts: SockOpts) -> &InputUdp {
+        self.sock_opts = sock_opts;
+        self
+    }
+
+    pub fn iat_buckets(&mut self, iat_buckets: Vec<Duration>) -> &InputUdp {
+        self.iat_buckets = iat_buckets;
+        self
+    }
+
+    pub fn mdi_opts(&mut self, mdi_opts: MdiOpts) -> &InputUdp {
+        self.mdi_opts = mdi_opts;
+        self
+    }
+
+    pub fn metrics(&mut self, metrics: Metrics) -> &InputUdp {
+        self.metrics = metrics;
+        self
+    }
+
+    /// stop receiver left from previous open
+    fn shutdown(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for handle in self.threads.drain(..) {
+            if handle.join().is_err() {
+                error!("({}) receiver thread panicked", self.url);
+            }
+        }
+        self.stop = Arc::new(AtomicBool::new(false));
+    }
 }
 
 impl Input for InputUdp {
     fn open(&mut self) -> Result<()> {
+        self.shutdown();
+
         let fifo = Arc::new((
             Mutex::new(VecDeque::with_capacity(self.fifo_sz)),
             Condvar::new(),
         ));
         self.fifo = Some(fifo.clone());
 
-        let host = self.url.host().ok_or_else(Error::udp_url_missing_host)?;
-        let host_str = host.to_owned().to_string();
-
         let port = self.url.port().unwrap_or(5500);
+        let socket = UdpSocket::bind(&self.url, port, &self.sock_opts)?;
+        rcvbuf_publish(&socket, &self.url, &self.metrics);
 
-        let socket = UdpSocket::bind((&*host_str, port))
-            .map_err(|err| Error::udp_socket_bind(err, &host_str, port))?;
+        let url = self.url.clone();
+        let metrics = self.metrics.clone();
+        let mut iat = Iat::new(&self.iat_buckets);
+        let mut mdi = Mdi::new(self.url.clone(), self.mdi_opts);
+        let stop = self.stop.clone();
 
-        debug!("({}) [+] OK bind udp socket", self.url);
+        let handle = thread::spawn(move || {
+            // MTU (maximum transmission unit) == 1500 for Ethertnet
+            // 7*ts::Packet::SZ = 7*188 = 1316 < 1500 => OK
+            let mut buf7 = [0; 7 * ts::Packet::SZ];
 
-        {
-            match host {
-                Host::Ipv4(v4) => {
-                    let iface = Ipv4Addr::new(0, 0, 0, 0);
-                    socket.join_multicast_v4(&v4, &iface).map_err(|err| {
-                        Error::udp_join_multicast_v4(err, host_str, port, iface.to_string())
-                    })?;
+            let mut stats = UdpStats::default();
+            let mut stats_at = Instant::now();
 
-                    debug!("({}) [+] OK join multicast v4", self.url);
-                    debug!("({}) [+] OK ({}:{}@{})", self.url, v4, port, iface);
-                }
-                Host::Ipv6(v6) => {
-                    // 0 to indicate any interface
-                    let iface = 0;
-                    socket
-                        .join_multicast_v6(&v6, iface)
-                        .map_err(|err| Error::udp_join_multicast_v6(err, host_str, port, iface))?;
-
-                    debug!("({}) [+] OK join multicast v6", self.url);
+            while !stop.load(Ordering::Relaxed) {
+                match socket.recv(&mut buf7) {
+                    Ok(Some((sz, drops))) => {
+                        let at = Instant::now();
+                        iat.update(at);
+                        mdi.update(at, &buf7[..sz]);
+                        stats.update(sz, drops);
+
+                        fifo_push(&fifo, &url, &buf7[..sz]);
+                    }
+                    Ok(None) => {}
+                    Err(err) => recv_error(&url, err),
                 }
-                Host::Domain(domain) => {
-                    let v4 = domain
-                        .parse()
-                        .map_err(|err| Error::udp_domain_to_ipv4(err, domain))?;
-
-                    let iface = Ipv4Addr::new(0, 0, 0, 0);
-                    socket.join_multicast_v4(&v4, &iface).map_err(|err| {
-                        Error::udp_join_multicast_v4(err, host_str, port, iface.to_string())
-                    })?;
-
-                    debug!("({}) [+] OK join multicast v4/domain", self.url);
-                    debug!("({}) [+] OK ({}:{}@{})", self.url, domain, port, iface);
+
+                if stats_at.elapsed() >= METRICS_INTERVAL {
+                    stats.publish(&metrics, "udp");
+                    iat.publish(&metrics, "iat");
+                    mdi.publish(&metrics, "mdi");
+                    stats_at = Instant::now();
                 }
             }
+        });
+        self.threads.push(handle);
+
+        Ok(())
+    }
+    fn read(&mut self, consumer: &dyn Consumer) -> Result<()> {
+        fifo_read(self.fifo.as_ref(), &self.url, consumer)
+    }
+    fn close(&self) -> Result<()> {
+        self.stop.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+pub struct InputRtp {
+    url: Url,
+
+    /// a.k.a. circular buffer size
+    fifo_sz: usize,
+
+    fec: FecMode,
+
+    sock_opts: SockOpts,
+
+    iat_buckets: Vec<Duration>,
+    mdi_opts: MdiOpts,
+
+    /// reorder buffer depth in RTP packets;
+    /// grows up to 2 FEC matrices when FEC is received
+    reorder_sz: usize,
+
+    fifo: Option<UDPFifo>,
+
+    /// media and FEC receivers of the current open;
+    /// stopped and joined on reopen
+    threads: Vec<JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
+
+    metrics: Metrics,
+}
+
+impl InputRtp {
+    /// column FEC port offset (SMPTE 2022-1)
+    const FEC_COL_PORT_OFFSET: u16 = 2;
+    /// row FEC port offset (SMPTE 2022-1)
+    const FEC_ROW_PORT_OFFSET: u16 = 4;
+
+    /// FEC packets waiting for media thread
+    const FEC_FIFO_SZ: usize = 1024;
+
+    /// how often to report FEC statistics
+    const STATS_INTERVAL: Duration = Duration::from_secs(10);
+
+    pub fn new(url: Url) -> InputRtp {
+        InputRtp {
+            url,
+            fifo_sz: 1000,
+            fec: FecMode::Off,
+            sock_opts: Default::default(),
+            iat_buckets: iat::BUCKETS_DEFAULT.to_vec(),
+            mdi_opts: Default::default(),
+            reorder_sz: 32,
+            fifo: None,
+            threads: Vec::new(),
+            stop: Arc::new(AtomicBool::new(false)),
+            metrics: Default::default(),
         }
+    }
 
-        let url = self.url.clone();
-        thread::spawn(move || {
-            let mut pkt_raw = [0; ts::Packet::SZ];
+    pub fn fifo_sz(&mut self, fifo_sz: usize) -> &InputRtp {
+        self.fifo_sz = fifo_sz;
+        self
+    }
 
-            // MTU (maximum transmission unit) == 1500 for Ethertnet
-            // 7*ts::Packet::SZ = 7*188 = 1316 < 1500 => OK
-            let mut buf7 = [0; 7 * ts::Packet::SZ];
+    pub fn fec(&mut self, fec: FecMode) -> &InputRtp {
+        self.fec = fec;
+        self
+    }
+
+    pub fn sock_opts(&mut self, sock_opts: SockOpts) -> &InputRtp {
+        self.sock_opts = sock_opts;
+        self
+    }
+
+    pub fn iat_buckets(&mut self, iat_buckets: Vec<Duration>) -> &InputRtp {
+        self.iat_buckets = iat_buckets;
+        self
+    }
+
+    pub fn mdi_opts(&mut self, mdi_opts: MdiOpts) -> &InputRtp {
+        self.mdi_opts = mdi_opts;
+        self
+    }
 
-            loop {
-                let (_, _) = socket.recv_from(&mut buf7).unwrap();
+    pub fn metrics(&mut self, metrics: Metrics) -> &InputRtp {
+        self.metrics = metrics;
+        self
+    }
+
+    /// stop receivers left from previous open
+    fn shutdown(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for handle in self.threads.drain(..) {
+            if handle.join().is_err() {
+                error!("({}) receiver thread panicked", self.url);
+            }
+        }
+        self.stop = Arc::new(AtomicBool::new(false));
+    }
+
+    /// receive FEC packets in separate thread and pass them
+    /// to media thread
+    fn spawn_fec(&mut self, port: u16, offset: u16, sender: Sender<Vec<u8>>) -> Result<()> {
+        let port = port
+            .checked_add(offset)
+            .ok_or_else(|| Error::rtp_fec_port(port, offset))?;
+        let socket = UdpSocket::bind(&self.url, port, &self.sock_opts)?;
 
-                let &(ref lock, ref cvar) = &*fifo;
-                let mut fifo = match lock.lock() {
-                    Err(e) => {
-                        error!("({}) lock and get buffer failed: {}", url, e);
-                        // will retry after timeout;
-                        thread::sleep(Duration::from_secs(1));
+        let url = self.url.clone();
+        let stop = self.stop.clone();
+        let handle = thread::spawn(move || {
+            let mut buf = [0; 1500];
+
+            while !stop.load(Ordering::Relaxed) {
+                let sz = match socket.recv(&mut buf) {
+                    Ok(Some((sz, _))) => sz,
+                    Ok(None) => continue,
+                    Err(err) => {
+                        recv_error(&url, err);
                         continue;
                     }
-                    Ok(buf) => buf,
                 };
 
-                for i in 0..7 {
-                    let f = i * ts::Packet::SZ;
-                    let t = (i + 1) * ts::Packet::SZ;
-                    let buf1 = &buf7[f..t];
-
-                    pkt_raw.copy_from_slice(buf1);
-                    fifo.push_back(pkt_raw);
+                match sender.try_send(buf[..sz].to_vec()) {
+                    Ok(_) => {}
+                    // no media - nobody drains FEC
+                    Err(TrySendError::Full(_)) => {}
+                    Err(TrySendError::Disconnected(_)) => {
+                        debug!("({}) [fec] media thread is gone (:port {})", url, port);
+                        return;
+                    }
                 }
-
-                cvar.notify_all();
             }
         });
+        self.threads.push(handle);
 
         Ok(())
     }
-    fn read(&self) -> Result<()> {
-        let fifo = self
-            .fifo
-            .as_ref()
-            .ok_or_else(Error::udp_fifo_not_initialized)?
-            .clone();
+}
+
+impl Input for InputRtp {
+    fn open(&mut self) -> Result<()> {
+        self.shutdown();
 
-        let &(ref lock, ref cvar) = &*fifo;
-        let mut fifo = lock
-            .lock()
-            .map_err(|err| Error::udp_fifo_lock(err.to_string()))?;
+        let fifo = Arc::new((
+            Mutex::new(VecDeque::with_capacity(self.fifo_sz)),
+            Condvar::new(),
+        ));
+        self.fifo = Some(fifo.clone());
 
-        fifo = cvar
-            .wait(fifo)
-            .map_err(|err| Error::udp_fifo_cvar_wait(err.to_string()))?;
+        let port = self.url.port().unwrap_or(5500);
+        let socket = UdpSocket::bind(&self.url, port, &self.sock_opts)?;
+        rcvbuf_publish(&socket, &self.url, &self.metrics);
 
-        while !fifo.is_empty() {
-            let ts_pkt_raw = fifo.pop_front().ok_or_else(Error::udp_fifo_pop_empty)?;
-            trace!("({}) [<] {}", self.url, ts_pkt_raw.len())
+        let (sender, receiver) = bounded(Self::FEC_FIFO_SZ);
+        if self.fec.col() {
+            self.spawn_fec(port, Self::FEC_COL_PORT_OFFSET, sender.clone())?;
         }
+        if self.fec.row() {
+            self.spawn_fec(port, Self::FEC_ROW_PORT_OFFSET, sender)?;
+        }
+
+        let url = self.url.clone();
+        let metrics = self.metrics.clone();
+        let fec_mode = self.fec;
+        let mut fec = Fec::new(self.fec, self.reorder_sz);
+        let mut iat = Iat::new(&self.iat_buckets);
+        let mut mdi = Mdi::new(self.url.clone(), self.mdi_opts);
+        let stop = self.stop.clone();
+
+        let handle = thread::spawn(move || {
+            let mut buf = [0; 1500];
+
+            let mut stats = fec.stats();
+            let mut stats_at = Instant::now();
+
+            let mut udp_stats = UdpStats::default();
+            let mut udp_stats_at = Instant::now();
+
+            let mut at = Instant::now();
+
+            while !stop.load(Ordering::Relaxed) {
+                // zero on timeout/error - FEC and counters only
+                let sz = match socket.recv(&mut buf) {
+                    Ok(Some((sz, drops))) => {
+                        at = Instant::now();
+                        iat.update(at);
+                        udp_stats.update(sz, drops);
+                        sz
+                    }
+                    Ok(None) => 0,
+                    Err(err) => {
+                        recv_error(&url, err);
+                        0
+                    }
+                };
+
+                // FEC packets first - they may complete
+                // recovery of already buffered media
+                for raw in receiver.try_iter() {
+                    let pkt = match rtp::Packet::try_new(&raw) {
+                        Ok(pkt) => pkt,
+                        Err(err) => {
+                            trace!("({}) [fec] skip packet (:reason {})", url, err);
+                            continue;
+                        }
+                    };
+
+                    match FecHeader::try_new(pkt.payload()) {
+                        Ok(header) => fec.push_fec(&header, |payload| {
+                            fifo_push(&fifo, &url, payload);
+                        }),
+                        Err(err) => trace!("({}) [fec] skip packet (:reason {})", url, err),
+                    }
+                }
+
+                match rtp::Packet::try_new(&buf[..sz]) {
+                    _ if sz == 0 => {}
+                    Ok(pkt) => {
+                        trace!(
+                            "({}) [<] (:seq {} :ts {} :pt {} :sz {})",
+                            url,
+                            pkt.sequence_number(),
+                            pkt.timestamp(),
+                            pkt.payload_type(),
+                            pkt.payload().len()
+                        );
+
+                        // as received; before FEC recovery
+                        mdi.update(at, pkt.payload());
+
+                        fec.push_media(pkt.sequence_number(), pkt.payload(), |payload| {
+                            fifo_push(&fifo, &url, payload);
+                        });
+                    }
+                    Err(err) => trace!("({}) skip packet (:reason {})", url, err),
+                }
+
+                if udp_stats_at.elapsed() >= METRICS_INTERVAL {
+                    let current = fec.stats();
+
+                    udp_stats.publish(&metrics, "udp");
+                    iat.publish(&metrics, "iat");
+                    mdi.publish(&metrics, "mdi");
+                    metrics.set("rtp-received", current.received);
+                    metrics.set("rtp-late", current.late);
+                    if fec_mode != FecMode::Off {
+                        metrics.set("fec-recovered", current.recovered);
+                        metrics.set("fec-unrecoverable", current.unrecoverable);
+                        metrics.set("fec-col-received", current.fec_col);
+                        metrics.set("fec-row-received", current.fec_row);
+                    }
+
+                    udp_stats_at = Instant::now();
+                }
+
+                if stats_at.elapsed() >= Self::STATS_INTERVAL {
+                    let current = fec.stats();
+
+                    if fec_mode != FecMode::Off
+                        && (current.recovered != stats.recovered
+                            || current.unrecoverable != stats.unrecoverable)
+                    {
+                        info!("({}) [fec] {}", url, current);
+                    } else {
+                        debug!("({}) [fec] {}", url, current);
+                    }
+
+                    stats = current;
+                    stats_at = Instant::now();
+                }
+            }
+        });
+        self.threads.push(handle);
 
         Ok(())
     }
+    fn read(&mut self, consumer: &dyn Consumer) -> Result<()> {
+        fifo_read(self.fifo.as_ref(), &self.url, consumer)
+    }
     fn close(&self) -> Result<()> {
+        self.stop.store(true, Ordering::Relaxed);
         Ok(())
     }
 }
 
 pub struct InputFile {
     url: Url,
+
+    file: Option<BufReader<File>>,
+
+    /// bytes in front of every TS packet (M2TS TP_extra_header)
+    prefix: usize,
+
+    /// end of file is reached
+    eof: bool,
 }
 
 impl InputFile {
+    /// nothing left to read - do not spin
+    const IDLE: Duration = Duration::from_secs(1);
+
     pub fn new(url: Url) -> InputFile {
-        InputFile { url }
+        InputFile {
+            url,
+            file: None,
+            prefix: 0,
+            eof: false,
+        }
+    }
+
+    /// 192 byte BDAV packets
+    pub fn m2ts(&mut self, m2ts: bool) -> &InputFile {
+        self.prefix = if m2ts {
+            Mediacontainer::M2TS_HEADER_SZ
+        } else {
+            0
+        };
+        self
     }
 }
 
 impl Input for InputFile {
     fn open(&mut self) -> Result<()> {
+        let path = self.url.path();
+        let file = File::open(path).map_err(|err| Error::file_open(err, path))?;
+
+        self.file = Some(BufReader::with_capacity(1000 * ts::Packet::SZ, file));
+        self.eof = false;
+
+        debug!("({}) [+] OK open file", self.url);
+
         Ok(())
     }
-    fn read(&self) -> Result<()> {
-        info!("[<] {}", self.url);
+    fn read(&mut self, consumer: &dyn Consumer) -> Result<()> {
+        let file = self.file.as_mut().ok_or_else(Error::file_not_initialized)?;
+
+        if self.eof {
+            thread::sleep(Self::IDLE);
+            return Ok(());
+        }
+
+        let mut buf = [0; Mediacontainer::M2TS_HEADER_SZ + ts::Packet::SZ];
+        let buf = &mut buf[..self.prefix + ts::Packet::SZ];
+        for _ in 0..7 {
+            match file.read_exact(buf) {
+                Ok(_) => consumer.consume_pkt_raw(&buf[self.prefix..]),
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    info!("({}) [<] EOF", self.url);
+                    self.eof = true;
+                    break;
+                }
+                Err(err) => return Err(Error::file_read(err, self.url.path())),
+            }
+        }
+
         Ok(())
     }
     fn close(&self) -> Result<()> {
         Ok(())
     }
 }
+
+pub struct InputGen {
+    url: Url,
+
+    params: GenParams,
+
+    gen: Option<Gen>,
+    started_at: Instant,
+
+    metrics: Metrics,
+    metrics_at: Instant,
+}
+
+impl InputGen {
+    /// packets per read; same as udp datagram
+    const BURST: usize = 7;
+
+    /// nothing left to generate - do not spin
+    const IDLE: Duration = Duration::from_secs(1);
+
+    pub fn new(url: Url, params: GenParams) -> InputGen {
+        InputGen {
+            url,
+            params,
+            gen: None,
+            started_at: Instant::now(),
+            metrics: Default::default(),
+            metrics_at: Instant::now(),
+        }
+    }
+
+    pub fn metrics(&mut self, metrics: Metrics) -> &InputGen {
+        self.metrics = metrics;
+        self
+    }
+}
+
+impl Input for InputGen {
+    fn open(&mut self) -> Result<()> {
+        self.gen = Some(Gen::new(self.params.clone()));
+        self.started_at = Instant::now();
+
+        debug!("({}) [+] OK generator {}", self.url, self.params);
+
+        Ok(())
+    }
+    fn read(&mut self, consumer: &dyn Consumer) -> Result<()> {
+        let gen = self.gen.as_mut().ok_or_else(Error::gen_not_initialized)?;
+
+        if self.params.realtime {
+            let at = self.started_at + gen.at();
+            let now = Instant::now();
+            if at > now {
+                thread::sleep(at - now);
+            }
+        }
+
+        for _ in 0..Self::BURST {
+            match gen.next_packet() {
+                Some(ts_pkt_raw) => consumer.consume_pkt_raw(&ts_pkt_raw),
+                None => {
+                    thread::sleep(Self::IDLE);
+                    break;
+                }
+            }
+        }
+
+        if self.metrics_at.elapsed() >= METRICS_INTERVAL {
+            let stats = gen.stats();
+
+            self.metrics.set("gen-packets", stats.packets);
+            self.metrics.set("gen-null", stats.null);
+            self.metrics.set("gen-cc-errors", stats.cc_errors);
+            self.metrics.set("gen-pcr-jumps", stats.pcr_jumps);
+
+            self.metrics_at = Instant::now();
+        }
+
+        Ok(())
+    }
+    fn close(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// how often receive threads publish counters
+const METRICS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// socket is broken? do not spin
+fn recv_error(url: &Url, err: io::Error) {
+    error!("({}) udp receive failed (:reason {})", url, err);
+    thread::sleep(Duration::from_secs(1));
+}
+
+/// log and publish effective kernel receive buffer size
+fn rcvbuf_publish(socket: &UdpSocket, url: &Url, metrics: &Metrics) {
+    match socket.rcvbuf() {
+        Ok(rcvbuf) => {
+            debug!("({}) [+] OK udp socket (:rcvbuf {})", url, rcvbuf);
+            metrics.set("udp-rcvbuf", rcvbuf);
+        }
+        Err(err) => warn!("({}) get udp socket rcvbuf failed (:reason {})", url, err),
+    }
+}
+
+/// split buffer into mpeg-ts packets and push them to fifo
+fn fifo_push(fifo: &UDPFifo, url: &Url, buf: &[u8]) {
+    let mut pkt_raw = [0; ts::Packet::SZ];
+
+    let (lock, cvar) = &**fifo;
+    let mut fifo = match lock.lock() {
+        Err(e) => {
+            error!("({}) lock and get buffer failed: {}", url, e);
+            return;
+        }
+        Ok(buf) => buf,
+    };
+
+    for buf1 in buf.chunks_exact(ts::Packet::SZ) {
+        pkt_raw.copy_from_slice(buf1);
+        fifo.push_back(pkt_raw);
+    }
+
+    cvar.notify_all();
+}
+
+fn fifo_read(fifo: Option<&UDPFifo>, url: &Url, consumer: &dyn Consumer) -> Result<()> {
+    let fifo = fifo.ok_or_else(Error::udp_fifo_not_initialized)?.clone();
+
+    let (lock, cvar) = &*fifo;
+    let mut fifo = lock
+        .lock()
+        .map_err(|err| Error::udp_fifo_lock(err.to_string()))?;
+
+    while fifo.is_empty() {
+        fifo = cvar
+            .wait(fifo)
+            .map_err(|err| Error::udp_fifo_cvar_wait(err.to_string()))?;
+    }
+
+    // do not block receive thread while consumers are busy
+    let pkts: Vec<[u8; ts::Packet::SZ]> = fifo.drain(..).collect();
+    drop(fifo);
+
+    for ts_pkt_raw in pkts.iter() {
+        trace!("({}) [<] {}", url, ts_pkt_raw.len());
+        consumer.consume_pkt_raw(ts_pkt_raw);
+    }
+
+    Ok(())
+}
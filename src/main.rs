@@ -3,25 +3,85 @@ extern crate bitflags;
 #[macro_use]
 extern crate lazy_static;
 
+mod audio;
+mod bitrate;
+mod bits;
+mod ca;
+mod captions;
+mod cea608;
+mod cea708;
+mod clock;
+mod colour;
+mod compression_standard;
 mod config;
+mod crc32;
+mod demuxer;
+mod dump;
+mod dvbsub;
+mod eit;
 mod error;
+mod es;
+mod fec;
 mod filter;
+mod frame;
+mod gen;
+mod gop;
+mod h264;
+mod h265;
+mod iat;
 mod input;
 mod logger;
+mod mdi;
 mod mediacontainer;
+mod metrics;
+mod mpeg2;
+mod nal;
 mod opt;
+mod packet;
+mod pcr;
+mod probe;
+mod psi;
+mod pts;
+mod reassembler;
+mod rtp;
+mod scte35;
+mod sei;
 mod source;
+mod subtitle;
+mod teletext;
+mod tr101290;
+mod track;
+mod udp;
 
 use std::process;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::{bounded, select, Receiver};
-use log::info;
+use log::{info, warn};
 
-use crate::config::Config;
+use crate::bitrate::Bitrate;
+use crate::ca::Ca;
+use crate::captions::Captions;
+use crate::config::{Action, Config, ConfigInput};
+use crate::demuxer::Demuxer;
+use crate::dump::EsDump;
+use crate::eit::Epg;
 use crate::error::{Error, Result};
-use crate::input::{InputFile, InputUdp};
+use crate::es::Es;
+use crate::filter::{Consumer, Producer};
+use crate::gen::Params as GenParams;
+use crate::gop::Gop;
+use crate::input::{Input, InputFile, InputGen, InputRtp, InputUdp};
 use crate::mediacontainer::Mediacontainer;
+use crate::metrics::Metrics;
+use crate::pcr::Pcr;
+use crate::probe::{Format as ProbeFormat, Probe};
+use crate::pts::Pts;
+use crate::reassembler::Reassembler;
+use crate::scte35::Scte35;
 use crate::source::Source;
+use crate::subtitle::Subtitles;
+use crate::tr101290::Tr101290;
 
 fn signal_chan() -> Result<Receiver<()>> {
     let (sender, receiver) = bounded(16);
@@ -34,6 +94,10 @@ fn signal_chan() -> Result<Receiver<()>> {
     Ok(receiver)
 }
 
+/// probe input is considered finished (e.g. EOF) after no data for
+const PROBE_IDLE: Duration = Duration::from_secs(1);
+const PROBE_POLL: Duration = Duration::from_millis(100);
+
 struct App {
     config: Config,
 }
@@ -44,30 +108,98 @@ impl App {
     }
 
     fn start(&self) -> Result<()> {
+        match self.config.action {
+            Action::Analyze => self.analyze(),
+            Action::Probe => self.probe(),
+        }
+    }
+
+    fn analyze(&self) -> Result<()> {
+        let mut metrics_all = Vec::with_capacity(self.config.inputs.len());
+
         for input in self.config.inputs.iter() {
-            match input.url.scheme() {
-                "udp" => {
-                    let mut udp = InputUdp::new(input.url.clone());
-                    udp.fifo_sz(input.udp_fifo_sz);
+            let metrics = Metrics::default();
+            metrics_all.push((input.url.to_string(), metrics.clone()));
+
+            let mut bitrate = Bitrate::new(input.url.clone());
+            bitrate.metrics(metrics.clone());
+
+            let mut tr101290 = Tr101290::new(input.url.clone());
+            tr101290.opts(input.tr101290_opts.clone());
+            tr101290.metrics(metrics.clone());
+
+            let mut pcr = Pcr::new(input.url.clone());
+            pcr.csv(input.pcr_csv.clone());
+            pcr.metrics(metrics.clone());
 
-                    let mut source = Source::new(udp);
-                    source.start()?;
+            let mut ca = Ca::new(input.url.clone());
+            ca.ecm_timeout(input.ecm_timeout);
+            ca.metrics(metrics.clone());
 
-                    let mc = Mediacontainer::from(&input.url);
-                    if mc == Mediacontainer::Ts {
-                        // source.add_consumer(ts-demuxer)
+            let mut epg = Epg::new(input.url.clone());
+            epg.out(input.epg_out.clone(), input.epg_format);
+            epg.metrics(metrics.clone());
+
+            let mut pts = Pts::new(input.url.clone());
+            pts.av_offset_max(input.av_offset_max);
+            pts.av_offset_abs_max(input.av_offset_abs_max);
+            pts.metrics(metrics.clone());
+
+            let mut gop = Gop::new(input.url.clone());
+            gop.keyframe_interval_max(input.keyframe_interval_max);
+            gop.metrics(metrics.clone());
+
+            let mut scte35 = Scte35::new(input.url.clone());
+            scte35.log(input.scte35_log.clone(), input.scte35_format);
+            scte35.metrics(metrics.clone());
+
+            let mut subtitles = Subtitles::new(input.url.clone());
+            subtitles.timeout(input.subtitle_timeout);
+            subtitles.metrics(metrics.clone());
+
+            let mut captions = Captions::new(input.url.clone());
+            captions.timeout(input.captions_timeout);
+            captions.out(input.captions_out.clone(), input.captions_format);
+            captions.metrics(metrics.clone());
+
+            let mut es = Es::new(input.url.clone());
+            es.metrics(metrics.clone());
+            es.add_consumer(Box::new(gop));
+            es.add_consumer(Box::new(scte35));
+            es.add_consumer(Box::new(subtitles));
+            es.add_consumer(Box::new(captions));
+            for output in input.outputs.iter() {
+                match output.url.to_file_path() {
+                    Ok(path) if output.url.scheme() == "file" => {
+                        es.add_consumer(Box::new(EsDump::new(input.url.clone(), path)))
                     }
+                    _ => warn!(
+                        "({}) [x] output not supported (:url {})",
+                        input.url, output.url
+                    ),
                 }
-                "file" => {
-                    let input = InputFile::new(input.url.clone());
+            }
 
-                    let mut source = Source::new(input);
-                    source.start()?;
-                }
-                _ => {}
-            };
+            let mut reassembler = Reassembler::new(input.url.clone());
+            reassembler.add_consumer(Box::new(es));
+
+            input_start(
+                input,
+                metrics,
+                vec![
+                    Box::new(bitrate),
+                    Box::new(tr101290),
+                    Box::new(pcr),
+                    Box::new(ca),
+                    Box::new(epg),
+                    Box::new(pts),
+                    Box::new(reassembler),
+                ],
+            )?;
         }
 
+        metrics::spawn_logger(metrics_all, self.config.metrics_interval)?;
+
         let chan = signal_chan()?;
         select! {
             recv(chan) -> _ => {
@@ -77,6 +209,143 @@ impl App {
 
         Ok(())
     }
+
+    /// read all inputs in parallel until every report is done
+    fn probe(&self) -> Result<()> {
+        let mut reports = Vec::with_capacity(self.config.inputs.len());
+
+        for input in self.config.inputs.iter() {
+            let probe = Probe::new(self.config.probe_packets);
+            reports.push(probe.report());
+
+            // stream parameters need access units
+            let mut es = Es::new(input.url.clone());
+            es.add_consumer(Box::new(probe));
+            let mut reassembler = Reassembler::new(input.url.clone());
+            reassembler.add_consumer(Box::new(es));
+
+            input_start(input, Metrics::default(), vec![Box::new(reassembler)])?;
+        }
+
+        let started_at = Instant::now();
+        let chan = signal_chan()?;
+        loop {
+            let done = reports
+                .iter()
+                .all(|report| report.is_done(started_at, self.config.probe_duration, PROBE_IDLE));
+            if done {
+                break;
+            }
+
+            select! {
+                recv(chan) -> _ => break,
+                default(PROBE_POLL) => {}
+            }
+        }
+
+        let outs: Vec<String> = reports
+            .iter()
+            .zip(self.config.inputs.iter())
+            .enumerate()
+            .map(|(i, (report, input))| report.render(i, &input.url, self.config.probe_format))
+            .collect();
+
+        match self.config.probe_format {
+            ProbeFormat::Text => print!("{}", outs.join("")),
+            ProbeFormat::Json => println!(r#"{{"inputs":[{}]}}"#, outs.join(",")),
+        }
+
+        Ok(())
+    }
+}
+
+/// build input by url scheme and start reading
+fn input_start(
+    input: &ConfigInput,
+    metrics: Metrics,
+    consumers: Vec<Box<dyn Consumer>>,
+) -> Result<()> {
+    match input.url.scheme() {
+        "udp" => {
+            let mut udp = InputUdp::new(input.url.clone());
+            udp.fifo_sz(input.udp_fifo_sz);
+            udp.sock_opts(input.udp_sock_opts);
+            udp.iat_buckets(input.iat_buckets.clone());
+            udp.mdi_opts(input.mdi_opts);
+            udp.metrics(metrics.clone());
+
+            source_start(udp, input, metrics, consumers)
+        }
+        "rtp" => {
+            let mut rtp = InputRtp::new(input.url.clone());
+            rtp.fifo_sz(input.udp_fifo_sz);
+            rtp.fec(input.rtp_fec);
+            rtp.sock_opts(input.udp_sock_opts);
+            rtp.iat_buckets(input.iat_buckets.clone());
+            rtp.mdi_opts(input.mdi_opts);
+            rtp.metrics(metrics.clone());
+
+            source_start(rtp, input, metrics, consumers)
+        }
+        "gen" => {
+            let mut gen = InputGen::new(input.url.clone(), GenParams::from_url(&input.url)?);
+            gen.metrics(metrics.clone());
+
+            source_start(gen, input, metrics, consumers)
+        }
+        "file" => {
+            let mut file = InputFile::new(input.url.clone());
+            file.m2ts(Mediacontainer::from(&input.url) == Mediacontainer::M2ts);
+
+            source_start(file, input, metrics, consumers)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// attach consumers by media container and start reading;
+/// extra consumers are fed by demuxer
+fn source_start<I>(
+    input: I,
+    cfg: &ConfigInput,
+    metrics: Metrics,
+    consumers: Vec<Box<dyn Consumer>>,
+) -> Result<()>
+where
+    I: Input + Send + 'static,
+{
+    let mut source = Source::new(input);
+
+    let mc = match Mediacontainer::detect(&cfg.url) {
+        Ok(detection) => {
+            info!(
+                "({}) [+] OK container (:container {} :confidence {})",
+                cfg.url, detection.container, detection.confidence
+            );
+            detection.container
+        }
+        Err(err) => {
+            warn!("({}) {}; fallback to ts", cfg.url, err);
+            Mediacontainer::Ts
+        }
+    };
+
+    match mc {
+        // rtp:// input de-encapsulates MPEG-TS payload,
+        // file:// input strips M2TS TP_extra_header
+        Mediacontainer::Ts | Mediacontainer::M2ts | Mediacontainer::Rtp => {
+            let mut demuxer = Demuxer::new(cfg.url.clone());
+            demuxer.metrics(metrics);
+            for consumer in consumers {
+                demuxer.add_consumer(consumer);
+            }
+
+            source.add_consumer(Box::new(demuxer));
+        }
+        _ => warn!("({}) [x] no demuxer for container {}", cfg.url, mc),
+    }
+
+    source.start()
 }
 
 /// main with optional Error
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use url::Url;
+
+use crate::clock::{discontinuity, PCR_GAP_MAX};
+use crate::metrics::Metrics;
+use crate::psi;
+
+/// 27MHz
+const PCR_CLOCK: f64 = 27_000_000.0;
+
+/// alarm thresholds and media rate
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Opts {
+    /// nominal media rate (bits/s); PCR-derived if not set
+    pub rate: Option<u64>,
+
+    /// delay factor alarm threshold
+    pub df_max: Duration,
+    /// media loss rate alarm threshold (packets/s)
+    pub mlr_max: f64,
+}
+
+impl Default for Opts {
+    fn default() -> Self {
+        Opts {
+            rate: None,
+            df_max: Duration::from_millis(50),
+            mlr_max: 0.0,
+        }
+    }
+}
+
+impl fmt::Display for Opts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.rate {
+            Some(rate) => write!(f, "(:rate {}", rate)?,
+            None => write!(f, "(:rate pcr")?,
+        }
+        write!(
+            f,
+            " :df-max {}ms :mlr-max {})",
+            self.df_max.as_secs_f64() * 1000.0,
+            self.mlr_max
+        )
+    }
+}
+
+/// RFC 4445 Media Delivery Index.
+///
+/// DF is virtual buffer depth swing over measurement interval
+/// divided by media rate; MLR is media packets lost (by CC) per second
+pub struct Mdi {
+    url: Url,
+    opts: Opts,
+
+    /// virtual buffer; bytes
+    vb: f64,
+    vb_at: Option<Instant>,
+    vb_min: f64,
+    vb_max: f64,
+
+    /// pid -> last continuity counter
+    cc: HashMap<u16, u8>,
+    lost: u64,
+
+    /// media rate estimation by PCR of first PCR PID
+    pcr_pid: Option<u16>,
+    pcr_last: Option<u64>,
+    pcr_bytes: u64,
+    /// bytes/s
+    rate_pcr: Option<f64>,
+
+    interval_at: Instant,
+
+    alarm: bool,
+    alarms: u64,
+}
+
+impl Mdi {
+    pub fn new(url: Url, opts: Opts) -> Mdi {
+        Mdi {
+            url,
+            opts,
+
+            vb: 0.0,
+            vb_at: None,
+            vb_min: 0.0,
+            vb_max: 0.0,
+
+            cc: HashMap::new(),
+            lost: 0,
+
+            pcr_pid: None,
+            pcr_last: None,
+            pcr_bytes: 0,
+            rate_pcr: None,
+
+            interval_at: Instant::now(),
+
+            alarm: false,
+            alarms: 0,
+        }
+    }
+
+    /// media rate; bytes/s
+    fn rate(&self) -> Option<f64> {
+        self.opts
+            .rate
+            .map(|rate| rate as f64 / 8.0)
+            .or(self.rate_pcr)
+    }
+
+    /// datagram payload (TS packets) received at
+    pub fn update(&mut self, at: Instant, buf: &[u8]) {
+        let rate = self.rate();
+
+        // drain at media rate since previous arrival
+        if let (Some(vb_at), Some(rate)) = (self.vb_at, rate) {
+            self.vb -= rate * at.saturating_duration_since(vb_at).as_secs_f64();
+        }
+        self.vb_min = self.vb_min.min(self.vb);
+
+        self.vb += buf.len() as f64;
+        self.vb_max = self.vb_max.max(self.vb);
+        self.vb_at = Some(at);
+
+        for raw in buf.chunks_exact(ts::Packet::SZ) {
+            self.packet(raw);
+        }
+    }
+
+    fn packet(&mut self, raw: &[u8]) {
+        let pkt = match ts::Packet::new(raw) {
+            Ok(pkt) => pkt,
+            Err(_) => return,
+        };
+
+        // null packets are part of the media (TS) rate
+        self.pcr_bytes += raw.len() as u64;
+
+        let pid = u16::from(pkt.pid());
+        if pid == psi::PID_NULL {
+            return;
+        }
+
+        if let Ok(Some(pcr)) = pkt.pcr() {
+            if *self.pcr_pid.get_or_insert(pid) == pid {
+                self.pcr(pcr.value());
+            }
+        }
+
+        let afc = raw[3] & 0b0011_0000;
+        let got_payload = (afc & 0b0001_0000) != 0;
+        let discontinuity = discontinuity(raw);
+
+        // CC increments only with payload
+        if !got_payload {
+            return;
+        }
+
+        let cc = pkt.cc();
+        if let Some(last) = self.cc.insert(pid, cc) {
+            let gap = cc.wrapping_sub(last) & 0x0F;
+            // gap 0 is a duplicate; 1 is in order
+            if gap > 1 && !discontinuity {
+                self.lost += u64::from(gap - 1);
+            }
+        }
+    }
+
+    fn pcr(&mut self, pcr: u64) {
+        if let Some(pcr_last) = self.pcr_last {
+            if pcr > pcr_last && pcr - pcr_last <= PCR_GAP_MAX {
+                let secs = (pcr - pcr_last) as f64 / PCR_CLOCK;
+                let rate = self.pcr_bytes as f64 / secs;
+
+                // smooth over PCR intervals
+                self.rate_pcr = Some(match self.rate_pcr {
+                    Some(rate_pcr) => rate_pcr * 0.9 + rate * 0.1,
+                    None => rate,
+                });
+            }
+        }
+
+        self.pcr_last = Some(pcr);
+        self.pcr_bytes = 0;
+    }
+
+    /// close measurement interval; publish DF:MLR
+    /// and raise/clear alarm
+    pub fn publish(&mut self, metrics: &Metrics, prefix: &str) {
+        let secs = self.interval_at.elapsed().as_secs_f64();
+        let rate = self.rate();
+
+        let df = match rate {
+            Some(rate) if rate > 0.0 => {
+                Duration::from_secs_f64((self.vb_max - self.vb_min).max(0.0) / rate)
+            }
+            _ => Duration::from_secs(0),
+        };
+        let mlr = if secs > 0.0 {
+            self.lost as f64 / secs
+        } else {
+            0.0
+        };
+        let df_ms = df.as_secs_f64() * 1000.0;
+
+        let alarm = rate.is_some() && (df > self.opts.df_max || mlr > self.opts.mlr_max);
+        if alarm {
+            self.alarms += 1;
+        }
+        if alarm && !self.alarm {
+            warn!(
+                "({}) [mdi] alarm raised (:df {:.3}ms :mlr {:.3} :thresholds {})",
+                self.url, df_ms, mlr, self.opts
+            );
+        } else if !alarm && self.alarm {
+            info!(
+                "({}) [mdi] alarm cleared (:df {:.3}ms :mlr {:.3})",
+                self.url, df_ms, mlr
+            );
+        }
+        self.alarm = alarm;
+
+        metrics.set(prefix.to_string(), format!("{:.3}:{:.3}", df_ms, mlr));
+        metrics.set(format!("{}-df-ms", prefix), df_ms);
+        metrics.set(format!("{}-mlr", prefix), mlr);
+        metrics.set(format!("{}-alarms", prefix), self.alarms);
+        if let Some(rate) = rate {
+            metrics.set(format!("{}-rate", prefix), (rate * 8.0) as u64);
+        }
+
+        // next interval starts with empty virtual buffer
+        self.vb = 0.0;
+        self.vb_min = 0.0;
+        self.vb_max = 0.0;
+        self.lost = 0;
+        self.interval_at = Instant::now();
+    }
+}
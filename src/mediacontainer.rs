@@ -1,16 +1,242 @@
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
 use url::Url;
 
-#[derive(Debug, Eq, PartialEq)]
+use crate::error::{Error, Result};
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Mediacontainer {
     Ts,
-    Mp4 { fragmented: bool },
+    /// BDAV MPEG-2 TS: 4 byte TP_extra_header before each packet
+    M2ts,
+    Mp4 {
+        fragmented: bool,
+    },
     WebM,
+    Hls,
     Rtp,
     Rtsp,
 }
 
+/// how sure detection is; ordered from weakest to strongest hint
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Confidence {
+    /// convention only (e.g. udp:// is usually MPEG-TS)
+    Low,
+    /// path extension
+    Medium,
+    /// content probing, scheme or explicit ?format=
+    High,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Detection {
+    pub container: Mediacontainer,
+    pub confidence: Confidence,
+}
+
+impl Detection {
+    fn new(container: Mediacontainer, confidence: Confidence) -> Detection {
+        Detection {
+            container,
+            confidence,
+        }
+    }
+}
+
+impl Mediacontainer {
+    /// bytes to read from local files for content probing
+    const PROBE_SZ: usize = 4 * 1024;
+
+    /// consecutive sync bytes required to accept MPEG-TS
+    const TS_SYNC_MIN: usize = 3;
+    const TS_SYNC_BYTE: u8 = 0x47;
+    /// TP_extra_header (copy permission + arrival time stamp)
+    pub const M2TS_HEADER_SZ: usize = 4;
+
+    const EBML_MAGIC: [u8; 4] = [0x1A, 0x45, 0xDF, 0xA3];
+
+    /// detect container by (strongest first):
+    ///   - ?format= query override
+    ///   - url scheme
+    ///   - content probing (file:// only)
+    ///   - path extension
+    pub fn detect(url: &Url) -> Result<Detection> {
+        if let Some((_, format)) = url.query_pairs().find(|(key, _)| key == "format") {
+            return Self::from_format(&format)
+                .map(|container| Detection::new(container, Confidence::High))
+                .ok_or_else(|| Error::mediacontainer_format(format.as_ref()));
+        }
+
+        match url.scheme() {
+            "rtp" => return Ok(Detection::new(Mediacontainer::Rtp, Confidence::High)),
+            "rtsp" => return Ok(Detection::new(Mediacontainer::Rtsp, Confidence::High)),
+            "gen" => return Ok(Detection::new(Mediacontainer::Ts, Confidence::High)),
+            _ => {}
+        }
+
+        if url.scheme() == "file" {
+            if let Some(detection) = Self::probe_file(url.path()) {
+                return Ok(detection);
+            }
+        }
+
+        if let Some(container) = Self::from_extension(url.path()) {
+            return Ok(Detection::new(container, Confidence::Medium));
+        }
+
+        if url.scheme() == "udp" {
+            return Ok(Detection::new(Mediacontainer::Ts, Confidence::Low));
+        }
+
+        Err(Error::mediacontainer_unknown(url.as_str()))
+    }
+
+    fn from_format(format: &str) -> Option<Mediacontainer> {
+        match format.to_lowercase().as_str() {
+            "ts" | "mpegts" => Some(Mediacontainer::Ts),
+            "m2ts" | "bdav" => Some(Mediacontainer::M2ts),
+            "mp4" => Some(Mediacontainer::Mp4 { fragmented: false }),
+            "fmp4" | "cmaf" => Some(Mediacontainer::Mp4 { fragmented: true }),
+            "webm" | "mkv" | "matroska" => Some(Mediacontainer::WebM),
+            "hls" | "m3u8" => Some(Mediacontainer::Hls),
+            "rtp" => Some(Mediacontainer::Rtp),
+            "rtsp" => Some(Mediacontainer::Rtsp),
+            _ => None,
+        }
+    }
+
+    fn from_extension(path: &str) -> Option<Mediacontainer> {
+        let ext = Path::new(path).extension()?.to_str()?.to_lowercase();
+
+        match ext.as_str() {
+            "ts" | "trp" => Some(Mediacontainer::Ts),
+            "m2ts" | "mts" => Some(Mediacontainer::M2ts),
+            "mp4" | "m4v" | "m4a" | "mov" => Some(Mediacontainer::Mp4 { fragmented: false }),
+            "m4s" | "cmfv" | "cmfa" => Some(Mediacontainer::Mp4 { fragmented: true }),
+            "webm" | "mkv" => Some(Mediacontainer::WebM),
+            "m3u8" => Some(Mediacontainer::Hls),
+            _ => None,
+        }
+    }
+
+    fn probe_file(path: &str) -> Option<Detection> {
+        let mut buf = Vec::with_capacity(Self::PROBE_SZ);
+        File::open(path)
+            .ok()?
+            .take(Self::PROBE_SZ as u64)
+            .read_to_end(&mut buf)
+            .ok()?;
+
+        Self::probe(&buf)
+    }
+
+    /// content sniffing of the first kilobytes
+    pub fn probe(buf: &[u8]) -> Option<Detection> {
+        if Self::probe_ts(buf, 0) {
+            return Some(Detection::new(Mediacontainer::Ts, Confidence::High));
+        }
+
+        if Self::probe_ts(buf, Self::M2TS_HEADER_SZ) {
+            return Some(Detection::new(Mediacontainer::M2ts, Confidence::High));
+        }
+
+        if let Some(fragmented) = Self::probe_mp4(buf) {
+            return Some(Detection::new(
+                Mediacontainer::Mp4 { fragmented },
+                Confidence::High,
+            ));
+        }
+
+        if buf.starts_with(&Self::EBML_MAGIC) {
+            // matroska without "webm" DocType is still demuxable the same way
+            let confidence = if find(buf, b"webm").is_some() {
+                Confidence::High
+            } else {
+                Confidence::Medium
+            };
+            return Some(Detection::new(Mediacontainer::WebM, confidence));
+        }
+
+        if buf.starts_with(b"#EXTM3U") {
+            return Some(Detection::new(Mediacontainer::Hls, Confidence::High));
+        }
+
+        None
+    }
+
+    /// 0x47 sync byte cadence at 188 (+ prefix) byte stride;
+    /// first sync byte may be shifted (e.g. truncated capture)
+    fn probe_ts(buf: &[u8], prefix: usize) -> bool {
+        let sz = ts::Packet::SZ + prefix;
+        let need = Self::TS_SYNC_MIN.min(buf.len() / sz);
+        if need == 0 {
+            return false;
+        }
+
+        (0..sz.min(buf.len())).any(|offset| {
+            let syncs = buf[offset..]
+                .iter()
+                .step_by(sz)
+                .take_while(|b| **b == Self::TS_SYNC_BYTE)
+                .count();
+
+            syncs >= need && syncs >= (buf.len() - offset) / sz
+        })
+    }
+
+    /// ISO BMFF: `ftyp` or `styp` box first;
+    /// fragmented if movie extends / fragment boxes are seen
+    fn probe_mp4(buf: &[u8]) -> Option<bool> {
+        if buf.len() < 8 || !(&buf[4..8] == b"ftyp" || &buf[4..8] == b"styp") {
+            return None;
+        }
+
+        let fragmented =
+            &buf[4..8] == b"styp" || find(buf, b"mvex").is_some() || find(buf, b"moof").is_some();
+
+        Some(fragmented)
+    }
+}
+
+fn find(buf: &[u8], needle: &[u8]) -> Option<usize> {
+    buf.windows(needle.len()).position(|w| w == needle)
+}
+
+/// detection with fallback to MPEG-TS
 impl From<&url::Url> for Mediacontainer {
-    fn from(u: &Url) -> Self {
-        Mediacontainer::Ts
+    fn from(url: &Url) -> Self {
+        Mediacontainer::detect(url)
+            .map(|detection| detection.container)
+            .unwrap_or(Mediacontainer::Ts)
+    }
+}
+
+impl fmt::Display for Mediacontainer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mediacontainer::Ts => write!(f, "ts"),
+            Mediacontainer::M2ts => write!(f, "m2ts"),
+            Mediacontainer::Mp4 { fragmented: false } => write!(f, "mp4"),
+            Mediacontainer::Mp4 { fragmented: true } => write!(f, "fmp4"),
+            Mediacontainer::WebM => write!(f, "webm"),
+            Mediacontainer::Hls => write!(f, "hls"),
+            Mediacontainer::Rtp => write!(f, "rtp"),
+            Mediacontainer::Rtsp => write!(f, "rtsp"),
+        }
+    }
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Confidence::Low => write!(f, "low"),
+            Confidence::Medium => write!(f, "medium"),
+            Confidence::High => write!(f, "high"),
+        }
     }
 }
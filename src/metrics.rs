@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use log::{error, info};
+
+use crate::error::{Error, Result};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Counter(u64),
+    Gauge(f64),
+    Text(String),
+}
+
+impl From<u64> for Value {
+    fn from(v: u64) -> Self {
+        Value::Counter(v)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(v: usize) -> Self {
+        Value::Counter(v as u64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Gauge(v)
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::Text(v)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::Text(v.to_string())
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Counter(v) => write!(f, "{}", v),
+            Value::Gauge(v) => write!(f, "{:.3}", v),
+            Value::Text(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// per-input metrics storage;
+/// inputs and analyzers write, reporters read.
+///
+/// keys are kebab-case e.g. "udp-rcvbuf", "fec-recovered"
+#[derive(Clone, Default)]
+pub struct Metrics(Arc<Mutex<BTreeMap<String, Value>>>);
+
+impl Metrics {
+    pub fn set<K: Into<String>, V: Into<Value>>(&self, key: K, value: V) {
+        match self.0.lock() {
+            Ok(mut map) => {
+                map.insert(key.into(), value.into());
+            }
+            Err(err) => error!("metrics lock failed (:reason {})", err),
+        }
+    }
+
+    /// copy of all metrics sorted by key
+    pub fn snapshot(&self) -> Result<BTreeMap<String, Value>> {
+        self.0
+            .lock()
+            .map(|map| map.clone())
+            .map_err(|err| Error::metrics_lock(err.to_string()))
+    }
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let snapshot = match self.snapshot() {
+            Ok(snapshot) => snapshot,
+            Err(err) => return write!(f, "(:error {})", err),
+        };
+
+        write!(f, "(")?;
+        for (i, (key, value)) in snapshot.iter().enumerate() {
+            if i != 0 {
+                write!(f, " ")?;
+            }
+            write!(f, ":{} {}", key, value)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// log metrics of every input each interval
+pub fn spawn_logger(inputs: Vec<(String, Metrics)>, interval: Duration) -> Result<()> {
+    thread::Builder::new()
+        .name("metrics".to_string())
+        .spawn(move || loop {
+            thread::sleep(interval);
+
+            for (name, metrics) in inputs.iter() {
+                info!("({}) [metrics] {}", name, metrics);
+            }
+        })
+        .map_err(Error::metrics_spawn)?;
+
+    Ok(())
+}
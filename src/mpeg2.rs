@@ -0,0 +1,666 @@
+use std::fmt;
+
+use crate::bits::Bits;
+use crate::colour::Colour;
+use crate::frame::{Picture, PictureType};
+use crate::h264::{chroma_str, fps_str};
+use crate::nal;
+use crate::probe::{json_opt_str, json_str};
+
+pub const PICTURE_START: u8 = 0x00;
+pub const SLICE_START_MIN: u8 = 0x01;
+pub const SLICE_START_MAX: u8 = 0xAF;
+pub const USER_DATA_START: u8 = 0xB2;
+pub const SEQUENCE_HEADER: u8 = 0xB3;
+pub const EXTENSION_START: u8 = 0xB5;
+pub const GROUP_START: u8 = 0xB8;
+
+/// longest fixed part of parsed headers (sequence display extension)
+const HEADER_SZ: usize = 8;
+
+const EXT_SEQUENCE: u8 = 1;
+const EXT_SEQUENCE_DISPLAY: u8 = 2;
+const EXT_PICTURE_CODING: u8 = 8;
+
+/// picture_structure
+const PICTURE_FRAME: u8 = 3;
+
+/// frame_rate_code 1..8 as (numerator, denominator)
+const FRAME_RATES: [(u32, u32); 9] = [
+    (0, 0),
+    (24000, 1001),
+    (24, 1),
+    (25, 1),
+    (30000, 1001),
+    (30, 1),
+    (50, 1),
+    (60000, 1001),
+    (60, 1),
+];
+
+/// sequence_header (ISO/IEC 13818-2 6.2.2.1)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sequence {
+    pub width: u16,
+    pub height: u16,
+    pub aspect_ratio_information: u8,
+    pub frame_rate_code: u8,
+    /// 400 bit/s units; 0x3FFFF is variable for MPEG-1
+    pub bit_rate_value: u32,
+    /// 16 kbit units
+    pub vbv_buffer_size_value: u16,
+    pub constrained_parameters: bool,
+}
+
+impl Sequence {
+    /// after start code
+    pub fn parse(data: &[u8]) -> Option<Sequence> {
+        let mut r = Bits::new(data);
+
+        let width = r.bits(12)? as u16;
+        let height = r.bits(12)? as u16;
+        let aspect_ratio_information = r.u8(4)?;
+        let frame_rate_code = r.u8(4)?;
+        let bit_rate_value = r.bits(18)?;
+        // marker_bit
+        r.skip(1)?;
+        let vbv_buffer_size_value = r.bits(10)? as u16;
+        let constrained_parameters = r.flag()?;
+
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        Some(Sequence {
+            width,
+            height,
+            aspect_ratio_information,
+            frame_rate_code,
+            bit_rate_value,
+            vbv_buffer_size_value,
+            constrained_parameters,
+        })
+    }
+}
+
+/// sequence_extension (6.2.2.3)
+#[derive(Clone, Debug, PartialEq)]
+pub struct SequenceExtension {
+    pub profile_and_level: u8,
+    pub progressive_sequence: bool,
+    pub chroma_format: u8,
+    pub horizontal_size_extension: u8,
+    pub vertical_size_extension: u8,
+    pub bit_rate_extension: u16,
+    pub vbv_buffer_size_extension: u8,
+    pub low_delay: bool,
+    pub frame_rate_extension_n: u8,
+    pub frame_rate_extension_d: u8,
+}
+
+impl SequenceExtension {
+    /// after extension_start_code_identifier
+    fn parse(r: &mut Bits) -> Option<SequenceExtension> {
+        let profile_and_level = r.u8(8)?;
+        let progressive_sequence = r.flag()?;
+        let chroma_format = r.u8(2)?;
+        let horizontal_size_extension = r.u8(2)?;
+        let vertical_size_extension = r.u8(2)?;
+        let bit_rate_extension = r.bits(12)? as u16;
+        // marker_bit
+        r.skip(1)?;
+        let vbv_buffer_size_extension = r.u8(8)?;
+        let low_delay = r.flag()?;
+        let frame_rate_extension_n = r.u8(2)?;
+        let frame_rate_extension_d = r.u8(5)?;
+
+        Some(SequenceExtension {
+            profile_and_level,
+            progressive_sequence,
+            chroma_format,
+            horizontal_size_extension,
+            vertical_size_extension,
+            bit_rate_extension,
+            vbv_buffer_size_extension,
+            low_delay,
+            frame_rate_extension_n,
+            frame_rate_extension_d,
+        })
+    }
+
+    pub fn profile(&self) -> &'static str {
+        // escape bit: 4:2:2 and multi-view profiles
+        if self.profile_and_level & 0x80 != 0 {
+            return match self.profile_and_level & 0x7F {
+                0x02 | 0x05 => "4:2:2",
+                0x0A | 0x0B | 0x0D | 0x0E => "multi-view",
+                _ => "reserved",
+            };
+        }
+
+        match (self.profile_and_level >> 4) & 0x07 {
+            1 => "high",
+            2 => "spatial",
+            3 => "snr",
+            4 => "main",
+            5 => "simple",
+            _ => "reserved",
+        }
+    }
+
+    pub fn level(&self) -> &'static str {
+        let level = if self.profile_and_level & 0x80 != 0 {
+            match self.profile_and_level & 0x7F {
+                0x02 | 0x0A => 4,
+                0x05 | 0x0B => 8,
+                0x0D => 6,
+                0x0E => 10,
+                _ => 0,
+            }
+        } else {
+            self.profile_and_level & 0x0F
+        };
+
+        match level {
+            4 => "high",
+            6 => "high-1440",
+            8 => "main",
+            10 => "low",
+            _ => "reserved",
+        }
+    }
+}
+
+/// sequence_display_extension (6.2.2.4)
+#[derive(Clone, Debug, PartialEq)]
+pub struct SequenceDisplay {
+    pub video_format: u8,
+    pub colour: Option<Colour>,
+    pub display_width: u16,
+    pub display_height: u16,
+}
+
+impl SequenceDisplay {
+    /// after extension_start_code_identifier
+    fn parse(r: &mut Bits) -> Option<SequenceDisplay> {
+        let video_format = r.u8(3)?;
+        let colour = if r.flag()? {
+            Some(Colour {
+                primaries: r.u8(8)?,
+                transfer: r.u8(8)?,
+                matrix: r.u8(8)?,
+            })
+        } else {
+            None
+        };
+        let display_width = r.bits(14)? as u16;
+        // marker_bit
+        r.skip(1)?;
+        let display_height = r.bits(14)? as u16;
+
+        Some(SequenceDisplay {
+            video_format,
+            colour,
+            display_width,
+            display_height,
+        })
+    }
+}
+
+/// group_of_pictures_header (6.2.2.6)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Gop {
+    pub drop_frame: bool,
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub pictures: u8,
+    pub closed: bool,
+    pub broken_link: bool,
+}
+
+impl Gop {
+    /// after start code
+    pub fn parse(data: &[u8]) -> Option<Gop> {
+        let mut r = Bits::new(data);
+
+        let drop_frame = r.flag()?;
+        let hours = r.u8(5)?;
+        let minutes = r.u8(6)?;
+        // marker_bit
+        r.skip(1)?;
+        let seconds = r.u8(6)?;
+        let pictures = r.u8(6)?;
+
+        Some(Gop {
+            drop_frame,
+            hours,
+            minutes,
+            seconds,
+            pictures,
+            closed: r.flag()?,
+            broken_link: r.flag()?,
+        })
+    }
+
+    /// SMPTE style; ';' before frames if drop frame
+    pub fn timecode(&self) -> String {
+        format!(
+            "{:02}:{:02}:{:02}{}{:02}",
+            self.hours,
+            self.minutes,
+            self.seconds,
+            if self.drop_frame { ';' } else { ':' },
+            self.pictures
+        )
+    }
+}
+
+impl fmt::Display for Gop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            ":timecode {} :closed {} :broken-link {}",
+            self.timecode(),
+            self.closed,
+            self.broken_link
+        )
+    }
+}
+
+/// stream parameters attached to track;
+/// MPEG-1 if sequence extension is absent
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mpeg2 {
+    pub seq: Sequence,
+    pub ext: Option<SequenceExtension>,
+    pub display: Option<SequenceDisplay>,
+}
+
+impl Mpeg2 {
+    pub fn width(&self) -> u32 {
+        let ext = self
+            .ext
+            .as_ref()
+            .map(|ext| u32::from(ext.horizontal_size_extension))
+            .unwrap_or(0);
+        (ext << 12) | u32::from(self.seq.width)
+    }
+
+    pub fn height(&self) -> u32 {
+        let ext = self
+            .ext
+            .as_ref()
+            .map(|ext| u32::from(ext.vertical_size_extension))
+            .unwrap_or(0);
+        (ext << 12) | u32::from(self.seq.height)
+    }
+
+    /// display aspect ratio for MPEG-2, pel aspect ratio (height/width) for MPEG-1
+    pub fn aspect(&self) -> &'static str {
+        let code = self.seq.aspect_ratio_information;
+        if self.ext.is_some() {
+            return match code {
+                1 => "1:1",
+                2 => "4:3",
+                3 => "16:9",
+                4 => "2.21:1",
+                _ => "reserved",
+            };
+        }
+
+        match code {
+            1 => "1.0",
+            2 => "0.6735",
+            3 => "0.7031",
+            4 => "0.7615",
+            5 => "0.8055",
+            6 => "0.8437",
+            7 => "0.8935",
+            8 => "0.9157",
+            9 => "0.9815",
+            10 => "1.0255",
+            11 => "1.0695",
+            12 => "1.0950",
+            13 => "1.1575",
+            14 => "1.2015",
+            _ => "reserved",
+        }
+    }
+
+    pub fn fps(&self) -> Option<f64> {
+        let (n, d) = *FRAME_RATES
+            .get(usize::from(self.seq.frame_rate_code))
+            .filter(|(n, _)| *n > 0)?;
+        let (ext_n, ext_d) = self
+            .ext
+            .as_ref()
+            .map(|ext| {
+                (
+                    u32::from(ext.frame_rate_extension_n) + 1,
+                    u32::from(ext.frame_rate_extension_d) + 1,
+                )
+            })
+            .unwrap_or((1, 1));
+
+        Some(f64::from(n * ext_n) / f64::from(d * ext_d))
+    }
+
+    /// bit/s; None if variable (MPEG-1)
+    pub fn bitrate(&self) -> Option<u64> {
+        match self.ext.as_ref() {
+            Some(ext) => {
+                let value =
+                    (u64::from(ext.bit_rate_extension) << 18) | u64::from(self.seq.bit_rate_value);
+                Some(value * 400)
+            }
+            None if self.seq.bit_rate_value == 0x3FFFF => None,
+            None => Some(u64::from(self.seq.bit_rate_value) * 400),
+        }
+    }
+
+    /// bits
+    pub fn vbv_size(&self) -> u64 {
+        let ext = self
+            .ext
+            .as_ref()
+            .map(|ext| u64::from(ext.vbv_buffer_size_extension))
+            .unwrap_or(0);
+        ((ext << 10) | u64::from(self.seq.vbv_buffer_size_value)) * 16 * 1024
+    }
+
+    pub fn chroma(&self) -> &'static str {
+        chroma_str(self.ext.as_ref().map(|ext| ext.chroma_format).unwrap_or(1))
+    }
+
+    pub fn scan(&self) -> &'static str {
+        match self.ext.as_ref() {
+            Some(ext) if !ext.progressive_sequence => "interlaced",
+            _ => "progressive",
+        }
+    }
+
+    pub fn colour(&self) -> Option<&Colour> {
+        self.display
+            .as_ref()
+            .and_then(|display| display.colour.as_ref())
+    }
+
+    pub fn json(&self) -> String {
+        let opt = |v: Option<String>| v.unwrap_or_else(|| "null".to_string());
+
+        format!(
+            r#"{{"standard":"{}","profile":{},"level":{},"width":{},"height":{},"aspect":{},"chroma_format":"{}","scan":"{}","fps":{},"bitrate":{},"vbv_size":{},"low_delay":{},"colour":{}}}"#,
+            if self.ext.is_some() { "mpeg2" } else { "mpeg1" },
+            json_opt_str(self.ext.as_ref().map(|ext| ext.profile())),
+            json_opt_str(self.ext.as_ref().map(|ext| ext.level())),
+            self.width(),
+            self.height(),
+            json_str(self.aspect()),
+            self.chroma(),
+            self.scan(),
+            opt(self.fps().map(fps_str)),
+            opt(self.bitrate().map(|v| v.to_string())),
+            self.vbv_size(),
+            self.ext.as_ref().map(|ext| ext.low_delay).unwrap_or(false),
+            opt(self.colour().map(|colour| colour.json())),
+        )
+    }
+}
+
+impl fmt::Display for Mpeg2 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.ext.as_ref() {
+            Some(ext) => write!(f, ":profile {} :level {}", ext.profile(), ext.level())?,
+            None => write!(f, ":standard mpeg1")?,
+        }
+        write!(
+            f,
+            " :size {}x{} :aspect {} :chroma {} :scan {}",
+            self.width(),
+            self.height(),
+            self.aspect(),
+            self.chroma(),
+            self.scan()
+        )?;
+        if let Some(fps) = self.fps() {
+            write!(f, " :fps {}", fps_str(fps))?;
+        }
+        if let Some(bitrate) = self.bitrate() {
+            write!(f, " :bitrate {}", bitrate)?;
+        }
+        write!(f, " :vbv {}", self.vbv_size())?;
+        if self.ext.as_ref().map(|ext| ext.low_delay).unwrap_or(false) {
+            write!(f, " :low-delay true")?;
+        }
+        if let Some(colour) = self.colour() {
+            write!(f, " {}", colour)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// sequence, GOP and picture headers of one stream
+#[derive(Default)]
+pub struct Headers {
+    seq: Option<Sequence>,
+    ext: Option<SequenceExtension>,
+    display: Option<SequenceDisplay>,
+
+    /// last GOP header not taken yet
+    gop: Option<Gop>,
+
+    /// first picture of last pushed access unit
+    picture: Option<Picture>,
+    /// picture_structure of previous field picture without its pair
+    field: Option<u8>,
+}
+
+impl Headers {
+    /// headers of access unit up to first slice;
+    /// true if sequence header or its extensions changed
+    pub fn push(&mut self, au: &[u8]) -> bool {
+        let mut changed = false;
+        let mut after_seq = false;
+        let mut after_picture = false;
+        let mut closed = false;
+        let mut kind = None;
+        let mut structure = PICTURE_FRAME;
+
+        for unit in nal::units(au) {
+            let (code, data) = match unit.split_first() {
+                Some((code, data)) => (*code, data),
+                None => continue,
+            };
+            // no stop bit: zero bytes before next start code are trimmed
+            // by units but may be header fields
+            let mut data = data.to_vec();
+            if data.len() < HEADER_SZ {
+                data.resize(HEADER_SZ, 0);
+            }
+            let data = &data[..];
+
+            match code {
+                SEQUENCE_HEADER => {
+                    if let Some(seq) = Sequence::parse(data) {
+                        // extensions are repeated with every sequence header
+                        if self.seq.as_ref() != Some(&seq) {
+                            self.ext = None;
+                            self.display = None;
+                            self.seq = Some(seq);
+                            changed = true;
+                        }
+                        after_seq = true;
+                    }
+                }
+                // sequence level extensions follow sequence header only
+                EXTENSION_START if after_seq => {
+                    let mut r = Bits::new(data);
+                    match r.u8(4) {
+                        Some(EXT_SEQUENCE) => {
+                            if let Some(ext) = SequenceExtension::parse(&mut r) {
+                                changed |= self.ext.as_ref() != Some(&ext);
+                                self.ext = Some(ext);
+                            }
+                        }
+                        Some(EXT_SEQUENCE_DISPLAY) => {
+                            if let Some(display) = SequenceDisplay::parse(&mut r) {
+                                changed |= self.display.as_ref() != Some(&display);
+                                self.display = Some(display);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                EXTENSION_START if after_picture => {
+                    let mut r = Bits::new(data);
+                    if r.u8(4) == Some(EXT_PICTURE_CODING) {
+                        // f_code[2][2], intra_dc_precision
+                        if let Some(v) = r.skip(16 + 2).and_then(|_| r.u8(2)) {
+                            structure = v;
+                        }
+                    }
+                }
+                GROUP_START => {
+                    after_seq = false;
+                    if let Some(gop) = Gop::parse(data) {
+                        closed = gop.closed;
+                        self.gop = Some(gop);
+                    }
+                }
+                PICTURE_START => {
+                    after_seq = false;
+                    after_picture = true;
+
+                    // temporal_reference, picture_coding_type
+                    let mut r = Bits::new(data);
+                    kind = match r.skip(10).and_then(|_| r.u8(3)) {
+                        Some(1) => Some(PictureType::I),
+                        Some(2) => Some(PictureType::P),
+                        Some(3) => Some(PictureType::B),
+                        _ => None,
+                    };
+                }
+                SLICE_START_MIN..=SLICE_START_MAX => break,
+                _ => {}
+            }
+        }
+
+        self.picture = kind.map(|kind| {
+            // field pair: top and bottom in either order
+            let second_field = match self.field {
+                Some(field) if structure != PICTURE_FRAME && field != structure => {
+                    self.field = None;
+                    true
+                }
+                _ => {
+                    self.field = Some(structure).filter(|s| *s != PICTURE_FRAME);
+                    false
+                }
+            };
+
+            Picture {
+                kind,
+                closed: kind == PictureType::I && closed,
+                second_field,
+            }
+        });
+
+        changed
+    }
+
+    pub fn params(&self) -> Option<Mpeg2> {
+        Some(Mpeg2 {
+            seq: self.seq.clone()?,
+            ext: self.ext.clone(),
+            display: self.display.clone(),
+        })
+    }
+
+    /// GOP header of last pushed access units
+    pub fn gop(&mut self) -> Option<Gop> {
+        self.gop.take()
+    }
+
+    /// first picture of last pushed access unit
+    pub fn picture(&self) -> Option<Picture> {
+        self.picture
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 720x576 4:3 25fps 15 Mbit/s
+    const SEQUENCE: [u8; 8] = [0x2D, 0x02, 0x40, 0x23, 0x24, 0x9F, 0x23, 0x80];
+    /// Main@Main 4:2:0 interlaced
+    const EXTENSION: [u8; 6] = [0x14, 0x82, 0x00, 0x01, 0x00, 0x00];
+    /// 10:20:30:12 closed
+    const GOP: [u8; 4] = [0x29, 0x4B, 0xC6, 0x40];
+    /// I picture
+    const PICTURE: [u8; 4] = [0x00, 0x0F, 0xFF, 0xF8];
+
+    fn au(extension: &[u8]) -> Vec<u8> {
+        let mut au = Vec::new();
+        for (code, data) in [
+            (SEQUENCE_HEADER, &SEQUENCE[..]),
+            (EXTENSION_START, extension),
+            (GROUP_START, &GOP[..]),
+            (PICTURE_START, &PICTURE[..]),
+            (SLICE_START_MIN, &[0x11, 0x22][..]),
+        ] {
+            au.extend_from_slice(&[0x00, 0x00, 0x01, code]);
+            au.extend_from_slice(data);
+        }
+        au
+    }
+
+    #[test]
+    fn valid() {
+        let mut headers = Headers::default();
+        assert!(headers.push(&au(&EXTENSION)));
+        assert!(!headers.push(&au(&EXTENSION)));
+
+        let params = headers.params().unwrap();
+        assert_eq!((params.width(), params.height()), (720, 576));
+        assert_eq!(params.aspect(), "4:3");
+        assert_eq!(params.fps(), Some(25.0));
+        assert_eq!(params.bitrate(), Some(15_000_000));
+        assert_eq!(params.vbv_size(), 112 * 16 * 1024);
+        let ext = params.ext.unwrap();
+        assert_eq!((ext.profile(), ext.level()), ("main", "main"));
+
+        assert_eq!(headers.gop().unwrap().timecode(), "10:20:30:12");
+        let picture = headers.picture().unwrap();
+        assert_eq!(picture.kind, PictureType::I);
+        assert!(picture.closed);
+    }
+
+    #[test]
+    fn truncated() {
+        for len in 0..SEQUENCE.len() {
+            assert!(Sequence::parse(&SEQUENCE[..len]).is_none());
+        }
+        assert!(Gop::parse(&GOP[..3]).is_none());
+
+        let au = au(&EXTENSION);
+        for len in 0..au.len() {
+            Headers::default().push(&au[..len]);
+        }
+    }
+
+    #[test]
+    fn length_overrun() {
+        // all size, bit rate, VBV and frame rate extension bits set
+        let mut headers = Headers::default();
+        headers.push(&au(&[0x14, 0x83, 0xFF, 0xFF, 0xFF, 0x7F]));
+
+        let params = headers.params().unwrap();
+        assert_eq!((params.width(), params.height()), (13008, 12864));
+        assert_eq!(params.fps(), Some(25.0 * 4.0 / 32.0));
+        assert_eq!(params.bitrate(), Some(((0xFFF << 18) | 37500) * 400));
+        assert_eq!(params.vbv_size(), ((0xFF << 10) | 112) * 16 * 1024);
+    }
+}
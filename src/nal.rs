@@ -0,0 +1,54 @@
+/// NAL units of Annex B byte stream without start codes
+pub struct Units<'a> {
+    data: &'a [u8],
+    /// after next start code
+    pos: Option<usize>,
+}
+
+/// start code prefix at or after from; position after it
+fn start_code(data: &[u8], from: usize) -> Option<usize> {
+    (from..data.len().saturating_sub(2))
+        .find(|i| data[*i] == 0 && data[i + 1] == 0 && data[i + 2] == 1)
+        .map(|i| i + 3)
+}
+
+pub fn units(data: &[u8]) -> Units<'_> {
+    Units {
+        data,
+        pos: start_code(data, 0),
+    }
+}
+
+impl<'a> Iterator for Units<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let start = self.pos?;
+        self.pos = start_code(self.data, start);
+
+        let mut end = self.pos.map(|pos| pos - 3).unwrap_or(self.data.len());
+        // trailing_zero_8bits / leading zero of 4-byte start code
+        while end > start && self.data[end - 1] == 0 {
+            end -= 1;
+        }
+
+        Some(&self.data[start..end])
+    }
+}
+
+/// emulation_prevention_three_byte removed
+pub fn rbsp(nal: &[u8]) -> Vec<u8> {
+    let mut rbsp = Vec::with_capacity(nal.len());
+    let mut zeros = 0;
+
+    for b in nal.iter() {
+        if zeros >= 2 && *b == 0x03 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if *b == 0 { zeros + 1 } else { 0 };
+        rbsp.push(*b);
+    }
+
+    rbsp
+}
@@ -1,6 +1,7 @@
 use regex::Regex;
 
 bitflags! {
+    #[derive(Clone, Copy)]
     struct State: u8 {
         /// parser inside positional arguments
         const POS = 0x01;
@@ -15,34 +16,34 @@ bitflags! {
 impl State {
     #[inline(always)]
     pub fn set_pos(&mut self) {
-        self.bits &= !Self::OPT.bits;
-        self.bits |= Self::POS.bits;
+        self.remove(Self::OPT);
+        self.insert(Self::POS);
     }
 
     #[inline(always)]
     pub fn set_opt(&mut self) {
-        self.bits &= !Self::POS.bits;
-        self.bits |= Self::OPT.bits;
+        self.remove(Self::POS);
+        self.insert(Self::OPT);
     }
 
     #[inline(always)]
     pub fn set_end(&mut self) {
-        self.bits |= Self::END.bits
+        self.insert(Self::END)
     }
 
     #[inline(always)]
     pub fn is_pos(self) -> bool {
-        (self.bits & Self::POS.bits) != 0
+        self.contains(Self::POS)
     }
 
     #[inline(always)]
     pub fn is_opt(self) -> bool {
-        (self.bits & Self::OPT.bits) != 0
+        self.contains(Self::OPT)
     }
 
     #[inline(always)]
     pub fn is_end(self) -> bool {
-        (self.bits & Self::END.bits) != 0
+        self.contains(Self::END)
     }
 }
 
@@ -51,7 +52,7 @@ pub enum Match<'opt> {
     /// positional parameter
     Positional(String),
     /// --key | -key
-    Key(&'opt str, Option<String>),
+    Key(&'opt str, #[allow(dead_code)] Option<String>),
     /// --key=value | -key value | --key:value | --key value
     KeyValue(&'opt str, String),
 
@@ -83,7 +84,7 @@ pub struct Opt<'a>(pub &'a str, pub &'a [&'a str], pub OptKind);
 
 pub type Opts<'opt> = &'opt [&'opt Opt<'opt>];
 
-fn opts_get<'opt, 's>(opts: Opts<'opt>, key: &'s str) -> Option<&'opt Opt<'opt>> {
+fn opts_get<'opt>(opts: Opts<'opt>, key: &str) -> Option<&'opt Opt<'opt>> {
     for opt in opts {
         if opt.0 == key {
             return Some(opt);
@@ -255,7 +256,9 @@ impl Arg {
                     (:?
                         =|:
                     )?
-                    (?P<value>[a-zA-Z0-9_\-]+)  # value
+                    "?
+                    (?P<value>[^\s"]+)     # value; up to whitespace or closing quote
+                    "?
                 )?
                 "#,
             )
@@ -0,0 +1,18 @@
+/// reassembled PES packet
+#[derive(Clone, Debug)]
+pub struct Packet {
+    pub pid: u16,
+
+    pub stream_id: u8,
+
+    /// demuxed TS packet number carrying PES header; 1-based
+    pub n: u64,
+
+    /// presentation time stamp; 90kHz
+    pub pts: Option<u64>,
+    /// decode time stamp; 90kHz
+    pub dts: Option<u64>,
+
+    /// PES payload (elementary stream data)
+    pub data: Vec<u8>,
+}
@@ -0,0 +1,377 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+use url::Url;
+
+use crate::clock::{discontinuity, Stat, Throttle, PCR_GAP_MAX, PCR_MAX};
+use crate::filter::Consumer;
+use crate::metrics::Metrics;
+
+/// 27MHz
+const PCR_CLOCK: f64 = 27_000_000.0;
+
+/// frequency offset is fitted over
+const FIT_WINDOW: Duration = Duration::from_secs(10);
+/// drift rate is fitted over frequency offset samples of
+const DRIFT_WINDOW: Duration = Duration::from_secs(60);
+/// shorter history gives noise instead of frequency offset / drift
+const FIT_SPAN_MIN: f64 = 1.0;
+const DRIFT_SPAN_MIN: f64 = 10.0;
+/// accuracy is measured against TS rate averaged over at least
+const AC_ORIGIN_MIN: u64 = 27_000_000;
+
+/// x range of points
+fn span(points: &VecDeque<(f64, f64)>) -> f64 {
+    match (points.front(), points.back()) {
+        (Some((front, _)), Some((back, _))) => back - front,
+        _ => 0.0,
+    }
+}
+
+/// least squares fit y = a + b * x
+fn fit<'a, I: Iterator<Item = &'a (f64, f64)>>(points: I) -> Option<(f64, f64)> {
+    let (mut n, mut sx, mut sy, mut sxx, mut sxy) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for (x, y) in points {
+        n += 1.0;
+        sx += x;
+        sy += y;
+        sxx += x * x;
+        sxy += x * y;
+    }
+
+    let d = n * sxx - sx * sx;
+    if n < 2.0 || d.abs() < f64::EPSILON {
+        return None;
+    }
+
+    let b = (n * sxy - sx * sy) / d;
+    Some(((sy - b * sx) / n, b))
+}
+
+/// one PCR PID; timeline restarts on discontinuity
+#[derive(Default)]
+struct Stream {
+    /// first PCR since discontinuity, its arrival and packet index
+    origin: Option<(u64, Instant, u64)>,
+    /// last PCR, its arrival and packet index
+    last: Option<(u64, Instant, u64)>,
+    /// ticks since origin (unwrapped)
+    ticks: u64,
+
+    /// (arrival, PCR - arrival) since origin; secs
+    offsets: VecDeque<(f64, f64)>,
+    /// (arrival since start, frequency offset Hz)
+    fos: VecDeque<(f64, f64)>,
+    fo_at: Option<Instant>,
+
+    /// 27MHz ticks per packet by origin
+    pkt_ticks: Option<f64>,
+
+    /// frequency offset vs receive clock; ppm
+    fo: Option<f64>,
+    /// frequency offset change; Hz/s
+    drift: Option<f64>,
+
+    discontinuities: u64,
+
+    /// publish interval; ms / ns / ns
+    interval: Stat,
+    oj: Stat,
+    ac: Stat,
+}
+
+impl Stream {
+    fn reset(&mut self) {
+        self.origin = None;
+        self.ticks = 0;
+        self.offsets.clear();
+        self.pkt_ticks = None;
+        self.fos.clear();
+        self.fo_at = None;
+        self.drift = None;
+    }
+}
+
+/// one time series row
+struct Sample {
+    pid: u16,
+    at: f64,
+    pcr: u64,
+    interval: Option<f64>,
+    oj: Option<f64>,
+    ac: Option<f64>,
+    fo: Option<f64>,
+}
+
+impl fmt::Display for Sample {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let v = |v: Option<f64>| v.map(|v| format!("{:.3}", v)).unwrap_or_default();
+
+        write!(
+            f,
+            "0x{:04X},{:.6},{:.6},{},{},{},{}",
+            self.pid,
+            self.at,
+            self.pcr as f64 / PCR_CLOCK,
+            v(self.interval),
+            v(self.oj),
+            v(self.ac),
+            v(self.fo)
+        )
+    }
+}
+
+#[derive(Default)]
+struct State {
+    origin: Option<Instant>,
+    /// packets since start
+    packets: u64,
+
+    streams: BTreeMap<u16, Stream>,
+
+    csv: Option<BufWriter<File>>,
+}
+
+/// per PCR PID overall jitter (PCR_OJ), accuracy (PCR_AC),
+/// frequency offset (PCR_FO) and drift rate (PCR_DR).
+///
+/// jitter and frequency offset are measured against receive clock
+/// so they are meaningful for live inputs only; accuracy assumes
+/// constant TS rate
+pub struct Pcr {
+    url: Url,
+    csv: Option<PathBuf>,
+
+    state: RefCell<State>,
+
+    metrics: Metrics,
+    metrics_at: Throttle,
+}
+
+impl Pcr {
+    pub fn new(url: Url) -> Pcr {
+        Pcr {
+            url,
+            csv: None,
+            state: Default::default(),
+            metrics: Default::default(),
+            metrics_at: Default::default(),
+        }
+    }
+
+    /// write every PCR as csv row for plotting
+    pub fn csv(&mut self, csv: Option<PathBuf>) -> &Pcr {
+        self.csv = csv;
+        self
+    }
+
+    pub fn metrics(&mut self, metrics: Metrics) -> &Pcr {
+        self.metrics = metrics;
+        self
+    }
+
+    fn csv_open(&self) -> Option<BufWriter<File>> {
+        let path = self.csv.as_ref()?;
+
+        let mut csv = match File::create(path) {
+            Ok(file) => BufWriter::new(file),
+            Err(err) => {
+                warn!(
+                    "({}) [pcr] csv disabled (:path {} :error {})",
+                    self.url,
+                    path.display(),
+                    err
+                );
+                return None;
+            }
+        };
+
+        let _ = writeln!(csv, "pid,arrival_s,pcr_s,interval_ms,oj_ns,ac_ns,fo_ppm");
+        Some(csv)
+    }
+
+    fn pcr(&self, state: &mut State, pid: u16, pcr: u64, discontinuity: bool, at: Instant) {
+        let start = *state.origin.get_or_insert(at);
+        let n = state.packets;
+        let stream = state.streams.entry(pid).or_default();
+
+        let mut sample = Sample {
+            pid,
+            at: at.saturating_duration_since(start).as_secs_f64(),
+            pcr,
+            interval: None,
+            oj: None,
+            ac: None,
+            fo: None,
+        };
+
+        let last = stream.last.replace((pcr, at, n));
+        if let Some((pcr_last, _, n_last)) = last {
+            let delta = (pcr + PCR_MAX - pcr_last) % PCR_MAX;
+
+            if discontinuity || delta > PCR_GAP_MAX {
+                stream.discontinuities += 1;
+                stream.reset();
+                debug!(
+                    "({}) [pcr] discontinuity (:pid 0x{:04X} :indicator {} :delta {:.3}ms)",
+                    self.url,
+                    pid,
+                    discontinuity,
+                    delta as f64 / PCR_CLOCK * 1000.0
+                );
+            } else {
+                let interval = delta as f64 / PCR_CLOCK * 1000.0;
+                stream.interval.add(interval);
+                sample.interval = Some(interval);
+
+                // PCR_AC
+                let packets = n - n_last;
+                if let Some(pkt_ticks) = stream.pkt_ticks {
+                    let ac = (delta as f64 - packets as f64 * pkt_ticks) / PCR_CLOCK * 1e9;
+                    stream.ac.add(ac);
+                    sample.ac = Some(ac);
+                }
+
+                stream.ticks += delta;
+            }
+        }
+
+        let (_, origin_at, origin_n) = *stream.origin.get_or_insert((pcr, at, n));
+
+        if stream.ticks >= AC_ORIGIN_MIN && n > origin_n {
+            stream.pkt_ticks = Some(stream.ticks as f64 / (n - origin_n) as f64);
+        }
+
+        // PCR_OJ: PCR vs arrival with frequency offset removed
+        let x = at.saturating_duration_since(origin_at).as_secs_f64();
+        let y = stream.ticks as f64 / PCR_CLOCK - x;
+        stream.offsets.push_back((x, y));
+        while stream
+            .offsets
+            .front()
+            .is_some_and(|(front, _)| x - front > FIT_WINDOW.as_secs_f64())
+        {
+            stream.offsets.pop_front();
+        }
+
+        let fitted = fit(stream.offsets.iter()).filter(|_| span(&stream.offsets) >= FIT_SPAN_MIN);
+        if let Some((a, b)) = fitted {
+            let oj = (y - (a + b * x)) * 1e9;
+            stream.oj.add(oj);
+            sample.oj = Some(oj);
+
+            // offset slope is relative frequency difference
+            let fo = b * 1e6;
+            stream.fo = Some(fo);
+            sample.fo = Some(fo);
+
+            // PCR_DR by frequency offset once per second,
+            // only from fits over the full window
+            if x >= FIT_WINDOW.as_secs_f64()
+                && stream.fo_at.is_none_or(|fo_at| {
+                    at.saturating_duration_since(fo_at) >= Duration::from_secs(1)
+                })
+            {
+                stream.fo_at = Some(at);
+                stream.fos.push_back((sample.at, b * PCR_CLOCK));
+                while stream
+                    .fos
+                    .front()
+                    .is_some_and(|(front, _)| sample.at - front > DRIFT_WINDOW.as_secs_f64())
+                {
+                    stream.fos.pop_front();
+                }
+                stream.drift = fit(stream.fos.iter())
+                    .filter(|_| span(&stream.fos) >= DRIFT_SPAN_MIN)
+                    .map(|(_, drift)| drift);
+            }
+        }
+
+        if let Some(csv) = state.csv.as_mut() {
+            let _ = writeln!(csv, "{}", sample);
+        }
+    }
+
+    fn metrics_publish(&self) {
+        if !self.metrics_at.ready() {
+            return;
+        }
+
+        let mut state = self.state.borrow_mut();
+        let state = &mut *state;
+
+        for (pid, stream) in state.streams.iter_mut() {
+            let v = |v: Option<f64>| {
+                v.map(|v| format!("{:.3}", v))
+                    .unwrap_or_else(|| "~".to_string())
+            };
+            let pp = match (stream.oj.min, stream.oj.max) {
+                (Some(min), Some(max)) => Some(max - min),
+                _ => None,
+            };
+
+            self.metrics.set(
+                format!("pcr-0x{:04X}", pid),
+                format!(
+                    "(:interval-min {}ms :interval-max {}ms :interval-mean {}ms :oj-min {}ns :oj-max {}ns :oj-pp {}ns :ac-min {}ns :ac-max {}ns :fo {}ppm :fo-hz {} :drift {}Hz/s :discontinuities {})",
+                    v(stream.interval.min),
+                    v(stream.interval.max),
+                    v(stream.interval.mean()),
+                    v(stream.oj.min),
+                    v(stream.oj.max),
+                    v(pp),
+                    v(stream.ac.min),
+                    v(stream.ac.max),
+                    v(stream.fo),
+                    v(stream.fo.map(|fo| fo * PCR_CLOCK / 1e6)),
+                    v(stream.drift),
+                    stream.discontinuities
+                ),
+            );
+
+            stream.interval = Default::default();
+            stream.oj = Default::default();
+            stream.ac = Default::default();
+        }
+
+        if let Some(csv) = state.csv.as_mut() {
+            let _ = csv.flush();
+        }
+    }
+}
+
+impl Consumer for Pcr {
+    fn consume_pkt_raw(&self, raw: &[u8]) {
+        let pkt = match ts::Packet::new(raw) {
+            Ok(pkt) => pkt,
+            Err(_) => return,
+        };
+        let at = Instant::now();
+
+        {
+            let mut state = self.state.borrow_mut();
+            let state = &mut *state;
+
+            if state.origin.is_none() {
+                state.csv = self.csv_open();
+            }
+            state.packets += 1;
+
+            if let Ok(Some(pcr)) = pkt.pcr() {
+                // PCR implies adaptation field
+                let discontinuity = discontinuity(raw);
+                self.pcr(state, u16::from(pkt.pid()), pcr.value(), discontinuity, at);
+            }
+
+            state.origin.get_or_insert(at);
+        }
+
+        self.metrics_publish();
+    }
+}
@@ -0,0 +1,449 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::error;
+use url::Url;
+
+use crate::clock::PCR_MAX;
+use crate::filter::Consumer;
+use crate::psi;
+use crate::subtitle;
+use crate::track::Track;
+
+/// 27MHz
+const PCR_CLOCK: f64 = 27_000_000.0;
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Format::Text => write!(f, "text"),
+            Format::Json => write!(f, "json"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Pid {
+    packets: u64,
+}
+
+#[derive(Default)]
+struct State {
+    tracks: BTreeMap<u16, Track>,
+    pids: BTreeMap<u16, Pid>,
+
+    packets: u64,
+    /// stop counting after limit
+    packets_max: Option<u64>,
+
+    /// wall-clock of first and last packet
+    first_at: Option<Instant>,
+    last_at: Option<Instant>,
+
+    /// first and last PCR of the first seen PCR PID; 27MHz
+    pcr_pid: Option<u16>,
+    pcr_first: Option<u64>,
+    pcr_last: Option<u64>,
+}
+
+impl State {
+    /// stream duration by PCR (may wrap once); falls back to arrival time
+    fn duration(&self) -> Duration {
+        if let (Some(first), Some(last)) = (self.pcr_first, self.pcr_last) {
+            let span = (last + PCR_MAX - first) % PCR_MAX;
+            if span > 0 {
+                return Duration::from_secs_f64(span as f64 / PCR_CLOCK);
+            }
+        }
+
+        match (self.first_at, self.last_at) {
+            (Some(first), Some(last)) => last - first,
+            _ => Duration::from_secs(0),
+        }
+    }
+
+    /// bits per second
+    fn bitrate(&self, packets: u64) -> u64 {
+        let secs = self.duration().as_secs_f64();
+        if secs <= 0.0 {
+            return 0;
+        }
+
+        ((packets * ts::Packet::SZ as u64 * 8) as f64 / secs) as u64
+    }
+}
+
+/// stream inventory shared between source thread and caller
+#[derive(Clone, Default)]
+pub struct Report(Arc<Mutex<State>>);
+
+impl Report {
+    /// stop after packets limit, time limit
+    /// or when input stalls (e.g. end of file)
+    pub fn is_done(&self, started_at: Instant, duration: Duration, idle: Duration) -> bool {
+        let elapsed = started_at.elapsed();
+
+        match self.0.lock() {
+            Ok(state) => {
+                elapsed >= duration
+                    || state
+                        .packets_max
+                        .map(|packets_max| state.packets >= packets_max)
+                        .unwrap_or(false)
+                    || state
+                        .last_at
+                        .map(|last_at| last_at.elapsed() >= idle)
+                        .unwrap_or(false)
+            }
+            Err(_) => true,
+        }
+    }
+
+    pub fn render(&self, i: usize, url: &Url, format: Format) -> String {
+        let state = match self.0.lock() {
+            Ok(state) => state,
+            Err(err) => {
+                error!("({}) probe report lock failed (:reason {})", url, err);
+                return String::new();
+            }
+        };
+
+        match format {
+            Format::Text => render_text(&state, i, url),
+            Format::Json => render_json(&state, i, url),
+        }
+    }
+}
+
+/// collects tracks and per-PID packet counters
+pub struct Probe {
+    report: Report,
+}
+
+impl Probe {
+    pub fn new(packets_max: Option<u64>) -> Probe {
+        let state = State {
+            packets_max,
+            ..Default::default()
+        };
+
+        Probe {
+            report: Report(Arc::new(Mutex::new(state))),
+        }
+    }
+
+    pub fn report(&self) -> Report {
+        self.report.clone()
+    }
+}
+
+impl Consumer for Probe {
+    fn consume_trk(&self, trk: &Track) {
+        if let Ok(mut state) = self.report.0.lock() {
+            state.tracks.insert(trk.pid, trk.clone());
+        }
+    }
+
+    fn consume_pkt_raw(&self, pkt_raw: &[u8]) {
+        let mut state = match self.report.0.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+
+        if let Some(packets_max) = state.packets_max {
+            if state.packets >= packets_max {
+                return;
+            }
+        }
+
+        let pkt = match ts::Packet::new(pkt_raw) {
+            Ok(pkt) => pkt,
+            Err(_) => return,
+        };
+        let pid = pkt.pid().into();
+
+        let now = Instant::now();
+        state.first_at.get_or_insert(now);
+        state.last_at = Some(now);
+
+        state.packets += 1;
+        state.pids.entry(pid).or_default().packets += 1;
+
+        if let Ok(Some(pcr)) = pkt.pcr() {
+            if *state.pcr_pid.get_or_insert(pid) == pid {
+                state.pcr_first.get_or_insert(pcr.value());
+                state.pcr_last = Some(pcr.value());
+            }
+        }
+    }
+}
+
+/// well-known PID name for PIDs without track
+fn pid_name(state: &State, pid: u16) -> &'static str {
+    match pid {
+        psi::PID_PAT => "pat",
+        psi::PID_CAT => "cat",
+        psi::PID_NIT => "nit",
+        psi::PID_SDT => "sdt",
+        psi::PID_EIT => "eit",
+        psi::PID_RST => "rst",
+        psi::PID_TDT => "tdt",
+        psi::PID_NULL => "null",
+        _ if state.tracks.values().any(|trk| trk.pmt_pid == pid) => "pmt",
+        _ if state.tracks.values().any(|trk| trk.pcr_pid == pid) => "pcr",
+        _ => "unknown",
+    }
+}
+
+/// tracks grouped by program number
+fn programs(state: &State) -> BTreeMap<u16, Vec<&Track>> {
+    let mut programs: BTreeMap<u16, Vec<&Track>> = BTreeMap::new();
+    for trk in state.tracks.values() {
+        programs.entry(trk.program_number).or_default().push(trk);
+    }
+    programs
+}
+
+fn pid_packets(state: &State, pid: u16) -> u64 {
+    state.pids.get(&pid).map(|p| p.packets).unwrap_or(0)
+}
+
+fn render_text(state: &State, i: usize, url: &Url) -> String {
+    let mut s = String::new();
+
+    let _ = writeln!(s, "Input #{}, mpegts, from '{}':", i, url);
+    let _ = writeln!(
+        s,
+        "  Duration: {:.3}s, packets: {}, bitrate: {} kb/s",
+        state.duration().as_secs_f64(),
+        state.packets,
+        state.bitrate(state.packets) / 1000
+    );
+
+    for (number, tracks) in programs(state).iter() {
+        let head = tracks[0];
+
+        let _ = write!(
+            s,
+            "  Program {} (:pmt-pid 0x{:04X} :pcr-pid 0x{:04X})",
+            number, head.pmt_pid, head.pcr_pid
+        );
+        if let Some(service_name) = &head.service_name {
+            let _ = write!(s, r#" service "{}""#, service_name);
+        }
+        if let Some(provider_name) = &head.provider_name {
+            let _ = write!(s, r#" provider "{}""#, provider_name);
+        }
+        let _ = writeln!(s);
+
+        for trk in tracks.iter() {
+            let _ = write!(
+                s,
+                "    Stream 0x{:04X} [0x{:02X}] {}: {}",
+                trk.pid,
+                trk.stream_type,
+                trk.kind(),
+                trk.codec()
+            );
+            if let Some(lang) = trk.lang() {
+                let _ = write!(s, " ({})", lang);
+            }
+            if let Some(video) = trk.video.as_ref() {
+                let _ = write!(s, " ({})", video);
+            }
+            if let Some(audio) = trk.audio.as_ref() {
+                let _ = write!(s, " ({})", audio);
+            }
+            let _ = writeln!(
+                s,
+                ", {} kb/s",
+                state.bitrate(pid_packets(state, trk.pid)) / 1000
+            );
+            for service in subtitle::services(trk) {
+                let _ = write!(s, "      Service {}", service.page);
+                match (service.lang.as_ref(), service.kind) {
+                    (Some(lang), Some(kind)) => {
+                        let _ = write!(s, " ({}, {})", lang, kind);
+                    }
+                    (None, Some(kind)) => {
+                        let _ = write!(s, " ({})", kind);
+                    }
+                    _ => {}
+                }
+                let _ = writeln!(
+                    s,
+                    ": {}",
+                    match (service.signalled, service.present) {
+                        (true, true) => "present",
+                        (true, false) => "signalled only",
+                        _ => "present, not signalled",
+                    }
+                );
+            }
+        }
+    }
+
+    let others: Vec<_> = state
+        .pids
+        .iter()
+        .filter(|(pid, _)| !state.tracks.contains_key(pid))
+        .collect();
+    if !others.is_empty() {
+        let _ = writeln!(s, "  Other PIDs:");
+        for (pid, p) in others {
+            let _ = writeln!(
+                s,
+                "    0x{:04X} {}, {} kb/s",
+                pid,
+                pid_name(state, *pid),
+                state.bitrate(p.packets) / 1000
+            );
+        }
+    }
+
+    s
+}
+
+fn render_json(state: &State, i: usize, url: &Url) -> String {
+    let mut s = String::new();
+
+    let _ = write!(
+        s,
+        r#"{{"index":{},"url":{},"format":"mpegts","duration":{:.3},"packets":{},"bitrate":{},"programs":["#,
+        i,
+        json_str(url.as_str()),
+        state.duration().as_secs_f64(),
+        state.packets,
+        state.bitrate(state.packets)
+    );
+
+    for (n, (number, tracks)) in programs(state).iter().enumerate() {
+        let head = tracks[0];
+
+        if n > 0 {
+            s.push(',');
+        }
+        let _ = write!(
+            s,
+            r#"{{"program_number":{},"pmt_pid":{},"pcr_pid":{},"service_name":{},"provider_name":{},"streams":["#,
+            number,
+            head.pmt_pid,
+            head.pcr_pid,
+            json_opt_str(head.service_name.as_deref()),
+            json_opt_str(head.provider_name.as_deref())
+        );
+
+        for (k, trk) in tracks.iter().enumerate() {
+            if k > 0 {
+                s.push(',');
+            }
+            let _ = write!(
+                s,
+                r#"{{"pid":{},"stream_type":{},"kind":"{}","codec":"{}","lang":{},"video":{},"audio":{},"subtitle":{},"services":[{}],"packets":{},"bitrate":{}}}"#,
+                trk.pid,
+                trk.stream_type,
+                trk.kind(),
+                trk.codec(),
+                json_opt_str(trk.lang().as_deref()),
+                trk.video
+                    .as_ref()
+                    .map(|video| video.json())
+                    .unwrap_or_else(|| "null".to_string()),
+                trk.audio
+                    .as_ref()
+                    .map(|audio| audio.json())
+                    .unwrap_or_else(|| "null".to_string()),
+                trk.subtitle
+                    .as_ref()
+                    .map(|subtitle| subtitle.json())
+                    .unwrap_or_else(|| "null".to_string()),
+                subtitle::services(trk)
+                    .iter()
+                    .map(|service| format!(
+                        r#"{{"page":{},"lang":{},"type":{},"signalled":{},"present":{}}}"#,
+                        json_str(&service.page),
+                        json_opt_str(service.lang.as_deref()),
+                        json_opt_str(service.kind),
+                        service.signalled,
+                        service.present
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(","),
+                pid_packets(state, trk.pid),
+                state.bitrate(pid_packets(state, trk.pid))
+            );
+        }
+
+        s.push_str("]}");
+    }
+
+    s.push_str(r#"],"pids":["#);
+
+    for (n, (pid, p)) in state.pids.iter().enumerate() {
+        if n > 0 {
+            s.push(',');
+        }
+        let name = match state.tracks.get(pid) {
+            Some(trk) => trk.codec(),
+            None => pid_name(state, *pid),
+        };
+        let _ = write!(
+            s,
+            r#"{{"pid":{},"name":"{}","packets":{},"bitrate":{}}}"#,
+            pid,
+            name,
+            p.packets,
+            state.bitrate(p.packets)
+        );
+    }
+
+    s.push_str("]}");
+
+    s
+}
+
+pub fn json_opt_str(v: Option<&str>) -> String {
+    v.map(json_str).unwrap_or_else(|| "null".to_string())
+}
+
+pub fn json_str(v: &str) -> String {
+    let mut s = String::with_capacity(v.len() + 2);
+
+    s.push('"');
+    for c in v.chars() {
+        match c {
+            '"' => s.push_str(r#"\""#),
+            '\\' => s.push_str(r"\\"),
+            '\n' => s.push_str(r"\n"),
+            '\r' => s.push_str(r"\r"),
+            '\t' => s.push_str(r"\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(s, r"\u{:04x}", c as u32);
+            }
+            c => s.push(c),
+        }
+    }
+    s.push('"');
+
+    s
+}
@@ -0,0 +1,437 @@
+use crate::crc32;
+
+pub const PID_PAT: u16 = 0x0000;
+pub const PID_CAT: u16 = 0x0001;
+pub const PID_NIT: u16 = 0x0010;
+pub const PID_SDT: u16 = 0x0011;
+pub const PID_EIT: u16 = 0x0012;
+pub const PID_RST: u16 = 0x0013;
+pub const PID_TDT: u16 = 0x0014;
+/// ATSC PSIP base PID
+pub const PID_PSIP: u16 = 0x1FFB;
+pub const PID_NULL: u16 = 0x1FFF;
+
+pub const TABLE_ID_PAT: u8 = 0x00;
+pub const TABLE_ID_CAT: u8 = 0x01;
+pub const TABLE_ID_PMT: u8 = 0x02;
+pub const TABLE_ID_NIT: u8 = 0x40;
+pub const TABLE_ID_NIT_OTHER: u8 = 0x41;
+pub const TABLE_ID_SDT: u8 = 0x42;
+pub const TABLE_ID_SDT_OTHER: u8 = 0x46;
+pub const TABLE_ID_BAT: u8 = 0x4A;
+pub const TABLE_ID_RST: u8 = 0x71;
+/// stuffing table; allowed on every SI PID
+pub const TABLE_ID_ST: u8 = 0x72;
+pub const TABLE_ID_TDT: u8 = 0x70;
+pub const TABLE_ID_TOT: u8 = 0x73;
+
+/// ATSC A/65 master guide, virtual channel and system time tables
+pub const TABLE_ID_MGT: u8 = 0xC7;
+pub const TABLE_ID_TVCT: u8 = 0xC8;
+pub const TABLE_ID_CVCT: u8 = 0xC9;
+pub const TABLE_ID_STT: u8 = 0xCD;
+
+/// EIT present/following actual
+pub const TABLE_ID_EIT_PF: u8 = 0x4E;
+pub const TABLE_ID_EIT_PF_OTHER: u8 = 0x4F;
+/// EIT schedule actual 0x50..=0x5F, other 0x60..=0x6F
+pub const TABLE_ID_EIT_SCHED: u8 = 0x50;
+pub const TABLE_ID_EIT_SCHED_LAST: u8 = 0x5F;
+pub const TABLE_ID_EIT_SCHED_OTHER_LAST: u8 = 0x6F;
+
+/// PIDs reserved for PSI/SI tables (and ATSC PSIP base PID)
+#[inline(always)]
+pub fn is_si_pid(pid: u16) -> bool {
+    matches!(
+        pid,
+        PID_PAT | PID_CAT | PID_NIT | PID_SDT | PID_EIT | PID_RST | PID_TDT | PID_PSIP
+    )
+}
+
+/// complete PSI/SI section
+///
+/// header is validated on construction; payload accessors
+/// are bounded by section_length
+pub struct Section<'buf> {
+    buf: &'buf [u8],
+}
+
+impl<'buf> Section<'buf> {
+    pub const HEADER_SZ: usize = 3;
+    /// table_id_extension .. last_section_number
+    const SYNTAX_SZ: usize = 5;
+    const CRC32_SZ: usize = 4;
+
+    pub const SZ_MAX: usize = 4096;
+
+    pub fn try_new(buf: &'buf [u8]) -> Option<Section<'buf>> {
+        if buf.len() < Self::HEADER_SZ {
+            return None;
+        }
+
+        let s = Section { buf };
+        let min = if s.syntax() {
+            Self::HEADER_SZ + Self::SYNTAX_SZ + Self::CRC32_SZ
+        } else {
+            Self::HEADER_SZ
+        };
+
+        if s.sz() < min || s.sz() > buf.len() {
+            return None;
+        }
+
+        Some(Section {
+            buf: &buf[..s.sz()],
+        })
+    }
+
+    #[inline(always)]
+    pub fn buf(&self) -> &'buf [u8] {
+        self.buf
+    }
+
+    #[inline(always)]
+    pub fn table_id(&self) -> u8 {
+        self.buf[0]
+    }
+
+    /// section_syntax_indicator
+    #[inline(always)]
+    pub fn syntax(&self) -> bool {
+        (self.buf[1] & 0b1000_0000) != 0
+    }
+
+    #[inline(always)]
+    pub fn section_length(&self) -> usize {
+        (usize::from(self.buf[1] & 0b0000_1111) << 8) | usize::from(self.buf[2])
+    }
+
+    /// complete section size with header and CRC
+    #[inline(always)]
+    pub fn sz(&self) -> usize {
+        Self::HEADER_SZ + self.section_length()
+    }
+
+    #[inline(always)]
+    pub fn table_id_extension(&self) -> u16 {
+        if self.syntax() {
+            (u16::from(self.buf[3]) << 8) | u16::from(self.buf[4])
+        } else {
+            0
+        }
+    }
+
+    #[inline(always)]
+    pub fn version_number(&self) -> u8 {
+        if self.syntax() {
+            (self.buf[5] & 0b0011_1110) >> 1
+        } else {
+            0
+        }
+    }
+
+    #[inline(always)]
+    pub fn current_next_indicator(&self) -> bool {
+        !self.syntax() || (self.buf[5] & 0b0000_0001) != 0
+    }
+
+    #[inline(always)]
+    pub fn section_number(&self) -> u8 {
+        if self.syntax() {
+            self.buf[6]
+        } else {
+            0
+        }
+    }
+
+    #[inline(always)]
+    pub fn last_section_number(&self) -> u8 {
+        if self.syntax() {
+            self.buf[7]
+        } else {
+            0
+        }
+    }
+
+    /// table data between syntax header and CRC_32
+    #[inline(always)]
+    pub fn body(&self) -> &'buf [u8] {
+        if self.syntax() {
+            &self.buf[Self::HEADER_SZ + Self::SYNTAX_SZ..self.buf.len() - Self::CRC32_SZ]
+        } else {
+            &self.buf[Self::HEADER_SZ..]
+        }
+    }
+
+    #[inline(always)]
+    pub fn crc32(&self) -> Option<u32> {
+        if self.syntax() {
+            let b = &self.buf[self.buf.len() - Self::CRC32_SZ..];
+            Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        } else {
+            None
+        }
+    }
+
+    /// sections without syntax (e.g. TDT) carry no CRC
+    #[inline(always)]
+    pub fn crc32_ok(&self) -> bool {
+        !self.syntax() || crc32::mpeg2(self.buf) == 0
+    }
+}
+
+/// iterate over descriptors loop as (tag, data);
+/// truncated tail is ignored
+pub fn descriptors(buf: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    let mut buf = buf;
+
+    std::iter::from_fn(move || {
+        if buf.len() < 2 {
+            return None;
+        }
+
+        let (tag, len) = (buf[0], usize::from(buf[1]));
+        if buf.len() < 2 + len {
+            return None;
+        }
+
+        let data = &buf[2..2 + len];
+        buf = &buf[2 + len..];
+
+        Some((tag, data))
+    })
+}
+
+/// CA_descriptor
+pub const TAG_CA: u8 = 0x09;
+
+/// CA_descriptor: ECM PID in PMT, EMM PID in CAT
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct CaDescriptor {
+    pub system_id: u16,
+    pub pid: u16,
+}
+
+/// CA_descriptors of descriptors loop
+pub fn ca_descriptors(buf: &[u8]) -> impl Iterator<Item = CaDescriptor> + '_ {
+    descriptors(buf)
+        .filter(|(tag, data)| *tag == TAG_CA && data.len() >= 4)
+        .map(|(_, data)| CaDescriptor {
+            system_id: (u16::from(data[0]) << 8) | u16::from(data[1]),
+            pid: (u16::from(data[2] & 0x1F) << 8) | u16::from(data[3]),
+        })
+}
+
+/// service_descriptor
+pub const TAG_SERVICE: u8 = 0x48;
+
+/// service of SDT named by service_descriptor
+#[derive(Clone, Debug)]
+pub struct SdtService {
+    pub service_id: u16,
+    pub provider: String,
+    pub name: String,
+}
+
+/// service_description_section body:
+/// original_network_id and named services
+pub fn sdt_services(body: &[u8]) -> Option<(u16, Vec<SdtService>)> {
+    if body.len() < 3 {
+        return None;
+    }
+    let original_network_id = (u16::from(body[0]) << 8) | u16::from(body[1]);
+
+    // original_network_id, reserved_future_use
+    let mut pos = 3;
+    let mut services = Vec::new();
+    while pos + 5 <= body.len() {
+        let service_id = (u16::from(body[pos]) << 8) | u16::from(body[pos + 1]);
+        let loop_length = (usize::from(body[pos + 3] & 0x0F) << 8) | usize::from(body[pos + 4]);
+        let end = std::cmp::min(pos + 5 + loop_length, body.len());
+
+        for (tag, data) in descriptors(&body[pos + 5..end]) {
+            if tag != TAG_SERVICE || data.len() < 2 {
+                continue;
+            }
+
+            let provider_end = 2 + usize::from(data[1]);
+            if data.len() < provider_end + 1 {
+                continue;
+            }
+            let name_end = std::cmp::min(
+                provider_end + 1 + usize::from(data[provider_end]),
+                data.len(),
+            );
+
+            services.push(SdtService {
+                service_id,
+                provider: dvb_str(&data[2..provider_end]),
+                name: dvb_str(&data[provider_end + 1..name_end]),
+            });
+        }
+
+        pos += 5 + loop_length;
+    }
+
+    Some((original_network_id, services))
+}
+
+/// ISO/IEC 6937 non-spacing diacritical marks 0xC1..0xCF:
+/// (base letters, precomposed letters, combining mark)
+const DIACRITICS: [(&str, &str, char); 15] = [
+    ("AEIOUaeiou", "ÀÈÌÒÙàèìòù", '\u{300}'),
+    (
+        "AEIOUYaeiouyCcNnSsZzLlRr",
+        "ÁÉÍÓÚÝáéíóúýĆćŃńŚśŹźĹĺŔŕ",
+        '\u{301}',
+    ),
+    (
+        "AEIOUaeiouCcGgHhJjSsWwYy",
+        "ÂÊÎÔÛâêîôûĈĉĜĝĤĥĴĵŜŝŴŵŶŷ",
+        '\u{302}',
+    ),
+    ("ANOanoIiUu", "ÃÑÕãñõĨĩŨũ", '\u{303}'),
+    ("AEIOUaeiou", "ĀĒĪŌŪāēīōū", '\u{304}'),
+    ("AaGgUu", "ĂăĞğŬŭ", '\u{306}'),
+    ("CcEeGgIZz", "ĊċĖėĠġİŻż", '\u{307}'),
+    ("AEIOUaeiouyY", "ÄËÏÖÜäëïöüÿŸ", '\u{308}'),
+    ("", "", '\u{308}'),
+    ("AaUu", "ÅåŮů", '\u{30A}'),
+    ("CcGKkLlNnRrSsTt", "ÇçĢĶķĻļŅņŖŗŞşŢţ", '\u{327}'),
+    ("", "", '\u{332}'),
+    ("OoUu", "ŐőŰű", '\u{30B}'),
+    ("AaEeIiUu", "ĄąĘęĮįŲų", '\u{328}'),
+    ("CcDdEeLlNnRrSsTtZz", "ČčĎďĚěĽľŇňŘřŠšŤťŽž", '\u{30C}'),
+];
+
+/// ISO/IEC 6937 (EN 300 468 figure A.1) 0xA0..0xFF; unassigned codes
+/// map to U+FFFD, row 0xC_ holds spacing forms of the diacritics
+#[rustfmt::skip]
+const ISO6937_HIGH: [char; 96] = [
+    '\u{A0}', '¡', '¢', '£', '€', '¥', '#', '§', '¤', '‘', '“', '«', '←', '↑', '→', '↓',
+    '°', '±', '²', '³', '×', 'µ', '¶', '·', '÷', '’', '”', '»', '¼', '½', '¾', '¿',
+    '\u{FFFD}', '`', '´', 'ˆ', '˜', '¯', '˘', '˙', '¨', '\u{FFFD}', '˚', '¸', '\u{FFFD}', '˝', '˛', 'ˇ',
+    '―', '¹', '®', '©', '™', '♪', '¬', '¦', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '⅛', '⅜', '⅝', '⅞',
+    'Ω', 'Æ', 'Đ', 'ª', 'Ħ', '\u{FFFD}', 'Ĳ', 'Ŀ', 'Ł', 'Ø', 'Œ', 'º', 'Þ', 'Ŧ', 'Ŋ', 'ŉ',
+    'ĸ', 'æ', 'đ', 'ð', 'ħ', 'ı', 'ĳ', 'ŀ', 'ł', 'ø', 'œ', 'ß', 'þ', 'ŧ', 'ŋ', '\u{AD}',
+];
+
+/// character code table 00 (Latin alphabet, ISO/IEC 6937 based)
+fn iso6937(buf: &[u8]) -> String {
+    let mut text = String::with_capacity(buf.len());
+    let mut mark: Option<usize> = None;
+
+    for b in buf.iter().copied() {
+        let c = match b {
+            0x20..=0x7E => char::from(b),
+            // CR/LF
+            0x8A => '\n',
+            0xC1..=0xCF => {
+                mark = Some(usize::from(b - 0xC1));
+                continue;
+            }
+            // soft hyphen
+            0xFF => continue,
+            0xA0..=0xFE => ISO6937_HIGH[usize::from(b - 0xA0)],
+            _ => continue,
+        };
+
+        match mark.take().map(|i| DIACRITICS[i]) {
+            Some((bases, composed, combining)) => match bases.chars().position(|base| base == c) {
+                Some(i) => text.extend(composed.chars().nth(i)),
+                None => {
+                    text.push(c);
+                    text.push(combining);
+                }
+            },
+            None => text.push(c),
+        }
+    }
+
+    text
+}
+
+/// ISO/IEC 8859 part
+fn iso8859(part: u8) -> Option<&'static encoding_rs::Encoding> {
+    match part {
+        // windows code pages are supersets for 0xA0..0xFF
+        1 => Some(encoding_rs::WINDOWS_1252),
+        2 => Some(encoding_rs::ISO_8859_2),
+        3 => Some(encoding_rs::ISO_8859_3),
+        4 => Some(encoding_rs::ISO_8859_4),
+        5 => Some(encoding_rs::ISO_8859_5),
+        6 => Some(encoding_rs::ISO_8859_6),
+        7 => Some(encoding_rs::ISO_8859_7),
+        8 => Some(encoding_rs::ISO_8859_8),
+        9 => Some(encoding_rs::WINDOWS_1254),
+        10 => Some(encoding_rs::ISO_8859_10),
+        11 => Some(encoding_rs::WINDOWS_874),
+        13 => Some(encoding_rs::ISO_8859_13),
+        14 => Some(encoding_rs::ISO_8859_14),
+        15 => Some(encoding_rs::ISO_8859_15),
+        16 => Some(encoding_rs::ISO_8859_16),
+        _ => None,
+    }
+}
+
+/// single byte tables: control codes 0x80..0x9F removed,
+/// CR/LF (0x8A) to new line; emphasis on/off (0x86/0x87) dropped
+fn single_byte_controls(buf: &[u8]) -> Vec<u8> {
+    buf.iter()
+        .filter_map(|b| match b {
+            0x8A => Some(b'\n'),
+            0x80..=0x9F => None,
+            _ => Some(*b),
+        })
+        .collect()
+}
+
+/// two byte tables: control codes 0xE080..0xE09F
+fn ucs2(buf: &[u8]) -> String {
+    let units = buf
+        .chunks_exact(2)
+        .map(|c| u16::from_be_bytes([c[0], c[1]]))
+        .filter_map(|u| match u {
+            0xE08A => Some(0x000A),
+            0xE080..=0xE09F => None,
+            _ => Some(u),
+        });
+
+    std::char::decode_utf16(units)
+        .map(|c| c.unwrap_or(std::char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// ETSI EN 300 468 Annex A text to utf-8; first byte selects
+/// character table, default is table 00 (ISO/IEC 6937);
+/// falls back to lossy latin when charset is not supported
+pub fn dvb_str(buf: &[u8]) -> String {
+    let encoding = |encoding: Option<&'static encoding_rs::Encoding>, data: &[u8]| {
+        let data = single_byte_controls(data);
+        match encoding {
+            Some(encoding) => encoding.decode_without_bom_handling(&data).0.into_owned(),
+            None => String::from_utf8_lossy(&data).into_owned(),
+        }
+    };
+
+    let text = match buf {
+        [] => String::new(),
+        [0x20..=0xFF, ..] => iso6937(buf),
+        [n @ 0x01..=0x0B, data @ ..] => encoding(iso8859(n + 4), data),
+        [0x10, 0x00, n, data @ ..] => encoding(iso8859(*n), data),
+        // ISO/IEC 10646 BMP and Big5 subset of it
+        [0x11, data @ ..] | [0x14, data @ ..] => ucs2(data),
+        [0x12, data @ ..] => encoding_rs::EUC_KR
+            .decode_without_bom_handling(data)
+            .0
+            .into_owned(),
+        [0x13, data @ ..] => encoding_rs::GBK
+            .decode_without_bom_handling(data)
+            .0
+            .into_owned(),
+        [0x15, data @ ..] => String::from_utf8_lossy(data).into_owned(),
+        [_, data @ ..] => String::from_utf8_lossy(data).into_owned(),
+    };
+
+    text.trim_end_matches('\0').to_string()
+}
@@ -0,0 +1,410 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use url::Url;
+
+use crate::clock::{discontinuity, wrapping_diff, Stat, Throttle, PCR_MAX, TS_MAX};
+use crate::filter::Consumer;
+use crate::metrics::Metrics;
+use crate::packet::Packet;
+use crate::track::{Kind, Track};
+
+/// system time clock of one PCR PID, interpolated by packets
+#[derive(Default)]
+struct Stc {
+    /// last PCR and its packet index
+    last: Option<(u64, u64)>,
+    /// 27MHz ticks per packet by last PCR interval
+    pkt_ticks: Option<f64>,
+    /// discontinuity indicators seen
+    discontinuities: u64,
+}
+
+impl Stc {
+    fn pcr(&mut self, pcr: u64, n: u64, discontinuity: bool) {
+        if discontinuity {
+            self.discontinuities += 1;
+            self.pkt_ticks = None;
+        } else if let Some((pcr_last, n_last)) = self.last {
+            let delta = wrapping_diff(pcr, pcr_last, PCR_MAX);
+            // 100ms; larger is a discontinuity without indicator
+            if delta > 0 && delta <= 2_700_000 && n > n_last {
+                self.pkt_ticks = Some(delta as f64 / (n - n_last) as f64);
+            } else {
+                self.pkt_ticks = None;
+            }
+        }
+        self.last = Some((pcr, n));
+    }
+
+    /// 27MHz at packet n
+    fn at(&self, n: u64) -> Option<u64> {
+        let (pcr, n_last) = self.last?;
+        let pkt_ticks = self.pkt_ticks?;
+        Some((pcr + (n.saturating_sub(n_last) as f64 * pkt_ticks) as u64) % PCR_MAX)
+    }
+}
+
+/// per elementary stream timestamps
+#[derive(Default)]
+struct Stream {
+    program: u16,
+    pcr_pid: u16,
+    kind: Option<Kind>,
+
+    /// last decode timestamp (DTS or PTS); 90kHz
+    last: Option<u64>,
+    /// STC discontinuities at last PES
+    discontinuities: u64,
+    /// last PTS - STC; ms
+    delay: Option<f64>,
+    /// (packet number, STC) of PES headers waiting for demuxer
+    starts: VecDeque<(u64, Option<u64>)>,
+
+    pes: u64,
+    no_pts: u64,
+    backwards: u64,
+    dts_after_pts: u64,
+    /// PTS before STC (buffer underflow)
+    late: u64,
+
+    /// publish interval; ms
+    delays: Stat,
+    /// reported with warning
+    reported: BTreeSet<&'static str>,
+}
+
+impl Stream {
+    /// PES headers of incomplete PES packets;
+    /// more is a PES the demuxer has dropped
+    const STARTS_MAX: usize = 16;
+}
+
+/// one audio/video pair of a program
+#[derive(Default)]
+struct Pair {
+    /// first complete interval mean offset; ms
+    baseline: Option<f64>,
+    /// publish interval; ms
+    offsets: Stat,
+
+    alarm: bool,
+    alarms: u64,
+}
+
+#[derive(Default)]
+struct State {
+    /// packets since start
+    packets: u64,
+
+    stcs: BTreeMap<u16, Stc>,
+    streams: BTreeMap<u16, Stream>,
+    /// (audio PID, video PID)
+    pairs: BTreeMap<(u16, u16), Pair>,
+}
+
+/// PTS/DTS checks per elementary stream and audio/video offset per program.
+///
+/// A/V offset is audio minus video presentation delay (PTS - STC at PES
+/// start); it depends on encoder buffering so alarm is raised when it
+/// drifts from its first measured value or exceeds absolute limit
+pub struct Pts {
+    url: Url,
+    av_offset_max: Duration,
+    av_offset_abs_max: Duration,
+
+    state: RefCell<State>,
+
+    metrics: Metrics,
+    metrics_at: Throttle,
+}
+
+impl Pts {
+    pub fn new(url: Url) -> Pts {
+        Pts {
+            url,
+            av_offset_max: Duration::from_millis(40),
+            av_offset_abs_max: Duration::from_millis(500),
+            state: Default::default(),
+            metrics: Default::default(),
+            metrics_at: Default::default(),
+        }
+    }
+
+    /// lip-sync drift alarm threshold
+    pub fn av_offset_max(&mut self, av_offset_max: Duration) -> &Pts {
+        self.av_offset_max = av_offset_max;
+        self
+    }
+
+    /// absolute A/V offset alarm threshold
+    pub fn av_offset_abs_max(&mut self, av_offset_abs_max: Duration) -> &Pts {
+        self.av_offset_abs_max = av_offset_abs_max;
+        self
+    }
+
+    pub fn metrics(&mut self, metrics: Metrics) -> &Pts {
+        self.metrics = metrics;
+        self
+    }
+
+    fn error(&self, stream: &mut Stream, pid: u16, kind: &'static str, reason: &str) {
+        if stream.reported.insert(kind) {
+            warn!(
+                "({}) [pts] {} (:pid 0x{:04X} :program {} {})",
+                self.url, kind, pid, stream.program, reason
+            );
+        } else {
+            debug!(
+                "({}) [pts] {} (:pid 0x{:04X} :program {} {})",
+                self.url, kind, pid, stream.program, reason
+            );
+        }
+    }
+
+    /// STC at PES header
+    fn pes_start(&self, state: &mut State, pid: u16) {
+        let n = state.packets;
+        let stream = match state.streams.get_mut(&pid) {
+            // private data may carry no timestamps
+            Some(stream) if stream.kind != Some(Kind::Data) => stream,
+            _ => return,
+        };
+        let stc = state.stcs.get(&stream.pcr_pid).and_then(|stc| stc.at(n));
+
+        if stream.starts.len() == Stream::STARTS_MAX {
+            stream.starts.pop_front();
+        }
+        stream.starts.push_back((n, stc));
+    }
+
+    fn pes(&self, state: &mut State, pes: &Packet) {
+        let (pid, pts, dts) = (pes.pid, pes.pts, pes.dts);
+        let stream = match state.streams.get_mut(&pid) {
+            Some(stream) if stream.kind != Some(Kind::Data) => stream,
+            _ => return,
+        };
+
+        // PES headers of dropped PES packets are skipped
+        while stream.starts.front().is_some_and(|(n, _)| *n < pes.n) {
+            stream.starts.pop_front();
+        }
+        let stc = match stream.starts.front() {
+            Some((n, stc)) if *n == pes.n => *stc,
+            _ => None,
+        };
+        stream.starts.pop_front();
+
+        let discontinuities = state
+            .stcs
+            .get(&stream.pcr_pid)
+            .map(|stc| stc.discontinuities)
+            .unwrap_or(0);
+
+        stream.pes += 1;
+
+        let pts = match pts {
+            Some(pts) => pts,
+            None => {
+                stream.no_pts += 1;
+                if dts.is_some() {
+                    self.error(stream, pid, "dts-without-pts", ":pts none");
+                }
+                return;
+            }
+        };
+
+        if let Some(dts) = dts {
+            if wrapping_diff(pts, dts, TS_MAX) < 0 {
+                stream.dts_after_pts += 1;
+                self.error(
+                    stream,
+                    pid,
+                    "dts-after-pts",
+                    &format!(":pts {} :dts {}", pts, dts),
+                );
+            }
+        }
+
+        // decode order is monotonic; PTS only without reordering
+        let decode = dts.unwrap_or(pts);
+        if let Some(last) = stream.last {
+            if discontinuities == stream.discontinuities && wrapping_diff(decode, last, TS_MAX) <= 0
+            {
+                stream.backwards += 1;
+                self.error(
+                    stream,
+                    pid,
+                    if dts.is_some() {
+                        "dts-backwards"
+                    } else {
+                        "pts-backwards"
+                    },
+                    &format!(":last {} :got {}", last, decode),
+                );
+            }
+        }
+        stream.last = Some(decode);
+        stream.discontinuities = discontinuities;
+
+        // buffer delay
+        stream.delay =
+            stc.map(|stc| wrapping_diff(pts * 300 % PCR_MAX, stc, PCR_MAX) as f64 / 27_000.0);
+        if let Some(delay) = stream.delay {
+            stream.delays.add(delay);
+            if delay < 0.0 {
+                stream.late += 1;
+                self.error(
+                    stream,
+                    pid,
+                    "pts-before-pcr",
+                    &format!(":delay {:.3}ms", delay),
+                );
+            }
+        }
+
+        // A/V offset is sampled on audio PES against latest video delay
+        let (program, kind, delay) = (stream.program, stream.kind, stream.delay);
+        if kind != Some(Kind::Audio) {
+            return;
+        }
+        let delay = match delay {
+            Some(delay) => delay,
+            None => return,
+        };
+
+        let videos: Vec<(u16, f64)> = state
+            .streams
+            .iter()
+            .filter(|(_, s)| s.program == program && s.kind == Some(Kind::Video))
+            .filter_map(|(video_pid, s)| s.delay.map(|d| (*video_pid, d)))
+            .collect();
+        for (video_pid, video_delay) in videos {
+            let pair = state.pairs.entry((pid, video_pid)).or_default();
+            pair.offsets.add(delay - video_delay);
+        }
+    }
+
+    fn metrics_publish(&self) {
+        if !self.metrics_at.ready() {
+            return;
+        }
+
+        let mut state = self.state.borrow_mut();
+        let state = &mut *state;
+
+        let v = |v: Option<f64>| {
+            v.map(|v| format!("{:.3}", v))
+                .unwrap_or_else(|| "~".to_string())
+        };
+
+        for (pid, stream) in state.streams.iter_mut() {
+            if stream.pes == 0 {
+                continue;
+            }
+
+            self.metrics.set(
+                format!("pts-0x{:04X}", pid),
+                format!(
+                    "(:pes {} :no-pts {} :backwards {} :dts-after-pts {} :late {} :delay-min {}ms :delay-max {}ms :delay-mean {}ms)",
+                    stream.pes,
+                    stream.no_pts,
+                    stream.backwards,
+                    stream.dts_after_pts,
+                    stream.late,
+                    v(stream.delays.min),
+                    v(stream.delays.max),
+                    v(stream.delays.mean())
+                ),
+            );
+            stream.delays = Default::default();
+        }
+
+        let max = self.av_offset_max.as_secs_f64() * 1000.0;
+        let abs_max = self.av_offset_abs_max.as_secs_f64() * 1000.0;
+        for ((audio_pid, video_pid), pair) in state.pairs.iter_mut() {
+            let offset = match pair.offsets.mean() {
+                Some(offset) => offset,
+                None => continue,
+            };
+            let baseline = *pair.baseline.get_or_insert(offset);
+            let drift = offset - baseline;
+
+            let alarm = drift.abs() > max || offset.abs() > abs_max;
+            if alarm {
+                pair.alarms += 1;
+            }
+            if alarm && !pair.alarm {
+                warn!(
+                    "({}) [pts] A/V offset alarm raised (:audio 0x{:04X} :video 0x{:04X} :offset {:.3}ms :drift {:.3}ms :threshold {}ms :abs-threshold {}ms)",
+                    self.url, audio_pid, video_pid, offset, drift, max, abs_max
+                );
+            } else if !alarm && pair.alarm {
+                info!(
+                    "({}) [pts] A/V offset alarm cleared (:audio 0x{:04X} :video 0x{:04X} :offset {:.3}ms :drift {:.3}ms)",
+                    self.url, audio_pid, video_pid, offset, drift
+                );
+            }
+            pair.alarm = alarm;
+
+            self.metrics.set(
+                format!("av-offset-0x{:04X}-0x{:04X}", audio_pid, video_pid),
+                format!(
+                    "(:offset {:.3}ms :baseline {:.3}ms :drift {:.3}ms :alarms {})",
+                    offset, baseline, drift, pair.alarms
+                ),
+            );
+            pair.offsets = Default::default();
+        }
+    }
+}
+
+impl Consumer for Pts {
+    fn consume_trk(&self, trk: &Track) {
+        let mut state = self.state.borrow_mut();
+
+        let stream = state.streams.entry(trk.pid).or_default();
+        stream.program = trk.program_number;
+        stream.pcr_pid = trk.pcr_pid;
+        stream.kind = Some(trk.kind());
+    }
+
+    fn consume_pkt_raw(&self, raw: &[u8]) {
+        let pkt = match ts::Packet::new(raw) {
+            Ok(pkt) => pkt,
+            Err(_) => return,
+        };
+        let pid = u16::from(pkt.pid());
+
+        {
+            let mut state = self.state.borrow_mut();
+            let state = &mut *state;
+
+            state.packets += 1;
+
+            if let Ok(Some(pcr)) = pkt.pcr() {
+                // PCR implies adaptation field
+                let discontinuity = discontinuity(raw);
+                let n = state.packets;
+                state
+                    .stcs
+                    .entry(pid)
+                    .or_default()
+                    .pcr(pcr.value(), n, discontinuity);
+            }
+
+            if pkt.pusi() {
+                self.pes_start(state, pid);
+            }
+        }
+
+        self.metrics_publish();
+    }
+
+    fn consume_pkt(&self, pes: &Packet) {
+        let mut state = self.state.borrow_mut();
+        self.pes(&mut state, pes);
+    }
+}
@@ -0,0 +1,425 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use log::trace;
+use url::Url;
+
+use crate::audio;
+use crate::clock::TS_MAX;
+use crate::filter::{Consumer, Consumers, Producer};
+use crate::frame::Frame;
+use crate::packet::Packet;
+use crate::track::Track;
+
+/// how PES payload is split into access units
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Split {
+    /// access unit starts with PES carrying PTS
+    Video,
+    /// and with access unit delimiter NAL
+    Nal,
+    /// frames by sync header; may span PES
+    Audio,
+    /// one PES is one access unit
+    Pes,
+}
+
+impl Split {
+    fn from_codec(codec: &str) -> Split {
+        match codec {
+            "h264" | "h265" => Split::Nal,
+            "mpeg1video" | "mpeg2video" => Split::Video,
+            "aac" | "aac-latm" | "mp2" | "ac3" | "eac3" => Split::Audio,
+            _ => Split::Pes,
+        }
+    }
+}
+
+/// longer access unit duration is a timestamp gap; 90kHz
+const DURATION_MAX: u64 = 90_000;
+
+/// Annex B access unit delimiter at i (start code prefix position)
+fn is_aud(codec: &str, data: &[u8], i: usize) -> bool {
+    if data[i] != 0 || data[i + 1] != 0 || data[i + 2] != 1 {
+        return false;
+    }
+
+    match (codec, data.get(i + 3)) {
+        ("h264", Some(nal)) => nal & 0x1F == 9,
+        ("h265", Some(nal)) => (nal >> 1) & 0x3F == 35,
+        _ => false,
+    }
+}
+
+struct Stream {
+    codec: &'static str,
+    split: Split,
+
+    /// video: access unit being collected
+    frame: Option<Frame>,
+    /// video: bytes of frame already searched for delimiter
+    scanned: usize,
+    /// video: DTS (PTS if absent) of last PES with timestamps
+    /// and access units started since (including its own)
+    pes_ts: Option<(u64, u64)>,
+    /// video: access unit duration by PES timestamps; 90kHz
+    duration: Option<u64>,
+
+    /// audio: bytes not forming whole frame yet
+    buf: Vec<u8>,
+    /// audio: PES PTS for first frame starting at or after buf offset
+    pts_pending: VecDeque<(usize, u64)>,
+    /// audio: extrapolated PTS of next frame
+    pts_next: Option<u64>,
+    /// audio: last frame header was followed by another one
+    synced: bool,
+    /// audio: bytes dropped since last frame
+    skipped: usize,
+}
+
+impl Stream {
+    fn new(codec: &'static str) -> Stream {
+        Stream {
+            codec,
+            split: Split::from_codec(codec),
+            frame: None,
+            scanned: 0,
+            pes_ts: None,
+            duration: None,
+            buf: Vec::new(),
+            pts_pending: VecDeque::new(),
+            pts_next: None,
+            synced: false,
+            skipped: 0,
+        }
+    }
+
+    fn video(&mut self, pkt: &Packet, emit: &mut Vec<Frame>) {
+        match self.frame.as_mut() {
+            Some(frame) if pkt.pts.is_none() => {
+                Arc::make_mut(&mut frame.data).extend_from_slice(&pkt.data)
+            }
+            _ => {
+                if let Some(frame) = self.frame.take() {
+                    emit.push(frame);
+                }
+                if let Some(ts) = pkt.dts.or(pkt.pts) {
+                    if let Some((last, aus)) = self.pes_ts {
+                        let duration = ((ts + TS_MAX - last) % TS_MAX) / aus;
+                        self.duration = Some(duration).filter(|d| *d > 0 && *d < DURATION_MAX);
+                    }
+                    self.pes_ts = Some((ts, 1));
+                }
+                self.frame = Some(Frame {
+                    pid: pkt.pid,
+                    codec: self.codec,
+                    pts: pkt.pts,
+                    dts: pkt.dts,
+                    data: Arc::new(pkt.data.clone()),
+                    picture: None,
+                    sei: Vec::new(),
+                    skipped: 0,
+                });
+                self.scanned = 0;
+            }
+        }
+
+        if self.split != Split::Nal {
+            return;
+        }
+
+        // delimiter inside collected data starts the next access unit
+        let codec = self.codec;
+        while let Some(frame) = self.frame.as_mut() {
+            let from = self.scanned.saturating_sub(3).max(1);
+            let to = frame.data.len().saturating_sub(3);
+            let i = match (from..to).find(|i| is_aud(codec, &frame.data, *i)) {
+                Some(i) => i,
+                None => {
+                    self.scanned = to;
+                    break;
+                }
+            };
+            self.scanned = i + 4;
+
+            // 4-byte start code
+            let start = if frame.data[i - 1] == 0 { i - 1 } else { i };
+            if start == 0 {
+                continue;
+            }
+            if let Some((_, aus)) = self.pes_ts.as_mut() {
+                *aus += 1;
+            }
+
+            // first access unit has PES timestamps; next ones follow
+            // in decode order, so PTS is known only without reordering
+            let duration = self.duration;
+            let next_ts = |ts: Option<u64>| {
+                ts.zip(duration)
+                    .map(|(ts, duration)| (ts + duration) % TS_MAX)
+            };
+            let (pts, dts) = match frame.dts {
+                Some(dts) => (None, next_ts(Some(dts))),
+                None => (next_ts(frame.pts), None),
+            };
+
+            let data = Arc::new(Arc::make_mut(&mut frame.data).split_off(start));
+            let next = Frame {
+                pid: frame.pid,
+                codec: frame.codec,
+                pts,
+                dts,
+                data,
+                picture: None,
+                sei: Vec::new(),
+                skipped: 0,
+            };
+            self.scanned -= start;
+            emit.push(self.frame.replace(next).unwrap());
+        }
+    }
+
+    fn audio(&mut self, pkt: &Packet, emit: &mut Vec<Frame>) {
+        if let Some(pts) = pkt.pts {
+            self.pts_pending.push_back((self.buf.len(), pts));
+        }
+        self.buf.extend_from_slice(&pkt.data);
+
+        let mut pos = 0;
+        while self.buf.len() - pos >= audio::SYNC_SZ {
+            let sync = match audio::sync(self.codec, &self.buf[pos..]) {
+                Some(sync) if sync.size >= audio::SYNC_SZ => sync,
+                _ => {
+                    self.synced = false;
+                    pos += 1;
+                    self.skipped += 1;
+                    continue;
+                }
+            };
+
+            // after sync loss header must be confirmed by the next one
+            let end = pos + sync.size;
+            if !self.synced {
+                if self.buf.len() < end + audio::SYNC_SZ {
+                    break;
+                }
+                if audio::sync(self.codec, &self.buf[end..]).is_none() {
+                    pos += 1;
+                    self.skipped += 1;
+                    continue;
+                }
+                self.synced = true;
+            }
+            if self.buf.len() < end {
+                break;
+            }
+
+            let mut pts = self.pts_next;
+            while let Some((offset, pes_pts)) = self.pts_pending.front() {
+                if *offset > pos {
+                    break;
+                }
+                pts = Some(*pes_pts);
+                self.pts_pending.pop_front();
+            }
+            self.pts_next = pts.and_then(|pts| sync.duration().map(|d| (pts + d) % TS_MAX));
+
+            emit.push(Frame {
+                pid: pkt.pid,
+                codec: self.codec,
+                pts,
+                dts: None,
+                data: Arc::new(self.buf[pos..end].to_vec()),
+                picture: None,
+                sei: Vec::new(),
+                skipped: self.skipped,
+            });
+            self.skipped = 0;
+            pos = end;
+        }
+
+        self.buf.drain(..pos);
+        for (offset, _) in self.pts_pending.iter_mut() {
+            *offset = offset.saturating_sub(pos);
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    streams: HashMap<u16, Stream>,
+}
+
+/// splits reassembled PES into access units with timestamps;
+/// feeds ES dump and codec analyzers, everything else is passed through
+pub struct Reassembler {
+    url: Url,
+
+    consumers: Consumers,
+
+    state: RefCell<State>,
+}
+
+impl Reassembler {
+    pub fn new(url: Url) -> Reassembler {
+        Reassembler {
+            url,
+            consumers: Default::default(),
+            state: Default::default(),
+        }
+    }
+}
+
+impl Producer for Reassembler {
+    fn consumers(&self) -> &Consumers {
+        &self.consumers
+    }
+
+    fn consumers_mut(&mut self) -> &mut Consumers {
+        &mut self.consumers
+    }
+}
+
+impl Consumer for Reassembler {
+    fn consume_trk(&self, trk: &Track) {
+        {
+            let mut state = self.state.borrow_mut();
+
+            let codec = trk.codec();
+            if state
+                .streams
+                .get(&trk.pid)
+                .is_none_or(|stream| stream.codec != codec)
+            {
+                state.streams.insert(trk.pid, Stream::new(codec));
+            }
+        }
+
+        self.produce_trk(trk)
+    }
+
+    fn consume_pkt_raw(&self, pkt_raw: &[u8]) {
+        self.produce_pkt_raw(pkt_raw)
+    }
+
+    fn consume_section(&self, pid: u16, section: &[u8]) {
+        self.produce_section(pid, section)
+    }
+
+    fn consume_pkt(&self, pkt: &Packet) {
+        self.produce_pkt(pkt);
+
+        let mut frames = Vec::new();
+        {
+            let mut state = self.state.borrow_mut();
+
+            let stream = match state.streams.get_mut(&pkt.pid) {
+                Some(stream) => stream,
+                None => return,
+            };
+
+            match stream.split {
+                Split::Video | Split::Nal => stream.video(pkt, &mut frames),
+                Split::Audio => stream.audio(pkt, &mut frames),
+                Split::Pes => frames.push(Frame {
+                    pid: pkt.pid,
+                    codec: stream.codec,
+                    pts: pkt.pts,
+                    dts: pkt.dts,
+                    data: Arc::new(pkt.data.clone()),
+                    picture: None,
+                    sei: Vec::new(),
+                    skipped: 0,
+                }),
+            }
+        }
+
+        for frame in frames.iter() {
+            trace!(
+                "({}) [reassembler] AU (:pid 0x{:04X} :codec {} :pts {:?} :dts {:?} :sz {})",
+                self.url,
+                frame.pid,
+                frame.codec,
+                frame.pts,
+                frame.dts,
+                frame.data.len()
+            );
+            self.produce_frm(frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// H.264 PES of access units, each AUD + non-IDR slice
+    fn pes(pts: u64, dts: Option<u64>, aus: usize) -> Packet {
+        let au = [0, 0, 0, 1, 0x09, 0xF0, 0, 0, 0, 1, 0x41, 0x9A, 0x00];
+        Packet {
+            pid: 0x100,
+            stream_id: 0xE0,
+            n: 1,
+            pts: Some(pts),
+            dts,
+            data: au.repeat(aus),
+        }
+    }
+
+    fn timestamps(stream: &mut Stream, pkts: &[Packet]) -> Vec<(Option<u64>, Option<u64>)> {
+        let mut frames = Vec::new();
+        for pkt in pkts {
+            stream.video(pkt, &mut frames);
+        }
+        frames.iter().map(|frm| (frm.pts, frm.dts)).collect()
+    }
+
+    #[test]
+    fn aud_split_extrapolates() {
+        let mut stream = Stream::new("h264");
+        let frames = timestamps(
+            &mut stream,
+            &[
+                pes(9000, None, 3),
+                pes(9000 + 3 * 3600, None, 3),
+                pes(0, None, 1),
+            ],
+        );
+
+        assert_eq!(
+            frames,
+            vec![
+                (Some(9000), None),
+                (None, None),
+                (None, None),
+                (Some(19800), None),
+                (Some(23400), None),
+                (Some(27000), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn aud_split_extrapolates_dts() {
+        let mut stream = Stream::new("h264");
+        let frames = timestamps(
+            &mut stream,
+            &[
+                pes(12600, Some(9000), 2),
+                pes(19800, Some(16200), 2),
+                pes(0, None, 1),
+            ],
+        );
+
+        assert_eq!(
+            frames,
+            vec![
+                (Some(12600), Some(9000)),
+                (None, None),
+                (Some(19800), Some(16200)),
+                (None, Some(19800)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,233 @@
+use crate::error::{Error, Result};
+
+/// RTP fixed header (RFC 3550)
+///
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |V=2|P|X|  CC   |M|     PT      |       sequence number         |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                           timestamp                           |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |           synchronization source (SSRC) identifier            |
+/// +=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+
+/// |            contributing source (CSRC) identifiers             |
+/// |                             ....                              |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+pub struct Packet<'buf> {
+    buf: &'buf [u8],
+}
+
+impl<'buf> Packet<'buf> {
+    pub const HEADER_SZ: usize = 12;
+    const VERSION: u8 = 2;
+
+    /// MPEG-2 transport stream static payload type (RFC 3551)
+    #[allow(dead_code)]
+    pub const PT_MP2T: u8 = 33;
+
+    #[inline(always)]
+    pub fn new(buf: &'buf [u8]) -> Packet<'buf> {
+        Packet { buf }
+    }
+
+    #[inline(always)]
+    pub fn try_new(buf: &'buf [u8]) -> Result<Packet<'buf>> {
+        let pkt = Packet::new(buf);
+        pkt.validate()?;
+        Ok(pkt)
+    }
+
+    #[inline(always)]
+    fn validate(&self) -> Result<()> {
+        if self.buf.len() < Self::HEADER_SZ {
+            Err(Error::rtp_buf(self.buf.len(), Self::HEADER_SZ))
+        } else if self.version() != Self::VERSION {
+            Err(Error::rtp_version(self.version()))
+        } else if self.buf.len() < self.payload_offset() + self.padding_sz() {
+            Err(Error::rtp_buf(
+                self.buf.len(),
+                self.payload_offset() + self.padding_sz(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline(always)]
+    pub fn version(&self) -> u8 {
+        (self.buf[0] & 0b1100_0000) >> 6
+    }
+
+    #[inline(always)]
+    fn padding(&self) -> bool {
+        (self.buf[0] & 0b0010_0000) != 0
+    }
+
+    #[inline(always)]
+    fn extension(&self) -> bool {
+        (self.buf[0] & 0b0001_0000) != 0
+    }
+
+    #[inline(always)]
+    fn csrc_count(&self) -> usize {
+        (self.buf[0] & 0b0000_1111) as usize
+    }
+
+    #[inline(always)]
+    #[allow(dead_code)]
+    pub fn marker(&self) -> bool {
+        (self.buf[1] & 0b1000_0000) != 0
+    }
+
+    #[inline(always)]
+    pub fn payload_type(&self) -> u8 {
+        self.buf[1] & 0b0111_1111
+    }
+
+    #[inline(always)]
+    pub fn sequence_number(&self) -> u16 {
+        (u16::from(self.buf[2]) << 8) | u16::from(self.buf[3])
+    }
+
+    #[inline(always)]
+    pub fn timestamp(&self) -> u32 {
+        (u32::from(self.buf[4]) << 24)
+            | (u32::from(self.buf[5]) << 16)
+            | (u32::from(self.buf[6]) << 8)
+            | u32::from(self.buf[7])
+    }
+
+    #[inline(always)]
+    #[allow(dead_code)]
+    pub fn ssrc(&self) -> u32 {
+        (u32::from(self.buf[8]) << 24)
+            | (u32::from(self.buf[9]) << 16)
+            | (u32::from(self.buf[10]) << 8)
+            | u32::from(self.buf[11])
+    }
+
+    /// header + CSRC list + header extension
+    #[inline(always)]
+    fn payload_offset(&self) -> usize {
+        let mut pos = Self::HEADER_SZ + 4 * self.csrc_count();
+
+        if self.extension() && self.buf.len() >= pos + 4 {
+            // 16 bits profile-defined + 16 bits length in 32-bit words
+            let length = (usize::from(self.buf[pos + 2]) << 8) | usize::from(self.buf[pos + 3]);
+            pos += 4 + 4 * length;
+        }
+
+        pos
+    }
+
+    #[inline(always)]
+    fn padding_sz(&self) -> usize {
+        if self.padding() {
+            self.buf[self.buf.len() - 1] as usize
+        } else {
+            0
+        }
+    }
+
+    #[inline(always)]
+    pub fn payload(&self) -> &'buf [u8] {
+        let lft = self.payload_offset();
+        let rght = self.buf.len() - self.padding_sz();
+
+        &self.buf[lft..rght]
+    }
+}
+
+/// SMPTE 2022-1 FEC header; follows RTP header of FEC packet
+///
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |      SNBase low bits          |        Length Recovery        |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |E| PT recovery |                    Mask                       |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                          TS recovery                          |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |X|D|type |index|    Offset     |      NA       |SNBase ext bits|
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+pub struct FecHeader<'buf> {
+    buf: &'buf [u8],
+}
+
+impl<'buf> FecHeader<'buf> {
+    pub const SZ: usize = 16;
+
+    #[inline(always)]
+    pub fn new(buf: &'buf [u8]) -> FecHeader<'buf> {
+        FecHeader { buf }
+    }
+
+    #[inline(always)]
+    pub fn try_new(buf: &'buf [u8]) -> Result<FecHeader<'buf>> {
+        let h = FecHeader::new(buf);
+        h.validate()?;
+        Ok(h)
+    }
+
+    #[inline(always)]
+    fn validate(&self) -> Result<()> {
+        if self.buf.len() < Self::SZ {
+            Err(Error::rtp_buf(self.buf.len(), Self::SZ))
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline(always)]
+    pub fn sn_base(&self) -> u16 {
+        (u16::from(self.buf[0]) << 8) | u16::from(self.buf[1])
+    }
+
+    #[inline(always)]
+    pub fn length_recovery(&self) -> u16 {
+        (u16::from(self.buf[2]) << 8) | u16::from(self.buf[3])
+    }
+
+    #[inline(always)]
+    #[allow(dead_code)]
+    pub fn pt_recovery(&self) -> u8 {
+        self.buf[4] & 0b0111_1111
+    }
+
+    #[inline(always)]
+    #[allow(dead_code)]
+    pub fn ts_recovery(&self) -> u32 {
+        (u32::from(self.buf[8]) << 24)
+            | (u32::from(self.buf[9]) << 16)
+            | (u32::from(self.buf[10]) << 8)
+            | u32::from(self.buf[11])
+    }
+
+    /// false - column (non-interleaved) FEC
+    /// true - row (interleaved) FEC
+    #[inline(always)]
+    pub fn d(&self) -> bool {
+        (self.buf[12] & 0b0100_0000) != 0
+    }
+
+    /// column FEC: L (distance between protected packets)
+    /// row FEC: 1
+    #[inline(always)]
+    pub fn offset(&self) -> u8 {
+        self.buf[13]
+    }
+
+    /// column FEC: D (number of protected packets)
+    /// row FEC: L
+    #[inline(always)]
+    pub fn na(&self) -> u8 {
+        self.buf[14]
+    }
+
+    #[inline(always)]
+    pub fn payload(&self) -> &'buf [u8] {
+        &self.buf[Self::SZ..]
+    }
+}
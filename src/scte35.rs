@@ -0,0 +1,1049 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use log::{debug, info, trace, warn};
+use url::Url;
+
+use crate::bits::Bits;
+use crate::clock::{wrapping_diff, Throttle, TS_MAX};
+use crate::crc32;
+use crate::filter::Consumer;
+use crate::frame::{Frame, PictureType};
+use crate::metrics::Metrics;
+use crate::probe::{json_str, Format};
+use crate::track::{Kind, Track};
+
+/// splice_info_section
+pub const TABLE_ID: u8 = 0xFC;
+
+/// splice_command_length of legacy encoders; command is parsed to find its end
+const COMMAND_LENGTH_UNKNOWN: usize = 0xFFF;
+
+/// "CUEI" splice descriptor identifier
+const IDENTIFIER_CUEI: u32 = 0x4355_4549;
+const TAG_SEGMENTATION: u8 = 0x02;
+
+/// cues waiting for splice point per program
+const PENDING_MAX: usize = 64;
+/// splice point not found this long after splice time (PTS discontinuity); 90kHz
+const PENDING_EXPIRE: i64 = 5 * 90_000;
+
+/// 33-bit time stamp
+fn bits33(r: &mut Bits) -> Option<u64> {
+    Some((u64::from(r.bits(1)?) << 32) | u64::from(r.bits(32)?))
+}
+
+/// splice_time(); None if time_specified_flag is not set
+fn splice_time(r: &mut Bits) -> Option<Option<u64>> {
+    if r.flag()? {
+        r.skip(6)?;
+        Some(Some(bits33(r)?))
+    } else {
+        r.skip(7)?;
+        Some(None)
+    }
+}
+
+fn ms(ticks: i64) -> f64 {
+    ticks as f64 / 90.0
+}
+
+/// SCTE 35 table 22 segmentation_type_id
+fn segmentation_type(type_id: u8) -> &'static str {
+    match type_id {
+        0x00 => "not-indicated",
+        0x01 => "content-identification",
+        0x10 => "program-start",
+        0x11 => "program-end",
+        0x12 => "program-early-termination",
+        0x13 => "program-breakaway",
+        0x14 => "program-resumption",
+        0x15 => "program-runover-planned",
+        0x16 => "program-runover-unplanned",
+        0x17 => "program-overlap-start",
+        0x18 => "program-blackout-override",
+        0x19 => "program-start-in-progress",
+        0x20 => "chapter-start",
+        0x21 => "chapter-end",
+        0x22 => "break-start",
+        0x23 => "break-end",
+        0x24 => "opening-credit-start",
+        0x25 => "opening-credit-end",
+        0x26 => "closing-credit-start",
+        0x27 => "closing-credit-end",
+        0x30 => "provider-ad-start",
+        0x31 => "provider-ad-end",
+        0x32 => "distributor-ad-start",
+        0x33 => "distributor-ad-end",
+        0x34 => "provider-placement-opportunity-start",
+        0x35 => "provider-placement-opportunity-end",
+        0x36 => "distributor-placement-opportunity-start",
+        0x37 => "distributor-placement-opportunity-end",
+        0x38 => "provider-overlay-placement-opportunity-start",
+        0x39 => "provider-overlay-placement-opportunity-end",
+        0x3A => "distributor-overlay-placement-opportunity-start",
+        0x3B => "distributor-overlay-placement-opportunity-end",
+        0x3C => "provider-promo-start",
+        0x3D => "provider-promo-end",
+        0x3E => "distributor-promo-start",
+        0x3F => "distributor-promo-end",
+        0x40 => "unscheduled-event-start",
+        0x41 => "unscheduled-event-end",
+        0x42 => "alternate-content-opportunity-start",
+        0x43 => "alternate-content-opportunity-end",
+        0x44 => "provider-ad-block-start",
+        0x45 => "provider-ad-block-end",
+        0x46 => "distributor-ad-block-start",
+        0x47 => "distributor-ad-block-end",
+        0x50 => "network-start",
+        0x51 => "network-end",
+        _ => "reserved",
+    }
+}
+
+/// SCTE 35 table 21 segmentation_upid_type
+fn upid_type(upid_type: u8) -> &'static str {
+    match upid_type {
+        0x00 => "none",
+        0x01 => "user",
+        0x02 => "isci",
+        0x03 => "ad-id",
+        0x04 => "umid",
+        0x05 | 0x06 => "isan",
+        0x07 => "tid",
+        0x08 => "ti",
+        0x09 => "adi",
+        0x0A => "eidr",
+        0x0B => "atsc",
+        0x0C => "mpu",
+        0x0D => "mid",
+        0x0E => "ads",
+        0x0F => "uri",
+        0x10 => "uuid",
+        0x11 => "scr",
+        _ => "reserved",
+    }
+}
+
+/// segmentation_upid as text: printable types as is, binary as hex;
+/// MID is a list of nested UPIDs
+fn upid(kind: u8, buf: &[u8]) -> String {
+    let hex = |buf: &[u8]| {
+        let mut s = String::with_capacity(2 + 2 * buf.len());
+        s.push_str("0x");
+        for b in buf {
+            s.push_str(&format!("{:02X}", b));
+        }
+        s
+    };
+
+    match kind {
+        0x02 | 0x03 | 0x07 | 0x09 | 0x0F | 0x11 => String::from_utf8_lossy(buf).into_owned(),
+        0x0D => {
+            let mut upids = Vec::new();
+            let mut buf = buf;
+            while buf.len() >= 2 && buf.len() >= 2 + usize::from(buf[1]) {
+                let (kind, len) = (buf[0], usize::from(buf[1]));
+                upids.push(format!(
+                    "{}:{}",
+                    upid_type(kind),
+                    upid(kind, &buf[2..2 + len])
+                ));
+                buf = &buf[2 + len..];
+            }
+            upids.join(",")
+        }
+        _ if buf.is_empty() => String::new(),
+        _ => hex(buf),
+    }
+}
+
+/// splice_insert()
+#[derive(Clone, Debug)]
+pub struct Insert {
+    pub event_id: u32,
+    pub cancel: bool,
+    /// cue-out (true) or cue-in
+    pub out_of_network: bool,
+    pub immediate: bool,
+    /// program splice time; 90kHz without pts_adjustment
+    pub pts: Option<u64>,
+    /// break_duration; 90kHz
+    pub duration: Option<u64>,
+    pub auto_return: bool,
+    pub unique_program_id: u16,
+    pub avail_num: u8,
+    pub avails_expected: u8,
+}
+
+impl Insert {
+    fn parse(r: &mut Bits) -> Option<Insert> {
+        let mut insert = Insert {
+            event_id: r.bits(32)?,
+            cancel: r.flag()?,
+            out_of_network: false,
+            immediate: false,
+            pts: None,
+            duration: None,
+            auto_return: false,
+            unique_program_id: 0,
+            avail_num: 0,
+            avails_expected: 0,
+        };
+        r.skip(7)?;
+        if insert.cancel {
+            return Some(insert);
+        }
+
+        insert.out_of_network = r.flag()?;
+        let program_splice = r.flag()?;
+        let duration = r.flag()?;
+        insert.immediate = r.flag()?;
+        r.skip(4)?;
+
+        if program_splice && !insert.immediate {
+            insert.pts = splice_time(r)?;
+        }
+        if !program_splice {
+            // component splice; first component time is used
+            let components = r.u8(8)?;
+            for _ in 0..components {
+                r.skip(8)?;
+                if !insert.immediate {
+                    let pts = splice_time(r)?;
+                    insert.pts = insert.pts.or(pts);
+                }
+            }
+        }
+        if duration {
+            insert.auto_return = r.flag()?;
+            r.skip(6)?;
+            insert.duration = Some(bits33(r)?);
+        }
+
+        insert.unique_program_id = r.bits(16)? as u16;
+        insert.avail_num = r.u8(8)?;
+        insert.avails_expected = r.u8(8)?;
+
+        Some(insert)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Command {
+    Null,
+    Schedule,
+    Insert(Insert),
+    /// splice time; 90kHz without pts_adjustment
+    TimeSignal(Option<u64>),
+    BandwidthReservation,
+    Private(u32),
+    /// encrypted or reserved command type
+    Unknown(u8),
+}
+
+impl Command {
+    fn parse(kind: u8, r: &mut Bits) -> Option<Command> {
+        Some(match kind {
+            0x00 => Command::Null,
+            0x04 => Command::Schedule,
+            0x05 => Command::Insert(Insert::parse(r)?),
+            0x06 => Command::TimeSignal(splice_time(r)?),
+            0x07 => Command::BandwidthReservation,
+            0xFF => Command::Private(r.bits(32)?),
+            kind => Command::Unknown(kind),
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Null => "splice-null",
+            Command::Schedule => "splice-schedule",
+            Command::Insert(_) => "splice-insert",
+            Command::TimeSignal(_) => "time-signal",
+            Command::BandwidthReservation => "bandwidth-reservation",
+            Command::Private(_) => "private",
+            Command::Unknown(_) => "unknown",
+        }
+    }
+}
+
+/// segmentation_descriptor()
+#[derive(Clone, Debug)]
+pub struct Segmentation {
+    pub event_id: u32,
+    pub cancel: bool,
+    /// 90kHz
+    pub duration: Option<u64>,
+    pub upid_type: u8,
+    pub upid: String,
+    pub type_id: u8,
+    pub segment_num: u8,
+    pub segments_expected: u8,
+}
+
+impl Segmentation {
+    fn parse(buf: &[u8]) -> Option<Segmentation> {
+        let mut r = Bits::new(buf);
+
+        let mut segmentation = Segmentation {
+            event_id: r.bits(32)?,
+            cancel: r.flag()?,
+            duration: None,
+            upid_type: 0,
+            upid: String::new(),
+            type_id: 0,
+            segment_num: 0,
+            segments_expected: 0,
+        };
+        r.skip(7)?;
+        if segmentation.cancel {
+            return Some(segmentation);
+        }
+
+        let program_segmentation = r.flag()?;
+        let duration = r.flag()?;
+        // delivery_not_restricted_flag and restrictions
+        r.skip(6)?;
+        if !program_segmentation {
+            let components = r.u8(8)?;
+            // component_tag, reserved, pts_offset
+            r.skip(usize::from(components) * (8 + 7 + 33))?;
+        }
+        if duration {
+            segmentation.duration = Some((u64::from(r.bits(8)?) << 32) | u64::from(r.bits(32)?));
+        }
+
+        segmentation.upid_type = r.u8(8)?;
+        let len = usize::from(r.u8(8)?);
+        let pos = buf.len() - r.left() / 8;
+        segmentation.upid = upid(segmentation.upid_type, buf.get(pos..pos + len)?);
+        r.skip(8 * len)?;
+
+        segmentation.type_id = r.u8(8)?;
+        segmentation.segment_num = r.u8(8)?;
+        segmentation.segments_expected = r.u8(8)?;
+
+        Some(segmentation)
+    }
+
+    pub fn kind(&self) -> &'static str {
+        segmentation_type(self.type_id)
+    }
+
+    fn json(&self) -> String {
+        format!(
+            r#"{{"event_id":{},"cancel":{},"type":"{}","type_id":{},"upid_type":"{}","upid":{},"segment_num":{},"segments_expected":{},"duration_ms":{}}}"#,
+            self.event_id,
+            self.cancel,
+            self.kind(),
+            self.type_id,
+            upid_type(self.upid_type),
+            json_str(&self.upid),
+            self.segment_num,
+            self.segments_expected,
+            self.duration
+                .map(|v| format!("{:.3}", ms(v as i64)))
+                .unwrap_or_else(|| "null".to_string())
+        )
+    }
+}
+
+impl fmt::Display for Segmentation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(:event-id {}", self.event_id)?;
+        if self.cancel {
+            return write!(f, " :cancel true)");
+        }
+        write!(f, " :type {}", self.kind())?;
+        if self.upid_type != 0 {
+            write!(f, " :upid {}:{}", upid_type(self.upid_type), self.upid)?;
+        }
+        write!(
+            f,
+            " :segment {}/{}",
+            self.segment_num, self.segments_expected
+        )?;
+        if let Some(duration) = self.duration {
+            write!(f, " :duration {:.3}ms", ms(duration as i64))?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// SCTE 35 splice_info_section
+#[derive(Clone, Debug)]
+pub struct SpliceInfo {
+    pub protocol_version: u8,
+    pub encrypted: bool,
+    /// 90kHz
+    pub pts_adjustment: u64,
+    pub tier: u16,
+    pub command: Command,
+    pub segmentations: Vec<Segmentation>,
+}
+
+impl SpliceInfo {
+    /// complete section with CRC_32 (not checked)
+    pub fn parse(buf: &[u8]) -> Option<SpliceInfo> {
+        if buf.len() < 3 || buf[0] != TABLE_ID {
+            return None;
+        }
+        let len = 3 + ((usize::from(buf[1] & 0x0F) << 8) | usize::from(buf[2]));
+        // CRC_32
+        let buf = buf.get(3..len.checked_sub(4)?)?;
+
+        let mut r = Bits::new(buf);
+        let protocol_version = r.u8(8)?;
+        let encrypted = r.flag()?;
+        // encryption_algorithm
+        r.skip(6)?;
+        let pts_adjustment = bits33(&mut r)?;
+        // cw_index
+        r.skip(8)?;
+        let tier = r.bits(12)? as u16;
+        let command_length = r.bits(12)? as usize;
+        let command_type = r.u8(8)?;
+
+        let mut info = SpliceInfo {
+            protocol_version,
+            encrypted,
+            pts_adjustment,
+            tier,
+            command: Command::Unknown(command_type),
+            segmentations: Vec::new(),
+        };
+        // command and descriptors can not be read without key
+        if encrypted {
+            return Some(info);
+        }
+
+        let pos = buf.len() - r.left() / 8;
+        let mut cmd = Bits::new(&buf[pos..]);
+        info.command = Command::parse(command_type, &mut cmd)?;
+        let command_length = if command_length == COMMAND_LENGTH_UNKNOWN {
+            (buf.len() - pos) - cmd.left() / 8
+        } else {
+            command_length
+        };
+
+        let mut r = Bits::new(buf.get(pos + command_length..)?);
+        let descriptors_length = r.bits(16)? as usize;
+        let pos = pos + command_length + 2;
+        let descriptors = buf.get(pos..pos + descriptors_length)?;
+
+        for (tag, data) in crate::psi::descriptors(descriptors) {
+            if data.len() < 4 {
+                continue;
+            }
+            let identifier = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+            if tag == TAG_SEGMENTATION && identifier == IDENTIFIER_CUEI {
+                if let Some(segmentation) = Segmentation::parse(&data[4..]) {
+                    info.segmentations.push(segmentation);
+                }
+            }
+        }
+
+        Some(info)
+    }
+
+    /// splice time with pts_adjustment; 90kHz
+    pub fn pts(&self) -> Option<u64> {
+        let pts = match &self.command {
+            Command::Insert(insert) => insert.pts,
+            Command::TimeSignal(pts) => *pts,
+            _ => None,
+        }?;
+        Some((pts + self.pts_adjustment) % TS_MAX)
+    }
+
+    /// splice point is reached with the next video picture
+    pub fn immediate(&self) -> bool {
+        matches!(&self.command, Command::Insert(insert) if insert.immediate && !insert.cancel)
+    }
+
+    pub fn event_id(&self) -> Option<u32> {
+        match &self.command {
+            Command::Insert(insert) => Some(insert.event_id),
+            _ => self.segmentations.first().map(|s| s.event_id),
+        }
+    }
+
+    fn json(&self) -> String {
+        let opt = |v: Option<String>| v.unwrap_or_else(|| "null".to_string());
+
+        let insert = match &self.command {
+            Command::Insert(insert) => format!(
+                r#"{{"event_id":{},"cancel":{},"out_of_network":{},"immediate":{},"duration_ms":{},"auto_return":{},"unique_program_id":{},"avail_num":{},"avails_expected":{}}}"#,
+                insert.event_id,
+                insert.cancel,
+                insert.out_of_network,
+                insert.immediate,
+                opt(insert.duration.map(|v| format!("{:.3}", ms(v as i64)))),
+                insert.auto_return,
+                insert.unique_program_id,
+                insert.avail_num,
+                insert.avails_expected
+            ),
+            _ => "null".to_string(),
+        };
+        let segmentations: Vec<String> = self.segmentations.iter().map(|s| s.json()).collect();
+
+        format!(
+            r#""command":"{}","protocol_version":{},"pts":{},"pts_adjustment":{},"tier":{},"encrypted":{},"insert":{},"segmentations":[{}]"#,
+            self.command.name(),
+            self.protocol_version,
+            opt(self.pts().map(|v| v.to_string())),
+            self.pts_adjustment,
+            self.tier,
+            self.encrypted,
+            insert,
+            segmentations.join(",")
+        )
+    }
+}
+
+impl fmt::Display for SpliceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, ":command {}", self.command.name())?;
+        if self.encrypted {
+            write!(f, " :encrypted true")?;
+        }
+        match &self.command {
+            Command::Insert(insert) if insert.cancel => {
+                write!(f, " :event-id {} :cancel true", insert.event_id)?
+            }
+            Command::Insert(insert) => {
+                write!(
+                    f,
+                    " :event-id {} :out {}",
+                    insert.event_id, insert.out_of_network
+                )?;
+                if insert.immediate {
+                    write!(f, " :immediate true")?;
+                }
+                if let Some(duration) = insert.duration {
+                    write!(
+                        f,
+                        " :duration {:.3}ms :auto-return {}",
+                        ms(duration as i64),
+                        insert.auto_return
+                    )?;
+                }
+                write!(f, " :avail {}/{}", insert.avail_num, insert.avails_expected)?;
+            }
+            Command::Private(identifier) => write!(f, " :identifier 0x{:08X}", identifier)?,
+            Command::Unknown(kind) => write!(f, " :type 0x{:02X}", kind)?,
+            _ => {}
+        }
+        if let Some(pts) = self.pts() {
+            write!(f, " :pts {}", pts)?;
+        }
+        for segmentation in self.segmentations.iter() {
+            write!(f, " :segmentation {}", segmentation)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// cue waiting for its splice point in video
+struct Pending {
+    pid: u16,
+    event_id: Option<u32>,
+    /// None for splice_immediate
+    pts: Option<u64>,
+    /// earliest picture in presentation order at or after splice time:
+    /// (PTS, coding type)
+    picture: Option<(u64, Option<PictureType>)>,
+}
+
+/// per SCTE-35 PID
+#[derive(Default)]
+struct Stream {
+    program: u16,
+
+    cues: u64,
+    inserts: u64,
+    time_signals: u64,
+    nulls: u64,
+    crc_errors: u64,
+    /// splice time already passed on arrival
+    late: u64,
+    splices: u64,
+    /// splice point not on intra picture
+    misaligned: u64,
+}
+
+/// per program
+#[derive(Default)]
+struct Program {
+    video_pid: Option<u16>,
+    /// last video picture PTS
+    video_pts: Option<u64>,
+    pending: Vec<Pending>,
+}
+
+#[derive(Default)]
+struct State {
+    origin: Option<Instant>,
+
+    streams: BTreeMap<u16, Stream>,
+    programs: BTreeMap<u16, Program>,
+
+    log: Option<BufWriter<File>>,
+}
+
+/// SCTE 35 cues on stream type 0x86 PIDs: splice_insert, time_signal,
+/// segmentation descriptors with UPIDs.
+///
+/// splice time is correlated with video PTS of the same program:
+/// lead time on cue arrival and the picture the splice point lands on
+pub struct Scte35 {
+    url: Url,
+    log: Option<PathBuf>,
+    log_format: Format,
+
+    state: RefCell<State>,
+
+    metrics: Metrics,
+    metrics_at: Throttle,
+}
+
+impl Scte35 {
+    pub fn new(url: Url) -> Scte35 {
+        Scte35 {
+            url,
+            log: None,
+            log_format: Format::Text,
+            state: Default::default(),
+            metrics: Default::default(),
+            metrics_at: Default::default(),
+        }
+    }
+
+    /// write every cue and splice point as event line
+    pub fn log(&mut self, log: Option<PathBuf>, log_format: Format) -> &Scte35 {
+        self.log = log;
+        self.log_format = log_format;
+        self
+    }
+
+    pub fn metrics(&mut self, metrics: Metrics) -> &Scte35 {
+        self.metrics = metrics;
+        self
+    }
+
+    fn log_open(&self) -> Option<BufWriter<File>> {
+        let path = self.log.as_ref()?;
+
+        match File::create(path) {
+            Ok(file) => Some(BufWriter::new(file)),
+            Err(err) => {
+                warn!(
+                    "({}) [scte35] event log disabled (:path {} :error {})",
+                    self.url,
+                    path.display(),
+                    err
+                );
+                None
+            }
+        }
+    }
+
+    /// text: same fields as log line; json: one object per line
+    fn event(&self, state: &mut State, pid: u16, event: &str, text: &str, json: &str) {
+        let at = state
+            .origin
+            .map(|origin| origin.elapsed().as_secs_f64())
+            .unwrap_or_default();
+
+        if let Some(log) = state.log.as_mut() {
+            let _ = match self.log_format {
+                Format::Text => writeln!(
+                    log,
+                    "(:at {:.3}s :pid 0x{:04X} :event {} {})",
+                    at, pid, event, text
+                ),
+                Format::Json => writeln!(
+                    log,
+                    r#"{{"at":{:.3},"pid":{},"event":"{}",{}}}"#,
+                    at, pid, event, json
+                ),
+            };
+            let _ = log.flush();
+        }
+    }
+
+    fn section(&self, state: &mut State, pid: u16, buf: &[u8]) {
+        let program = {
+            let stream = match state.streams.get_mut(&pid) {
+                Some(stream) => stream,
+                None => return,
+            };
+
+            if crc32::mpeg2(buf) != 0 {
+                stream.crc_errors += 1;
+                debug!("({}) [scte35] CRC mismatch (:pid 0x{:04X})", self.url, pid);
+                return;
+            }
+            stream.program
+        };
+
+        let info = match SpliceInfo::parse(buf) {
+            Some(info) => info,
+            None => {
+                debug!(
+                    "({}) [scte35] malformed splice_info_section (:pid 0x{:04X})",
+                    self.url, pid
+                );
+                return;
+            }
+        };
+
+        let stream = state.streams.entry(pid).or_default();
+        match info.command {
+            // heartbeat
+            Command::Null => {
+                stream.nulls += 1;
+                trace!("({}) [scte35] splice-null (:pid 0x{:04X})", self.url, pid);
+                return;
+            }
+            Command::Insert(_) => stream.inserts += 1,
+            Command::TimeSignal(_) => stream.time_signals += 1,
+            _ => {}
+        }
+        stream.cues += 1;
+
+        let prog = state.programs.entry(program).or_default();
+        let lead = match (info.pts(), prog.video_pts) {
+            (Some(pts), Some(video_pts)) => Some(wrapping_diff(pts, video_pts, TS_MAX)),
+            _ => None,
+        };
+
+        // cancel drops scheduled splice of the same event
+        if let Command::Insert(insert) = &info.command {
+            if insert.cancel {
+                prog.pending
+                    .retain(|p| p.pid != pid || p.event_id != Some(insert.event_id));
+            }
+        }
+        // late cue has no splice point left to find
+        let late = lead.is_some_and(|lead| lead < 0);
+        if prog.video_pid.is_some() && !late && (info.pts().is_some() || info.immediate()) {
+            if prog.pending.len() >= PENDING_MAX {
+                let p = prog.pending.remove(0);
+                warn!(
+                    "({}) [scte35] too many pending cues; dropped (:pid 0x{:04X} :event-id {})",
+                    self.url,
+                    p.pid,
+                    p.event_id
+                        .map(|id| id.to_string())
+                        .unwrap_or_else(|| "~".to_string())
+                );
+            }
+            prog.pending.push(Pending {
+                pid,
+                event_id: info.event_id(),
+                pts: info.pts(),
+                picture: None,
+            });
+        }
+
+        let mut text = info.to_string();
+        if let Some(lead) = lead {
+            text.push_str(&format!(" :lead {:.3}ms", ms(lead)));
+        }
+        info!("({}) [scte35] cue (:pid 0x{:04X} {})", self.url, pid, text);
+
+        if let Some(lead) = lead.filter(|lead| *lead < 0) {
+            state.streams.entry(pid).or_default().late += 1;
+            warn!(
+                "({}) [scte35] late cue; splice time already passed (:pid 0x{:04X} :lead {:.3}ms)",
+                self.url,
+                pid,
+                ms(lead)
+            );
+        }
+
+        let json = format!(
+            r#"{},"lead_ms":{}"#,
+            info.json(),
+            lead.map(|lead| format!("{:.3}", ms(lead)))
+                .unwrap_or_else(|| "null".to_string())
+        );
+        self.event(state, pid, "cue", &text, &json);
+    }
+
+    /// video picture of program in decode order; splice point is the
+    /// earliest picture in presentation order at or after splice time,
+    /// final once decoding passed its PTS (later pictures present later)
+    fn picture(&self, state: &mut State, program: u16, frm: &Frame, pts: u64) {
+        let dts = frm.dts.unwrap_or(pts);
+        let kind = frm.picture.map(|picture| picture.kind);
+
+        let (reached, expired): (Vec<Pending>, Vec<Pending>) = {
+            let prog = state.programs.entry(program).or_default();
+            prog.video_pts = Some(pts);
+
+            for p in prog.pending.iter_mut() {
+                let after = p.pts.is_none_or(|at| wrapping_diff(pts, at, TS_MAX) >= 0);
+                let earlier = p
+                    .picture
+                    .is_none_or(|(at, _)| wrapping_diff(pts, at, TS_MAX) < 0);
+                if after && earlier {
+                    p.picture = Some((pts, kind));
+                }
+            }
+
+            let (reached, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut prog.pending)
+                .into_iter()
+                .partition(|p| {
+                    p.picture
+                        .is_some_and(|(at, _)| wrapping_diff(dts, at, TS_MAX) >= 0)
+                });
+            let (expired, pending) = pending.into_iter().partition(|p| {
+                p.pts
+                    .is_some_and(|at| wrapping_diff(dts, at, TS_MAX) > PENDING_EXPIRE)
+            });
+            prog.pending = pending;
+            (reached, expired)
+        };
+
+        for p in expired {
+            warn!(
+                "({}) [scte35] splice point not found (:pid 0x{:04X} :event-id {} :pts {})",
+                self.url,
+                p.pid,
+                p.event_id
+                    .map(|id| id.to_string())
+                    .unwrap_or_else(|| "~".to_string()),
+                p.pts.unwrap_or_default()
+            );
+        }
+
+        for p in reached {
+            let (pts, kind) = p.picture.unwrap_or((pts, kind));
+            let intra = matches!(kind, Some(PictureType::Idr) | Some(PictureType::I));
+            let picture = match kind {
+                Some(PictureType::Idr) => "idr",
+                Some(PictureType::I) => "i",
+                Some(PictureType::P) => "p",
+                Some(PictureType::B) => "b",
+                None => "~",
+            };
+            let offset = p.pts.map(|at| wrapping_diff(pts, at, TS_MAX));
+            let event_id = p
+                .event_id
+                .map(|id| id.to_string())
+                .unwrap_or_else(|| "~".to_string());
+
+            {
+                let stream = state.streams.entry(p.pid).or_default();
+                stream.splices += 1;
+                if kind.is_some() && !intra {
+                    stream.misaligned += 1;
+                }
+            }
+
+            let mut text = format!(
+                ":event-id {} :video-pid 0x{:04X} :video-pts {} :picture {}",
+                event_id, frm.pid, pts, picture
+            );
+            if let Some(offset) = offset {
+                text.push_str(&format!(" :offset {:.3}ms", ms(offset)));
+            }
+
+            if kind.is_some() && !intra {
+                warn!(
+                    "({}) [scte35] splice point not on intra picture (:pid 0x{:04X} {})",
+                    self.url, p.pid, text
+                );
+            } else {
+                info!(
+                    "({}) [scte35] splice point (:pid 0x{:04X} {})",
+                    self.url, p.pid, text
+                );
+            }
+
+            let json = format!(
+                r#""event_id":{},"video_pid":{},"video_pts":{},"picture":"{}","intra":{},"offset_ms":{}"#,
+                p.event_id
+                    .map(|id| id.to_string())
+                    .unwrap_or_else(|| "null".to_string()),
+                frm.pid,
+                pts,
+                picture,
+                intra,
+                offset
+                    .map(|offset| format!("{:.3}", ms(offset)))
+                    .unwrap_or_else(|| "null".to_string())
+            );
+            self.event(state, p.pid, "splice", &text, &json);
+        }
+    }
+
+    fn metrics_publish(&self) {
+        if !self.metrics_at.ready() {
+            return;
+        }
+
+        for (pid, stream) in self.state.borrow().streams.iter() {
+            self.metrics.set(
+                format!("scte35-0x{:04X}", pid),
+                format!(
+                    "(:cues {} :splice-inserts {} :time-signals {} :splice-nulls {} :crc-errors {} :late {} :splices {} :misaligned {})",
+                    stream.cues,
+                    stream.inserts,
+                    stream.time_signals,
+                    stream.nulls,
+                    stream.crc_errors,
+                    stream.late,
+                    stream.splices,
+                    stream.misaligned
+                ),
+            );
+        }
+    }
+}
+
+impl Consumer for Scte35 {
+    fn consume_trk(&self, trk: &Track) {
+        let mut state = self.state.borrow_mut();
+
+        if trk.codec() == "scte35" {
+            state.streams.entry(trk.pid).or_default().program = trk.program_number;
+        } else if trk.kind() == Kind::Video {
+            let prog = state.programs.entry(trk.program_number).or_default();
+            if prog.video_pid != Some(trk.pid) {
+                prog.video_pid = Some(trk.pid);
+                prog.video_pts = None;
+            }
+        }
+    }
+
+    fn consume_section(&self, pid: u16, section: &[u8]) {
+        if section.first() != Some(&TABLE_ID) {
+            return;
+        }
+
+        {
+            let mut state = self.state.borrow_mut();
+            let state = &mut *state;
+
+            if !state.streams.contains_key(&pid) {
+                return;
+            }
+            if state.origin.is_none() {
+                state.origin = Some(Instant::now());
+                state.log = self.log_open();
+            }
+
+            self.section(state, pid, section);
+        }
+
+        self.metrics_publish();
+    }
+
+    fn consume_frm(&self, frm: &Frame) {
+        let pts = match frm.pts {
+            Some(pts) => pts,
+            None => return,
+        };
+
+        {
+            let mut state = self.state.borrow_mut();
+            let state = &mut *state;
+
+            let program = state
+                .programs
+                .iter()
+                .find(|(_, prog)| prog.video_pid == Some(frm.pid))
+                .map(|(program, _)| *program);
+            // second field belongs to previous picture
+            if let Some(program) = program {
+                if !frm.picture.is_some_and(|picture| picture.second_field) {
+                    self.picture(state, program, frm, pts);
+                }
+            }
+        }
+
+        self.metrics_publish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// time_signal() at 10s
+    const TIME_SIGNAL: [u8; 5] = [0xFE, 0x00, 0x0D, 0xBB, 0xA0];
+
+    /// splice_info_section of time_signal() with CRC placeholder
+    fn section(descriptors: &[u8], loop_length: u16) -> Vec<u8> {
+        let mut body = vec![0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF];
+        // tier, splice_command_length, splice_command_type
+        body.extend_from_slice(&[0xFF, 0xF0, TIME_SIGNAL.len() as u8, 0x06]);
+        body.extend_from_slice(&TIME_SIGNAL);
+        body.extend_from_slice(&loop_length.to_be_bytes());
+        body.extend_from_slice(descriptors);
+        body.extend_from_slice(&[0; 4]);
+
+        let mut buf = vec![TABLE_ID, 0x30 | (body.len() >> 8) as u8, body.len() as u8];
+        buf.extend_from_slice(&body);
+        buf
+    }
+
+    /// segmentation_descriptor() of event 42, provider placement opportunity start
+    fn segmentation(upid: &[u8], upid_length: u8) -> Vec<u8> {
+        let mut data = vec![0x43, 0x55, 0x45, 0x49, 0x00, 0x00, 0x00, 0x2A, 0x7F, 0xBF];
+        data.extend_from_slice(&[0x09, upid_length]);
+        data.extend_from_slice(upid);
+        data.extend_from_slice(&[0x34, 0x01, 0x01]);
+
+        let mut buf = vec![TAG_SEGMENTATION, data.len() as u8];
+        buf.extend_from_slice(&data);
+        buf
+    }
+
+    #[test]
+    fn valid() {
+        let descriptors = segmentation(b"ABCD", 4);
+        let info = SpliceInfo::parse(&section(&descriptors, descriptors.len() as u16)).unwrap();
+
+        assert_eq!(info.command.name(), "time-signal");
+        assert_eq!(info.pts(), Some(900_000));
+        assert_eq!(info.segmentations.len(), 1);
+        assert_eq!(info.segmentations[0].event_id, 42);
+        assert_eq!(info.segmentations[0].type_id, 0x34);
+        assert_eq!(info.segmentations[0].upid, "ABCD");
+    }
+
+    #[test]
+    fn truncated() {
+        let descriptors = segmentation(b"ABCD", 4);
+        let buf = section(&descriptors, descriptors.len() as u16);
+
+        // CRC_32 is not read
+        for len in 0..buf.len() - 4 {
+            assert!(SpliceInfo::parse(&buf[..len]).is_none());
+        }
+    }
+
+    #[test]
+    fn length_overrun() {
+        let descriptors = segmentation(b"ABCD", 4);
+        assert!(SpliceInfo::parse(&section(&descriptors, 0x3FF)).is_none());
+
+        // upid_length past descriptor end drops segmentation only
+        let descriptors = segmentation(b"ABCD", 0xFF);
+        let info = SpliceInfo::parse(&section(&descriptors, descriptors.len() as u16)).unwrap();
+        assert_eq!(info.pts(), Some(900_000));
+        assert!(info.segmentations.is_empty());
+    }
+}
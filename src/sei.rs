@@ -0,0 +1,225 @@
+use std::fmt;
+
+use crate::bits::Bits;
+use crate::colour::{ContentLight, MasteringDisplay};
+use crate::probe::json_opt_str;
+
+pub const SEI_USER_DATA_REGISTERED: u32 = 4;
+const SEI_USER_DATA_UNREGISTERED: u32 = 5;
+const SEI_RECOVERY_POINT: u32 = 6;
+const SEI_MASTERING_DISPLAY: u32 = 137;
+const SEI_CONTENT_LIGHT_LEVEL: u32 = 144;
+const SEI_ALTERNATIVE_TRANSFER: u32 = 147;
+
+/// stream-level SEI of H.264 / H.265
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sei {
+    /// user_data_unregistered text e.g. x264 version
+    pub encoder: Option<String>,
+    /// ATSC A/53 cc_data in user_data_registered_itu_t_t35
+    pub captions: bool,
+    /// active format description
+    pub afd: Option<u8>,
+    /// recovery_point: recovery_frame_cnt (H.264), recovery_poc_cnt (H.265)
+    pub recovery_point: Option<i32>,
+
+    pub mastering_display: Option<MasteringDisplay>,
+    pub content_light: Option<ContentLight>,
+    /// preferred_transfer_characteristics
+    pub alternative_transfer: Option<u8>,
+    /// SMPTE ST 2094-40 dynamic metadata
+    pub hdr10plus: bool,
+}
+
+impl Sei {
+    /// sei_rbsp: H.264 7.3.2.3, H.265 7.3.2.4;
+    /// messages of sei_rbsp; codec is Track::codec;
+    /// true if any field changed
+    pub fn parse(&mut self, codec: &str, messages: &[(u32, &[u8])]) -> bool {
+        let mut changed = false;
+        for (payload_type, payload) in messages {
+            changed |= self.message(codec, *payload_type, payload);
+        }
+        changed
+    }
+
+    fn message(&mut self, codec: &str, payload_type: u32, payload: &[u8]) -> bool {
+        match payload_type {
+            SEI_USER_DATA_REGISTERED => self.user_data_registered(payload),
+            SEI_USER_DATA_UNREGISTERED if payload.len() > 16 && self.encoder.is_none() => {
+                self.encoder = encoder(&payload[16..]);
+                self.encoder.is_some()
+            }
+            SEI_RECOVERY_POINT => {
+                let mut r = Bits::new(payload);
+                let recovery_point = if codec == "h265" {
+                    r.se()
+                } else {
+                    r.ue().map(|v| v as i32)
+                };
+                set(&mut self.recovery_point, recovery_point)
+            }
+            SEI_MASTERING_DISPLAY => set(
+                &mut self.mastering_display,
+                MasteringDisplay::parse(payload),
+            ),
+            SEI_CONTENT_LIGHT_LEVEL => set(&mut self.content_light, ContentLight::parse(payload)),
+            SEI_ALTERNATIVE_TRANSFER => {
+                set(&mut self.alternative_transfer, payload.first().copied())
+            }
+            _ => false,
+        }
+    }
+
+    /// ITU-T T.35 country code USA (0xB5)
+    fn user_data_registered(&mut self, payload: &[u8]) -> bool {
+        if payload.len() < 6 || payload[0] != 0xB5 {
+            return false;
+        }
+
+        match (&payload[1..3], &payload[3..]) {
+            // ATSC
+            ([0x00, 0x31], data) if data.len() >= 5 => match &data[..4] {
+                // cc_data
+                b"GA94" if data[4] == 0x03 => set(&mut self.captions, true),
+                // afd_data: active_format_flag
+                b"DTG1" if data[4] & 0x40 != 0 && data.len() >= 6 => {
+                    set(&mut self.afd, Some(data[5] & 0x0F))
+                }
+                _ => false,
+            },
+            // Samsung: provider oriented code 1, application identifier 4
+            ([0x00, 0x3C], [0x00, 0x01, 0x04, ..]) => set(&mut self.hdr10plus, true),
+            _ => false,
+        }
+    }
+
+    pub fn json_fields(&self) -> String {
+        let opt = |v: Option<String>| v.unwrap_or_else(|| "null".to_string());
+
+        format!(
+            r#""captions":{},"afd":{},"recovery_point":{},"mastering_display":{},"max_cll":{},"max_fall":{},"alternative_transfer":{},"hdr10plus":{},"encoder":{}"#,
+            self.captions,
+            opt(self.afd.map(|v| v.to_string())),
+            opt(self.recovery_point.map(|v| v.to_string())),
+            json_opt_str(self.mastering_display.map(|v| v.to_string()).as_deref()),
+            opt(self.content_light.map(|v| v.max_cll.to_string())),
+            opt(self.content_light.map(|v| v.max_fall.to_string())),
+            opt(self.alternative_transfer.map(|v| v.to_string())),
+            self.hdr10plus,
+            json_opt_str(self.encoder.as_deref())
+        )
+    }
+}
+
+/// fields prefixed with space
+impl fmt::Display for Sei {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.captions {
+            write!(f, " :captions a53")?;
+        }
+        if let Some(afd) = self.afd {
+            write!(f, " :afd {}", afd)?;
+        }
+        if let Some(recovery_point) = self.recovery_point {
+            write!(f, " :recovery-point {}", recovery_point)?;
+        }
+        if let Some(mastering_display) = self.mastering_display.as_ref() {
+            write!(f, " :mastering-display {}", mastering_display)?;
+        }
+        if let Some(content_light) = self.content_light.as_ref() {
+            write!(
+                f,
+                " :max-cll {} :max-fall {}",
+                content_light.max_cll, content_light.max_fall
+            )?;
+        }
+        if let Some(alternative_transfer) = self.alternative_transfer {
+            write!(f, " :alternative-transfer {}", alternative_transfer)?;
+        }
+        if let Some(encoder) = self.encoder.as_ref() {
+            write!(f, r#" :encoder "{}""#, encoder)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// (payloadType, payload) of sei_message in sei_rbsp
+pub fn messages(rbsp: &[u8]) -> Vec<(u32, &[u8])> {
+    let mut messages = Vec::new();
+    let mut pos = 0;
+
+    // rbsp_trailing_bits
+    while pos + 1 < rbsp.len() {
+        let mut payload_type = 0u32;
+        while pos < rbsp.len() && rbsp[pos] == 0xFF {
+            payload_type += 255;
+            pos += 1;
+        }
+        let mut payload_size = 0usize;
+        if pos < rbsp.len() {
+            payload_type += u32::from(rbsp[pos]);
+            pos += 1;
+        }
+        while pos < rbsp.len() && rbsp[pos] == 0xFF {
+            payload_size += 255;
+            pos += 1;
+        }
+        if pos < rbsp.len() {
+            payload_size += usize::from(rbsp[pos]);
+            pos += 1;
+        }
+
+        let end = pos + payload_size;
+        if end > rbsp.len() {
+            break;
+        }
+        messages.push((payload_type, &rbsp[pos..end]));
+        pos = end;
+    }
+
+    messages
+}
+
+/// ATSC A/53 user_data_type_structure after country/provider code
+/// or MPEG-2 user_data start code: cc_data after "GA94" 0x03
+pub fn a53_cc_data(data: &[u8]) -> Option<&[u8]> {
+    match data {
+        [b'G', b'A', b'9', b'4', 0x03, cc_data @ ..] => Some(cc_data),
+        _ => None,
+    }
+}
+
+/// cc_data of user_data_registered_itu_t_t35 payload
+pub fn t35_cc_data(payload: &[u8]) -> Option<&[u8]> {
+    match payload {
+        [0xB5, 0x00, 0x31, data @ ..] => a53_cc_data(data),
+        _ => None,
+    }
+}
+
+/// printable prefix of user data text;
+/// x264/x265 options are cut off
+fn encoder(text: &[u8]) -> Option<String> {
+    let text: String = text
+        .iter()
+        .take_while(|b| b.is_ascii_graphic() || **b == b' ')
+        .map(|b| *b as char)
+        .collect();
+    let text = match text.find(" - H.26") {
+        Some(end) => text[..end].to_string(),
+        None => text.chars().take(64).collect(),
+    };
+
+    Some(text.trim().to_string()).filter(|text| !text.is_empty())
+}
+
+/// assign; true if value differs
+fn set<T: PartialEq>(field: &mut T, value: T) -> bool {
+    if *field == value {
+        return false;
+    }
+    *field = value;
+    true
+}
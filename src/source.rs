@@ -6,10 +6,11 @@ use std::time::Duration;
 use log::error;
 
 use crate::error::{Error, Result};
-use crate::filter::Filter;
+use crate::filter::{Consumer, Filter, Producer};
 use crate::input::Input;
 
 pub struct Source<I> {
+    /// entry point; moved to source thread on start
     filter: Filter,
 
     input: Arc<Mutex<I>>,
@@ -31,8 +32,14 @@ where
         }
     }
 
+    /// must be called before start
+    pub fn add_consumer(&mut self, consumer: Box<dyn Consumer>) {
+        self.filter.add_consumer(consumer)
+    }
+
     pub fn start(&mut self) -> Result<()> {
         let input = self.input.clone();
+        let filter = std::mem::take(&mut self.filter);
 
         #[inline(always)]
         fn fn_lock_map_err<I>(err: std::sync::PoisonError<std::sync::MutexGuard<'_, I>>) -> Error {
@@ -45,7 +52,7 @@ where
             }
 
             loop {
-                input.lock().map_err(fn_lock_map_err)?.read()?;
+                input.lock().map_err(fn_lock_map_err)?.read(&filter)?;
 
                 // thread::sleep(Duration::from_secs(1));
             }
@@ -0,0 +1,433 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use log::{info, trace, warn};
+use url::Url;
+
+use crate::clock::{Clock, Throttle};
+use crate::compression_standard::Subtitle;
+use crate::dvbsub::{self, SEGMENT_PAGE_COMPOSITION};
+use crate::filter::Consumer;
+use crate::frame::Frame;
+use crate::metrics::Metrics;
+use crate::packet::Packet;
+use crate::teletext;
+use crate::track::Track;
+
+/// subtitle service of a PID: signalled in PMT and/or found in stream
+pub struct Service {
+    /// teletext page e.g. 888 or DVB composition page id
+    pub page: String,
+    pub lang: Option<String>,
+    /// teletext_type / subtitling_type
+    pub kind: Option<&'static str>,
+    pub signalled: bool,
+    pub present: bool,
+}
+
+/// signalled services joined with pages found by Es (Track::subtitle);
+/// unsignalled teletext pages are listed only if flagged as subtitle
+pub fn services(trk: &Track) -> Vec<Service> {
+    let mut services = Vec::new();
+
+    match trk.codec() {
+        "teletext" => {
+            let pages = match trk.subtitle.as_ref() {
+                Some(Subtitle::Teletext(teletext)) => Some(teletext),
+                _ => None,
+            };
+
+            let signalled = teletext::Service::from_track(trk);
+            for service in signalled.iter() {
+                services.push(Service {
+                    page: service.number(),
+                    lang: Some(service.lang.clone()),
+                    kind: Some(service.kind_name()),
+                    signalled: true,
+                    present: pages
+                        .is_some_and(|pages| pages.contains(service.magazine, service.page)),
+                });
+            }
+            for ((magazine, page), subtitle) in pages.iter().flat_map(|pages| pages.pages.iter()) {
+                let known = signalled
+                    .iter()
+                    .any(|s| s.magazine == *magazine && s.page == *page);
+                if *subtitle && !known {
+                    services.push(Service {
+                        page: teletext::page_number(*magazine, *page),
+                        lang: None,
+                        kind: Some("subtitle"),
+                        signalled: false,
+                        present: true,
+                    });
+                }
+            }
+        }
+        "dvb-subtitle" => {
+            let pages = match trk.subtitle.as_ref() {
+                Some(Subtitle::Dvb(dvb)) => Some(dvb),
+                _ => None,
+            };
+
+            let signalled = dvbsub::Service::from_track(trk);
+            for service in signalled.iter() {
+                services.push(Service {
+                    page: service.composition_page.to_string(),
+                    lang: Some(service.lang.clone()),
+                    kind: Some(service.kind_name()),
+                    signalled: true,
+                    present: pages
+                        .is_some_and(|pages| pages.pages.contains(&service.composition_page)),
+                });
+            }
+            for page in pages.iter().flat_map(|pages| pages.pages.iter()) {
+                let known = signalled
+                    .iter()
+                    .any(|s| s.composition_page == *page || s.ancillary_page == *page);
+                if !known {
+                    services.push(Service {
+                        page: page.to_string(),
+                        lang: None,
+                        kind: None,
+                        signalled: false,
+                        present: true,
+                    });
+                }
+            }
+        }
+        _ => {}
+    }
+
+    services
+}
+
+/// teletext (magazine << 8 | page) or DVB page_id
+type Key = u16;
+
+struct Page {
+    label: String,
+    lang: Option<String>,
+    signalled: bool,
+
+    /// stream time page was last seen
+    last_at: Option<Duration>,
+    alive: bool,
+    /// signalled but not found reported
+    missing: bool,
+}
+
+impl Page {
+    fn signalled(label: String, lang: String) -> Page {
+        Page {
+            label,
+            lang: Some(lang),
+            signalled: true,
+            last_at: None,
+            alive: false,
+            missing: false,
+        }
+    }
+
+    fn lang(&self) -> &str {
+        self.lang.as_deref().unwrap_or("~")
+    }
+}
+
+/// per teletext / DVB subtitle PID
+struct Stream {
+    teletext: bool,
+    /// stream time track is known since; signalled pages are expected within timeout
+    started_at: Duration,
+    pages: BTreeMap<Key, Page>,
+    /// DVB ancillary pages shared by services
+    ancillary: Vec<u16>,
+}
+
+impl Stream {
+    fn new(trk: &Track, now: Duration) -> Stream {
+        let mut pages = BTreeMap::new();
+        let mut ancillary = Vec::new();
+
+        let teletext = trk.codec() == "teletext";
+        if teletext {
+            for service in teletext::Service::from_track(trk) {
+                pages.insert(
+                    (u16::from(service.magazine) << 8) | u16::from(service.page),
+                    Page::signalled(service.number(), service.lang),
+                );
+            }
+        } else {
+            for service in dvbsub::Service::from_track(trk) {
+                if service.ancillary_page != service.composition_page {
+                    ancillary.push(service.ancillary_page);
+                }
+                pages.insert(
+                    service.composition_page,
+                    Page::signalled(service.composition_page.to_string(), service.lang),
+                );
+            }
+        }
+
+        Stream {
+            teletext,
+            started_at: now,
+            pages,
+            ancillary,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    clock: Clock,
+    /// stream time of current packet
+    now: Duration,
+
+    streams: BTreeMap<u16, Stream>,
+}
+
+/// teletext and DVB subtitle services actually present and alive
+/// versus only signalled in PMT.
+///
+/// service is alive while its page is seen at least once per timeout
+/// of stream time
+pub struct Subtitles {
+    url: Url,
+    timeout: Duration,
+
+    state: RefCell<State>,
+
+    metrics: Metrics,
+    metrics_at: Throttle,
+}
+
+impl Subtitles {
+    pub fn new(url: Url) -> Subtitles {
+        Subtitles {
+            url,
+            timeout: Duration::from_secs(30),
+            state: Default::default(),
+            metrics: Default::default(),
+            metrics_at: Default::default(),
+        }
+    }
+
+    /// service is lost when not seen for
+    pub fn timeout(&mut self, timeout: Duration) -> &Subtitles {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn metrics(&mut self, metrics: Metrics) -> &Subtitles {
+        self.metrics = metrics;
+        self
+    }
+
+    /// page keys found in PES
+    fn keys(&self, stream: &Stream, frm: &Frame) -> Vec<(Key, String)> {
+        if stream.teletext {
+            // other teletext pages are not subtitles
+            teletext::headers(&frm.data)
+                .into_iter()
+                .map(|h| ((u16::from(h.magazine) << 8) | u16::from(h.page), h))
+                .filter(|(key, h)| h.subtitle || stream.pages.contains_key(key))
+                .map(|(key, h)| (key, teletext::page_number(h.magazine, h.page)))
+                .collect()
+        } else {
+            let segments = dvbsub::segments(&frm.data).unwrap_or_default();
+            for segment in segments.iter() {
+                if segment.kind == SEGMENT_PAGE_COMPOSITION && segment.data.len() >= 2 {
+                    trace!(
+                        "({}) [subtitle] page composition (:pid 0x{:04X} :page {} :state {} :regions {})",
+                        self.url,
+                        frm.pid,
+                        segment.page_id,
+                        dvbsub::page_state((segment.data[1] >> 2) & 0x03),
+                        (segment.data.len() - 2) / 6
+                    );
+                }
+            }
+            segments
+                .iter()
+                .filter(|segment| {
+                    stream.pages.contains_key(&segment.page_id)
+                        || !stream.ancillary.contains(&segment.page_id)
+                })
+                .map(|segment| (segment.page_id, segment.page_id.to_string()))
+                .collect()
+        }
+    }
+
+    /// timeouts by stream time; then metrics
+    fn check(&self) {
+        {
+            let mut state = self.state.borrow_mut();
+            let now = state.now;
+            for (pid, stream) in state.streams.iter_mut() {
+                self.timeouts(*pid, stream, now);
+            }
+        }
+
+        self.metrics_publish();
+    }
+
+    fn timeouts(&self, pid: u16, stream: &mut Stream, now: Duration) {
+        let started_at = stream.started_at;
+
+        for page in stream.pages.values_mut() {
+            match page.last_at {
+                Some(last_at) if page.alive && now.saturating_sub(last_at) > self.timeout => {
+                    page.alive = false;
+                    warn!(
+                        "({}) [subtitle] service lost (:pid 0x{:04X} :page {} :lang {} :idle {:.3}s)",
+                        self.url,
+                        pid,
+                        page.label,
+                        page.lang(),
+                        now.saturating_sub(last_at).as_secs_f64()
+                    );
+                }
+                None if page.signalled
+                    && !page.missing
+                    && now.saturating_sub(started_at) > self.timeout =>
+                {
+                    page.missing = true;
+                    warn!(
+                        "({}) [subtitle] signalled service not present (:pid 0x{:04X} :page {} :lang {})",
+                        self.url,
+                        pid,
+                        page.label,
+                        page.lang()
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn metrics_publish(&self) {
+        if !self.metrics_at.ready() {
+            return;
+        }
+
+        for (pid, stream) in self.state.borrow().streams.iter() {
+            let count = |f: &dyn Fn(&Page) -> bool| stream.pages.values().filter(|p| f(p)).count();
+            let alive: Vec<String> = stream
+                .pages
+                .values()
+                .filter(|page| page.alive)
+                .map(|page| match page.lang.as_ref() {
+                    Some(lang) => format!("{}/{}", page.label, lang),
+                    None => page.label.clone(),
+                })
+                .collect();
+
+            self.metrics.set(
+                format!("subtitle-0x{:04X}", pid),
+                format!(
+                    "(:signalled {} :present {} :alive {} :pages {})",
+                    count(&|page| page.signalled),
+                    count(&|page| page.last_at.is_some()),
+                    alive.len(),
+                    if alive.is_empty() {
+                        "~".to_string()
+                    } else {
+                        alive.join(",")
+                    }
+                ),
+            );
+        }
+    }
+}
+
+impl Consumer for Subtitles {
+    fn consume_trk(&self, trk: &Track) {
+        let mut state = self.state.borrow_mut();
+        let now = state.now;
+        let streams = &mut state.streams;
+
+        match trk.codec() {
+            "teletext" | "dvb-subtitle" => {
+                let mut stream = Stream::new(trk, now);
+                // keep liveness of pages still signalled or already seen
+                if let Some(old) = streams.remove(&trk.pid) {
+                    if old.teletext == stream.teletext {
+                        stream.started_at = old.started_at;
+                        for (key, page) in old.pages {
+                            match stream.pages.get_mut(&key) {
+                                Some(new) => {
+                                    new.last_at = page.last_at;
+                                    new.alive = page.alive;
+                                    new.missing = page.missing;
+                                }
+                                None if page.last_at.is_some() => {
+                                    stream.pages.insert(
+                                        key,
+                                        Page {
+                                            signalled: false,
+                                            ..page
+                                        },
+                                    );
+                                }
+                                None => {}
+                            }
+                        }
+                    }
+                }
+                streams.insert(trk.pid, stream);
+            }
+            _ => {
+                streams.remove(&trk.pid);
+            }
+        }
+    }
+
+    fn consume_pkt_raw(&self, pkt_raw: &[u8]) {
+        if let Ok(pkt) = ts::Packet::new(pkt_raw) {
+            let mut state = self.state.borrow_mut();
+            state.now = Duration::from_nanos(state.clock.update(&pkt, Instant::now()));
+        }
+    }
+
+    fn consume_pkt(&self, _: &Packet) {
+        self.check();
+    }
+
+    fn consume_frm(&self, frm: &Frame) {
+        {
+            let mut state = self.state.borrow_mut();
+            let now = state.now;
+            let stream = match state.streams.get_mut(&frm.pid) {
+                Some(stream) => stream,
+                None => return,
+            };
+
+            for (key, label) in self.keys(stream, frm) {
+                let page = stream.pages.entry(key).or_insert_with(|| Page {
+                    label,
+                    lang: None,
+                    signalled: false,
+                    last_at: None,
+                    alive: false,
+                    missing: false,
+                });
+                page.last_at = Some(now);
+
+                if !page.alive {
+                    page.alive = true;
+                    info!(
+                        "({}) [subtitle] service alive (:pid 0x{:04X} :page {} :lang {} :signalled {})",
+                        self.url,
+                        frm.pid,
+                        page.label,
+                        page.lang(),
+                        page.signalled
+                    );
+                }
+            }
+        }
+
+        self.check();
+    }
+}
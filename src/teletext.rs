@@ -0,0 +1,223 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::probe::json_str;
+use crate::track::Track;
+
+/// teletext_descriptor
+const TAG_TELETEXT: u8 = 0x56;
+
+/// EBU data in PES_data_field
+const DATA_IDENTIFIER_MIN: u8 = 0x10;
+const DATA_IDENTIFIER_MAX: u8 = 0x1F;
+
+const DATA_UNIT_TELETEXT: u8 = 0x02;
+const DATA_UNIT_SUBTITLE: u8 = 0x03;
+const DATA_UNIT_SZ: usize = 44;
+
+const FRAMING_CODE: u8 = 0xE4;
+
+/// page number of time filling header
+const PAGE_FILLER: u8 = 0xFF;
+
+/// Hamming 8/4 codewords by value; bit 0 is first transmitted bit
+const HAMMING_8_4: [u8; 16] = [
+    0x15, 0x02, 0x49, 0x5E, 0x64, 0x73, 0x38, 0x2F, 0xD0, 0xC7, 0x8C, 0x9B, 0xA1, 0xB6, 0xFD, 0xEA,
+];
+
+/// ETS 300 706 8.2; single bit errors are corrected
+fn hamming_8_4(b: u8) -> Option<u8> {
+    HAMMING_8_4
+        .iter()
+        .position(|code| (code ^ b).count_ones() <= 1)
+        .map(|v| v as u8)
+}
+
+/// page as shown on screen e.g. 888
+pub fn page_number(magazine: u8, page: u8) -> String {
+    format!("{}{:02X}", magazine, page)
+}
+
+/// magazine 0 is 8
+fn magazine(v: u8) -> u8 {
+    if v == 0 {
+        8
+    } else {
+        v
+    }
+}
+
+/// teletext_descriptor entry
+#[derive(Clone, Debug, PartialEq)]
+pub struct Service {
+    pub lang: String,
+    pub kind: u8,
+    /// 1..8
+    pub magazine: u8,
+    /// BCD
+    pub page: u8,
+}
+
+impl Service {
+    /// signalled in PMT
+    pub fn from_track(trk: &Track) -> Vec<Service> {
+        let mut services = Vec::new();
+
+        for (_, data) in trk.descriptors().filter(|(tag, _)| *tag == TAG_TELETEXT) {
+            for entry in data.chunks_exact(5) {
+                services.push(Service {
+                    lang: String::from_utf8_lossy(&entry[..3]).into_owned(),
+                    kind: entry[3] >> 3,
+                    magazine: magazine(entry[3] & 0x07),
+                    page: entry[4],
+                });
+            }
+        }
+
+        services
+    }
+
+    pub fn kind_name(&self) -> &'static str {
+        match self.kind {
+            0x01 => "initial",
+            0x02 => "subtitle",
+            0x03 => "additional-info",
+            0x04 => "schedule",
+            0x05 => "subtitle-hard-of-hearing",
+            _ => "reserved",
+        }
+    }
+
+    pub fn number(&self) -> String {
+        page_number(self.magazine, self.page)
+    }
+}
+
+/// page header (packet X/0)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Header {
+    /// 1..8
+    pub magazine: u8,
+    pub page: u8,
+    /// C6
+    pub subtitle: bool,
+    /// C12-C14 national option character subset
+    pub charset: u8,
+}
+
+/// EN 300 472 teletext page headers in PES_data_field
+pub fn headers(buf: &[u8]) -> Vec<Header> {
+    let mut headers = Vec::new();
+
+    match buf.first() {
+        Some(id) if (DATA_IDENTIFIER_MIN..=DATA_IDENTIFIER_MAX).contains(id) => {}
+        _ => return headers,
+    }
+
+    let mut pos = 1;
+    while pos + 2 <= buf.len() {
+        let (id, len) = (buf[pos], usize::from(buf[pos + 1]));
+        let unit = match buf.get(pos + 2..pos + 2 + len) {
+            Some(unit) => unit,
+            None => break,
+        };
+        pos += 2 + len;
+
+        if (id != DATA_UNIT_TELETEXT && id != DATA_UNIT_SUBTITLE) || len != DATA_UNIT_SZ {
+            continue;
+        }
+        // field_parity/line_offset, framing_code, address, data_block
+        if unit[1] != FRAMING_CODE {
+            continue;
+        }
+        if let Some(header) = header(&unit[2..]) {
+            headers.push(header);
+        }
+    }
+
+    headers
+}
+
+/// bytes are transmitted LSB first
+fn header(packet: &[u8]) -> Option<Header> {
+    let ham = |i: usize| packet.get(i).and_then(|b| hamming_8_4(b.reverse_bits()));
+
+    let address = ham(0)? | (ham(1)? << 4);
+    let packet_number = address >> 3;
+    if packet_number != 0 {
+        return None;
+    }
+
+    let page = ham(2)? | (ham(3)? << 4);
+    if page == PAGE_FILLER {
+        return None;
+    }
+
+    Some(Header {
+        magazine: magazine(address & 0x07),
+        page,
+        subtitle: ham(7)? & 0x08 != 0,
+        charset: ham(9)? >> 1,
+    })
+}
+
+/// pages seen in elementary stream
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Teletext {
+    /// (magazine, page) to subtitle flag
+    pub pages: BTreeMap<(u8, u8), bool>,
+}
+
+impl Teletext {
+    /// true if new page found
+    pub fn push(&mut self, buf: &[u8]) -> bool {
+        let mut changed = false;
+        for header in headers(buf) {
+            let key = (header.magazine, header.page);
+            if self.pages.get(&key) != Some(&header.subtitle) {
+                self.pages.insert(key, header.subtitle);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    pub fn contains(&self, magazine: u8, page: u8) -> bool {
+        self.pages.contains_key(&(magazine, page))
+    }
+
+    pub fn json(&self) -> String {
+        let pages: Vec<String> = self
+            .pages
+            .iter()
+            .map(|((magazine, page), subtitle)| {
+                format!(
+                    r#"{{"page":{},"subtitle":{}}}"#,
+                    json_str(&page_number(*magazine, *page)),
+                    subtitle
+                )
+            })
+            .collect();
+
+        format!(r#"{{"pages":[{}]}}"#, pages.join(","))
+    }
+}
+
+impl fmt::Display for Teletext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // full service carries hundreds of pages
+        let subtitles: Vec<String> = self
+            .pages
+            .iter()
+            .filter(|(_, subtitle)| **subtitle)
+            .map(|((magazine, page), _)| page_number(*magazine, *page))
+            .collect();
+
+        write!(f, ":pages {}", self.pages.len())?;
+        if !subtitles.is_empty() {
+            write!(f, " :subtitle-pages {}", subtitles.join(","))?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1536 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+use url::Url;
+
+use crate::clock::{discontinuity, Clock, Throttle, PCR_MAX};
+use crate::crc32;
+use crate::filter::Consumer;
+use crate::metrics::Metrics;
+use crate::packet::Packet;
+use crate::psi::{self, Section};
+use crate::track::{Kind, Track};
+
+/// SI table with repetition limit
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Table {
+    Nit,
+    NitOther,
+    Sdt,
+    SdtOther,
+    Bat,
+    EitPf,
+    EitPfOther,
+    EitSched,
+    EitSchedOther,
+    Tdt,
+    Tot,
+
+    // ATSC PSIP
+    Mgt,
+    Tvct,
+    Cvct,
+    Stt,
+}
+
+impl Table {
+    pub const ALL: &'static [Table] = &[
+        Table::Nit,
+        Table::NitOther,
+        Table::Sdt,
+        Table::SdtOther,
+        Table::Bat,
+        Table::EitPf,
+        Table::EitPfOther,
+        Table::EitSched,
+        Table::EitSchedOther,
+        Table::Tdt,
+        Table::Tot,
+        Table::Mgt,
+        Table::Tvct,
+        Table::Cvct,
+        Table::Stt,
+    ];
+
+    fn from_section(pid: u16, table_id: u8) -> Option<Table> {
+        let table = match (pid, table_id) {
+            (psi::PID_NIT, psi::TABLE_ID_NIT) => Table::Nit,
+            (psi::PID_NIT, psi::TABLE_ID_NIT_OTHER) => Table::NitOther,
+            (psi::PID_SDT, psi::TABLE_ID_SDT) => Table::Sdt,
+            (psi::PID_SDT, psi::TABLE_ID_SDT_OTHER) => Table::SdtOther,
+            (psi::PID_SDT, psi::TABLE_ID_BAT) => Table::Bat,
+            (psi::PID_EIT, psi::TABLE_ID_EIT_PF) => Table::EitPf,
+            (psi::PID_EIT, psi::TABLE_ID_EIT_PF_OTHER) => Table::EitPfOther,
+            (psi::PID_EIT, 0x50..=0x5F) => Table::EitSched,
+            (psi::PID_EIT, 0x60..=0x6F) => Table::EitSchedOther,
+            (psi::PID_TDT, psi::TABLE_ID_TDT) => Table::Tdt,
+            (psi::PID_TDT, psi::TABLE_ID_TOT) => Table::Tot,
+            (psi::PID_PSIP, psi::TABLE_ID_MGT) => Table::Mgt,
+            (psi::PID_PSIP, psi::TABLE_ID_TVCT) => Table::Tvct,
+            (psi::PID_PSIP, psi::TABLE_ID_CVCT) => Table::Cvct,
+            (psi::PID_PSIP, psi::TABLE_ID_STT) => Table::Stt,
+            _ => return None,
+        };
+        Some(table)
+    }
+
+    fn pid(self) -> u16 {
+        match self {
+            Table::Nit | Table::NitOther => psi::PID_NIT,
+            Table::Sdt | Table::SdtOther | Table::Bat => psi::PID_SDT,
+            Table::EitPf | Table::EitPfOther | Table::EitSched | Table::EitSchedOther => {
+                psi::PID_EIT
+            }
+            Table::Tdt | Table::Tot => psi::PID_TDT,
+            Table::Mgt | Table::Tvct | Table::Cvct | Table::Stt => psi::PID_PSIP,
+        }
+    }
+
+    /// dedicated indicator for table absence
+    fn check(self) -> Option<Check> {
+        match self {
+            Table::Nit => Some(Check::NitActualError),
+            Table::NitOther => Some(Check::NitOtherError),
+            Table::Sdt => Some(Check::SdtActualError),
+            Table::SdtOther => Some(Check::SdtOtherError),
+            Table::EitPf => Some(Check::EitActualError),
+            Table::EitPfOther => Some(Check::EitOtherError),
+            Table::Tdt => Some(Check::TdtError),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Table::Nit => "nit",
+            Table::NitOther => "nit-other",
+            Table::Sdt => "sdt",
+            Table::SdtOther => "sdt-other",
+            Table::Bat => "bat",
+            Table::EitPf => "eit-pf",
+            Table::EitPfOther => "eit-pf-other",
+            Table::EitSched => "eit-sched",
+            Table::EitSchedOther => "eit-sched-other",
+            Table::Tdt => "tdt",
+            Table::Tot => "tot",
+            Table::Mgt => "mgt",
+            Table::Tvct => "tvct",
+            Table::Cvct => "cvct",
+            Table::Stt => "stt",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Table {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Table::ALL
+            .iter()
+            .find(|table| table.to_string() == s)
+            .cloned()
+            .ok_or(())
+    }
+}
+
+/// maximum interval between sections of a table
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Repetition {
+    pub max: Duration,
+    /// absence is an error from start;
+    /// otherwise checked once table occurred
+    pub required: bool,
+}
+
+/// SI repetition-rate profile
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Profile {
+    /// ETSI TS 101 211 / TR 101 290
+    Dvb,
+    /// ATSC A/65 PSIP
+    Atsc,
+    /// only tables given per table
+    Custom,
+}
+
+impl Profile {
+    pub fn repetition(self, table: Table) -> Option<Repetition> {
+        let (max, required) = match (self, table) {
+            (Profile::Dvb, Table::Nit) => (10_000, true),
+            (Profile::Dvb, Table::NitOther) => (10_000, false),
+            (Profile::Dvb, Table::Sdt) => (2_000, true),
+            (Profile::Dvb, Table::SdtOther) => (10_000, false),
+            (Profile::Dvb, Table::Bat) => (10_000, false),
+            (Profile::Dvb, Table::EitPf) => (2_000, true),
+            (Profile::Dvb, Table::EitPfOther) => (10_000, false),
+            (Profile::Dvb, Table::EitSched) => (10_000, false),
+            (Profile::Dvb, Table::EitSchedOther) => (10_000, false),
+            (Profile::Dvb, Table::Tdt) => (30_000, true),
+            (Profile::Dvb, Table::Tot) => (30_000, false),
+
+            (Profile::Atsc, Table::Mgt) => (150, true),
+            (Profile::Atsc, Table::Tvct) => (400, false),
+            (Profile::Atsc, Table::Cvct) => (400, false),
+            (Profile::Atsc, Table::Stt) => (1_000, true),
+
+            _ => return None,
+        };
+
+        Some(Repetition {
+            max: Duration::from_millis(max),
+            required,
+        })
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Profile::Dvb => write!(f, "dvb"),
+            Profile::Atsc => write!(f, "atsc"),
+            Profile::Custom => write!(f, "custom"),
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dvb" => Ok(Profile::Dvb),
+            "atsc" => Ok(Profile::Atsc),
+            "custom" => Ok(Profile::Custom),
+            _ => Err(()),
+        }
+    }
+}
+
+/// parse per table maximum intervals e.g. "nit=10000,sdt=2000,bat=off";
+/// milliseconds, "off" or 0 disables the table
+pub fn si_repetition_parse<S: AsRef<str>>(s: S) -> Option<BTreeMap<Table, Option<Duration>>> {
+    let mut tables = BTreeMap::new();
+    for kv in s.as_ref().split(',') {
+        let (table, value) = kv.split_once('=')?;
+        let table = table.trim().parse::<Table>().ok()?;
+        let max = match value.trim() {
+            "off" | "0" => None,
+            ms => Some(
+                ms.parse::<f64>()
+                    .ok()
+                    .filter(|ms| *ms > 0.0)
+                    .and_then(|ms| Duration::try_from_secs_f64(ms / 1000.0).ok())?,
+            ),
+        };
+        tables.insert(table, max);
+    }
+
+    Some(tables)
+}
+
+/// thresholds operators commonly deviate from;
+/// defaults are TR 101 290 values
+#[derive(Clone, Debug, PartialEq)]
+pub struct Opts {
+    /// 1.6: referenced PID absence
+    pub pid_interval_max: Duration,
+    /// 2.3.a: PCR repetition
+    pub pcr_interval_max: Duration,
+    /// 2.3.b: PCR delta without discontinuity_indicator
+    pub pcr_discontinuity_max: Duration,
+    /// 2.4: PCR inaccuracy
+    pub pcr_accuracy_max: Duration,
+    /// 2.5: PTS repetition
+    pub pts_interval_max: Duration,
+
+    /// priority 3 repetition limits
+    pub si_profile: Profile,
+    /// per table overrides; listed tables are required,
+    /// None disables
+    pub si_repetition: BTreeMap<Table, Option<Duration>>,
+}
+
+impl Opts {
+    pub fn repetition(&self, table: Table) -> Option<Repetition> {
+        match self.si_repetition.get(&table) {
+            Some(max) => max.map(|max| Repetition {
+                max,
+                required: true,
+            }),
+            None => self.si_profile.repetition(table),
+        }
+    }
+}
+
+impl Default for Opts {
+    fn default() -> Self {
+        Opts {
+            pid_interval_max: Duration::from_secs(5),
+            pcr_interval_max: Duration::from_millis(40),
+            pcr_discontinuity_max: Duration::from_millis(100),
+            pcr_accuracy_max: Duration::from_nanos(500),
+            pts_interval_max: Duration::from_millis(700),
+            si_profile: Profile::Dvb,
+            si_repetition: BTreeMap::new(),
+        }
+    }
+}
+
+impl fmt::Display for Opts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ms = |v: Duration| v.as_secs_f64() * 1000.0;
+
+        write!(
+            f,
+            "(:pid-interval-max {}ms :pcr-interval-max {}ms :pcr-discontinuity-max {}ms :pcr-accuracy-max {}ns :pts-interval-max {}ms :si-profile {}",
+            ms(self.pid_interval_max),
+            ms(self.pcr_interval_max),
+            ms(self.pcr_discontinuity_max),
+            self.pcr_accuracy_max.as_nanos(),
+            ms(self.pts_interval_max),
+            self.si_profile
+        )?;
+        for (table, max) in self.si_repetition.iter() {
+            match max {
+                Some(max) => write!(f, " :{} {}ms", table, ms(*max))?,
+                None => write!(f, " :{} off", table)?,
+            }
+        }
+        write!(f, ")")
+    }
+}
+
+/// ETSI TR 101 290 indicator
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Check {
+    // priority 1
+    TsSyncLoss,
+    SyncByteError,
+    PatError2,
+    ContinuityCountError,
+    PmtError2,
+    PidError,
+
+    // priority 2
+    TransportError,
+    CrcError,
+    PcrRepetitionError,
+    PcrDiscontinuityIndicatorError,
+    PcrAccuracyError,
+    PtsError,
+    CatError,
+
+    // priority 3
+    NitActualError,
+    NitOtherError,
+    SiRepetitionError,
+    UnreferencedPid,
+    SdtActualError,
+    SdtOtherError,
+    EitActualError,
+    EitOtherError,
+    RstError,
+    TdtError,
+}
+
+impl Check {
+    /// TR 101 290 section number
+    pub fn id(self) -> &'static str {
+        match self {
+            Check::TsSyncLoss => "1.1",
+            Check::SyncByteError => "1.2",
+            Check::PatError2 => "1.3.a",
+            Check::ContinuityCountError => "1.4",
+            Check::PmtError2 => "1.5.a",
+            Check::PidError => "1.6",
+            Check::TransportError => "2.1",
+            Check::CrcError => "2.2",
+            Check::PcrRepetitionError => "2.3.a",
+            Check::PcrDiscontinuityIndicatorError => "2.3.b",
+            Check::PcrAccuracyError => "2.4",
+            Check::PtsError => "2.5",
+            Check::CatError => "2.6",
+            Check::NitActualError => "3.1.a",
+            Check::NitOtherError => "3.1.b",
+            Check::SiRepetitionError => "3.2",
+            Check::UnreferencedPid => "3.4.a",
+            Check::SdtActualError => "3.5.a",
+            Check::SdtOtherError => "3.5.b",
+            Check::EitActualError => "3.6.a",
+            Check::EitOtherError => "3.6.b",
+            Check::RstError => "3.7",
+            Check::TdtError => "3.8",
+        }
+    }
+
+    /// metrics key suffix
+    pub fn key(self) -> &'static str {
+        match self {
+            Check::TsSyncLoss => "ts-sync-loss",
+            Check::SyncByteError => "sync-byte-error",
+            Check::PatError2 => "pat-error-2",
+            Check::ContinuityCountError => "continuity-count-error",
+            Check::PmtError2 => "pmt-error-2",
+            Check::PidError => "pid-error",
+            Check::TransportError => "transport-error",
+            Check::CrcError => "crc-error",
+            Check::PcrRepetitionError => "pcr-repetition-error",
+            Check::PcrDiscontinuityIndicatorError => "pcr-discontinuity-indicator-error",
+            Check::PcrAccuracyError => "pcr-accuracy-error",
+            Check::PtsError => "pts-error",
+            Check::CatError => "cat-error",
+            Check::NitActualError => "nit-actual-error",
+            Check::NitOtherError => "nit-other-error",
+            Check::SiRepetitionError => "si-repetition-error",
+            Check::UnreferencedPid => "unreferenced-pid",
+            Check::SdtActualError => "sdt-actual-error",
+            Check::SdtOtherError => "sdt-other-error",
+            Check::EitActualError => "eit-actual-error",
+            Check::EitOtherError => "eit-other-error",
+            Check::RstError => "rst-error",
+            Check::TdtError => "tdt-error",
+        }
+    }
+
+    pub fn priority(self) -> u8 {
+        match self {
+            Check::TsSyncLoss
+            | Check::SyncByteError
+            | Check::PatError2
+            | Check::ContinuityCountError
+            | Check::PmtError2
+            | Check::PidError => 1,
+            Check::TransportError
+            | Check::CrcError
+            | Check::PcrRepetitionError
+            | Check::PcrDiscontinuityIndicatorError
+            | Check::PcrAccuracyError
+            | Check::PtsError
+            | Check::CatError => 2,
+            _ => 3,
+        }
+    }
+
+    pub const ALL: &'static [Check] = &[
+        Check::TsSyncLoss,
+        Check::SyncByteError,
+        Check::PatError2,
+        Check::ContinuityCountError,
+        Check::PmtError2,
+        Check::PidError,
+        Check::TransportError,
+        Check::CrcError,
+        Check::PcrRepetitionError,
+        Check::PcrDiscontinuityIndicatorError,
+        Check::PcrAccuracyError,
+        Check::PtsError,
+        Check::CatError,
+        Check::NitActualError,
+        Check::NitOtherError,
+        Check::SiRepetitionError,
+        Check::UnreferencedPid,
+        Check::SdtActualError,
+        Check::SdtOtherError,
+        Check::EitActualError,
+        Check::EitOtherError,
+        Check::RstError,
+        Check::TdtError,
+    ];
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Check::TsSyncLoss => "TS_sync_loss",
+            Check::SyncByteError => "Sync_byte_error",
+            Check::PatError2 => "PAT_error_2",
+            Check::ContinuityCountError => "Continuity_count_error",
+            Check::PmtError2 => "PMT_error_2",
+            Check::PidError => "PID_error",
+            Check::TransportError => "Transport_error",
+            Check::CrcError => "CRC_error",
+            Check::PcrRepetitionError => "PCR_repetition_error",
+            Check::PcrDiscontinuityIndicatorError => "PCR_discontinuity_indicator_error",
+            Check::PcrAccuracyError => "PCR_accuracy_error",
+            Check::PtsError => "PTS_error",
+            Check::CatError => "CAT_error",
+            Check::NitActualError => "NIT_actual_error",
+            Check::NitOtherError => "NIT_other_error",
+            Check::SiRepetitionError => "SI_repetition_error",
+            Check::UnreferencedPid => "Unreferenced_PID",
+            Check::SdtActualError => "SDT_actual_error",
+            Check::SdtOtherError => "SDT_other_error",
+            Check::EitActualError => "EIT_actual_error",
+            Check::EitOtherError => "EIT_other_error",
+            Check::RstError => "RST_error",
+            Check::TdtError => "TDT_error",
+        };
+        write!(f, "{} {}", self.id(), name)
+    }
+}
+
+/// occurrences of one check; time is stream time since first packet
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Counter {
+    pub count: u64,
+    pub first: Option<Duration>,
+    pub last: Option<Duration>,
+
+    /// last affected
+    pub pid: Option<u16>,
+    pub program: Option<u16>,
+}
+
+impl fmt::Display for Counter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(:count {}", self.count)?;
+        if let Some(first) = self.first {
+            write!(f, " :first {:.3}s", first.as_secs_f64())?;
+        }
+        if let Some(last) = self.last {
+            write!(f, " :last {:.3}s", last.as_secs_f64())?;
+        }
+        if let Some(pid) = self.pid {
+            write!(f, " :pid 0x{:04X}", pid)?;
+        }
+        if let Some(program) = self.program {
+            write!(f, " :program {}", program)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// per PID continuity state
+#[derive(Clone, Copy, Default)]
+struct Cc {
+    last: Option<u8>,
+    /// repeated packets with same CC
+    dups: u8,
+}
+
+/// per PCR PID timing state
+#[derive(Clone, Copy, Default)]
+struct Pcr {
+    /// last PCR (27MHz), its packet index and stream time
+    last: Option<(u64, u64, Duration)>,
+    /// PCR and packet index since last discontinuity
+    origin: Option<(u64, u64)>,
+    /// 27MHz ticks per packet (constant TS rate) by origin
+    pkt_ticks: Option<f64>,
+}
+
+#[derive(Default)]
+struct State {
+    clock: Clock,
+    /// stream time of current packet
+    now: Duration,
+    /// packets since start
+    packets: u64,
+
+    // 1.1 / 1.2
+    sync: bool,
+    sync_good: u8,
+    sync_bad: u8,
+
+    cc: HashMap<u16, Cc>,
+
+    /// PAT: program -> PMT PID
+    programs: BTreeMap<u16, u16>,
+    /// PMT PID -> ES PIDs (with PCR PID)
+    pmts: BTreeMap<u16, BTreeSet<u16>>,
+    /// PMT PID -> ECM PIDs
+    ecms: BTreeMap<u16, BTreeSet<u16>>,
+    /// EMM PIDs by CAT
+    emms: BTreeSet<u16>,
+    /// ATSC PSIP PIDs by MGT
+    psip: BTreeSet<u16>,
+    /// unreferenced PID -> first occurrence; None once reported
+    unreferenced: HashMap<u16, Option<Duration>>,
+
+    /// last PAT/PMT section occurrence
+    pat_at: Option<Duration>,
+    pmt_at: HashMap<u16, Duration>,
+    /// last occurrence of referenced PIDs
+    pid_at: HashMap<u16, Duration>,
+
+    pcrs: HashMap<u16, Pcr>,
+    /// last PTS occurrence per PES PID
+    pts_at: HashMap<u16, Duration>,
+    /// video and audio PIDs by PMT stream type; PTS_error applies to them only
+    av: HashSet<u16>,
+    /// CAT section occurred
+    cat: bool,
+    /// first scrambled packet and last 2.6 report while CAT is missing
+    scrambled_at: Option<Duration>,
+    cat_error_at: Option<Duration>,
+
+    /// SI table -> last occurrence (start for required tables)
+    si_at: BTreeMap<Table, Duration>,
+    /// SI tables seen at least once
+    si_seen: BTreeSet<Table>,
+    /// (pid, table_id, table_id_extension, section_number) -> last occurrence
+    sections_at: HashMap<(u16, u8, u16, u8), Duration>,
+
+    counters: BTreeMap<Check, Counter>,
+    /// (check, pid) already reported with warning
+    reported: BTreeSet<(Check, Option<u16>)>,
+}
+
+impl State {
+    fn program_by_pid(&self, pid: u16) -> Option<u16> {
+        self.programs
+            .iter()
+            .find(|(_, pmt_pid)| {
+                **pmt_pid == pid
+                    || self
+                        .pmts
+                        .get(pmt_pid)
+                        .map(|pids| pids.contains(&pid))
+                        .unwrap_or(false)
+            })
+            .map(|(program, _)| *program)
+    }
+
+    /// PIDs which must occur: PMTs, ES and PCR PIDs
+    fn referenced(&self) -> impl Iterator<Item = u16> + '_ {
+        self.pmts.values().flat_map(|pids| pids.iter().cloned())
+    }
+
+    /// 3.4.a: PID is neither reserved, PSI/SI nor referenced
+    /// by PAT, PMT, CAT or MGT; false until all PMTs are known
+    fn is_unreferenced(&self, pid: u16) -> bool {
+        let psi_complete = !self.programs.is_empty()
+            && self
+                .programs
+                .values()
+                .all(|pmt_pid| self.pmts.contains_key(pmt_pid));
+
+        psi_complete
+            && pid >= 0x0020
+            && pid != psi::PID_PSIP
+            && pid != psi::PID_NULL
+            && !self.programs.values().any(|pmt_pid| *pmt_pid == pid)
+            && !self.pmts.values().any(|pids| pids.contains(&pid))
+            && !self.ecms.values().any(|pids| pids.contains(&pid))
+            && !self.emms.contains(&pid)
+            && !self.psip.contains(&pid)
+    }
+}
+
+/// ETSI TR 101 290 measurements over raw packets and sections
+pub struct Tr101290 {
+    url: Url,
+    opts: Opts,
+
+    state: RefCell<State>,
+
+    metrics: Metrics,
+    metrics_at: Throttle,
+}
+
+impl Tr101290 {
+    /// 1.1: sync acquired after 5 correct sync bytes;
+    /// lost after 2 or more consecutive corrupted
+    const SYNC_ACQUIRE: u8 = 5;
+    const SYNC_LOSE: u8 = 2;
+
+    /// 1.3.a / 1.5.a: PAT and PMT sections repetition
+    const PSI_INTERVAL_MAX: Duration = Duration::from_millis(500);
+
+    /// PCR accuracy is measured against TS rate averaged over at least 1s
+    const PCR_ORIGIN_MIN: u64 = 27_000_000;
+
+    /// 3.2 / 3.7 / 3.8: minimum interval between sections of same table
+    const SI_INTERVAL_MIN: Duration = Duration::from_millis(25);
+
+    /// 3.4.a: PID is not referred to by PMT within 0.5s
+    const UNREFERENCED_MAX: Duration = Duration::from_millis(500);
+
+    /// 2.6: CAT is awaited this long after first scrambled packet;
+    /// missing CAT is then reported once per interval
+    const CAT_INTERVAL: Duration = Duration::from_secs(1);
+
+    pub fn new(url: Url) -> Tr101290 {
+        Tr101290 {
+            url,
+            opts: Default::default(),
+            state: Default::default(),
+            metrics: Default::default(),
+            metrics_at: Default::default(),
+        }
+    }
+
+    pub fn opts(&mut self, opts: Opts) -> &Tr101290 {
+        self.opts = opts;
+        self
+    }
+
+    pub fn metrics(&mut self, metrics: Metrics) -> &Tr101290 {
+        self.metrics = metrics;
+        self
+    }
+
+    fn error(&self, state: &mut State, check: Check, pid: Option<u16>, reason: &str) {
+        let now = state.now;
+        let program = pid.and_then(|pid| state.program_by_pid(pid));
+
+        let counter = state.counters.entry(check).or_default();
+        counter.count += 1;
+        counter.first.get_or_insert(now);
+        counter.last = Some(now);
+        counter.pid = pid;
+        counter.program = program;
+
+        let pid_str = pid
+            .map(|pid| format!(" :pid 0x{:04X}", pid))
+            .unwrap_or_default();
+        let program_str = program
+            .map(|program| format!(" :program {}", program))
+            .unwrap_or_default();
+
+        // warn once per check and PID; details are in counters
+        if state.reported.insert((check, pid)) {
+            warn!(
+                "({}) [tr101290] {} (:at {:.3}s{}{} :reason {})",
+                self.url,
+                check,
+                now.as_secs_f64(),
+                pid_str,
+                program_str,
+                reason
+            );
+        } else {
+            debug!(
+                "({}) [tr101290] {} (:at {:.3}s{}{} :reason {})",
+                self.url,
+                check,
+                now.as_secs_f64(),
+                pid_str,
+                program_str,
+                reason
+            );
+        }
+    }
+
+    /// 1.1 / 1.2; false if packet is not usable
+    fn sync(&self, state: &mut State, raw: &[u8]) -> bool {
+        if raw.first() == Some(&0x47) {
+            state.sync_bad = 0;
+            state.sync_good = state.sync_good.saturating_add(1);
+            if !state.sync && state.sync_good >= Self::SYNC_ACQUIRE {
+                state.sync = true;
+            }
+            return true;
+        }
+
+        state.sync_good = 0;
+        state.sync_bad = state.sync_bad.saturating_add(1);
+
+        if state.sync {
+            self.error(state, Check::SyncByteError, None, "sync byte is not 0x47");
+
+            if state.sync_bad >= Self::SYNC_LOSE {
+                state.sync = false;
+                self.error(
+                    state,
+                    Check::TsSyncLoss,
+                    None,
+                    "consecutive corrupted sync bytes",
+                );
+            }
+        }
+
+        false
+    }
+
+    /// 1.4
+    fn cc(&self, state: &mut State, pkt: &ts::Packet, raw: &[u8], pid: u16) {
+        if pid == psi::PID_NULL {
+            return;
+        }
+
+        let afc = raw[3] & 0b0011_0000;
+        let got_payload = (afc & 0b0001_0000) != 0;
+        let discontinuity = discontinuity(raw);
+
+        let cc = pkt.cc();
+        let entry = state.cc.entry(pid).or_default();
+        let last = entry.last.replace(cc);
+
+        let last = match last {
+            Some(last) if !discontinuity => last,
+            _ => {
+                entry.dups = 0;
+                return;
+            }
+        };
+
+        // CC is not incremented without payload
+        let expected = if got_payload { (last + 1) & 0x0F } else { last };
+
+        if cc == expected {
+            entry.dups = 0;
+            return;
+        }
+
+        if got_payload && cc == last {
+            entry.dups = entry.dups.saturating_add(1);
+            // one duplicate is allowed
+            if entry.dups > 1 {
+                self.error(
+                    state,
+                    Check::ContinuityCountError,
+                    Some(pid),
+                    "packet occurs more than twice",
+                );
+            }
+            return;
+        }
+
+        entry.dups = 0;
+        self.error(
+            state,
+            Check::ContinuityCountError,
+            Some(pid),
+            &format!("(:expected {} :got {})", expected, cc),
+        );
+    }
+
+    /// 1.3.a / 1.5.a / 2.6 scrambling and 1.6 occurrence
+    fn pid(&self, state: &mut State, raw: &[u8], pid: u16) {
+        let scrambled = (raw[3] & 0b1100_0000) != 0;
+
+        if scrambled && !state.cat {
+            let now = state.now;
+            let first = *state.scrambled_at.get_or_insert(now);
+            let due = now.saturating_sub(first) >= Self::CAT_INTERVAL
+                && state
+                    .cat_error_at
+                    .is_none_or(|at| now.saturating_sub(at) >= Self::CAT_INTERVAL);
+
+            if due {
+                state.cat_error_at = Some(now);
+                self.error(
+                    state,
+                    Check::CatError,
+                    Some(pid),
+                    "scrambled packet without CAT",
+                );
+            }
+        }
+
+        if pid == psi::PID_PAT && scrambled {
+            self.error(
+                state,
+                Check::PatError2,
+                Some(pid),
+                "scrambling control is not 00",
+            );
+        }
+        if state.programs.values().any(|pmt_pid| *pmt_pid == pid) && scrambled {
+            self.error(
+                state,
+                Check::PmtError2,
+                Some(pid),
+                "scrambling control is not 00",
+            );
+        }
+
+        if let Some(at) = state.pid_at.get_mut(&pid) {
+            *at = state.now;
+        }
+
+        if !state.unreferenced.contains_key(&pid) && state.is_unreferenced(pid) {
+            state.unreferenced.insert(pid, Some(state.now));
+        }
+    }
+
+    /// repetition timers; re-armed on error so every missed
+    /// period is counted once
+    fn timers(&self, state: &mut State) {
+        let now = state.now;
+
+        if let Some(pat_at) = state.pat_at {
+            if now.saturating_sub(pat_at) > Self::PSI_INTERVAL_MAX {
+                state.pat_at = Some(now);
+                self.error(
+                    state,
+                    Check::PatError2,
+                    Some(psi::PID_PAT),
+                    "PAT interval exceeds 500ms",
+                );
+            }
+        }
+
+        let late: Vec<u16> = state
+            .pmt_at
+            .iter()
+            .filter(|(_, at)| now.saturating_sub(**at) > Self::PSI_INTERVAL_MAX)
+            .map(|(pid, _)| *pid)
+            .collect();
+        for pid in late {
+            state.pmt_at.insert(pid, now);
+            self.error(
+                state,
+                Check::PmtError2,
+                Some(pid),
+                "PMT interval exceeds 500ms",
+            );
+        }
+
+        let late: Vec<u16> = state
+            .pid_at
+            .iter()
+            .filter(|(_, at)| now.saturating_sub(**at) > self.opts.pid_interval_max)
+            .map(|(pid, _)| *pid)
+            .collect();
+        for pid in late {
+            state.pid_at.insert(pid, now);
+            self.error(
+                state,
+                Check::PidError,
+                Some(pid),
+                "referenced PID does not occur",
+            );
+        }
+
+        let late: Vec<u16> = state
+            .pts_at
+            .iter()
+            .filter(|(_, at)| now.saturating_sub(**at) > self.opts.pts_interval_max)
+            .map(|(pid, _)| *pid)
+            .collect();
+        for pid in late {
+            state.pts_at.insert(pid, now);
+            self.error(
+                state,
+                Check::PtsError,
+                Some(pid),
+                &format!(
+                    "PTS interval exceeds {}ms",
+                    self.opts.pts_interval_max.as_millis()
+                ),
+            );
+        }
+
+        let late: Vec<u16> = state
+            .unreferenced
+            .iter()
+            .filter(|(_, at)| at.is_some_and(|at| now.saturating_sub(at) > Self::UNREFERENCED_MAX))
+            .map(|(pid, _)| *pid)
+            .collect();
+        for pid in late {
+            state.unreferenced.insert(pid, None);
+            self.error(
+                state,
+                Check::UnreferencedPid,
+                Some(pid),
+                "PID is not referred to by PMT",
+            );
+        }
+
+        let late: Vec<(Table, Duration)> = state
+            .si_at
+            .iter()
+            .filter_map(|(table, at)| {
+                let max = self.opts.repetition(*table)?.max;
+                if now.saturating_sub(*at) > max {
+                    Some((*table, max))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        for (table, max) in late {
+            state.si_at.insert(table, now);
+
+            // absent table has own indicator; 3.2 is for present ones
+            match table.check().filter(|_| !state.si_seen.contains(&table)) {
+                Some(check) => {
+                    let reason = format!("{} is absent for {}ms", table, max.as_millis());
+                    self.error(state, check, Some(table.pid()), &reason);
+                }
+                None => {
+                    let reason = format!("{} interval exceeds {}ms", table, max.as_millis());
+                    self.error(state, Check::SiRepetitionError, Some(table.pid()), &reason);
+                }
+            }
+        }
+    }
+
+    /// 3.1.a / 3.5.a / 3.6.a / 3.7 / 3.8 table_id on SI PIDs;
+    /// 3.2 / 3.7 / 3.8 sections repetition
+    fn si(&self, state: &mut State, pid: u16, section: &Section) {
+        let table_id = section.table_id();
+        let st = table_id == psi::TABLE_ID_ST;
+
+        let allowed = match pid {
+            psi::PID_NIT => Some((
+                Check::NitActualError,
+                st || table_id == psi::TABLE_ID_NIT || table_id == psi::TABLE_ID_NIT_OTHER,
+            )),
+            psi::PID_SDT => Some((
+                Check::SdtActualError,
+                st || table_id == psi::TABLE_ID_SDT
+                    || table_id == psi::TABLE_ID_SDT_OTHER
+                    || table_id == psi::TABLE_ID_BAT,
+            )),
+            psi::PID_EIT => Some((
+                Check::EitActualError,
+                st || (psi::TABLE_ID_EIT_PF..=0x6F).contains(&table_id),
+            )),
+            psi::PID_RST => Some((Check::RstError, st || table_id == psi::TABLE_ID_RST)),
+            psi::PID_TDT => Some((
+                Check::TdtError,
+                st || table_id == psi::TABLE_ID_TDT || table_id == psi::TABLE_ID_TOT,
+            )),
+            _ => None,
+        };
+        if let Some((check, false)) = allowed {
+            self.error(
+                state,
+                check,
+                Some(pid),
+                &format!("table_id 0x{:02X} on SI PID", table_id),
+            );
+            return;
+        }
+
+        let table = Table::from_section(pid, table_id);
+        if table.is_none() && pid != psi::PID_RST {
+            return;
+        }
+
+        let now = state.now;
+        let key = (
+            pid,
+            table_id,
+            section.table_id_extension(),
+            section.section_number(),
+        );
+        if let Some(at) = state.sections_at.insert(key, now) {
+            let interval = now.saturating_sub(at);
+            if interval < Self::SI_INTERVAL_MIN {
+                let check = match table {
+                    Some(Table::Tdt) => Check::TdtError,
+                    None => Check::RstError,
+                    _ => Check::SiRepetitionError,
+                };
+                self.error(
+                    state,
+                    check,
+                    Some(pid),
+                    &format!(
+                        "(:table-id 0x{:02X} :interval {:.3}ms) below 25ms",
+                        table_id,
+                        interval.as_secs_f64() * 1000.0
+                    ),
+                );
+            }
+        }
+
+        if let Some(table) = table {
+            if self.opts.repetition(table).is_some() {
+                state.si_at.insert(table, now);
+                state.si_seen.insert(table);
+            }
+        }
+    }
+
+    /// 2.3.a / 2.3.b / 2.4
+    ///
+    /// PCR interval is measured by packets at TS rate once it is known
+    /// (stream time until then); accuracy assumes constant TS rate
+    /// which is averaged since last discontinuity
+    fn pcr(&self, state: &mut State, pkt: &ts::Packet, raw: &[u8], pid: u16) {
+        let pcr = match pkt.pcr() {
+            Ok(Some(pcr)) => pcr.value(),
+            _ => return,
+        };
+        // PCR implies adaptation field
+        let discontinuity = discontinuity(raw);
+
+        let (n, now) = (state.packets, state.now);
+        let entry = state.pcrs.entry(pid).or_default();
+
+        let (pcr_last, n_last, at_last) = match entry.last.replace((pcr, n, now)) {
+            Some(last) => last,
+            None => {
+                entry.origin = Some((pcr, n));
+                return;
+            }
+        };
+
+        let packets = n - n_last;
+        let ticks = (pcr + PCR_MAX - pcr_last) % PCR_MAX;
+        let ticks_ns = |ticks: f64| Duration::from_nanos((ticks * 1000.0 / 27.0) as u64);
+
+        let mut errors = Vec::new();
+
+        let interval = match entry.pkt_ticks {
+            Some(pkt_ticks) => ticks_ns(packets as f64 * pkt_ticks),
+            None => now.saturating_sub(at_last),
+        };
+        if interval > self.opts.pcr_interval_max {
+            errors.push((
+                Check::PcrRepetitionError,
+                format!("(:interval {:.3}ms)", interval.as_secs_f64() * 1000.0),
+            ));
+        }
+
+        // going backwards wraps to far future
+        let jump = ticks_ns(ticks as f64) > self.opts.pcr_discontinuity_max;
+        if jump && !discontinuity {
+            let delta = if ticks > PCR_MAX / 2 {
+                -(ticks_ns((PCR_MAX - ticks) as f64).as_secs_f64())
+            } else {
+                ticks_ns(ticks as f64).as_secs_f64()
+            };
+            errors.push((
+                Check::PcrDiscontinuityIndicatorError,
+                format!("(:delta {:.3}ms)", delta * 1000.0),
+            ));
+        }
+
+        if jump || discontinuity {
+            entry.origin = Some((pcr, n));
+        } else {
+            if let Some(pkt_ticks) = entry.pkt_ticks {
+                let inaccuracy = ticks as f64 - packets as f64 * pkt_ticks;
+                if ticks_ns(inaccuracy.abs()) > self.opts.pcr_accuracy_max {
+                    errors.push((
+                        Check::PcrAccuracyError,
+                        format!("(:inaccuracy {:.0}ns)", inaccuracy * 1000.0 / 27.0),
+                    ));
+                }
+            }
+
+            if let Some((pcr_origin, n_origin)) = entry.origin {
+                let ticks = (pcr + PCR_MAX - pcr_origin) % PCR_MAX;
+                if ticks >= Self::PCR_ORIGIN_MIN && n > n_origin {
+                    entry.pkt_ticks = Some(ticks as f64 / (n - n_origin) as f64);
+                }
+            }
+        }
+
+        for (check, reason) in errors {
+            self.error(state, check, Some(pid), &reason);
+        }
+    }
+
+    /// 2.2 for PAT, CAT, PMT, NIT, SDT, BAT, EIT and TOT
+    fn crc(&self, state: &mut State, pid: u16, section: &Section) {
+        let table_id = section.table_id();
+        let pmt = table_id == psi::TABLE_ID_PMT && state.programs.values().any(|p| *p == pid);
+        if !psi::is_si_pid(pid) && !pmt {
+            return;
+        }
+
+        // TOT carries CRC without section syntax
+        let ok = if table_id == psi::TABLE_ID_TOT {
+            crc32::mpeg2(section.buf()) == 0
+        } else {
+            section.crc32_ok()
+        };
+
+        if !ok {
+            self.error(
+                state,
+                Check::CrcError,
+                Some(pid),
+                &format!("(:table-id 0x{:02X})", table_id),
+            );
+        }
+    }
+
+    fn pat(&self, state: &mut State, section: &Section) {
+        let programs: BTreeMap<u16, u16> = section
+            .body()
+            .chunks_exact(4)
+            .map(|b| {
+                (
+                    (u16::from(b[0]) << 8) | u16::from(b[1]),
+                    (u16::from(b[2] & 0x1F) << 8) | u16::from(b[3]),
+                )
+            })
+            // network PID
+            .filter(|(program, _)| *program != 0)
+            .collect();
+
+        if programs == state.programs {
+            return;
+        }
+
+        let now = state.now;
+        state
+            .pmt_at
+            .retain(|pid, _| programs.values().any(|p| p == pid));
+        for pmt_pid in programs.values() {
+            state.pmt_at.entry(*pmt_pid).or_insert(now);
+        }
+        state
+            .pmts
+            .retain(|pid, _| programs.values().any(|p| p == pid));
+        state
+            .ecms
+            .retain(|pid, _| programs.values().any(|p| p == pid));
+
+        state.programs = programs;
+        self.referenced_sync(state);
+    }
+
+    fn pmt(&self, state: &mut State, pid: u16, section: &Section) {
+        let body = section.body();
+        if body.len() < 4 {
+            return;
+        }
+
+        let mut pids = BTreeSet::new();
+        pids.insert((u16::from(body[0] & 0x1F) << 8) | u16::from(body[1]));
+
+        let program_info_length = (usize::from(body[2] & 0x0F) << 8) | usize::from(body[3]);
+        let program_info = body.get(4..4 + program_info_length).unwrap_or_default();
+        let mut ecms: BTreeSet<u16> = ca_pids(program_info).collect();
+
+        let mut buf = body.get(4 + program_info_length..).unwrap_or_default();
+        while buf.len() >= 5 {
+            pids.insert((u16::from(buf[1] & 0x1F) << 8) | u16::from(buf[2]));
+
+            let es_info_length = (usize::from(buf[3] & 0x0F) << 8) | usize::from(buf[4]);
+            let es_info = buf.get(5..5 + es_info_length).unwrap_or_default();
+            ecms.extend(ca_pids(es_info));
+
+            buf = buf.get(5 + es_info_length..).unwrap_or_default();
+        }
+        pids.remove(&psi::PID_NULL);
+
+        if state.pmts.get(&pid) != Some(&pids) || state.ecms.get(&pid) != Some(&ecms) {
+            state.pmts.insert(pid, pids);
+            state.ecms.insert(pid, ecms);
+            self.referenced_sync(state);
+        }
+    }
+
+    fn cat(&self, state: &mut State, section: &Section) {
+        let emms: BTreeSet<u16> = ca_pids(section.body()).collect();
+        if emms != state.emms {
+            state.emms = emms;
+            self.referenced_sync(state);
+        }
+    }
+
+    /// ATSC A/65 MGT; EIT/ETT/RRT PIDs are referenced
+    fn mgt(&self, state: &mut State, section: &Section) {
+        let body = section.body();
+        if body.len() < 3 {
+            return;
+        }
+
+        let mut psip = BTreeSet::new();
+        let mut buf = &body[3..];
+        for _ in 0..((u16::from(body[1]) << 8) | u16::from(body[2])) {
+            if buf.len() < 11 {
+                break;
+            }
+            psip.insert((u16::from(buf[2] & 0x1F) << 8) | u16::from(buf[3]));
+
+            let descriptors_length = (usize::from(buf[9] & 0x0F) << 8) | usize::from(buf[10]);
+            buf = buf.get(11 + descriptors_length..).unwrap_or_default();
+        }
+
+        if psip != state.psip {
+            state.psip = psip;
+            self.referenced_sync(state);
+        }
+    }
+
+    /// keep PID_error timers only for referenced PIDs;
+    /// drop Unreferenced_PID candidates which are referenced now
+    fn referenced_sync(&self, state: &mut State) {
+        let now = state.now;
+        let referenced: BTreeSet<u16> = state.referenced().collect();
+
+        state.pid_at.retain(|pid, _| referenced.contains(pid));
+        state.pts_at.retain(|pid, _| referenced.contains(pid));
+
+        let unreferenced: Vec<u16> = state
+            .unreferenced
+            .keys()
+            .filter(|pid| !state.is_unreferenced(**pid))
+            .cloned()
+            .collect();
+        for pid in unreferenced {
+            state.unreferenced.remove(&pid);
+        }
+        for pid in referenced {
+            state.pid_at.entry(pid).or_insert(now);
+        }
+    }
+
+    fn metrics_publish(&self) {
+        if !self.metrics_at.ready() {
+            return;
+        }
+
+        let state = self.state.borrow();
+        for check in Check::ALL {
+            let counter = state.counters.get(check).cloned().unwrap_or_default();
+            self.metrics.set(
+                format!("tr101290-p{}-{}", check.priority(), check.key()),
+                counter.to_string(),
+            );
+        }
+    }
+}
+
+/// CA_PID of CA descriptors (ECM in PMT, EMM in CAT)
+fn ca_pids(buf: &[u8]) -> impl Iterator<Item = u16> + '_ {
+    psi::ca_descriptors(buf).map(|ca| ca.pid)
+}
+
+impl Consumer for Tr101290 {
+    fn consume_trk(&self, trk: &Track) {
+        let mut state = self.state.borrow_mut();
+
+        match trk.kind() {
+            Kind::Video | Kind::Audio => {
+                state.av.insert(trk.pid);
+            }
+            _ => {
+                state.av.remove(&trk.pid);
+                state.pts_at.remove(&trk.pid);
+            }
+        }
+    }
+
+    fn consume_pkt_raw(&self, raw: &[u8]) {
+        {
+            let mut state = self.state.borrow_mut();
+            let state = &mut *state;
+
+            if !self.sync(state, raw) {
+                return;
+            }
+
+            let pkt = match ts::Packet::new(raw) {
+                Ok(pkt) => pkt,
+                Err(_) => return,
+            };
+            let pid = u16::from(pkt.pid());
+
+            state.packets += 1;
+            state.now = Duration::from_nanos(state.clock.update(&pkt, Instant::now()));
+            // PAT and required SI tables are expected from the very first packet
+            if state.pat_at.is_none() {
+                state.pat_at = Some(state.now);
+                for table in Table::ALL {
+                    if self.opts.repetition(*table).is_some_and(|r| r.required) {
+                        state.si_at.insert(*table, state.now);
+                    }
+                }
+            }
+
+            if (raw[1] & 0b1000_0000) != 0 {
+                self.error(
+                    state,
+                    Check::TransportError,
+                    Some(pid),
+                    "transport_error_indicator is set",
+                );
+            }
+
+            self.cc(state, &pkt, raw, pid);
+            self.pid(state, raw, pid);
+            self.pcr(state, &pkt, raw, pid);
+            self.timers(state);
+        }
+
+        self.metrics_publish();
+    }
+
+    fn consume_section(&self, pid: u16, buf: &[u8]) {
+        let section = match Section::try_new(buf) {
+            Some(section) => section,
+            None => return,
+        };
+
+        let mut state = self.state.borrow_mut();
+        let state = &mut *state;
+
+        self.crc(state, pid, &section);
+        self.si(state, pid, &section);
+
+        if pid == psi::PID_CAT {
+            if section.table_id() == psi::TABLE_ID_CAT {
+                state.cat = true;
+                if section.crc32_ok() && section.current_next_indicator() {
+                    self.cat(state, &section);
+                }
+            } else {
+                self.error(
+                    state,
+                    Check::CatError,
+                    Some(pid),
+                    &format!("table_id 0x{:02X} on CAT PID", section.table_id()),
+                );
+            }
+            return;
+        }
+
+        if pid == psi::PID_PSIP {
+            if section.table_id() == psi::TABLE_ID_MGT
+                && section.crc32_ok()
+                && section.current_next_indicator()
+            {
+                self.mgt(state, &section);
+            }
+            return;
+        }
+
+        if pid == psi::PID_PAT {
+            if section.table_id() != psi::TABLE_ID_PAT {
+                self.error(
+                    state,
+                    Check::PatError2,
+                    Some(pid),
+                    &format!("table_id 0x{:02X} on PAT PID", section.table_id()),
+                );
+                return;
+            }
+
+            state.pat_at = Some(state.now);
+            if section.crc32_ok() && section.current_next_indicator() {
+                self.pat(state, &section);
+            }
+            return;
+        }
+
+        if section.table_id() == psi::TABLE_ID_PMT && state.pmt_at.contains_key(&pid) {
+            state.pmt_at.insert(pid, state.now);
+            if section.crc32_ok() && section.current_next_indicator() {
+                self.pmt(state, pid, &section);
+            }
+        }
+    }
+
+    fn consume_pkt(&self, pkt: &Packet) {
+        if pkt.pts.is_none() {
+            return;
+        }
+
+        let mut state = self.state.borrow_mut();
+        if !state.av.contains(&pkt.pid) {
+            return;
+        }
+        let now = state.now;
+        state.pts_at.insert(pkt.pid, now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PMT_PID: u16 = 0x1000;
+
+    /// section of program 1 with CRC
+    fn section(table_id: u8, body: &[u8]) -> Vec<u8> {
+        let len = 5 + body.len() + 4;
+        let mut buf = vec![
+            table_id,
+            0xB0 | (len >> 8) as u8,
+            len as u8,
+            0x00,
+            0x01,
+            0xC1,
+            0x00,
+            0x00,
+        ];
+        buf.extend_from_slice(body);
+        let crc = crc32::mpeg2(&buf);
+        buf.extend_from_slice(&crc.to_be_bytes());
+        buf
+    }
+
+    /// H.264 on 0x100 with PCR and ECM on 0x200 by es_info_length
+    fn pmt(es_info_length: u16) -> Vec<u8> {
+        section(
+            psi::TABLE_ID_PMT,
+            &[
+                0xE1,
+                0x00,
+                0xF0,
+                0x00,
+                0x1B,
+                0xE1,
+                0x00,
+                0xF0 | (es_info_length >> 8) as u8,
+                es_info_length as u8,
+                psi::TAG_CA,
+                4,
+                0x01,
+                0x00,
+                0xE2,
+                0x00,
+            ],
+        )
+    }
+
+    fn tr101290() -> Tr101290 {
+        let tr = Tr101290::new(Url::parse("udp://239.0.0.1:1234").unwrap());
+        let pat = section(psi::TABLE_ID_PAT, &[0x00, 0x01, 0xF0, 0x00]);
+        tr.consume_section(psi::PID_PAT, &pat);
+        tr
+    }
+
+    #[test]
+    fn valid() {
+        let tr = tr101290();
+        tr.consume_section(PMT_PID, &pmt(6));
+
+        let state = tr.state.borrow();
+        assert_eq!(state.programs.get(&1), Some(&PMT_PID));
+        assert_eq!(state.pmts[&PMT_PID], BTreeSet::from([0x100]));
+        assert_eq!(state.ecms[&PMT_PID], BTreeSet::from([0x200]));
+        assert!(state.counters.is_empty());
+    }
+
+    #[test]
+    fn truncated() {
+        let tr = tr101290();
+        let buf = pmt(6);
+        for len in 0..buf.len() {
+            tr.consume_section(PMT_PID, &buf[..len]);
+        }
+        assert!(tr.state.borrow().pmts.is_empty());
+
+        // CA descriptor cut by es_info_length
+        tr.consume_section(PMT_PID, &pmt(5));
+        let state = tr.state.borrow();
+        assert_eq!(state.pmts[&PMT_PID], BTreeSet::from([0x100]));
+        assert!(state.ecms[&PMT_PID].is_empty());
+    }
+
+    #[test]
+    fn length_overrun() {
+        let tr = tr101290();
+        tr.consume_section(PMT_PID, &pmt(0xFFF));
+        {
+            let state = tr.state.borrow();
+            assert_eq!(state.pmts[&PMT_PID], BTreeSet::from([0x100]));
+            assert!(state.ecms[&PMT_PID].is_empty());
+        }
+
+        // tables_defined past MGT body
+        let mgt = section(psi::TABLE_ID_MGT, &[0x00, 0xFF, 0xFF, 0x00]);
+        tr.consume_section(psi::PID_PSIP, &mgt);
+        assert!(tr.state.borrow().psip.is_empty());
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt;
+
+use crate::compression_standard::{Audio, Subtitle, Video};
+use crate::psi;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Kind {
+    Video,
+    Audio,
+    Subtitle,
+    Data,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Kind::Video => write!(f, "video"),
+            Kind::Audio => write!(f, "audio"),
+            Kind::Subtitle => write!(f, "subtitle"),
+            Kind::Data => write!(f, "data"),
+        }
+    }
+}
+
+/// elementary stream announced by PMT
+#[derive(Clone, Debug, PartialEq)]
+pub struct Track {
+    pub pid: u16,
+
+    pub program_number: u16,
+    pub pmt_pid: u16,
+    pub pcr_pid: u16,
+
+    pub stream_type: u8,
+
+    /// raw ES_info descriptors loop
+    pub descriptors: Vec<u8>,
+
+    /// from SDT service_descriptor
+    pub provider_name: Option<String>,
+    pub service_name: Option<String>,
+
+    /// from elementary stream headers (see Es)
+    pub video: Option<Video>,
+    pub audio: Option<Audio>,
+    pub subtitle: Option<Subtitle>,
+}
+
+impl Track {
+    const TAG_REGISTRATION: u8 = 0x05;
+    const TAG_ISO_639: u8 = 0x0A;
+    const TAG_DVB_TELETEXT: u8 = 0x56;
+    const TAG_DVB_SUBTITLING: u8 = 0x59;
+    const TAG_DVB_AC3: u8 = 0x6A;
+    const TAG_DVB_EAC3: u8 = 0x7A;
+    const TAG_DVB_DTS: u8 = 0x7B;
+    const TAG_DVB_AAC: u8 = 0x7C;
+
+    pub fn descriptors(&self) -> impl Iterator<Item = (u8, &[u8])> {
+        psi::descriptors(&self.descriptors)
+    }
+
+    fn has_descriptor(&self, tag: u8) -> bool {
+        self.descriptors().any(|(t, _)| t == tag)
+    }
+
+    /// ISO_639_language_descriptor (first language)
+    pub fn lang(&self) -> Option<String> {
+        self.descriptors()
+            .find(|(tag, data)| *tag == Self::TAG_ISO_639 && data.len() >= 3)
+            .map(|(_, data)| String::from_utf8_lossy(&data[..3]).into_owned())
+            .or_else(|| {
+                // teletext and subtitling descriptors also carry language
+                self.descriptors()
+                    .find(|(tag, data)| {
+                        (*tag == Self::TAG_DVB_TELETEXT || *tag == Self::TAG_DVB_SUBTITLING)
+                            && data.len() >= 3
+                    })
+                    .map(|(_, data)| String::from_utf8_lossy(&data[..3]).into_owned())
+            })
+    }
+
+    /// registration_descriptor format_identifier e.g. "AC-3", "HEVC"
+    fn registration(&self) -> Option<[u8; 4]> {
+        self.descriptors()
+            .find(|(tag, data)| *tag == Self::TAG_REGISTRATION && data.len() >= 4)
+            .map(|(_, data)| [data[0], data[1], data[2], data[3]])
+    }
+
+    pub fn codec(&self) -> &'static str {
+        match self.stream_type {
+            0x01 => "mpeg1video",
+            0x02 => "mpeg2video",
+            0x03 | 0x04 => "mp2",
+            0x05 => "private-sections",
+            0x0F => "aac",
+            0x10 => "mpeg4",
+            0x11 => "aac-latm",
+            0x15 => "metadata",
+            0x1B => "h264",
+            0x24 => "h265",
+            0x42 => "cavs",
+            0x81 => "ac3",
+            0x86 => "scte35",
+            0x87 => "eac3",
+            0x06 => {
+                if self.has_descriptor(Self::TAG_DVB_AC3) {
+                    "ac3"
+                } else if self.has_descriptor(Self::TAG_DVB_EAC3) {
+                    "eac3"
+                } else if self.has_descriptor(Self::TAG_DVB_DTS) {
+                    "dts"
+                } else if self.has_descriptor(Self::TAG_DVB_AAC) {
+                    "aac"
+                } else if self.has_descriptor(Self::TAG_DVB_TELETEXT) {
+                    "teletext"
+                } else if self.has_descriptor(Self::TAG_DVB_SUBTITLING) {
+                    "dvb-subtitle"
+                } else {
+                    match self.registration().as_ref() {
+                        Some(b"AC-3") => "ac3",
+                        Some(b"EAC3") => "eac3",
+                        Some(b"HEVC") => "h265",
+                        Some(b"Opus") => "opus",
+                        _ => "private",
+                    }
+                }
+            }
+            _ => "unknown",
+        }
+    }
+
+    pub fn kind(&self) -> Kind {
+        match self.codec() {
+            "mpeg1video" | "mpeg2video" | "mpeg4" | "h264" | "h265" | "cavs" => Kind::Video,
+            "mp2" | "aac" | "aac-latm" | "ac3" | "eac3" | "dts" | "opus" => Kind::Audio,
+            "teletext" | "dvb-subtitle" => Kind::Subtitle,
+            _ => Kind::Data,
+        }
+    }
+
+    /// carried in PSI-like sections rather than PES
+    pub fn is_sectioned(&self) -> bool {
+        self.stream_type == 0x05 || self.stream_type == 0x86
+    }
+}
+
+impl fmt::Display for Track {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "(:pid 0x{:04X} :program {} :stream-type 0x{:02X} :kind {} :codec {}",
+            self.pid,
+            self.program_number,
+            self.stream_type,
+            self.kind(),
+            self.codec()
+        )?;
+        if self.pid == self.pcr_pid {
+            write!(f, " :pcr true")?;
+        }
+        if let Some(lang) = self.lang() {
+            write!(f, " :lang {}", lang)?;
+        }
+        if let Some(service_name) = &self.service_name {
+            write!(f, r#" :service "{}""#, service_name)?;
+        }
+        write!(f, ")")
+    }
+}
@@ -0,0 +1,295 @@
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+use log::{debug, warn};
+use socket2::{Domain, Protocol, SockAddr, Socket as RawSocket, Type};
+use url::{Host, Url};
+
+use crate::error::{Error, Result};
+use crate::metrics::Metrics;
+
+/// udp socket tuning
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SockOpts {
+    /// SO_RCVBUF in bytes; kernel default if not set.
+    /// linux doubles the value and caps it by net.core.rmem_max
+    pub rcvbuf: Option<usize>,
+
+    /// SO_REUSEADDR + SO_REUSEPORT;
+    /// if not set - enabled for multicast groups only
+    pub reuse: Option<bool>,
+
+    /// IP_TTL + IP_MULTICAST_TTL (hops for ipv6)
+    pub ttl: Option<u32>,
+}
+
+impl SockOpts {
+    /// expected kernel receive buffer size for requested one.
+    /// socket is created but never bound
+    pub fn rcvbuf_effective(&self) -> io::Result<usize> {
+        let socket = RawSocket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        if let Some(rcvbuf) = self.rcvbuf {
+            socket.set_recv_buffer_size(rcvbuf)?;
+        }
+        socket.recv_buffer_size()
+    }
+}
+
+pub struct Socket {
+    inner: UdpSocket,
+
+    /// SO_RXQ_OVFL enabled;
+    /// kernel reports dropped datagrams counter via ancillary data
+    rxq_ovfl: bool,
+}
+
+impl Socket {
+    /// recv wakes up at least this often
+    /// to let receive thread publish counters
+    const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// bind udp socket and join multicast group if host is multicast one
+    pub fn bind(url: &Url, port: u16, opts: &SockOpts) -> Result<Socket> {
+        let host = url.host().ok_or_else(Error::udp_url_missing_host)?;
+        let host_str = host.to_owned().to_string();
+
+        let addr: SocketAddr = (&*host_str, port)
+            .to_socket_addrs()
+            .map_err(|err| Error::udp_socket_bind(err, &host_str, port))?
+            .next()
+            .ok_or_else(Error::udp_url_missing_host)?;
+
+        let socket = RawSocket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))
+            .map_err(|err| Error::udp_socket_bind(err, &host_str, port))?;
+
+        if opts.reuse.unwrap_or_else(|| addr.ip().is_multicast()) {
+            socket
+                .set_reuse_address(true)
+                .map_err(|err| Error::udp_socket_opt(err, "SO_REUSEADDR"))?;
+            socket
+                .set_reuse_port(true)
+                .map_err(|err| Error::udp_socket_opt(err, "SO_REUSEPORT"))?;
+        }
+
+        if let Some(rcvbuf) = opts.rcvbuf {
+            socket
+                .set_recv_buffer_size(rcvbuf)
+                .map_err(|err| Error::udp_socket_opt(err, "SO_RCVBUF"))?;
+        }
+
+        if let Some(ttl) = opts.ttl {
+            match addr {
+                SocketAddr::V4(_) => {
+                    socket
+                        .set_ttl_v4(ttl)
+                        .map_err(|err| Error::udp_socket_opt(err, "IP_TTL"))?;
+                    socket
+                        .set_multicast_ttl_v4(ttl)
+                        .map_err(|err| Error::udp_socket_opt(err, "IP_MULTICAST_TTL"))?;
+                }
+                SocketAddr::V6(_) => {
+                    socket
+                        .set_unicast_hops_v6(ttl)
+                        .map_err(|err| Error::udp_socket_opt(err, "IPV6_UNICAST_HOPS"))?;
+                    socket
+                        .set_multicast_hops_v6(ttl)
+                        .map_err(|err| Error::udp_socket_opt(err, "IPV6_MULTICAST_HOPS"))?;
+                }
+            }
+        }
+
+        let rxq_ovfl = match rxq_ovfl_enable(&socket) {
+            Ok(_) => true,
+            Err(err) => {
+                warn!("({}) no kernel drop counter (:reason {})", url, err);
+                false
+            }
+        };
+
+        socket
+            .bind(&SockAddr::from(addr))
+            .map_err(|err| Error::udp_socket_bind(err, &host_str, port))?;
+
+        socket
+            .set_read_timeout(Some(Self::READ_TIMEOUT))
+            .map_err(|err| Error::udp_socket_opt(err, "SO_RCVTIMEO"))?;
+
+        let socket: UdpSocket = socket.into();
+
+        debug!("({}) [+] OK bind udp socket (:port {})", url, port);
+
+        if addr.ip().is_multicast() {
+            match host {
+                Host::Ipv4(v4) => {
+                    let iface = Ipv4Addr::new(0, 0, 0, 0);
+                    socket.join_multicast_v4(&v4, &iface).map_err(|err| {
+                        Error::udp_join_multicast_v4(err, host_str, port, iface.to_string())
+                    })?;
+
+                    debug!("({}) [+] OK join multicast v4", url);
+                    debug!("({}) [+] OK ({}:{}@{})", url, v4, port, iface);
+                }
+                Host::Ipv6(v6) => {
+                    // 0 to indicate any interface
+                    let iface = 0;
+                    socket
+                        .join_multicast_v6(&v6, iface)
+                        .map_err(|err| Error::udp_join_multicast_v6(err, host_str, port, iface))?;
+
+                    debug!("({}) [+] OK join multicast v6", url);
+                }
+                Host::Domain(domain) => {
+                    let v4 = domain
+                        .parse()
+                        .map_err(|err| Error::udp_domain_to_ipv4(err, domain))?;
+
+                    let iface = Ipv4Addr::new(0, 0, 0, 0);
+                    socket.join_multicast_v4(&v4, &iface).map_err(|err| {
+                        Error::udp_join_multicast_v4(err, host_str, port, iface.to_string())
+                    })?;
+
+                    debug!("({}) [+] OK join multicast v4/domain", url);
+                    debug!("({}) [+] OK ({}:{}@{})", url, domain, port, iface);
+                }
+            }
+        }
+
+        Ok(Socket {
+            inner: socket,
+            rxq_ovfl,
+        })
+    }
+
+    /// effective (kernel) receive buffer size
+    pub fn rcvbuf(&self) -> io::Result<usize> {
+        socket2::SockRef::from(&self.inner).recv_buffer_size()
+    }
+
+    /// receive datagram;
+    /// also return kernel dropped datagrams counter if available.
+    /// None on read timeout
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<Option<(usize, Option<u32>)>> {
+        let res = if self.rxq_ovfl {
+            recv_rxq_ovfl(&self.inner, buf)
+        } else {
+            self.inner.recv(buf).map(|sz| (sz, None))
+        };
+
+        match res {
+            Ok(res) => Ok(Some(res)),
+            Err(err)
+                if err.kind() == io::ErrorKind::WouldBlock
+                    || err.kind() == io::ErrorKind::TimedOut =>
+            {
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn rxq_ovfl_enable(socket: &RawSocket) -> io::Result<()> {
+    use std::mem;
+    use std::os::unix::io::AsRawFd;
+
+    let enable: libc::c_int = 1;
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_RXQ_OVFL,
+            &enable as *const _ as *const libc::c_void,
+            mem::size_of_val(&enable) as libc::socklen_t,
+        )
+    };
+
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn rxq_ovfl_enable(_: &RawSocket) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "SO_RXQ_OVFL is linux only",
+    ))
+}
+
+/// recvmsg(2) with SO_RXQ_OVFL control message parsing.
+/// kernel attaches counter only after first drop
+#[cfg(target_os = "linux")]
+fn recv_rxq_ovfl(socket: &UdpSocket, buf: &mut [u8]) -> io::Result<(usize, Option<u32>)> {
+    use std::mem;
+    use std::os::unix::io::AsRawFd;
+    use std::ptr;
+
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+
+    // u64 for cmsghdr alignment
+    let mut control = [0u64; 8];
+
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = mem::size_of_val(&control) as _;
+
+    let sz = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut msg, 0) };
+    if sz < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut drops = None;
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SO_RXQ_OVFL {
+                drops = Some(ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const u32));
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+
+    Ok((sz as usize, drops))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn recv_rxq_ovfl(socket: &UdpSocket, buf: &mut [u8]) -> io::Result<(usize, Option<u32>)> {
+    socket.recv(buf).map(|sz| (sz, None))
+}
+
+/// received datagrams counters
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    pub datagrams: u64,
+    pub bytes: u64,
+    /// datagrams dropped by kernel (receive buffer overflow)
+    pub drops: u64,
+}
+
+impl Stats {
+    #[inline(always)]
+    pub fn update(&mut self, sz: usize, drops: Option<u32>) {
+        self.datagrams += 1;
+        self.bytes += sz as u64;
+
+        // cumulative counter since socket creation
+        if let Some(drops) = drops {
+            self.drops = u64::from(drops);
+        }
+    }
+
+    pub fn publish(&self, metrics: &Metrics, prefix: &str) {
+        metrics.set(format!("{}-datagrams", prefix), self.datagrams);
+        metrics.set(format!("{}-bytes", prefix), self.bytes);
+        metrics.set(format!("{}-drops", prefix), self.drops);
+    }
+}